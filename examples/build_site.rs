@@ -0,0 +1,51 @@
+//! Builds a project with the library and prints the stages and the site files, like
+//! `cargo run --example build_site -- examples/project`
+
+use camino::Utf8PathBuf;
+use cargo_leptos::{
+    command::build_all,
+    config::{BuildOpts, Config, Opts},
+    ext::anyhow::Result,
+    signal::{StatusEvent, StatusSignal},
+};
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let dir = std::env::args()
+        .nth(1)
+        .unwrap_or_else(|| "examples/project".to_string());
+    let cwd = Utf8PathBuf::from(dir).canonicalize_utf8()?;
+    let config = Config::load(Opts::default(), &cwd, &cwd.join("Cargo.toml"), false)?;
+    cargo_leptos::setup(&config)?;
+
+    let mut events = StatusSignal::subscribe_events();
+    tokio::spawn(async move {
+        while let Ok(event) = events.recv().await {
+            if let StatusEvent::StageFinished { stage, outcome, .. } = event {
+                println!("event: {stage:?} {outcome:?}");
+            }
+        }
+    });
+
+    let report = build_all(&config, &BuildOpts::default()).await?;
+    for proj in &report.projects {
+        println!(
+            "{} built: {} in {:.1?}",
+            proj.name, proj.built, proj.duration
+        );
+        for stage in &proj.stages {
+            println!(
+                "  {:?} {:?} in {:.1?}",
+                stage.stage, stage.outcome, stage.duration
+            );
+        }
+        for file in &proj.site_files {
+            println!("  {}/{} {} bytes", proj.site_root, file.path, file.size);
+        }
+        if let Some(exe) = &proj.server_exe {
+            println!("  server exe {exe}");
+        }
+    }
+    report.check()?;
+    Ok(())
+}
@@ -0,0 +1,4 @@
+// the deliberate compile error
+pub fn answer() -> u32 {
+    "42"
+}
@@ -0,0 +1,4 @@
+#[cfg(target_arch = "wasm32")]
+compile_error!("server_only is only for the server, the hydrate feature got the ssr deps");
+
+pub const SIDE: &str = "server";
@@ -0,0 +1,5 @@
+#[cfg(feature = "hydrate")]
+pub use wasm_only::SIDE;
+
+#[cfg(feature = "ssr")]
+pub use server_only::SIDE;
@@ -0,0 +1,4 @@
+#[cfg(not(target_arch = "wasm32"))]
+compile_error!("wasm_only is only for the front, the ssr feature got the hydrate deps");
+
+pub const SIDE: &str = "front";
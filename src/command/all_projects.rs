@@ -0,0 +1,153 @@
+use std::{collections::HashMap, env, process::Stdio, sync::Arc, time::Duration};
+
+use tokio::{
+    process::{Child, Command},
+    select,
+    task::JoinHandle,
+    time::timeout,
+};
+
+use crate::{
+    config::{Config, Project},
+    ext::{
+        anyhow::{bail, ensure, Context, Result},
+        sync::{interrupt, print_prefixed, print_unchanged, ProcessGroup},
+    },
+    logger,
+    signal::Interrupt,
+};
+
+/// the time a project has to stop on top of the shutdown-grace-period of its server
+const STOP_MARGIN: Duration = Duration::from_secs(10);
+
+/// Runs the serve or watch command of each project in its own cargo-leptos process, so that
+/// their servers, reload websockets and rebuilds stay apart. The output is prefixed with the
+/// project name. A project that fails doesn't stop the others, and Ctrl+C stops them all.
+/// The builds share the target dirs, where cargo's lock serializes them.
+pub async fn all_projects(conf: &Config) -> Result<()> {
+    check_ports(&conf.projects)?;
+    let exe = env::current_exe().context("Could not find the cargo-leptos executable")?;
+    let args = project_args();
+    let width = conf.projects.iter().map(|p| p.name.len()).max();
+
+    let mut runs = Vec::new();
+    for proj in &conf.projects {
+        let mut cmd = Command::new(&exe);
+        cmd.args(&args).arg(format!("--project={}", proj.name));
+        cmd.stdout(Stdio::piped()).stderr(Stdio::piped());
+        // cargo and the logs of the projects aren't colored when piped
+        if logger::is_colored() && !logger::is_json() {
+            cmd.env("CARGO_TERM_COLOR", "always")
+                .env("CLICOLOR_FORCE", "1");
+        }
+        // the log file gets the output of the projects from here
+        cmd.env_remove("LEPTOS_LOG_FILE");
+        let mut child = cmd
+            .spawn()
+            .context(format!("Could not start cargo-leptos for {}", proj.name))?;
+        let printers = if logger::is_json() {
+            // the JSON lines of the projects have their project field
+            print_unchanged(&mut child)
+        } else {
+            let prefix = format!("[{:width$}]", proj.name, width = width.unwrap_or_default());
+            print_prefixed(&mut child, &prefix)
+        };
+        log::debug!("Project {} started", proj.name);
+        runs.push(tokio::spawn(run_project(proj.clone(), child, printers)));
+    }
+
+    let mut failed = Vec::new();
+    for run in runs {
+        let (name, ok) = run.await.dot()?;
+        if !ok {
+            failed.push(name);
+        }
+    }
+    ensure!(failed.is_empty(), "Projects failed: {}", failed.join(", "));
+    Ok(())
+}
+
+/// Waits for the project's cargo-leptos, which gets the Ctrl+C of the terminal as well.
+/// Returns the project name and whether it stopped without error.
+async fn run_project(
+    proj: Arc<Project>,
+    mut child: Child,
+    printers: Vec<JoinHandle<()>>,
+) -> (String, bool) {
+    let _group = ProcessGroup::of(&child);
+    let mut int = Interrupt::subscribe_shutdown();
+    let exited = select! {
+        res = child.wait() => Some(res),
+        _ = int.recv() => None,
+    };
+    let status = match exited {
+        Some(status) => status,
+        None => {
+            // not sent by the terminal when the shutdown was requested otherwise
+            interrupt(&child);
+            match timeout(proj.shutdown_grace_period + STOP_MARGIN, child.wait()).await {
+                Ok(status) => status,
+                Err(_) => {
+                    log::warn!("Project {} didn't stop, killing it", proj.name);
+                    _ = child.kill().await;
+                    child.wait().await
+                }
+            }
+        }
+    };
+    for printer in printers {
+        _ = printer.await;
+    }
+    let ok = match status {
+        Ok(status) if status.success() => {
+            log::debug!("Project {} stopped", proj.name);
+            true
+        }
+        Ok(status) => {
+            log::error!("Project {} failed with {status}", proj.name);
+            false
+        }
+        Err(e) => {
+            log::error!("Project {} could not be waited for: {e}", proj.name);
+            false
+        }
+    };
+    (proj.name.clone(), ok)
+}
+
+/// The projects run side by side, so their servers can't share a port
+fn check_ports(projects: &[Arc<Project>]) -> Result<()> {
+    let mut used: HashMap<u16, String> = HashMap::new();
+    for proj in projects {
+        let mut ports = vec![
+            ("site-addr", proj.site.addr.port()),
+            ("reload-port", proj.site.reload.port()),
+        ];
+        if let Some(addr) = proj.proxy.as_ref().and_then(|proxy| proxy.addr) {
+            ports.push(("proxy-addr", addr.port()));
+        }
+        for (what, port) in ports {
+            let owner = format!("the {what} of {}", proj.name);
+            if let Some(other) = used.insert(port, owner.clone()) {
+                bail!("Port {port} is {other} and {owner}. Each project needs its own ports for --all-projects");
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The arguments of this run, for running the same command for one project
+fn project_args() -> Vec<String> {
+    let mut args: Vec<String> = env::args().skip(1).collect();
+    // as in main, when running as cargo leptos
+    if args.first().map_or(false, |a| a == "leptos") {
+        args.remove(0);
+    }
+    args.retain(|a| a != "--all-projects" && a != "--log-file-append");
+    // the log file is written by this run
+    if let Some(i) = args.iter().position(|a| a == "--log-file") {
+        args.drain(i..(i + 2).min(args.len()));
+    }
+    args.retain(|a| !a.starts_with("--log-file="));
+    args
+}
@@ -1,44 +1,302 @@
-use std::sync::Arc;
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use camino::Utf8PathBuf;
 
 use crate::{
     compile,
-    compile::ChangeSet,
-    config::{Config, Project},
+    compile::{BuildTimings, ChangeSet, Fingerprints, ManifestFile},
+    config::{BuildOpts, Config, Project},
     ext::{
-        anyhow::{Context, Result},
-        fs,
+        anyhow::{Context, Error, Result},
+        exit::Exit,
+        format_bytes, fs, PathBufExt,
     },
+    logger::GRAY,
+    service::site::SourcedSiteFile,
+    signal::{Interrupt, Outcome, Product, ReloadSignal, Stage, StageOutcome, StatusSignal},
 };
 
-pub async fn build_all(conf: &Config) -> Result<()> {
+use super::export::export;
+
+/// The outcome of the build of each project, in the order of the config
+#[derive(Debug, Clone, Default)]
+pub struct BuildReport {
+    pub projects: Vec<ProjectReport>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ProjectReport {
+    pub name: String,
+    /// true when all the stages succeeded
+    pub built: bool,
+    /// the stages that ran, the ones after a failed stage don't
+    pub stages: Vec<StageReport>,
+    /// the site files of a successful build, relative to the site-root
+    pub site_files: Vec<ManifestFile>,
+    pub site_root: Utf8PathBuf,
+    /// the server exe of a successful build, when the project has a bin
+    pub server_exe: Option<Utf8PathBuf>,
+    /// the dir that the site was exported to with --out-dir
+    pub out_dir: Option<Utf8PathBuf>,
+    pub duration: Duration,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct StageReport {
+    pub stage: Stage,
+    pub outcome: StageOutcome,
+    pub duration: Duration,
+}
+
+impl BuildReport {
+    pub fn failed(&self) -> Vec<&str> {
+        self.projects
+            .iter()
+            .filter(|proj| !proj.built)
+            .map(|proj| proj.name.as_str())
+            .collect()
+    }
+
+    /// The report, or the error of the binary when a project failed to build
+    pub fn check(self) -> Result<Self> {
+        let failed = self.failed();
+        if !failed.is_empty() {
+            return Err(Exit::Failed).context(format!("Build failed for {}", failed.join(", ")));
+        }
+        Ok(self)
+    }
+}
+
+/// Builds the projects, with the export of `--out-dir`. The build failures are in the report,
+/// see [`BuildReport::check`], while the shutdown stops at the project being built with an error
+pub async fn build_all(conf: &Config, opts: &BuildOpts) -> Result<BuildReport> {
+    let mut report = BuildReport::default();
     for proj in &conf.projects {
-        build_proj(proj).await?;
+        let fingerprints = Fingerprints::load(proj, opts.force).await;
+        let mut proj_report = build_proj_report(proj, Some(fingerprints)).await?;
+        if !proj_report.built {
+            if Interrupt::is_shutdown_requested().await {
+                return Err(not_built(proj).await);
+            }
+            report.projects.push(proj_report);
+            continue;
+        }
+        if proj.release {
+            log_debuginfo(proj);
+            copy_server_exe(proj).await?;
+        }
+        if let Some(out_dir) = &opts.out_dir {
+            // the projects of a workspace would remove the files of each other
+            let out_dir = if conf.projects.len() > 1 {
+                out_dir.join(&proj.name)
+            } else {
+                out_dir.clone()
+            };
+            export(proj, &out_dir, opts.include_exe).await?;
+            proj_report.out_dir = Some(out_dir);
+        }
+        report.projects.push(proj_report);
     }
+    Ok(report)
+}
+
+/// The error of a build that didn't succeed, which failed or was stopped by the shutdown
+pub(super) async fn not_built(proj: &Project) -> Error {
+    let exit = if Interrupt::is_shutdown_requested().await {
+        Exit::Interrupted
+    } else {
+        Exit::Failed
+    };
+    Error::new(exit).context(format!("Could not build {}", proj.name))
+}
+
+/// With copy-server-exe, copies the server exe to the server-exe-dir for the deployment
+/// bundles, unless it is unchanged. The copy keeps the permissions, so the exe bit.
+async fn copy_server_exe(proj: &Project) -> Result<()> {
+    let (Some(dir), Some(bin)) = (&proj.server_exe_dir, &proj.bin) else {
+        return Ok(());
+    };
+    let name = bin
+        .exe_file
+        .file_name()
+        .context(format!("Invalid server exe {}", bin.exe_file))?;
+    let dest = dir.join(name);
+    let file = SourcedSiteFile {
+        source: bin.exe_file.clone(),
+        site: Utf8PathBuf::from(dest.as_str()),
+        dest: dest.clone(),
+    };
+    let copied = proj.site.updated(&file).await.dot()?;
+    let size = fs::metadata(&dest).await?.len();
+    log::info!(
+        "Bundle server exe {} {}",
+        if copied { "copied to" } else { "unchanged at" },
+        GRAY.paint(format!("{dest} ({})", format_bytes(size)))
+    );
+
+    let site = &proj.site.root_dir;
+    let tree = site.ls_ascii(1).unwrap_or_default();
+    log::info!(
+        "Bundle layout, the server exe {} and the site-root {site}:\n{tree}",
+        proj.working_dir.join(&dest)
+    );
     Ok(())
 }
 
+/// The split debug info of the server exe, which the crash reports are symbolicated with
+fn log_debuginfo(proj: &Project) {
+    let Some(path) = proj.bin.as_ref().and_then(|bin| bin.debuginfo_path()) else {
+        return;
+    };
+    if path.exists() {
+        log::info!("Build server debug info at {}", GRAY.paint(path.as_str()));
+    } else {
+        log::warn!("Build found no server debug info at {path}, see bin-split-debuginfo");
+    }
+}
+
 /// Build the project. Returns true if the build was successful
 pub async fn build_proj(proj: &Arc<Project>) -> Result<bool> {
-    if proj.site.root_dir.exists() {
-        fs::rm_dir_content(&proj.site.root_dir).await.dot()?;
-    }
+    Ok(build_proj_report(proj, None).await?.built)
+}
+
+/// With the fingerprints of the `build` command, the stages that are up to date are skipped
+async fn build_proj_report(
+    proj: &Arc<Project>,
+    mut fingerprints: Option<Fingerprints>,
+) -> Result<ProjectReport> {
     let changes = ChangeSet::all_changes();
+    StatusSignal::build_started(&changes);
+    let timings = BuildTimings::start();
+    let mut stages = Vec::new();
+    let built = build_steps(proj, &changes, &mut stages, fingerprints.as_mut()).await?;
+    let mut report = ProjectReport {
+        name: proj.name.clone(),
+        built,
+        stages,
+        site_files: Vec::new(),
+        site_root: proj.site.root_dir.clone(),
+        server_exe: None,
+        out_dir: None,
+        duration: timings.elapsed(),
+    };
+    if built {
+        timings.finished(proj);
+        let files = compile::site_manifest(proj, proj.summary).await.dot()?;
+        if proj.summary {
+            compile::site_summary(proj, &files);
+        }
+        report.site_files = files;
+        report.server_exe = proj.bin.as_ref().map(|bin| bin.exe_file.clone());
+        if let Some(fingerprints) = fingerprints {
+            fingerprints.save(proj).await.dot()?;
+        }
+    } else if fingerprints.is_some() {
+        Fingerprints::remove(proj).await.dot()?;
+    }
+    ReloadSignal::send_build_status(built);
+    Ok(report)
+}
 
-    if !compile::front(proj, &changes).await.await??.is_success() {
-        return Ok(false);
+/// Sends the stage-finished event, and keeps the outcome for the report
+fn stage_finished(
+    stages: &mut Vec<StageReport>,
+    stage: Stage,
+    outcome: &Outcome<Product>,
+    started: Instant,
+) {
+    StatusSignal::stage_finished(stage, outcome);
+    stages.push(StageReport {
+        stage,
+        outcome: outcome.into(),
+        duration: started.elapsed(),
+    });
+}
+
+async fn build_steps(
+    proj: &Arc<Project>,
+    changes: &ChangeSet,
+    stages: &mut Vec<StageReport>,
+    mut fingerprints: Option<&mut Fingerprints>,
+) -> Result<bool> {
+    if matches!(&fingerprints, Some(fingerprints) if fingerprints.has_previous()) {
+        // the skipped stages rely on the site of the last build
+        log::debug!("Build keeping the site of the last build");
+    } else if proj.assets.is_some() {
+        // the assets sync removes the stale files from the rest of the site,
+        // so that the unchanged assets don't have to be copied again
+        fs::rm_dir_content(proj.site.root_relative_pkg_dir())
+            .await
+            .dot()?;
+    } else if proj.site.root_dir.exists() {
+        fs::rm_dir_content(&proj.site.root_dir).await.dot()?;
     }
-    if !compile::assets(proj, &changes, true)
-        .await
-        .await??
-        .is_success()
-    {
+    let started = Instant::now();
+    let front = match fingerprints.as_deref_mut() {
+        Some(fingerprints) => front_step(proj, fingerprints).await?,
+        None => compile::front(proj, changes).await.await??,
+    };
+    stage_finished(stages, Stage::Front, &front, started);
+    if !front.is_success() {
         return Ok(false);
     }
-    if !compile::style(proj, &changes).await.await??.is_success() {
+    let started = Instant::now();
+    let inputs = compile::assets_inputs(proj);
+    let assets = match fingerprints.as_deref_mut() {
+        Some(fingerprints) if fingerprints.up_to_date("assets", inputs, &[]) => {
+            log::info!("Assets up to date");
+            Outcome::Success(Product::None)
+        }
+        _ => compile::assets(proj, changes, true).await.await??,
+    };
+    stage_finished(stages, Stage::Assets, &assets, started);
+    if !assets.is_success() {
         return Ok(false);
     }
-    if !compile::server(proj, &changes).await.await??.is_success() {
+    if proj.bin.is_none() {
+        compile::csr_index(proj).await.dot()?;
+    }
+    let started = Instant::now();
+    let inputs = compile::style_inputs(proj);
+    let outputs: &[&Utf8PathBuf] = if proj.style.file.is_some() || proj.style.tailwind.is_some() {
+        &[&proj.style.site_file.dest]
+    } else {
+        &[]
+    };
+    let style = match fingerprints.as_deref_mut() {
+        Some(fingerprints) if fingerprints.up_to_date("style", inputs, outputs) => {
+            log::info!("Style up to date");
+            Outcome::Success(Product::None)
+        }
+        _ => compile::style(proj, changes).await.await??,
+    };
+    stage_finished(stages, Stage::Style, &style, started);
+    if !style.is_success() {
         return Ok(false);
     }
-    Ok(true)
+    compile::hash_files(proj).await.dot()?;
+    let started = Instant::now();
+    let server = compile::server(proj, changes).await.await??;
+    stage_finished(stages, Stage::Server, &server, started);
+    Ok(server.is_success())
+}
+
+/// The cargo build always runs, as cargo knows best whether the wasm is up to date, while
+/// wasm-bindgen and wasm-opt are skipped when it is the one of the last build
+async fn front_step(proj: &Project, fingerprints: &mut Fingerprints) -> Result<Outcome<Product>> {
+    match compile::front_cargo(proj).await? {
+        Outcome::Success(()) => {}
+        Outcome::Stopped => return Ok(Outcome::Stopped),
+        Outcome::Failed => return Ok(Outcome::Failed),
+    }
+    let inputs = compile::front_inputs(proj);
+    let outputs = [&proj.lib.wasm_file.dest, &proj.lib.js_file.dest];
+    if fingerprints.up_to_date("front", inputs, &outputs) {
+        log::info!("Front up to date");
+        return Ok(Outcome::Success(Product::None));
+    }
+    compile::bindgen(proj).await.dot()
 }
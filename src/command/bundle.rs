@@ -0,0 +1,255 @@
+use std::{fs::File, io::Write, sync::Arc};
+
+use camino::{Utf8Path, Utf8PathBuf};
+use flate2::{write::GzEncoder, Compression};
+
+use super::export::{normalized, remove_stale, site_files};
+use crate::{
+    config::{BundleFormat, BundleOpts, Project},
+    ext::{
+        anyhow::{bail, Context, Result},
+        fs,
+    },
+    logger::GRAY,
+    service::site::SourcedSiteFile,
+};
+
+/// The dir of the bundle with the site files, that the LEPTOS_SITE_ROOT of the env file is
+const SITE_DIR: &str = "site";
+/// The env file of the bundle, with the env vars that the server needs
+const ENV_FILE: &str = ".env.production";
+
+/// Assembles the deployment bundle of a release build, with the layout:
+///
+/// ```text
+/// <server exe>       the server exe, without one for a csr project
+/// hash.txt           with hash-files, named like the hash-file-name
+/// <exe>.dwp          with --debuginfo, the .dSYM dir on macos and the .pdb on windows
+/// .env.production    the env vars of the server, relative to the bundle
+/// site/              the site-root, with the site manifest unless --no-manifest
+/// ```
+pub async fn bundle(proj: &Arc<Project>, opts: &BundleOpts) -> Result<()> {
+    if opts.no_build {
+        super::serve::check_artifacts(proj, "Bundle")?;
+    } else if !super::build::build_proj(proj).await.dot()? {
+        return Err(super::build::not_built(proj).await);
+    }
+
+    let output = match &opts.output {
+        Some(output) => normalized(output),
+        None => proj.working_dir.join(default_output(proj, opts.format)),
+    };
+    let site_root = normalized(&proj.working_dir.join(&proj.site.root_dir));
+    check_output(&output, &site_root, &proj.working_dir)?;
+
+    let files = bundle_files(proj, &site_root, opts).await?;
+    let env = env_file(proj);
+    match opts.format {
+        BundleFormat::Dir => write_dir(proj, &output, &files, &env).await?,
+        format => {
+            let (output, files) = (output.clone(), files.clone());
+            tokio::task::spawn_blocking(move || write_archive(format, &output, &files, &env))
+                .await??
+        }
+    }
+    log::info!(
+        "Bundle written to {output} {}",
+        GRAY.paint(format!("{} files", files.len() + 1))
+    );
+    Ok(())
+}
+
+/// In target/bundle, named after the project
+pub(crate) fn default_output(proj: &Project, format: BundleFormat) -> Utf8PathBuf {
+    let name = match format {
+        BundleFormat::Dir => proj.name.clone(),
+        BundleFormat::Tar => format!("{}.tar.gz", proj.name),
+        BundleFormat::Zip => format!("{}.zip", proj.name),
+    };
+    Utf8PathBuf::from("target/bundle").join(name)
+}
+
+/// A bundle in the site-root would bundle itself, and a bundle dir that contains the
+/// site-root or the project would lose their files to the removal of the stale files
+pub(crate) fn check_output(
+    output: &Utf8Path,
+    site_root: &Utf8Path,
+    working_dir: &Utf8Path,
+) -> Result<()> {
+    if output.starts_with(site_root) {
+        bail!("The bundle --output {output} is inside the site-root {site_root}");
+    }
+    if site_root.starts_with(output) || working_dir.starts_with(output) {
+        bail!("The bundle --output {output} contains the project {working_dir}");
+    }
+    Ok(())
+}
+
+/// The files of the bundle by their path in it, with their source
+async fn bundle_files(
+    proj: &Project,
+    site_root: &Utf8Path,
+    opts: &BundleOpts,
+) -> Result<Vec<(Utf8PathBuf, Utf8PathBuf)>> {
+    let mut files = Vec::new();
+    if let Some(bin) = &proj.bin {
+        let exe = proj.working_dir.join(&bin.exe_file);
+        files.push((Utf8PathBuf::from(exe.file_name().unwrap_or_default()), exe));
+    }
+    if opts.debuginfo {
+        files.extend(debuginfo_files(proj).await?);
+    }
+    if let Some(hash_file) = &proj.hash_file {
+        let name = hash_file.file_name().unwrap_or_default();
+        files.push((Utf8PathBuf::from(name), proj.working_dir.join(hash_file)));
+    }
+    let site_manifest = normalized(&proj.working_dir.join(&proj.site_manifest));
+    for (rel, source) in site_files(site_root).await? {
+        if opts.no_manifest && source == site_manifest {
+            continue;
+        }
+        files.push((Utf8PathBuf::from(SITE_DIR).join(rel), source));
+    }
+    Ok(files)
+}
+
+/// The debug info beside the exe, with the files of the .dSYM dir of macos
+async fn debuginfo_files(proj: &Project) -> Result<Vec<(Utf8PathBuf, Utf8PathBuf)>> {
+    let Some(path) = proj.bin.as_ref().and_then(|bin| bin.debuginfo_path()) else {
+        bail!(r#"Bundle --debuginfo needs a server with bin-split-debuginfo = "packed""#);
+    };
+    let path = proj.working_dir.join(path);
+    if !path.exists() {
+        bail!("Bundle --debuginfo found no server debug info at {path}");
+    }
+    let name = Utf8PathBuf::from(path.file_name().unwrap_or_default());
+    if !path.is_dir() {
+        return Ok(vec![(name, path)]);
+    }
+    let files = site_files(&path).await?;
+    Ok(files
+        .into_iter()
+        .map(|(rel, source)| (name.join(rel), source))
+        .collect())
+}
+
+/// The env vars of the server started from the bundle dir, as `cargo leptos serve` sets
+/// them without the ones of the reload
+pub(crate) fn env_file(proj: &Project) -> String {
+    let mut vars = vec![
+        ("LEPTOS_OUTPUT_NAME", proj.lib.output_name.to_string()),
+        ("LEPTOS_SITE_ROOT", SITE_DIR.to_string()),
+        ("LEPTOS_SITE_PKG_DIR", proj.site.pkg_dir.to_string()),
+        ("LEPTOS_SITE_ADDR", proj.site.addr.to_string()),
+        ("LEPTOS_ENV", "PROD".to_string()),
+    ];
+    let base_path = proj.site.base_path();
+    if !base_path.is_empty() {
+        vars.push(("LEPTOS_SITE_BASE_PATH", base_path))
+    }
+    if let Some(hash_file) = &proj.hash_file {
+        let name = hash_file.file_name().unwrap_or_default();
+        vars.push(("LEPTOS_HASH_FILES", "true".to_string()));
+        vars.push(("LEPTOS_HASH_FILE_NAME", name.to_string()));
+    }
+
+    let mut env = format!(
+        "# The env vars of the {} server, which is started from the bundle dir. Set the\n\
+         # LEPTOS_SITE_ADDR to 0.0.0.0:<port> to listen on all the interfaces.\n",
+        proj.name
+    );
+    for (key, value) in vars {
+        env.push_str(&format!("{key}={value}\n"));
+    }
+    env
+}
+
+/// Syncs the bundle dir like the --out-dir of build, so that the unchanged files aren't
+/// written again
+async fn write_dir(
+    proj: &Project,
+    output: &Utf8Path,
+    files: &[(Utf8PathBuf, Utf8PathBuf)],
+    env: &str,
+) -> Result<()> {
+    fs::create_dir_all(output).await?;
+    let env_file = output.join(ENV_FILE);
+    let mut kept = files.to_vec();
+    kept.push((Utf8PathBuf::from(ENV_FILE), env_file.clone()));
+    remove_stale(output, &kept).await?;
+
+    for (rel, source) in files {
+        let dest = output.join(rel);
+        let file = SourcedSiteFile {
+            source: source.clone(),
+            site: dest.clone(),
+            dest,
+        };
+        proj.site.updated(&file).await?;
+    }
+    fs::write(&env_file, env).await
+}
+
+/// Writes the archive with the permissions of the files, so the exe bit of the server exe
+fn write_archive(
+    format: BundleFormat,
+    output: &Utf8Path,
+    files: &[(Utf8PathBuf, Utf8PathBuf)],
+    env: &str,
+) -> Result<()> {
+    if let Some(dir) = output.parent() {
+        std::fs::create_dir_all(dir).context(format!("Could not create {dir}"))?;
+    }
+    let file = File::create(output).context(format!("Could not create {output}"))?;
+    match format {
+        BundleFormat::Tar => {
+            let mut tar = tar::Builder::new(GzEncoder::new(file, Compression::default()));
+            for (rel, source) in files {
+                tar.append_path_with_name(source, archive_path(rel))
+                    .context(format!("Could not add {source} to {output}"))?;
+            }
+            let mut header = tar::Header::new_gnu();
+            header.set_size(env.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            tar.append_data(&mut header, ENV_FILE, env.as_bytes())
+                .dot()?;
+            tar.into_inner().dot()?.finish().dot()?;
+        }
+        BundleFormat::Zip => {
+            let mut zip = zip::ZipWriter::new(file);
+            for (rel, source) in files {
+                let options = zip::write::FileOptions::default().unix_permissions(mode(source)?);
+                zip.start_file(archive_path(rel), options).dot()?;
+                let mut source_file =
+                    File::open(source).context(format!("Could not open {source}"))?;
+                std::io::copy(&mut source_file, &mut zip)
+                    .context(format!("Could not add {source} to {output}"))?;
+            }
+            let options = zip::write::FileOptions::default().unix_permissions(0o644);
+            zip.start_file(ENV_FILE, options).dot()?;
+            zip.write_all(env.as_bytes()).dot()?;
+            zip.finish().dot()?;
+        }
+        BundleFormat::Dir => unreachable!("the dir isn't an archive"),
+    }
+    Ok(())
+}
+
+/// With the `/` separators of the archives
+fn archive_path(rel: &Utf8Path) -> String {
+    rel.as_str().replace('\\', "/")
+}
+
+#[cfg(target_family = "unix")]
+fn mode(path: &Utf8Path) -> Result<u32> {
+    use std::os::unix::fs::PermissionsExt;
+    let metadata = std::fs::metadata(path).context(format!("Could not read {path}"))?;
+    Ok(metadata.permissions().mode() & 0o777)
+}
+
+/// Windows has no unix mode to keep
+#[cfg(not(target_family = "unix"))]
+fn mode(_path: &Utf8Path) -> Result<u32> {
+    Ok(0o644)
+}
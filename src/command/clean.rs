@@ -0,0 +1,111 @@
+use camino::{Utf8Path, Utf8PathBuf};
+#[cfg(feature = "cli")]
+use clap::Args;
+
+use crate::{
+    command::export::normalized,
+    config::Config,
+    ext::{
+        anyhow::{bail, Context, Result},
+        exe::{cached_tools, dir_size},
+        format_bytes,
+    },
+    logger::GRAY,
+};
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(Args))]
+pub struct CleanCommand {
+    /// Which project to clean, from a list of projects defined in a workspace. The target
+    /// dirs of the cargo builds are shared by the projects and always removed.
+    #[cfg_attr(feature = "cli", arg(short, long))]
+    pub project: Option<String>,
+
+    /// Also remove the tools downloaded to the cache directory.
+    #[cfg_attr(feature = "cli", arg(long))]
+    pub tools: bool,
+
+    /// List what would be removed, without removing it.
+    #[cfg_attr(feature = "cli", arg(long))]
+    pub dry_run: bool,
+
+    /// Also remove the dirs outside of the workspace, like an absolute site-root.
+    #[cfg_attr(feature = "cli", arg(long))]
+    pub force: bool,
+}
+
+impl CleanCommand {
+    pub fn run(&self, config: &Config) -> Result<()> {
+        let (removing, freeing) = if self.dry_run {
+            ("would remove", "would free")
+        } else {
+            ("removed", "freed")
+        };
+        let dirs = clean_dirs(config);
+        for dir in &dirs {
+            check_dir(dir, &config.working_dir, self.force)?;
+        }
+        let mut freed = 0;
+        for dir in dirs {
+            if !dir.exists() {
+                continue;
+            }
+            let size = dir_size(dir.as_std_path());
+            if !self.dry_run {
+                std::fs::remove_dir_all(&dir).context(format!("Could not remove {dir}"))?;
+            }
+            log::info!("Clean {removing} {dir} {}", GRAY.paint(format_bytes(size)));
+            freed += size;
+        }
+
+        if self.tools {
+            for tool in cached_tools()? {
+                if !self.dry_run {
+                    std::fs::remove_dir_all(&tool.dir)
+                        .context(format!("Could not remove {}", tool.dir.display()))?;
+                }
+                log::info!(
+                    "Clean {removing} {} {}",
+                    tool.name(),
+                    GRAY.paint(format_bytes(tool.size))
+                );
+                freed += tool.size;
+            }
+        }
+        log::info!("Clean {freeing} {}", format_bytes(freed));
+        Ok(())
+    }
+}
+
+/// The target dirs of the front and the server builds, with all their profiles and target
+/// triples, then the site-root and the cache dir of each project, without the duplicates
+pub(crate) fn clean_dirs(config: &Config) -> Vec<Utf8PathBuf> {
+    let working_dir = &config.working_dir;
+    let mut dirs = vec![
+        working_dir.join("target/front"),
+        working_dir.join("target/server"),
+    ];
+    for proj in &config.projects {
+        dirs.push(normalized(&working_dir.join(&proj.site.root_dir)));
+        dirs.push(working_dir.join(proj.cache_dir()));
+    }
+    let mut unique = Vec::new();
+    for dir in dirs {
+        if !unique.contains(&dir) {
+            unique.push(dir);
+        }
+    }
+    unique
+}
+
+/// A misconfigured site-root could be anywhere: the dirs outside of the workspace are only
+/// removed with --force, and the workspace or one of its parents never
+pub(crate) fn check_dir(dir: &Utf8Path, working_dir: &Utf8Path, force: bool) -> Result<()> {
+    if working_dir.starts_with(dir) {
+        bail!("Clean refusing to remove {dir}, which contains the workspace {working_dir}");
+    }
+    if !dir.starts_with(working_dir) && !force {
+        bail!("Clean refusing to remove {dir}, outside of the workspace {working_dir}. Use --force to remove it");
+    }
+    Ok(())
+}
@@ -0,0 +1,91 @@
+use camino::Utf8PathBuf;
+use tokio::process::Command;
+
+use crate::{
+    compile::{build_cargo_front_cmd, build_cargo_server_cmd},
+    config::{Config, DocOpts, Project},
+    ext::{
+        anyhow::{Context, Result},
+        exit::Exit,
+        sync::{new_process_group, wait_interruptible, CommandResult},
+    },
+    logger::GRAY,
+    signal::Interrupt,
+};
+
+/// Documents the lib compiled to wasm with the lib features and the bin with the bin
+/// features, of each project, as a `cargo doc` of the workspace would unify the features
+pub async fn doc_all(conf: &Config, opts: &DocOpts) -> Result<()> {
+    let mut failed = Vec::new();
+    for proj in &conf.projects {
+        match doc_proj(proj, opts).await? {
+            CommandResult::Success(()) => {}
+            CommandResult::Failure(()) => failed.push(proj.name.as_str()),
+            CommandResult::Interrupted => return Err(Exit::Interrupted.into()),
+        }
+    }
+    if !failed.is_empty() {
+        return Err(Exit::Failed).context(format!("Doc failed for {}", failed.join(", ")));
+    }
+    Ok(())
+}
+
+/// Both sides are documented, the project failing when one of them does
+async fn doc_proj(proj: &Project, opts: &DocOpts) -> Result<CommandResult<()>> {
+    let mut args = vec!["--lib"];
+    if opts.no_deps {
+        args.push("--no-deps");
+    }
+    if opts.open {
+        args.push("--open");
+    }
+
+    let mut command = Command::new("cargo");
+    let (envs, line) = build_cargo_front_cmd("doc", &args, true, proj, &mut command);
+    let dir = Utf8PathBuf::from("target/front/wasm32-unknown-unknown/doc");
+    let front = run_doc("front", command, envs, line, dir, proj).await?;
+    if matches!(front, CommandResult::Interrupted) {
+        return Ok(front);
+    }
+
+    let Some(bin) = &proj.bin else {
+        return Ok(front);
+    };
+    let mut command = Command::new("cargo");
+    let (envs, line) = build_cargo_server_cmd("doc", &args[1..], proj, &mut command);
+    let dir = match &bin.target_triple {
+        Some(triple) => Utf8PathBuf::from(format!("target/server/{triple}/doc")),
+        None => Utf8PathBuf::from("target/server/doc"),
+    };
+    let server = run_doc("server", command, envs, line, dir, proj).await?;
+    Ok(match (front, server) {
+        (_, CommandResult::Interrupted) => CommandResult::Interrupted,
+        (CommandResult::Success(()), CommandResult::Success(())) => CommandResult::Success(()),
+        _ => CommandResult::Failure(()),
+    })
+}
+
+async fn run_doc(
+    side: &str,
+    mut command: Command,
+    envs: String,
+    line: String,
+    dir: Utf8PathBuf,
+    proj: &Project,
+) -> Result<CommandResult<()>> {
+    new_process_group(&mut command);
+    let process = command.spawn().dot()?;
+    log::debug!("Cargo envs: {}", GRAY.paint(envs));
+    let timeout = proj.command_timeouts.get("cargo");
+    let res = wait_interruptible("Cargo doc", process, Interrupt::subscribe_any(), timeout).await?;
+    match res {
+        CommandResult::Success(()) => log::info!(
+            "Doc {side} written to {} {}",
+            proj.working_dir.join(dir),
+            GRAY.paint(line)
+        ),
+        CommandResult::Failure(()) => log::error!("Doc {side} failed {}", GRAY.paint(line)),
+        CommandResult::Interrupted => log::info!("Doc {side} interrupted"),
+    }
+    Ok(res)
+}
@@ -1,64 +1,322 @@
+use std::process::Stdio;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use anyhow::bail;
 use camino::Utf8Path;
+use itertools::Itertools;
 use tokio::process::Command;
 
-use crate::config::{Config, Project};
+use super::test::TestResult;
+use crate::config::{Config, End2EndConfig, End2EndSuite, EndToEndOpts, Project};
 use crate::ext::anyhow::{anyhow, Context, Result};
-use crate::service::serve;
+use crate::ext::sync::{
+    connectable, new_process_group, print_finding, print_prefixed, wait_for_ready,
+    wait_interruptible, CommandResult, Probe, Readiness,
+};
+use crate::logger::{self, GRAY};
+use crate::service::serve::{self, ServerOutput};
 use crate::signal::Interrupt;
 
-pub async fn end2end_all(conf: &Config) -> Result<()> {
+/// How long to wait for an already running server when using --no-serve
+const NO_SERVE_WAIT: Duration = Duration::from_secs(5);
+
+/// Installs the browsers of Playwright with the system libraries that they need
+const PLAYWRIGHT_INSTALL: &str = "npx playwright install --with-deps";
+
+/// The output of Playwright when its browsers aren't installed
+const PLAYWRIGHT_MISSING_BROWSER: &[&str] = &[
+    "Executable doesn't exist at",
+    "Please run the following command to download new browsers",
+];
+
+pub async fn end2end_all(conf: &Config, opts: &EndToEndOpts) -> Result<()> {
     for proj in &conf.projects {
-        end2end_proj(proj).await?;
+        end2end_proj(proj, opts).await?;
+        Interrupt::end_server_shutdown().await;
     }
     Ok(())
 }
 
-pub async fn end2end_proj(proj: &Arc<Project>) -> Result<()> {
+/// The end2end run of `test --e2e`, whose failure is logged and is in the result instead of
+/// being the error of the command
+pub(crate) async fn end2end_result(proj: &Arc<Project>, opts: &EndToEndOpts) -> TestResult {
+    let result = end2end_proj(proj, opts).await;
+    if Interrupt::is_ctrl_c_received() {
+        return TestResult::Interrupted;
+    }
+    // the server was stopped by a shutdown, which the next project doesn't get
+    Interrupt::end_server_shutdown().await;
+    match result {
+        Ok(()) => TestResult::Passed,
+        Err(e) => {
+            log::error!("End2End failed for {}: {e:?}", proj.name);
+            TestResult::Failed
+        }
+    }
+}
+
+pub async fn end2end_proj(proj: &Arc<Project>, opts: &EndToEndOpts) -> Result<()> {
+    let Some(e2e) = &proj.end2end else {
+        log::info!("end2end the Crate.toml package.metadata.leptos.end2end_cmd parameter or end2end suites not set");
+        return Ok(())
+    };
+    let suite = e2e.suite(opts.suite.as_deref())?;
+    if suite.name == End2EndConfig::DEFAULT_SUITE && e2e.suites.len() > 1 {
+        log::info!(
+            "End2End running the default suite, other suites (--suite): {}",
+            e2e.suites.iter().map(|s| &s.name).join(", ")
+        );
+    }
+
+    if opts.install_deps {
+        if !is_playwright(&suite.cmd) {
+            log::warn!(
+                "End2End --install-deps is for Playwright, which the suite {} doesn't run",
+                suite.name
+            );
+        } else if !install_playwright(proj, &suite.dir).await? {
+            return Ok(());
+        }
+    }
+
+    if opts.no_serve {
+        let (base_url, probe) = match &opts.url {
+            Some(url) => {
+                let url = url.trim_end_matches('/').to_string();
+                let probe = match &e2e.health_path {
+                    Some(path) => format!("{url}/{}", path.trim_start_matches('/')),
+                    None => url.clone(),
+                };
+                (url, Probe::Get(probe))
+            }
+            None => (
+                local_url(proj),
+                Probe::new(proj.site.addr, e2e.health_path.as_deref()),
+            ),
+        };
+        log::debug!("End2End not serving, testing {base_url}");
+        match wait_for_ready("End2End", &probe, NO_SERVE_WAIT, Interrupt::subscribe_any()).await {
+            Readiness::Ready => {}
+            Readiness::Interrupted => return Ok(()),
+            Readiness::TimedOut => {
+                bail!("No server responding at {probe}. Start the server or run without --no-serve")
+            }
+        }
+        return run_suite(proj, suite, &base_url, &opts.args, None).await;
+    }
+
+    if !super::build::build_proj(proj).await.dot()? {
+        return Err(super::build::not_built(proj).await);
+    }
+
+    let (server, output) = serve::spawn_captured(proj).await;
+
+    let probe = Probe::new(proj.site.addr, e2e.health_path.as_deref());
+    match wait_for_ready("End2End", &probe, e2e.wait, Interrupt::subscribe_any()).await {
+        Readiness::Ready => {}
+        Readiness::Interrupted => {
+            Interrupt::request_shutdown().await;
+            server.await.dot()??;
+            return Ok(());
+        }
+        Readiness::TimedOut => {
+            Interrupt::request_shutdown().await;
+            server.await.dot()??;
+            bail!(
+                "The server at {} was not ready after {}s. Server output:\n{}",
+                proj.site.addr,
+                e2e.wait.as_secs(),
+                output.text()
+            );
+        }
+    }
+
+    let result = run_suite(proj, suite, &local_url(proj), &opts.args, Some(&output)).await;
+    Interrupt::request_shutdown().await;
+    server.await.dot()??;
+    result
+}
+
+fn local_url(proj: &Project) -> String {
+    format!(
+        "http://{}{}",
+        connectable(proj.site.addr),
+        proj.site.base_path()
+    )
+}
+
+async fn run_suite(
+    proj: &Project,
+    suite: &End2EndSuite,
+    base_url: &str,
+    extra_args: &[String],
+    server: Option<&ServerOutput>,
+) -> Result<()> {
+    let mut envs = end2end_envs(proj, base_url);
     if let Some(e2e) = &proj.end2end {
-        if !super::build::build_proj(proj).await.dot()? {
+        envs.extend(e2e.env.iter().cloned());
+    }
+    envs.extend(suite.env.iter().cloned());
+
+    let started = Instant::now();
+    let timeout = proj.command_timeouts.get("end2end");
+    let result = try_run(&suite.cmd, extra_args, &suite.dir, &envs, timeout)
+        .await
+        .context(format!("running: {}", &suite.cmd));
+    let elapsed = started.elapsed();
+    let name = &suite.name;
+
+    let error = match result {
+        Ok(CommandResult::Success(())) => {
+            log::info!("End2End suite {name} passed in {elapsed:.1?}");
+            return Ok(());
+        }
+        Ok(CommandResult::Interrupted) => {
+            log::info!("End2End suite {name} interrupted after {elapsed:.1?}");
             return Ok(());
         }
+        Ok(CommandResult::Failure(())) => anyhow!("The end2end command {:?} failed", suite.cmd),
+        Err(e) => e,
+    };
+    log::error!("End2End suite {name} failed in {elapsed:.1?}");
 
-        let server = serve::spawn(proj).await;
-        try_run(&e2e.cmd, &e2e.dir)
-            .await
-            .context(format!("running: {}", &e2e.cmd))?;
-        Interrupt::request_shutdown().await;
-        server.await.dot()??;
-    } else {
-        log::info!("end2end the Crate.toml package.metadata.leptos.end2end_cmd parameter not set")
+    match server {
+        Some(output) if output.is_closed() => Err(error.context(format!(
+            "The server stopped while the tests were running, which is the probable cause. Server output:\n{}",
+            output.text()
+        ))),
+        _ => Err(error),
     }
-    Ok(())
 }
 
-async fn try_run(cmd: &str, dir: &Utf8Path) -> Result<()> {
+/// The server's env vars, with an absolute site root since the command runs in the
+/// end2end-dir, and the url of the server to test against.
+fn end2end_envs(proj: &Project, base_url: &str) -> Vec<(String, String)> {
+    let mut envs: Vec<(String, String)> = proj
+        .to_envs()
+        .into_iter()
+        .filter(|(key, _)| *key != "LEPTOS_SITE_ROOT")
+        .map(|(key, val)| (key.to_string(), val))
+        .collect();
+    envs.push((
+        "LEPTOS_SITE_ROOT".to_string(),
+        proj.working_dir.join(&proj.site.root_dir).to_string(),
+    ));
+    envs.push(("BASE_URL".to_string(), base_url.to_string()));
+    let port = reqwest::Url::parse(base_url)
+        .ok()
+        .and_then(|url| url.port_or_known_default());
+    if let Some(port) = port {
+        envs.push(("PORT".to_string(), port.to_string()));
+    }
+    envs
+}
+
+async fn try_run(
+    cmd: &str,
+    extra_args: &[String],
+    dir: &Utf8Path,
+    envs: &[(String, String)],
+    timeout: Option<Duration>,
+) -> Result<CommandResult<()>> {
     let mut parts = cmd.split(' ');
     let exe = parts
         .next()
         .ok_or_else(|| anyhow!("Invalid command {cmd:?}"))?;
 
-    let args = parts.collect::<Vec<_>>();
+    let mut args = parts.map(str::to_string).collect::<Vec<_>>();
+    args.extend(extra_args.iter().cloned());
 
-    log::trace!("End2End running {cmd:?}");
-    let mut process = Command::new(exe)
+    let cmd_line = std::iter::once(exe.to_string())
+        .chain(args.iter().map(|arg| quoted(arg)))
+        .join(" ");
+    log::info!("End2End running {}", GRAY.paint(&cmd_line));
+    log::debug!("End2End dir {}", GRAY.paint(dir.as_str()));
+    log::debug!(
+        "End2End env {}",
+        GRAY.paint(envs.iter().map(|(key, _)| key).unique().join(", "))
+    );
+    let mut command = Command::new(exe);
+    command
         .args(args)
-        .current_dir(dir)
+        .envs(envs.iter().cloned())
+        .current_dir(dir);
+    // the browsers started by the tests are killed with the command
+    new_process_group(&mut command);
+    // the output of playwright is read for its missing browsers error
+    let playwright = is_playwright(cmd);
+    if playwright {
+        command.stdout(Stdio::piped()).stderr(Stdio::piped());
+        if logger::is_colored() && !logger::is_json() {
+            command.env("FORCE_COLOR", "1");
+        }
+    }
+    let mut process = command
         .spawn()
         .context(format!("Could not spawn command {cmd:?}"))?;
+    let missing_browser =
+        playwright.then(|| print_finding(&mut process, "end2end", PLAYWRIGHT_MISSING_BROWSER));
 
-    let mut int = Interrupt::subscribe_any();
+    let result =
+        wait_interruptible("End2End", process, Interrupt::subscribe_any(), timeout).await?;
+    if let Some(missing_browser) = missing_browser {
+        // awaited in any case, for the output to be printed before the result
+        let missing_browser = missing_browser.await.unwrap_or_default();
+        if missing_browser && matches!(result, CommandResult::Failure(())) {
+            log::error!(
+                "End2End the Playwright browsers aren't installed. Install them with `{PLAYWRIGHT_INSTALL}` in {dir}, or run with --install-deps"
+            );
+        }
+    }
+    Ok(result)
+}
 
-    tokio::select! {
-          _ = int.recv() => Ok(()),
-          result = process.wait() => {
-            let status = result?;
-            if !status.success() {
-                bail!("Command terminated with exit code {}", status)
-            }
-            Ok(())
+/// A command run by Playwright, like `npx playwright test` or `pnpm exec playwright test`
+pub(crate) fn is_playwright(cmd: &str) -> bool {
+    cmd.split_whitespace()
+        .any(|part| part == "playwright" || part.ends_with("/playwright"))
+}
+
+/// Runs the Playwright install in the end2end-dir, with its progress in the logs. false when
+/// it was interrupted.
+async fn install_playwright(proj: &Project, dir: &Utf8Path) -> Result<bool> {
+    log::info!(
+        "End2End installing the Playwright browsers {}",
+        GRAY.paint(PLAYWRIGHT_INSTALL)
+    );
+    let mut parts = PLAYWRIGHT_INSTALL.split(' ');
+    let mut command = Command::new(parts.next().unwrap_or_default());
+    command
+        .args(parts)
+        .current_dir(dir)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    new_process_group(&mut command);
+    let mut process = command
+        .spawn()
+        .context(format!("Could not spawn {PLAYWRIGHT_INSTALL:?} in {dir}"))?;
+    let printers = print_prefixed(&mut process, "[playwright]");
+    let timeout = proj.command_timeouts.get("end2end");
+    let result =
+        wait_interruptible("End2End", process, Interrupt::subscribe_any(), timeout).await?;
+    for printer in printers {
+        _ = printer.await;
+    }
+    match result {
+        CommandResult::Success(()) => Ok(true),
+        CommandResult::Interrupted => Ok(false),
+        CommandResult::Failure(()) => {
+            bail!("The Playwright install {PLAYWRIGHT_INSTALL:?} failed in {dir}")
         }
     }
 }
+
+/// quotes the arg for display if it contains whitespace or quotes
+fn quoted(arg: &str) -> String {
+    if arg.is_empty() || arg.contains(|c: char| c.is_whitespace() || c == '"' || c == '\'') {
+        format!("{arg:?}")
+    } else {
+        arg.to_string()
+    }
+}
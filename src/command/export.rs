@@ -0,0 +1,168 @@
+use std::collections::HashSet;
+
+use camino::{Utf8Component, Utf8Path, Utf8PathBuf};
+
+use crate::{
+    compile::MAX_SYMLINK_DEPTH,
+    config::Project,
+    ext::{
+        anyhow::{anyhow, bail, Result},
+        fs, PathExt,
+    },
+    logger::GRAY,
+    service::site::SourcedSiteFile,
+};
+
+/// Syncs the site into the out dir, with the server exe (and the hash file beside it) when
+/// `include_exe`. The files are copied like the site files, so that the unchanged ones aren't
+/// written again, and the files of the out dir that aren't exported anymore are removed.
+pub async fn export(proj: &Project, out_dir: &Utf8Path, include_exe: bool) -> Result<()> {
+    let site_root = proj.working_dir.join(&proj.site.root_dir);
+    let out_dir = normalized(out_dir);
+    check_out_dir(&out_dir, &normalized(&site_root), &proj.working_dir)?;
+
+    // the files by their path in the out dir
+    let mut files = site_files(&site_root).await?;
+    if include_exe {
+        if let Some(bin) = &proj.bin {
+            let exe = proj.working_dir.join(&bin.exe_file);
+            let name = exe.file_name().unwrap_or_default();
+            files.push((Utf8PathBuf::from(name), exe));
+        }
+        if let Some(hash_file) = &proj.hash_file {
+            let name = hash_file.file_name().unwrap_or_default();
+            files.push((Utf8PathBuf::from(name), proj.working_dir.join(hash_file)));
+        }
+    }
+
+    fs::create_dir_all(&out_dir).await?;
+    let removed = remove_stale(&out_dir, &files).await?;
+
+    let (mut copied, mut unchanged) = (0, 0);
+    for (rel, source) in &files {
+        let dest = out_dir.join(rel);
+        let file = SourcedSiteFile {
+            source: source.clone(),
+            // not the site path, which is registered with the hash of the site file
+            site: dest.clone(),
+            dest,
+        };
+        if proj.site.updated(&file).await? {
+            copied += 1;
+        } else {
+            unchanged += 1;
+        }
+    }
+    log::info!(
+        "Export {copied} files copied, {unchanged} unchanged and {removed} removed {}",
+        GRAY.paint(out_dir.as_str())
+    );
+    Ok(())
+}
+
+/// An out dir in the site-root would be exported into itself, and one that contains the
+/// site-root or the project would lose their files to the removal of the stale files
+pub(crate) fn check_out_dir(
+    out_dir: &Utf8Path,
+    site_root: &Utf8Path,
+    working_dir: &Utf8Path,
+) -> Result<()> {
+    if out_dir.starts_with(site_root) {
+        bail!("The --out-dir {out_dir} is inside the site-root {site_root}");
+    }
+    if site_root.starts_with(out_dir) {
+        bail!("The --out-dir {out_dir} contains the site-root {site_root}");
+    }
+    if working_dir.starts_with(out_dir) {
+        bail!("The --out-dir {out_dir} contains the project {working_dir}");
+    }
+    Ok(())
+}
+
+/// The absolute path without the `.` and `..` components, as the out dir may not exist
+pub(crate) fn normalized(path: &Utf8Path) -> Utf8PathBuf {
+    let mut normalized = Utf8PathBuf::new();
+    for component in path.components() {
+        match component {
+            Utf8Component::CurDir => {}
+            Utf8Component::ParentDir => {
+                normalized.pop();
+            }
+            c => normalized.push(c),
+        }
+    }
+    normalized
+}
+
+/// The files of the site, with the dot-files, by their path relative to the site-root. The
+/// symlinked files and dirs are followed.
+pub(crate) async fn site_files(site_root: &Utf8Path) -> Result<Vec<(Utf8PathBuf, Utf8PathBuf)>> {
+    let mut files = Vec::new();
+    let mut dirs = vec![(site_root.to_path_buf(), 0)];
+    while let Some((dir, link_depth)) = dirs.pop() {
+        let mut entries = fs::read_dir(&dir).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let path = Utf8PathBuf::from_path_buf(entry.path())
+                .map_err(|p| anyhow!("Non utf8 path {p:?}"))?;
+            if fs::is_tmp_path(path.as_std_path()) {
+                continue;
+            }
+            let is_link = entry.file_type().await?.is_symlink();
+            let Ok(metadata) = fs::metadata(&path).await else {
+                log::warn!("Export skipping the dangling symlink {path}");
+                continue;
+            };
+            if metadata.is_dir() {
+                if is_link && link_depth >= MAX_SYMLINK_DEPTH {
+                    log::warn!(
+                        "Export not following {path}: more than {MAX_SYMLINK_DEPTH} nested symlinked dirs"
+                    );
+                    continue;
+                }
+                dirs.push((path, link_depth + usize::from(is_link)));
+            } else {
+                files.push((path.unbase(site_root)?, path));
+            }
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Removes the files and dirs of the out dir that aren't exported, and returns their number
+pub(crate) async fn remove_stale(
+    out_dir: &Utf8Path,
+    files: &[(Utf8PathBuf, Utf8PathBuf)],
+) -> Result<usize> {
+    let exported: HashSet<&Utf8Path> = files.iter().map(|(rel, _)| rel.as_path()).collect();
+    let dirs: HashSet<&Utf8Path> = exported
+        .iter()
+        .flat_map(|rel| rel.ancestors().skip(1))
+        .collect();
+
+    let mut removed = 0;
+    let mut pending = vec![out_dir.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        let mut entries = fs::read_dir(&dir).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let path = Utf8PathBuf::from_path_buf(entry.path())
+                .map_err(|p| anyhow!("Non utf8 path {p:?}"))?;
+            let rel = path.unbase(out_dir)?;
+            let file_type = entry.file_type().await?;
+            if file_type.is_dir() {
+                if dirs.contains(rel.as_path()) {
+                    pending.push(path);
+                } else {
+                    log::trace!("Export removing {path}");
+                    fs::remove_dir_all(&path).await?;
+                    removed += 1;
+                }
+            } else if !exported.contains(rel.as_path()) {
+                log::trace!("Export removing {path}");
+                fs::remove_file(&path).await?;
+                removed += 1;
+            }
+        }
+    }
+    Ok(removed)
+}
@@ -0,0 +1,159 @@
+use std::{collections::BTreeSet, process::Stdio, time::Duration};
+
+use camino::{Utf8Path, Utf8PathBuf};
+use itertools::Itertools;
+use tokio::process::Command;
+
+use crate::{
+    config::{Config, FmtOpts},
+    ext::{
+        anyhow::{Context, Result},
+        exit::Exit,
+        sync::{new_process_group, print_prefixed_lines, wait_interruptible, CommandResult},
+    },
+    logger::GRAY,
+    signal::Interrupt,
+};
+
+/// The config of leptosfmt, in the workspace root
+const LEPTOSFMT_CONFIG: &str = "leptosfmt.toml";
+
+/// Formats the packages with cargo fmt, which leaves the view! macros as they are, then the
+/// sources of the lib and bin packages with leptosfmt. With --check nothing is written and
+/// the command fails with the files that either tool would change.
+pub async fn fmt_all(conf: &Config, opts: &FmtOpts) -> Result<()> {
+    let leptosfmt = which::which("leptosfmt").context(
+        "Could not find leptosfmt, which formats the view! macros. Install it with `cargo install leptosfmt`",
+    )?;
+    let timeout = conf
+        .projects
+        .first()
+        .and_then(|proj| proj.command_timeouts.get("cargo"));
+
+    let mut command = Command::new("cargo");
+    command.arg("fmt").current_dir(&conf.working_dir);
+    match &conf.cli.project {
+        Some(_) => command.args(packages(conf).iter().flat_map(|name| ["-p", name.as_str()])),
+        None => command.arg("--all"),
+    };
+    if opts.check {
+        command.args(["--", "--check", "--files-with-diff"]);
+    }
+    let rustfmt = run("rustfmt", command, timeout).await?;
+    if matches!(rustfmt, CommandResult::Interrupted) {
+        return Err(Exit::Interrupted.into());
+    }
+
+    let mut command = Command::new(leptosfmt);
+    command.current_dir(&conf.working_dir);
+    if opts.check {
+        command.arg("--check");
+    }
+    let config_file = conf.working_dir.join(LEPTOSFMT_CONFIG);
+    if config_file.exists() {
+        command.arg("--config-file").arg(&config_file);
+    }
+    command.args(src_dirs(conf));
+    let leptosfmt = run("leptosfmt", command, timeout).await?;
+
+    let mut failed = Vec::new();
+    let mut unformatted = BTreeSet::new();
+    for (tool, result) in [("rustfmt", rustfmt), ("leptosfmt", leptosfmt)] {
+        match result {
+            CommandResult::Success(_) => {}
+            CommandResult::Failure(lines) => {
+                failed.push(tool);
+                unformatted.extend(rs_files(&lines, &conf.working_dir));
+            }
+            CommandResult::Interrupted => return Err(Exit::Interrupted.into()),
+        }
+    }
+    if failed.is_empty() {
+        let done = if opts.check {
+            "all formatted"
+        } else {
+            "formatted"
+        };
+        log::info!("Fmt {done}");
+        return Ok(());
+    }
+    let context = if !opts.check {
+        format!("Fmt failed for {}", failed.join(", "))
+    } else if unformatted.is_empty() {
+        format!("Fmt found unformatted files with {}", failed.join(", "))
+    } else {
+        format!(
+            "Fmt found {} unformatted files, run `cargo leptos fmt` to format them:\n  {}",
+            unformatted.len(),
+            unformatted.iter().join("\n  ")
+        )
+    };
+    Err(Exit::Failed).context(context)
+}
+
+/// The lib and bin packages of the selected projects
+fn packages(conf: &Config) -> BTreeSet<String> {
+    conf.projects
+        .iter()
+        .flat_map(|proj| {
+            let bin = proj.bin.as_ref().map(|bin| bin.name.clone());
+            std::iter::once(proj.lib.name.clone()).chain(bin)
+        })
+        .collect()
+}
+
+/// The source dirs of the lib and bin packages in the workspace, without the path
+/// dependencies outside of it, which have their own formatting
+fn src_dirs(conf: &Config) -> BTreeSet<Utf8PathBuf> {
+    conf.projects
+        .iter()
+        .flat_map(|proj| {
+            let bin = proj.bin.iter().flat_map(|bin| &bin.src_paths);
+            proj.lib.src_paths.iter().chain(bin)
+        })
+        .filter(|dir| dir.is_relative() && conf.working_dir.join(dir).exists())
+        .cloned()
+        .collect()
+}
+
+/// The .rs files named in the output of the tools, relative to the workspace root
+pub(crate) fn rs_files(lines: &[String], root: &Utf8Path) -> BTreeSet<Utf8PathBuf> {
+    lines
+        .iter()
+        .flat_map(|line| line.split_whitespace())
+        .map(|word| word.trim_matches(|c: char| !c.is_alphanumeric() && !"/._-".contains(c)))
+        .filter(|word| word.ends_with(".rs"))
+        .map(|word| {
+            let path = Utf8PathBuf::from(word);
+            path.strip_prefix(root)
+                .map_or(path.clone(), Utf8Path::to_path_buf)
+        })
+        .collect()
+}
+
+/// Runs the tool in its own process group, with its output prefixed, and keeps its lines
+async fn run(
+    tool: &str,
+    mut command: Command,
+    timeout: Option<Duration>,
+) -> Result<CommandResult<Vec<String>>> {
+    command.stdout(Stdio::piped()).stderr(Stdio::piped());
+    new_process_group(&mut command);
+    log::debug!(
+        "Fmt running {}",
+        GRAY.paint(format!("{:?}", command.as_std()))
+    );
+    let program = command.as_std().get_program().to_owned();
+    let mut process = command
+        .spawn()
+        .context(format!("Could not spawn {program:?} for {tool}"))?;
+    let lines = print_prefixed_lines(&mut process, &format!("[{tool}]"));
+    let name = format!("Fmt {tool}");
+    Ok(
+        match wait_interruptible(&name, process, Interrupt::subscribe_any(), timeout).await? {
+            CommandResult::Success(()) => CommandResult::Success(lines.await.unwrap_or_default()),
+            CommandResult::Failure(()) => CommandResult::Failure(lines.await.unwrap_or_default()),
+            CommandResult::Interrupted => CommandResult::Interrupted,
+        },
+    )
+}
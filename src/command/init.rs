@@ -0,0 +1,428 @@
+use std::io::{stdin, stdout, IsTerminal, Write};
+
+use camino::{Utf8Path, Utf8PathBuf};
+use cargo_metadata::{Metadata, Package};
+use clap::{Args, ValueEnum};
+
+use crate::{
+    compile::DEFAULT_TAILWIND_CONFIG,
+    config::{Config, Opts},
+    ext::{
+        anyhow::{bail, Context, Result},
+        fs, MetadataExt, PackageExt, PathBufExt,
+    },
+    logger::GRAY,
+};
+
+#[derive(Clone, Debug, Args, PartialEq, Eq)]
+pub struct InitCommand {
+    /// The project name. Defaults to the package name, or to the dir name in a workspace.
+    #[arg(long)]
+    pub name: Option<String>,
+
+    /// Server-side rendering with hydration, or client-side rendering without a server bin.
+    /// Defaults to ssr when there is a bin target.
+    #[arg(long, value_enum)]
+    pub mode: Option<RenderMode>,
+
+    /// The package of the server bin, in a workspace.
+    #[arg(long)]
+    pub bin_package: Option<String>,
+
+    /// The package of the front lib, in a workspace.
+    #[arg(long)]
+    pub lib_package: Option<String>,
+
+    /// The style file to create. Defaults to style/main.scss.
+    #[arg(long)]
+    pub style_file: Option<Utf8PathBuf>,
+
+    /// The assets dir to create. Defaults to public.
+    #[arg(long)]
+    pub assets_dir: Option<Utf8PathBuf>,
+
+    /// Also create a tailwind input file and config.
+    #[arg(long)]
+    pub tailwind: bool,
+
+    /// Don't ask, use the flags and the defaults.
+    #[arg(long, short)]
+    pub yes: bool,
+
+    /// Overwrite the existing files that differ from the generated ones.
+    #[arg(long, short)]
+    pub force: bool,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+pub enum RenderMode {
+    Ssr,
+    Csr,
+}
+
+/// Where the leptos config goes: the package section of a single package, or a project of the
+/// workspace section
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Layout {
+    Package,
+    Workspace {
+        bin_package: Option<String>,
+        lib_package: String,
+    },
+}
+
+/// The answers to the questions of init, from which the config and the files are generated
+#[derive(Debug, Clone)]
+pub(crate) struct InitConfig {
+    pub name: String,
+    pub layout: Layout,
+    pub csr: bool,
+    pub style_file: Utf8PathBuf,
+    pub assets_dir: Utf8PathBuf,
+    pub tailwind: bool,
+    /// the features of the lib and the bin builds, like hydrate and ssr, when the packages have them
+    pub lib_features: Vec<String>,
+    pub bin_features: Vec<String>,
+}
+
+impl InitCommand {
+    pub async fn run(&self, cwd: &Utf8Path, manifest_path: &Utf8Path) -> Result<()> {
+        let metadata = Metadata::load_cleaned(manifest_path)?;
+        if let Some(path) = existing_config(&metadata) {
+            bail!("{path} already has a leptos config");
+        }
+        let interactive = !self.yes && stdin().is_terminal();
+        let (config, dir) = self.answers(&metadata, interactive)?;
+        let manifest = dir.join("Cargo.toml");
+
+        // the existing files that have the generated contents are kept as they are
+        let mut files = Vec::new();
+        let mut conflicts = Vec::new();
+        for (path, contents) in config.files() {
+            let path = dir.join(path);
+            match std::fs::read_to_string(&path) {
+                Ok(existing) if existing == contents => continue,
+                Ok(existing) => {
+                    conflicts.push(format!("{path}\n{}", line_diff(&existing, &contents)))
+                }
+                Err(_) => {}
+            }
+            files.push((path, contents));
+        }
+        let assets_dir = dir.join(&config.assets_dir);
+        if assets_dir.exists() && !assets_dir.is_dir() {
+            bail!("The assets-dir {assets_dir} isn't a dir");
+        }
+        if !conflicts.is_empty() {
+            if !self.force {
+                bail!(
+                    "Init would overwrite files that differ, use --force to overwrite them:\n{}",
+                    conflicts.join("\n")
+                );
+            }
+            log::warn!("Init overwriting:\n{}", conflicts.join("\n"));
+        }
+
+        for (path, contents) in &files {
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent).await?;
+            }
+            fs::write(&path, contents).await?;
+            log::info!("Init created {}", GRAY.paint(path.as_str()));
+        }
+        fs::create_dir_all(&assets_dir).await?;
+
+        let original = fs::read_to_string(&manifest).await?;
+        let separator = if original.ends_with('\n') {
+            "\n"
+        } else {
+            "\n\n"
+        };
+        let updated = format!("{original}{separator}{}", config.section());
+        fs::write(&manifest, &updated).await?;
+
+        // the same check as a build, with the original manifest back when it fails
+        if let Err(e) = Config::load(Opts::default(), cwd, manifest_path, false) {
+            fs::write(&manifest, &original).await?;
+            return Err(e).context(format!(
+                "The generated leptos config is invalid, {manifest} is left unchanged"
+            ));
+        }
+        log::info!(
+            "Init added the leptos config to {}. Next: cargo leptos watch",
+            GRAY.paint(manifest.as_str())
+        );
+        Ok(())
+    }
+
+    /// The config from the flags, the questions and the packages, with the dir of the manifest
+    /// it goes into
+    fn answers(&self, metadata: &Metadata, interactive: bool) -> Result<(InitConfig, Utf8PathBuf)> {
+        let answer = |question: &str, given: &Option<String>, default: String| match given {
+            Some(given) => Ok(given.clone()),
+            None if interactive => ask(question, &default),
+            None => Ok(default),
+        };
+        let packages = metadata.workspace_packages();
+
+        let (name, layout, lib, bin, dir) = match (metadata.root_package(), &packages[..]) {
+            (Some(package), [_]) => {
+                let name = answer("Project name", &self.name, package.name.clone())?;
+                let csr = self.csr(package.has_bin_target(), interactive)?;
+                let dir = package.manifest_path.clone().without_last();
+                let bin = (!csr).then(|| package.clone());
+                (name, Layout::Package, package.clone(), bin, dir)
+            }
+            _ => {
+                let dir_name = metadata.workspace_root.file_name().unwrap_or("app");
+                let name = answer("Project name", &self.name, dir_name.to_string())?;
+                let default_lib = packages
+                    .iter()
+                    .find(|p| p.cdylib_target().is_some())
+                    .or(packages.first())
+                    .map(|p| p.name.clone())
+                    .unwrap_or_default();
+                let lib_package = answer("Lib package", &self.lib_package, default_lib)?;
+                let lib = find_package(&packages, &lib_package)?;
+
+                let default_bin = packages.iter().find(|p| p.has_bin_target());
+                let csr = self.csr(default_bin.is_some(), interactive)?;
+                let bin = if csr {
+                    None
+                } else {
+                    let default_bin = default_bin.map(|p| p.name.clone()).unwrap_or_default();
+                    let bin_package = answer("Bin package", &self.bin_package, default_bin)?;
+                    Some(find_package(&packages, &bin_package)?)
+                };
+                let layout = Layout::Workspace {
+                    bin_package: bin.as_ref().map(|p| p.name.clone()),
+                    lib_package,
+                };
+                (name, layout, lib, bin, metadata.workspace_root.clone())
+            }
+        };
+
+        if lib.cdylib_target().is_none() {
+            bail!(
+                "The lib package {} has no cdylib target for the wasm build, add to its Cargo.toml:\n[lib]\ncrate-type = [\"cdylib\", \"rlib\"]",
+                lib.name
+            );
+        }
+        if let Some(bin) = &bin {
+            if !bin.has_bin_target() {
+                bail!(
+                    "The bin package {} has no bin target, use --mode csr for a client-side-rendered project",
+                    bin.name
+                );
+            }
+        }
+        let csr = bin.is_none();
+        let lib_feature = if csr { "csr" } else { "hydrate" };
+
+        let style_file = answer(
+            "Style file",
+            &self.style_file.as_ref().map(ToString::to_string),
+            "style/main.scss".to_string(),
+        )?;
+        let assets_dir = answer(
+            "Assets dir",
+            &self.assets_dir.as_ref().map(ToString::to_string),
+            "public".to_string(),
+        )?;
+        let tailwind = self.tailwind || (interactive && ask_yes("Use tailwind", false)?);
+
+        let config = InitConfig {
+            name,
+            layout,
+            csr,
+            style_file: style_file.into(),
+            assets_dir: assets_dir.into(),
+            tailwind,
+            lib_features: features(&lib, lib_feature),
+            bin_features: bin.map(|bin| features(&bin, "ssr")).unwrap_or_default(),
+        };
+        Ok((config, dir))
+    }
+
+    fn csr(&self, has_bin: bool, interactive: bool) -> Result<bool> {
+        let default = if has_bin { "ssr" } else { "csr" };
+        let mode = match self.mode {
+            Some(mode) => mode,
+            None if interactive => loop {
+                match ask("Rendering, ssr or csr", default)?.as_str() {
+                    "ssr" => break RenderMode::Ssr,
+                    "csr" => break RenderMode::Csr,
+                    _ => continue,
+                }
+            },
+            None if has_bin => RenderMode::Ssr,
+            None => RenderMode::Csr,
+        };
+        Ok(mode == RenderMode::Csr)
+    }
+}
+
+impl InitConfig {
+    /// The metadata section appended to the Cargo.toml
+    pub fn section(&self) -> String {
+        let mut lines = Vec::new();
+        match &self.layout {
+            Layout::Package => {
+                lines.push("[package.metadata.leptos]".to_string());
+                lines.push(format!("output-name = {:?}", self.name));
+            }
+            Layout::Workspace {
+                bin_package,
+                lib_package,
+            } => {
+                lines.push("[[workspace.metadata.leptos]]".to_string());
+                lines.push(format!("name = {:?}", self.name));
+                if let Some(bin_package) = bin_package {
+                    lines.push(format!("bin-package = {bin_package:?}"));
+                }
+                lines.push(format!("lib-package = {lib_package:?}"));
+            }
+        }
+        if self.csr {
+            lines.push("csr = true".to_string());
+        }
+        lines.push(format!("style-file = {:?}", self.style_file.as_str()));
+        lines.push(format!("assets-dir = {:?}", self.assets_dir.as_str()));
+        if self.tailwind {
+            lines.push(format!(
+                "tailwind-input-file = {:?}",
+                self.tailwind_input_file().as_str()
+            ));
+            lines.push("tailwind-config-file = \"tailwind.config.js\"".to_string());
+        }
+        if !self.bin_features.is_empty() {
+            lines.push(format!("bin-features = {:?}", self.bin_features));
+            lines.push("bin-default-features = false".to_string());
+        }
+        if !self.lib_features.is_empty() {
+            lines.push(format!("lib-features = {:?}", self.lib_features));
+            lines.push("lib-default-features = false".to_string());
+        }
+        lines.push(String::new());
+        lines.join("\n")
+    }
+
+    /// The files to create, relative to the dir of the manifest
+    pub fn files(&self) -> Vec<(Utf8PathBuf, String)> {
+        let mut files = vec![(
+            self.style_file.clone(),
+            "body {\n  font-family: sans-serif;\n  text-align: center;\n}\n".to_string(),
+        )];
+        if self.tailwind {
+            files.push((
+                self.tailwind_input_file(),
+                "@tailwind base;\n@tailwind components;\n@tailwind utilities;\n".to_string(),
+            ));
+            files.push((
+                Utf8PathBuf::from("tailwind.config.js"),
+                DEFAULT_TAILWIND_CONFIG.to_string(),
+            ));
+        }
+        files
+    }
+
+    /// beside the style file
+    fn tailwind_input_file(&self) -> Utf8PathBuf {
+        match self.style_file.parent() {
+            Some(dir) => dir.join("tailwind.css"),
+            None => Utf8PathBuf::from("tailwind.css"),
+        }
+    }
+}
+
+/// The Cargo.toml with a leptos config, of the workspace or of a package
+fn existing_config(metadata: &Metadata) -> Option<Utf8PathBuf> {
+    if metadata.workspace_metadata.get("leptos").is_some() {
+        return Some(metadata.workspace_root.join("Cargo.toml"));
+    }
+    metadata
+        .workspace_packages()
+        .into_iter()
+        .find(|p| p.metadata.get("leptos").is_some())
+        .map(|p| p.manifest_path.clone())
+}
+
+fn find_package(packages: &[&Package], name: &str) -> Result<Package> {
+    match packages.iter().find(|p| p.name == name) {
+        Some(package) => Ok((*package).clone()),
+        None => {
+            let names: Vec<&str> = packages.iter().map(|p| p.name.as_str()).collect();
+            bail!(
+                "No package {name:?} in the workspace, expected one of {}",
+                names.join(", ")
+            )
+        }
+    }
+}
+
+/// The feature when the package has it
+fn features(package: &Package, feature: &str) -> Vec<String> {
+    if package.features.contains_key(feature) {
+        vec![feature.to_string()]
+    } else {
+        log::warn!(
+            "Init the package {} has no {feature} feature, add it and set it in the config",
+            package.name
+        );
+        vec![]
+    }
+}
+
+/// Asks on the terminal, with the default for an empty answer
+fn ask(question: &str, default: &str) -> Result<String> {
+    print!("{question} {}: ", GRAY.paint(format!("[{default}]")));
+    stdout().flush()?;
+    let mut answer = String::new();
+    stdin().read_line(&mut answer)?;
+    let answer = answer.trim();
+    Ok(if answer.is_empty() {
+        default.to_string()
+    } else {
+        answer.to_string()
+    })
+}
+
+fn ask_yes(question: &str, default: bool) -> Result<bool> {
+    let answer = ask(question, if default { "y" } else { "n" })?;
+    Ok(matches!(answer.to_lowercase().as_str(), "y" | "yes"))
+}
+
+/// The lines removed with `-` and the added ones with `+`, from the longest common subsequence
+/// of the lines
+pub(crate) fn line_diff(old: &str, new: &str) -> String {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+    // common[i][j] is the length of the common subsequence of old[i..] and new[j..]
+    let mut common = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            common[i][j] = if old[i] == new[j] {
+                common[i + 1][j + 1] + 1
+            } else {
+                common[i + 1][j].max(common[i][j + 1])
+            };
+        }
+    }
+
+    let mut diff = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            diff.push(format!("  {}", old[i]));
+            i += 1;
+            j += 1;
+        } else if j < new.len() && (i == old.len() || common[i][j + 1] > common[i + 1][j]) {
+            diff.push(format!("+ {}", new[j]));
+            j += 1;
+        } else {
+            diff.push(format!("- {}", old[i]));
+            i += 1;
+        }
+    }
+    diff.join("\n")
+}
@@ -1,13 +1,39 @@
+#[cfg(test)]
+mod tests;
+
+mod all_projects;
 mod build;
+mod bundle;
+mod clean;
+mod doc;
 mod end2end;
+mod export;
+mod fmt;
+#[cfg(feature = "cli")]
+mod init;
+#[cfg(feature = "cli")]
 mod new;
 mod serve;
 mod test;
+#[cfg(feature = "cli")]
+mod tools;
+mod version;
 pub mod watch;
 
-pub use build::build_all;
+pub use all_projects::all_projects;
+pub use build::{build_all, BuildReport, ProjectReport, StageReport};
+pub use bundle::bundle;
+pub use clean::CleanCommand;
+pub use doc::doc_all;
 pub use end2end::end2end_all;
+pub use fmt::fmt_all;
+#[cfg(feature = "cli")]
+pub use init::InitCommand;
+#[cfg(feature = "cli")]
 pub use new::NewCommand;
-pub use serve::serve;
-pub use test::test_all;
+pub use serve::{run, serve};
+pub use test::{test_all, ProjectResult, TestReport, TestResult};
+#[cfg(feature = "cli")]
+pub use tools::{ToolsAction, ToolsCommand};
+pub use version::version;
 pub use watch::watch;
@@ -1,9 +1,24 @@
-use crate::ext::anyhow::{Context, Result};
+use std::{
+    collections::HashSet,
+    io::{stdin, IsTerminal},
+    path::Path,
+    process::Stdio,
+};
+
+use crate::ext::anyhow::{anyhow, bail, ensure, Context, Result};
+use camino::{Utf8Path, Utf8PathBuf};
 use clap::Args;
 
 use tokio::process::Command;
 
-use crate::ext::exe::Exe;
+use crate::{
+    command::export::site_files,
+    ext::{
+        exe::{get_cache_dir, Exe, ToolVersions},
+        fs, PathExt,
+    },
+    logger::GRAY,
+};
 
 // A subset of the cargo-generate commands available.
 // See: https://github.com/cargo-generate/cargo-generate/blob/main/src/args.rs
@@ -13,19 +28,35 @@ use crate::ext::exe::Exe;
 #[clap(about)]
 pub struct NewCommand {
     /// Git repository to clone template from. Can be a URL (like
-    /// `https://github.com/rust-cli/cli-template`), a path (relative or absolute), or an
-    /// `owner/repo` abbreviated GitHub URL (like `rust-cli/cli-template`).
+    /// `https://github.com/rust-cli/cli-template` or an ssh remote like
+    /// `git@git.example.com:templates/leptos.git`), a path (relative or absolute), or an
+    /// `owner/repo` abbreviated GitHub URL (like `rust-cli/cli-template`). The template is
+    /// cached, so that it is used offline the next times.
     #[clap(short, long, group("SpecificPath"))]
     pub git: Option<String>,
 
     /// Branch to use when installing from git
-    #[clap(short, long, conflicts_with = "tag")]
+    #[clap(short, long, conflicts_with_all = ["tag", "rev"])]
     pub branch: Option<String>,
 
     /// Tag to use when installing from git
-    #[clap(short, long, conflicts_with = "branch")]
+    #[clap(short, long, conflicts_with_all = ["branch", "rev"])]
     pub tag: Option<String>,
 
+    /// Commit to use when installing from git
+    #[clap(short, long, conflicts_with_all = ["branch", "tag"])]
+    pub rev: Option<String>,
+
+    /// The dir of the template in the git repository or the path, for the repositories of
+    /// several templates.
+    #[clap(long)]
+    pub subdir: Option<Utf8PathBuf>,
+
+    /// Fetch the git template again instead of using the cached one, e.g. for the new
+    /// commits of its branch.
+    #[clap(long, action)]
+    pub refresh: bool,
+
     /// Local path to copy the template from. Can not be specified together with --git.
     #[clap(short, long, group("SpecificPath"))]
     pub path: Option<String>,
@@ -47,34 +78,490 @@ pub struct NewCommand {
     /// Generate the template directly into the current dir. No subfolder will be created and no vcs is initialized.
     #[clap(long, action)]
     pub init: bool,
+
+    /// Value of a placeholder of the template, like `--define css=tailwind`, which isn't asked
+    /// for then. Can be given several times.
+    #[clap(short, long, value_name = "KEY=VALUE")]
+    pub define: Vec<String>,
+
+    /// Print the files that would be generated, without writing them.
+    #[clap(long, action)]
+    pub dry_run: bool,
+
+    /// A client-side-rendered project: a lib compiled to wasm, with the site served as static
+    /// files. Without --git or --path, the built-in template is used.
+    #[clap(long, action, conflicts_with_all = ["ssr", "islands"])]
+    pub csr: bool,
+
+    /// A server-side-rendered axum project, with the bin built with the ssr feature and the
+    /// lib hydrated in the browser with the hydrate feature.
+    #[clap(long, action)]
+    pub ssr: bool,
+
+    /// With --ssr, only the islands of the pages are hydrated in the browser.
+    #[clap(long, action, requires = "ssr")]
+    pub islands: bool,
+}
+
+/// The rendering of the project, for the templates that declare a `flavor` placeholder with
+/// these choices in their cargo-generate.toml, like the built-in one
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Flavor {
+    Csr,
+    Ssr,
+    Islands,
+}
+
+impl Flavor {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Csr => "csr",
+            Self::Ssr => "ssr",
+            Self::Islands => "islands",
+        }
+    }
+}
+
+/// The branch, tag or commit of a git template
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GitRef<'a> {
+    Branch(&'a str),
+    Tag(&'a str),
+    Rev(&'a str),
 }
 
 impl NewCommand {
     pub async fn run(&self) -> Result<()> {
-        let args = self.to_args();
-        let exe = Exe::CargoGenerate.get().await.dot()?;
+        // without a terminal, cargo-generate would wait for the answers on stdin
+        let interactive = stdin().is_terminal();
+        if !interactive && self.name.is_none() {
+            bail!("The --name is needed when not run in a terminal, as it can't be asked for");
+        }
+        if let Some(name) = &self.name {
+            check_crate_name(name)?;
+        }
+        for define in &self.define {
+            parse_define(define)?;
+        }
 
-        let mut process = Command::new(exe)
-            .arg("generate")
-            .args(&args)
-            .spawn()
-            .context("Could not spawn cargo-generate command (verify that it is installed)")?;
-        process.wait().await.dot()?;
+        let flavor = self.flavor();
+        if flavor.is_some() && self.define.iter().any(|d| d.starts_with("flavor=")) {
+            bail!("The flavor is given twice, by the flag and by --define flavor=");
+        }
+
+        let template = match (&self.git, &self.path) {
+            (Some(git), _) => self.fetch_template(git).await?,
+            (None, Some(path)) => match &self.subdir {
+                Some(subdir) => Utf8Path::new(path).join(subdir),
+                None => Utf8PathBuf::from(path),
+            },
+            (None, None) if self.subdir.is_some() => bail!("The --subdir needs --git or --path"),
+            (None, None) => builtin_template().await?,
+        };
+        if let Some(flavor) = flavor {
+            check_flavor(&template, flavor)?;
+        }
+        let args = self.to_args(&template, interactive);
+
+        if !self.dry_run {
+            let dir = self
+                .generate(&args, Utf8Path::new("."), interactive)
+                .await?;
+            if self.init {
+                log::info!("New project generated. Next: cargo leptos watch");
+            } else {
+                log::info!("New project generated in {dir}. Next: cd {dir} && cargo leptos watch");
+            }
+            return Ok(());
+        }
+
+        // generated in a temporary dir, for the file tree
+        let tmp = Utf8PathBuf::from_path_buf(std::env::temp_dir())
+            .map_err(|p| anyhow!("Non utf8 path {p:?}"))?
+            .join(format!("cargo-leptos-new-{}", std::process::id()));
+        fs::create_dir_all(&tmp).await?;
+        let res = self.generate(&args, &tmp, interactive).await;
+        let res = match res {
+            Ok(dir) => site_files(&dir).await.map(|files| (dir, files)),
+            Err(e) => Err(e),
+        };
+        fs::remove_dir_all(&tmp).await?;
+        let (dir, files) = res?;
+
+        let name = dir.unbase(&tmp).unwrap_or_default();
+        let tree: Vec<String> = files
+            .iter()
+            .map(|(rel, _)| format!("  {}", name.join(rel)))
+            .collect();
+        log::info!(
+            "New would generate {} files, nothing written:\n{}",
+            files.len(),
+            tree.join("\n")
+        );
         Ok(())
     }
 
-    pub fn to_args(&self) -> Vec<String> {
-        let mut args = vec![];
-        opt_push(&mut args, "git", &absolute_git_url(&self.git));
-        opt_push(&mut args, "branch", &self.branch);
-        opt_push(&mut args, "tag", &self.tag);
-        opt_push(&mut args, "path", &self.path);
+    /// Runs cargo-generate in the out dir and returns the dir of the project it generated
+    async fn generate(
+        &self,
+        args: &[String],
+        out_dir: &Utf8Path,
+        interactive: bool,
+    ) -> Result<Utf8PathBuf> {
+        let exe = Exe::CargoGenerate
+            .get(&ToolVersions::default())
+            .await
+            .dot()?;
+
+        let before = dirs(out_dir).await?;
+        let mut command = Command::new(exe);
+        command.arg("generate").args(args);
+        let in_cwd = out_dir.as_str() == ".";
+        if !in_cwd {
+            command.arg("--destination").arg(out_dir);
+        }
+        if !interactive {
+            command.stdin(Stdio::null());
+        }
+        let status = command
+            .status()
+            .await
+            .context("Could not spawn cargo-generate command (verify that it is installed)")?;
+        ensure!(status.success(), "cargo-generate failed with {status}");
+
+        let dir = if self.init {
+            out_dir.to_path_buf()
+        } else {
+            let mut created: Vec<Utf8PathBuf> =
+                dirs(out_dir).await?.difference(&before).cloned().collect();
+            created.retain(|name| out_dir.join(name).join("Cargo.toml").exists());
+            match created.as_slice() {
+                [name] if in_cwd => name.clone(),
+                [name] => out_dir.join(name),
+                _ => bail!("Could not find the project generated by cargo-generate"),
+            }
+        };
+        let source = self.git.as_ref().or(self.path.as_ref());
+        check_leptos_project(&dir, source.map_or("built-in", String::as_str))?;
+        Ok(dir)
+    }
+
+    /// The template is always a dir: a git template is copied from the cache. Without a
+    /// terminal the placeholders that aren't defined get the defaults of the template.
+    pub fn to_args(&self, template: &Utf8Path, interactive: bool) -> Vec<String> {
+        let mut args = vec!["--path".to_string(), template.to_string()];
         opt_push(&mut args, "name", &self.name);
         bool_push(&mut args, "force", self.force);
         bool_push(&mut args, "verbose", self.verbose);
         bool_push(&mut args, "init", self.init);
+        for define in &self.define {
+            args.push("--define".to_string());
+            args.push(define.clone());
+        }
+        if let Some(flavor) = self.flavor() {
+            args.push("--define".to_string());
+            args.push(format!("flavor={}", flavor.name()));
+        }
+        bool_push(&mut args, "silent", !interactive);
         args
     }
+
+    fn flavor(&self) -> Option<Flavor> {
+        if self.csr {
+            Some(Flavor::Csr)
+        } else if self.islands {
+            Some(Flavor::Islands)
+        } else if self.ssr {
+            Some(Flavor::Ssr)
+        } else {
+            None
+        }
+    }
+
+    fn git_ref(&self) -> Option<GitRef> {
+        if let Some(branch) = &self.branch {
+            Some(GitRef::Branch(branch))
+        } else if let Some(tag) = &self.tag {
+            Some(GitRef::Tag(tag))
+        } else {
+            self.rev.as_deref().map(GitRef::Rev)
+        }
+    }
+
+    /// The template dir in the cache, which is cloned unless it was already or with --refresh
+    async fn fetch_template(&self, git: &str) -> Result<Utf8PathBuf> {
+        let url = git_url(git);
+        let git_ref = self.git_ref();
+        let cache_dir = Utf8PathBuf::from_path_buf(get_cache_dir()?.join("templates"))
+            .map_err(|p| anyhow!("Non utf8 path {p:?}"))?;
+        let dir = cache_dir.join(template_dir_name(&url, git_ref));
+
+        if dir.exists() && !self.refresh {
+            log::info!(
+                "New using the cached template {url} {}",
+                GRAY.paint(dir.as_str())
+            );
+        } else {
+            log::info!("New fetching the template {url}");
+            fs::create_dir_all(&cache_dir).await?;
+            clone(&url, git_ref, &dir).await.context(format!(
+                "Could not fetch the template {url}{}",
+                if dir.exists() {
+                    ". Without --refresh the cached one is used"
+                } else {
+                    ""
+                }
+            ))?;
+        }
+
+        let Some(subdir) = &self.subdir else {
+            return Ok(dir);
+        };
+        let template = dir.join(subdir);
+        ensure!(
+            template.is_dir(),
+            "The template {url} has no subdir {subdir}"
+        );
+        Ok(template)
+    }
+}
+
+/// The files of the built-in template, by their path in the template
+const BUILTIN_TEMPLATE: &[(&str, &str)] = &[
+    (
+        "cargo-generate.toml",
+        include_str!("../../templates/leptos/cargo-generate.toml"),
+    ),
+    // not named Cargo.toml, which would make it a package of its own for cargo
+    (
+        "Cargo.toml",
+        include_str!("../../templates/leptos/Cargo.toml.liquid"),
+    ),
+    (
+        ".gitignore",
+        include_str!("../../templates/leptos/gitignore"),
+    ),
+    (
+        "src/app.rs",
+        include_str!("../../templates/leptos/src/app.rs"),
+    ),
+    (
+        "src/fileserv.rs",
+        include_str!("../../templates/leptos/src/fileserv.rs"),
+    ),
+    (
+        "src/lib.rs",
+        include_str!("../../templates/leptos/src/lib.rs"),
+    ),
+    (
+        "src/main.rs",
+        include_str!("../../templates/leptos/src/main.rs"),
+    ),
+    (
+        "style/main.scss",
+        include_str!("../../templates/leptos/style/main.scss"),
+    ),
+    (
+        "public/robots.txt",
+        include_str!("../../templates/leptos/public/robots.txt"),
+    ),
+];
+
+/// Writes the built-in template to the cache, by version of cargo-leptos
+async fn builtin_template() -> Result<Utf8PathBuf> {
+    let dir = Utf8PathBuf::from_path_buf(get_cache_dir()?.join("templates"))
+        .map_err(|p| anyhow!("Non utf8 path {p:?}"))?
+        .join(format!("builtin-{}", env!("CARGO_PKG_VERSION")));
+    for (path, contents) in BUILTIN_TEMPLATE {
+        let path = dir.join(path);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+        fs::write(&path, contents).await?;
+    }
+    Ok(dir)
+}
+
+/// The template declares the flavor in the choices of its `flavor` placeholder
+pub(crate) fn check_flavor(template: &Utf8Path, flavor: Flavor) -> Result<()> {
+    let file = template.join("cargo-generate.toml");
+    let choices: Vec<String> = match std::fs::read_to_string(&file) {
+        Ok(text) => {
+            let toml: toml::Value = toml::from_str(&text).context(format!("Invalid {file}"))?;
+            toml.get("placeholders")
+                .and_then(|placeholders| placeholders.get("flavor"))
+                .and_then(|placeholder| placeholder.get("choices"))
+                .and_then(|choices| choices.as_array())
+                .map(|choices| {
+                    choices
+                        .iter()
+                        .filter_map(|choice| choice.as_str().map(ToString::to_string))
+                        .collect()
+                })
+                .unwrap_or_default()
+        }
+        Err(_) => vec![],
+    };
+    ensure!(
+        !choices.is_empty(),
+        "The template {template} has no flavors: --{} needs a template with a flavor placeholder in its cargo-generate.toml",
+        flavor.name()
+    );
+    ensure!(
+        choices.iter().any(|choice| choice == flavor.name()),
+        "The template {template} has no {} flavor, only {}",
+        flavor.name(),
+        choices.join(", ")
+    );
+    Ok(())
+}
+
+/// The url to clone, with the `owner/repo` abbreviations of cargo-generate as GitHub urls
+pub(crate) fn git_url(git: &str) -> String {
+    let abbreviated = !git.contains(':')
+        && !git.starts_with('.')
+        && !git.starts_with('/')
+        && git.matches('/').count() == 1
+        && !Path::new(git).exists();
+    if abbreviated {
+        format!("https://github.com/{git}")
+    } else {
+        git.to_string()
+    }
+}
+
+/// The repository name with a hash of the url and the ref, like `start-axum-1a2b3c4d5e6f7a8b`
+pub(crate) fn template_dir_name(url: &str, git_ref: Option<GitRef>) -> String {
+    let name = url
+        .trim_end_matches('/')
+        .trim_end_matches(".git")
+        .rsplit(['/', ':'])
+        .next()
+        .unwrap_or_default();
+    let name: String = name
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    let hash = seahash::hash(format!("{url} {git_ref:?}").as_bytes());
+    format!("{name}-{hash:016x}")
+}
+
+/// Clones the template beside the dir and then moves it over, so that a failed clone keeps
+/// the cached template. The git history isn't kept, as cargo-generate only needs the files.
+async fn clone(url: &str, git_ref: Option<GitRef<'_>>, dir: &Utf8Path) -> Result<()> {
+    let tmp = Utf8PathBuf::try_from(fs::tmp_path(dir.as_std_path())).dot()?;
+    if tmp.exists() {
+        fs::remove_dir_all(&tmp).await?;
+    }
+    let mut args = vec!["clone", "--quiet"];
+    match git_ref {
+        Some(GitRef::Branch(name) | GitRef::Tag(name)) => {
+            args.extend(["--depth", "1", "--branch", name])
+        }
+        // a commit can't be fetched by a shallow clone from every host
+        Some(GitRef::Rev(_)) => {}
+        None => args.extend(["--depth", "1"]),
+    }
+    args.extend([url, tmp.as_str()]);
+    git(&args).await?;
+    if let Some(GitRef::Rev(rev)) = git_ref {
+        let res = git(&["-C", tmp.as_str(), "checkout", "--quiet", rev]).await;
+        if res.is_err() {
+            _ = fs::remove_dir_all(&tmp).await;
+        }
+        res.context(format!("No commit {rev}"))?;
+    }
+    fs::remove_dir_all(tmp.join(".git")).await?;
+
+    if dir.exists() {
+        fs::remove_dir_all(dir).await?;
+    }
+    fs::rename(&tmp, dir).await
+}
+
+async fn git(args: &[&str]) -> Result<()> {
+    let status = Command::new("git")
+        .args(args)
+        .status()
+        .await
+        .context("Could not run git (verify that it is installed)")?;
+    ensure!(
+        status.success(),
+        "git {} failed with {status}",
+        args.join(" ")
+    );
+    Ok(())
+}
+
+/// The names of the dirs of the dir
+async fn dirs(dir: &Utf8Path) -> Result<HashSet<Utf8PathBuf>> {
+    let mut dirs = HashSet::new();
+    let mut entries = fs::read_dir(dir).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        if entry.file_type().await?.is_dir() {
+            let name = entry
+                .file_name()
+                .into_string()
+                .map_err(|name| anyhow!("Non utf8 name {name:?}"))?;
+            dirs.insert(Utf8PathBuf::from(name));
+        }
+    }
+    Ok(dirs)
+}
+
+/// The generated project has the leptos config of a package or of a workspace
+pub(crate) fn check_leptos_project(dir: &Utf8Path, source: &str) -> Result<()> {
+    let manifest = dir.join("Cargo.toml");
+    let text = std::fs::read_to_string(&manifest).context(format!("Could not read {manifest}"))?;
+    let toml: toml::Value = toml::from_str(&text).context(format!("Invalid {manifest}"))?;
+    let has_config = |section: &str| {
+        toml.get(section)
+            .and_then(|section| section.get("metadata"))
+            .and_then(|metadata| metadata.get("leptos"))
+            .is_some()
+    };
+    ensure!(
+        has_config("package") || has_config("workspace"),
+        "The template {source} isn't a leptos project: {manifest} has no [package.metadata.leptos] \
+         or [[workspace.metadata.leptos]] config. Is it a template for cargo-leptos?"
+    );
+    Ok(())
+}
+
+/// The name of the project is its crate name with `_` for `-`
+pub(crate) fn check_crate_name(name: &str) -> Result<()> {
+    const KEYWORDS: &[&str] = &[
+        "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum",
+        "extern", "false", "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move",
+        "mut", "pub", "ref", "return", "self", "static", "struct", "super", "trait", "true",
+        "type", "unsafe", "use", "where", "while",
+    ];
+    let crate_name = name.replace('-', "_");
+    let valid = crate_name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && crate_name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && crate_name != "_"
+        && !KEYWORDS.contains(&crate_name.as_str());
+    ensure!(
+        valid,
+        "The name {name:?} isn't a valid crate name: it needs to start with a letter and to have only letters, digits, - and _, and not to be a Rust keyword"
+    );
+    Ok(())
+}
+
+/// The key and the value of a --define
+pub(crate) fn parse_define(define: &str) -> Result<(&str, &str)> {
+    match define.split_once('=') {
+        Some((key, value)) if !key.trim().is_empty() => Ok((key.trim(), value)),
+        _ => bail!("Invalid --define {define:?}, expected KEY=VALUE"),
+    }
 }
 
 fn bool_push(args: &mut Vec<String>, name: &str, set: bool) {
@@ -89,16 +576,3 @@ fn opt_push(args: &mut Vec<String>, name: &str, arg: &Option<String>) {
         args.push(arg.clone());
     }
 }
-
-/// Workaround to support short `new --git leptos-rs/start` command when behind Git proxy.
-/// See https://github.com/cargo-generate/cargo-generate/issues/752.
-fn absolute_git_url(url: &Option<String>) -> Option<String> {
-    match url {
-        Some(url) => match url.as_str() {
-            "leptos-rs/start" => Some("https://github.com/leptos-rs/start".to_string()),
-            "leptos-rs/start-axum" => Some("https://github.com/leptos-rs/start-axum".to_string()),
-            _ => Some(url.to_string()),
-        },
-        None => None,
-    }
-}
@@ -1,14 +1,68 @@
 use std::sync::Arc;
 
-use crate::config::Project;
-use crate::ext::anyhow::{Context, Result};
-use crate::service::serve;
+use crate::config::{Project, ServeOpts};
+use crate::ext::anyhow::{bail, Context, Result};
+use crate::ext::exit::{Exit, ServerExit};
+use crate::service::{open, proxy, serve};
 
-pub async fn serve(proj: &Arc<Project>) -> Result<()> {
-    if !super::build::build_proj(proj).await.dot()? {
-        return Ok(());
+pub async fn serve(proj: &Arc<Project>, opts: &ServeOpts) -> Result<()> {
+    if opts.stats {
+        bail!("The --stats flag is only for watch, serve doesn't rebuild");
+    }
+    if opts.no_build {
+        check_artifacts(proj, "Serve")?;
+    } else if !super::build::build_proj(proj).await.dot()? {
+        return Err(super::build::not_built(proj).await);
     }
+    proxy::spawn(proj).await;
     let server = serve::spawn(proj).await;
+    if let Some(path) = &opts.open {
+        open::spawn(proj, path);
+    }
     server.await??;
     Ok(())
 }
+
+/// Builds and runs the server once, without the reload vars, exiting with the exit code of
+/// the server. A csr site is served until ctrl-c.
+pub async fn run(proj: &Arc<Project>) -> Result<()> {
+    if !super::build::build_proj(proj).await.dot()? {
+        return Err(super::build::not_built(proj).await);
+    }
+    proxy::spawn(proj).await;
+    if proj.bin.is_none() {
+        return serve::spawn(proj).await.await?;
+    }
+    match serve::run(proj).await? {
+        Some(status) if status.success() => Ok(()),
+        Some(status) => match status.code() {
+            Some(code) => Err(ServerExit(code).into()),
+            None => Err(Exit::Failed).context(format!("The server exited with {status}")),
+        },
+        // stopped by ctrl-c
+        None => Ok(()),
+    }
+}
+
+/// The server exe and the site pkg dir of a previous build, that `--no-build` serves or bundles
+pub(super) fn check_artifacts(proj: &Project, tag: &str) -> Result<()> {
+    let mut missing = Vec::new();
+    if let Some(bin) = &proj.bin {
+        if !bin.exe_file.is_file() {
+            missing.push(bin.exe_file.to_string());
+        }
+    }
+    let pkg_dir = proj.site.root_relative_pkg_dir();
+    if !pkg_dir.is_dir() {
+        missing.push(pkg_dir.to_string());
+    }
+    let release = if proj.release { " --release" } else { "" };
+    if !missing.is_empty() {
+        bail!(
+            "{tag} --no-build is missing the artifacts of a previous build: {}. Build them with `cargo leptos build{release}`",
+            missing.join(", ")
+        );
+    }
+    log::info!("{tag} skipping the build, using the last `cargo leptos build{release}`");
+    Ok(())
+}
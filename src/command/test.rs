@@ -1,26 +1,558 @@
-use crate::compile::{front_cargo_process, server_cargo_process};
-use crate::config::{Config, Project};
-use crate::ext::anyhow::{Context, Result};
+use std::fmt::Display;
+use std::process::Stdio;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use super::end2end::end2end_result;
+use crate::compile::{
+    build_cargo_front_cmd, build_cargo_lib_ssr_cmd, build_cargo_server_cmd, ChangeSet,
+};
+use crate::config::{Config, EndToEndOpts, Project, TestOpts, TestRunner, WasmTestBrowser};
+use crate::ext::anyhow::{bail, Context, Result};
+use crate::ext::sync::{new_process_group, print_prefixed, wait_interruptible, CommandResult};
+use crate::ext::Exe;
 use crate::logger::GRAY;
+use crate::service;
+use crate::signal::Interrupt;
+use tokio::process::Command;
+use tokio::sync::Semaphore;
+
+/// Tests the projects. The failed tests are in the report, see [`TestReport::check`], which
+/// is empty with `--watch`
+pub async fn test_all(conf: &Config, opts: &TestOpts) -> Result<TestReport> {
+    if opts.watch {
+        watch_proj(&conf.current_project()?, opts).await?;
+        return Ok(TestReport::default());
+    }
+    let e2e = opts.e2e || opts.e2e_only;
+    let mut results = if opts.e2e_only {
+        conf.projects
+            .iter()
+            .map(|proj| ProjectResult {
+                name: proj.name.clone(),
+                result: TestResult::Skipped,
+                e2e: None,
+                time: Duration::ZERO,
+            })
+            .collect()
+    } else if opts.jobs > 1 && conf.projects.len() > 1 {
+        test_parallel(conf, opts).await?
+    } else {
+        test_serial(conf, opts).await?
+    };
+    if e2e {
+        e2e_serial(conf, opts, &mut results).await;
+    }
+
+    if conf.projects.len() > 1 || e2e {
+        log::info!("Tests summary:");
+        for res in &results {
+            let e2e = match res.e2e {
+                Some(result) => format!("e2e {result}"),
+                None if e2e => "no e2e".to_string(),
+                None => String::new(),
+            };
+            log::info!(
+                "  {:<20} {:<11} {:<15} {:.2?}",
+                res.name,
+                res.result.to_string(),
+                e2e,
+                res.time
+            );
+        }
+    }
+
+    Ok(TestReport { projects: results })
+}
+
+/// The outcome of the tests of each project, without the projects skipped by `--fail-fast`
+#[derive(Debug, Default)]
+pub struct TestReport {
+    pub projects: Vec<ProjectResult>,
+}
 
-pub async fn test_all(conf: &Config) -> Result<()> {
+impl TestReport {
+    /// The report, or the error of the binary when the tests or the end2end suite of a
+    /// project failed
+    pub fn check(self) -> Result<Self> {
+        let mut failed = Vec::new();
+        for res in &self.projects {
+            if res.result == TestResult::Failed {
+                failed.push(res.name.clone());
+            }
+            if res.e2e == Some(TestResult::Failed) {
+                failed.push(format!("{} (e2e)", res.name));
+            }
+        }
+        if !failed.is_empty() {
+            bail!("Tests failed for {}", failed.join(", "));
+        }
+        Ok(self)
+    }
+}
+
+#[derive(Debug)]
+pub struct ProjectResult {
+    pub name: String,
+    /// of the cargo tests, skipped with `--e2e-only`
+    pub result: TestResult,
+    /// of the end2end suite with `--e2e`, None when the project has none or it didn't run
+    pub e2e: Option<TestResult>,
+    /// of the cargo tests and the end2end run
+    pub time: Duration,
+}
+
+async fn test_serial(conf: &Config, opts: &TestOpts) -> Result<Vec<ProjectResult>> {
+    let mut results = Vec::new();
+    let changes = ChangeSet::all_changes();
     for proj in &conf.projects {
-        test_proj(proj).await?;
+        let start = Instant::now();
+        let result = test_proj(proj, opts, &changes, false).await?;
+        let stop = match result {
+            TestResult::Passed | TestResult::Skipped => false,
+            TestResult::Failed => opts.fail_fast,
+            TestResult::Interrupted => true,
+        };
+        results.push(ProjectResult {
+            name: proj.name.clone(),
+            result,
+            e2e: None,
+            time: start.elapsed(),
+        });
+        if stop {
+            break;
+        }
     }
-    Ok(())
+    Ok(results)
 }
 
-pub async fn test_proj(proj: &Project) -> Result<()> {
-    let (envs, line, mut proc) = server_cargo_process("test", proj).dot()?;
+/// Tests up to `--jobs` projects at the same time, with the output prefixed by the project
+/// name. The projects share the target dirs, so cargo's lock on the target dir makes the
+/// compilation mostly sequential, and it is the test execution that runs in parallel.
+async fn test_parallel(conf: &Config, opts: &TestOpts) -> Result<Vec<ProjectResult>> {
+    let permits = Arc::new(Semaphore::new(opts.jobs));
+    let stop = Arc::new(AtomicBool::new(false));
+    let mut handles = Vec::new();
 
-    proc.wait().await.dot()?;
-    log::debug!("Cargo envs: {}", GRAY.paint(envs));
-    log::info!("Cargo server tests finished {}", GRAY.paint(line));
+    for proj in &conf.projects {
+        let permit = permits.clone().acquire_owned().await.dot()?;
+        if stop.load(Ordering::Relaxed) || Interrupt::is_shutdown_requested().await {
+            break;
+        }
+        let (proj, opts, stop) = (proj.clone(), opts.clone(), stop.clone());
+
+        handles.push(tokio::spawn(async move {
+            let start = Instant::now();
+            let result = test_proj(&proj, &opts, &ChangeSet::all_changes(), true).await;
+            drop(permit);
+            match result {
+                Ok(TestResult::Passed) => {}
+                Ok(TestResult::Failed) if !opts.fail_fast => {}
+                _ => stop.store(true, Ordering::Relaxed),
+            }
+            result.map(|result| ProjectResult {
+                name: proj.name.clone(),
+                result,
+                e2e: None,
+                time: start.elapsed(),
+            })
+        }));
+    }
+
+    let mut results = Vec::new();
+    for handle in handles {
+        results.push(handle.await??);
+    }
+    Ok(results)
+}
+
+/// Runs the end2end suites after the tests of all the projects, one project at a time as
+/// their servers may listen on the same address. The build for the server reuses the
+/// dependencies compiled by the tests, as it has the same target dirs and features.
+async fn e2e_serial(conf: &Config, opts: &TestOpts, results: &mut [ProjectResult]) {
+    let e2e_opts = EndToEndOpts {
+        suite: opts.e2e_suite.clone(),
+        opts: opts.opts.clone(),
+        ..Default::default()
+    };
+    // the results are in the order of the projects, without the ones after a stop
+    for (proj, res) in conf.projects.iter().zip(results.iter_mut()) {
+        match res.result {
+            TestResult::Interrupted => break,
+            TestResult::Failed if opts.fail_fast => break,
+            _ => {}
+        }
+        if proj.end2end.is_none() {
+            log::debug!("End2End no suite for {}", proj.name);
+            continue;
+        }
+        let start = Instant::now();
+        let result = end2end_result(proj, &e2e_opts).await;
+        res.time += start.elapsed();
+        res.e2e = Some(result);
+        match result {
+            TestResult::Interrupted => break,
+            TestResult::Failed if opts.fail_fast => break,
+            _ => {}
+        }
+    }
+}
+
+/// Reruns the tests affected by the source changes, until ctrl-c is pressed.
+/// A test run is interrupted when new changes arrive.
+async fn watch_proj(proj: &Arc<Project>, opts: &TestOpts) -> Result<()> {
+    let _watch = service::notify::spawn(proj).await?;
+    let mut int = Interrupt::subscribe_any();
+    // all tests are run until a run has completed
+    let mut run_all = true;
+
+    loop {
+        let changes = if run_all {
+            ChangeSet::all_changes()
+        } else {
+            Interrupt::get_source_changes().await
+        };
+
+        if changes.need_server_build() || changes.need_front_build() {
+            let start = Instant::now();
+            let res = test_proj(proj, opts, &changes, false).await?;
+            let time = start.elapsed();
+            match res {
+                TestResult::Passed => log::info!("Test watch passed in {time:.2?}"),
+                TestResult::Failed => log::warn!("Test watch failed in {time:.2?}"),
+                TestResult::Interrupted if Interrupt::is_shutdown_requested().await => {
+                    return Ok(())
+                }
+                TestResult::Interrupted => log::info!("Test watch interrupted. Restarting."),
+                TestResult::Skipped => {}
+            }
+            if !matches!(res, TestResult::Interrupted) {
+                run_all = false;
+                Interrupt::clear_source_changes().await;
+            }
+        } else {
+            log::trace!("Test watch ignoring changes that don't affect the tests");
+            Interrupt::clear_source_changes().await;
+        }
+
+        log::debug!("Test watch waiting for changes");
+        int.recv().await.dot()?;
+
+        if Interrupt::is_shutdown_requested().await {
+            log::debug!("Shutting down");
+            return Ok(());
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TestResult {
+    Passed,
+    Failed,
+    Interrupted,
+    /// the cargo tests of `--e2e-only`
+    Skipped,
+}
+
+impl Display for TestResult {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Passed => write!(f, "passed"),
+            Self::Failed => write!(f, "failed"),
+            Self::Interrupted => write!(f, "interrupted"),
+            Self::Skipped => write!(f, "skipped"),
+        }
+    }
+}
+
+/// Which doctests are run. By default the front tests are the lib unit tests and the
+/// server tests are what `cargo test` runs for the bin package, including its doctests
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum DocTests {
+    Default,
+    Include,
+    Exclude,
+    Only,
+}
 
-    let (envs, line, mut proc) = front_cargo_process("test", false, proj).dot()?;
+impl DocTests {
+    pub(crate) fn new(opts: &TestOpts) -> Self {
+        if opts.doc_only {
+            Self::Only
+        } else if opts.no_doc {
+            Self::Exclude
+        } else if opts.doc {
+            Self::Include
+        } else {
+            Self::Default
+        }
+    }
+
+    /// The cargo targets for testing the lib package
+    pub(crate) fn lib_targets(&self) -> &'static [&'static str] {
+        match self {
+            Self::Default | Self::Exclude => &["--lib"],
+            // all targets, like cargo test does
+            Self::Include => &[],
+            Self::Only => &["--doc"],
+        }
+    }
+
+    /// The cargo targets for testing the bin package, None when it isn't tested
+    pub(crate) fn bin_targets(&self, proj: &Project) -> Option<&'static [&'static str]> {
+        let same_package = proj.lib.name == proj.bin.as_ref()?.name;
+        match self {
+            Self::Default | Self::Include => Some(&[]),
+            Self::Exclude if same_package => Some(&["--lib", "--bins", "--tests"]),
+            Self::Exclude => Some(&["--bins", "--tests"]),
+            // a separate bin package is not expected to have a lib with doctests
+            Self::Only if same_package => Some(&["--doc"]),
+            Self::Only => None,
+        }
+    }
+}
+
+/// Runs the server tests when the bin changed and the front tests when the lib changed.
+/// When prefixed, the output is printed with the project name as prefix
+pub async fn test_proj(
+    proj: &Project,
+    opts: &TestOpts,
+    changes: &ChangeSet,
+    prefixed: bool,
+) -> Result<TestResult> {
+    let prefix = prefixed.then(|| format!("[{}]", proj.name));
+    let prefix = prefix.as_deref();
+    let (cargo_args, harness_args) = split_test_args(&opts.args);
+    let runner = if opts.nextest {
+        TestRunner::Nextest
+    } else {
+        proj.test_runner
+    };
+    let doc = DocTests::new(opts);
+    if doc == DocTests::Only && runner == TestRunner::Nextest {
+        bail!("cargo-nextest doesn't run doctests, use --doc-only with the cargo test-runner");
+    }
+    let cmd = runner.cargo_cmd()?;
+    let timeout = proj.command_timeouts.get("cargo");
+    let mut failed = Vec::new();
+
+    let bin_targets = doc
+        .bin_targets(proj)
+        .filter(|_| changes.need_server_build());
+    let test_lib = changes.need_front_build();
+
+    if let Some(targets) = bin_targets {
+        let mut command = Command::new("cargo");
+        let (envs, line) = build_cargo_server_cmd(cmd, targets, proj, &mut command);
+        let line = append_args(&mut command, line, &cargo_args, &harness_args);
+        match run_tests("server", command, envs, line, prefix, timeout).await? {
+            CommandResult::Success(()) => {}
+            CommandResult::Failure(()) => failed.push("server"),
+            CommandResult::Interrupted => return Ok(TestResult::Interrupted),
+        }
+    }
+
+    if test_lib && (!opts.fail_fast || failed.is_empty()) {
+        let mut command = Command::new("cargo");
+        let (envs, line) = build_cargo_front_cmd(cmd, doc.lib_targets(), false, proj, &mut command);
+        let line = append_args(&mut command, line, &cargo_args, &harness_args);
+        match run_tests("front", command, envs, line, prefix, timeout).await? {
+            CommandResult::Success(()) => {}
+            CommandResult::Failure(()) => failed.push("front"),
+            CommandResult::Interrupted => return Ok(TestResult::Interrupted),
+        }
+    }
+
+    if test_lib
+        && (opts.feature_matrix || proj.test_feature_matrix)
+        && proj.bin.is_some()
+        && (!opts.fail_fast || failed.is_empty())
+    {
+        let mut command = Command::new("cargo");
+        let (envs, line) = build_cargo_lib_ssr_cmd(cmd, doc.lib_targets(), proj, &mut command);
+        let line = append_args(&mut command, line, &cargo_args, &harness_args);
+        match run_tests("front ssr", command, envs, line, prefix, timeout).await? {
+            CommandResult::Success(()) => {}
+            CommandResult::Failure(()) => failed.push("front ssr"),
+            CommandResult::Interrupted => return Ok(TestResult::Interrupted),
+        }
+    }
+
+    let test_wasm_lib = test_lib && opts.wasm;
+    if test_wasm_lib && doc == DocTests::Only {
+        log::info!("Cargo wasm tests skipped, doctests aren't run in the browser");
+    } else if test_wasm_lib && (!opts.fail_fast || failed.is_empty()) {
+        match test_wasm(proj, &cargo_args, &harness_args, prefix).await? {
+            CommandResult::Success(()) => {}
+            CommandResult::Failure(()) => failed.push("wasm"),
+            CommandResult::Interrupted => return Ok(TestResult::Interrupted),
+        }
+    }
+
+    if failed.is_empty() {
+        Ok(TestResult::Passed)
+    } else {
+        log::error!("Tests failed for {}: {}", proj.name, failed.join(", "));
+        Ok(TestResult::Failed)
+    }
+}
 
-    proc.wait().await.dot()?;
+pub(crate) async fn run_tests(
+    name: &str,
+    mut command: Command,
+    envs: String,
+    line: String,
+    prefix: Option<&str>,
+    timeout: Option<Duration>,
+) -> Result<CommandResult<()>> {
+    let name = match prefix {
+        Some(prefix) => format!("{prefix} {name}"),
+        None => name.to_string(),
+    };
+    if prefix.is_some() {
+        command.stdout(Stdio::piped()).stderr(Stdio::piped());
+    }
+    new_process_group(&mut command);
+    let mut proc = command.spawn().dot()?;
     log::debug!("Cargo envs: {}", GRAY.paint(envs));
-    log::info!("Cargo front tests finished {}", GRAY.paint(line));
-    Ok(())
+    let printers = prefix.map(|prefix| print_prefixed(&mut proc, prefix));
+
+    let res = wait_interruptible("Cargo test", proc, Interrupt::subscribe_any(), timeout).await?;
+    match res {
+        CommandResult::Success(()) => {
+            log::info!("Cargo {name} tests finished {}", GRAY.paint(line))
+        }
+        CommandResult::Failure(()) => log::error!("Cargo {name} tests failed {}", GRAY.paint(line)),
+        CommandResult::Interrupted => log::info!("Cargo {name} tests interrupted"),
+    }
+    // the remaining output is printed, unless interrupted when the pipes could be kept
+    // open by the test binaries
+    if let (Some(printers), false) = (printers, matches!(res, CommandResult::Interrupted)) {
+        for printer in printers {
+            printer.await?;
+        }
+    }
+    Ok(res)
+}
+
+/// Runs the lib tests compiled to wasm with wasm-bindgen-test-runner as cargo's runner,
+/// in the headless wasm-test-browser.
+async fn test_wasm(
+    proj: &Project,
+    cargo_args: &[String],
+    harness_args: &[String],
+    prefix: Option<&str>,
+) -> Result<CommandResult<()>> {
+    let runner = Exe::WasmBindgenTestRunner
+        .get(&proj.tool_versions)
+        .await
+        .dot()?;
+    let browser = proj.wasm_test.browser;
+    let driver = match &proj.wasm_test.driver {
+        Some(driver) => driver.clone().into_std_path_buf(),
+        None => which::which(browser.driver()).context(format!(
+            "Could not find {} for running the wasm tests. Install it or set wasm-test-driver",
+            browser.driver()
+        ))?,
+    };
+
+    let mut command = Command::new("cargo");
+    let (envs, line) = build_cargo_front_cmd("test", &["--lib"], true, proj, &mut command);
+    command.env("CARGO_TARGET_WASM32_UNKNOWN_UNKNOWN_RUNNER", &runner);
+    for other in [
+        WasmTestBrowser::Chrome,
+        WasmTestBrowser::Firefox,
+        WasmTestBrowser::Safari,
+    ] {
+        command.env_remove(other.driver_env());
+    }
+    command.env(browser.driver_env(), &driver);
+    let line = append_args(&mut command, line, cargo_args, harness_args);
+    log::debug!(
+        "Cargo wasm tests using {} {}",
+        browser.driver(),
+        GRAY.paint(driver.to_string_lossy())
+    );
+
+    // the runner, webdriver and browser are in the process group of cargo, and are killed
+    // with it on interrupt
+    let timeout = proj.command_timeouts.get("cargo");
+    run_tests("wasm", command, envs, line, prefix, timeout).await
+}
+
+fn append_args(
+    command: &mut Command,
+    mut line: String,
+    cargo_args: &[String],
+    harness_args: &[String],
+) -> String {
+    command.args(cargo_args);
+    for arg in cargo_args {
+        line.push(' ');
+        line.push_str(arg);
+    }
+    if !harness_args.is_empty() {
+        command.arg("--").args(harness_args);
+        line.push_str(" --");
+        for arg in harness_args {
+            line.push(' ');
+            line.push_str(arg);
+        }
+    }
+    line
+}
+
+/// `cargo test` flags, that are passed to cargo instead of the test harness
+const CARGO_FLAGS: &[&str] = &[
+    "--no-run",
+    "--no-fail-fast",
+    "--doc",
+    "--tests",
+    "--benches",
+    "--examples",
+    "--bins",
+    "--all-targets",
+    "--quiet",
+    "-q",
+    "--offline",
+    "--locked",
+    "--frozen",
+];
+
+/// `cargo test` options that take a value, either as `--opt=value` or `--opt value`
+const CARGO_OPTIONS: &[&str] = &[
+    "--test",
+    "--bench",
+    "--example",
+    "--jobs",
+    "-j",
+    "--color",
+    "--message-format",
+];
+
+/// Splits the args into the ones for cargo and the ones for the test harness. When the
+/// args contain a `--` it separates the two, otherwise the known cargo flags go to cargo
+/// and the rest (test names and harness flags) to the harness.
+pub(crate) fn split_test_args(args: &[String]) -> (Vec<String>, Vec<String>) {
+    if let Some(pos) = args.iter().position(|arg| arg == "--") {
+        return (args[..pos].to_vec(), args[pos + 1..].to_vec());
+    }
+    let mut cargo = Vec::new();
+    let mut harness = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let name = arg.split('=').next().unwrap_or_default();
+        if CARGO_FLAGS.contains(&arg.as_str()) {
+            cargo.push(arg.clone());
+        } else if CARGO_OPTIONS.contains(&name) {
+            cargo.push(arg.clone());
+            if !arg.contains('=') {
+                cargo.extend(args.next().cloned());
+            }
+        } else {
+            harness.push(arg.clone());
+        }
+    }
+    (cargo, harness)
 }
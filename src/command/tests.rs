@@ -0,0 +1,459 @@
+use crate::{
+    compile::{build_cargo_front_cmd, build_cargo_server_cmd},
+    config::{Config, Opts, TestOpts},
+};
+use insta::assert_display_snapshot;
+use tokio::process::Command;
+
+use super::{
+    end2end::is_playwright,
+    fmt::rs_files,
+    test::{split_test_args, DocTests, ProjectResult, TestReport, TestResult},
+};
+
+fn strings(args: &[&str]) -> Vec<String> {
+    args.iter().map(|s| s.to_string()).collect()
+}
+
+#[test]
+fn test_split_test_args() {
+    let (cargo, harness) = split_test_args(&strings(&[
+        "my_test",
+        "--no-run",
+        "--nocapture",
+        "--test",
+        "integration",
+        "-j=2",
+    ]));
+    assert_eq!(
+        cargo,
+        strings(&["--no-run", "--test", "integration", "-j=2"])
+    );
+    assert_eq!(harness, strings(&["my_test", "--nocapture"]));
+
+    let (cargo, harness) = split_test_args(&strings(&["--doc", "--", "--ignored"]));
+    assert_eq!(cargo, strings(&["--doc"]));
+    assert_eq!(harness, strings(&["--ignored"]));
+}
+
+fn test_lines(opts: &TestOpts) -> (Option<String>, String) {
+    let cli = Opts::default();
+    let conf = Config::test_load(cli, "examples", "examples/project/Cargo.toml", true);
+    let proj = &conf.projects[0];
+    let doc = DocTests::new(opts);
+
+    let server = doc
+        .bin_targets(proj)
+        .map(|targets| build_cargo_server_cmd("test", targets, proj, &mut Command::new("cargo")).1);
+    let (_, front) = build_cargo_front_cmd(
+        "test",
+        doc.lib_targets(),
+        false,
+        proj,
+        &mut Command::new("cargo"),
+    );
+    (server, front)
+}
+
+#[test]
+fn test_doc_tests_args() {
+    let (server, front) = test_lines(&TestOpts::default());
+    assert_display_snapshot!(server.unwrap(), @"cargo test --package=example --target-dir=target/server --no-default-features --features=ssr");
+    assert_display_snapshot!(front, @"cargo test --package=example --lib --target-dir=target/front --no-default-features --features=hydrate");
+
+    let (server, front) = test_lines(&TestOpts {
+        doc: true,
+        ..Default::default()
+    });
+    assert_display_snapshot!(server.unwrap(), @"cargo test --package=example --target-dir=target/server --no-default-features --features=ssr");
+    assert_display_snapshot!(front, @"cargo test --package=example --target-dir=target/front --no-default-features --features=hydrate");
+
+    let (server, front) = test_lines(&TestOpts {
+        no_doc: true,
+        ..Default::default()
+    });
+    assert_display_snapshot!(server.unwrap(), @"cargo test --package=example --lib --bins --tests --target-dir=target/server --no-default-features --features=ssr");
+    assert_display_snapshot!(front, @"cargo test --package=example --lib --target-dir=target/front --no-default-features --features=hydrate");
+
+    let (server, front) = test_lines(&TestOpts {
+        doc_only: true,
+        ..Default::default()
+    });
+    assert_display_snapshot!(server.unwrap(), @"cargo test --package=example --doc --target-dir=target/server --no-default-features --features=ssr");
+    assert_display_snapshot!(front, @"cargo test --package=example --doc --target-dir=target/front --no-default-features --features=hydrate");
+}
+
+#[test]
+fn test_clean_dirs() {
+    use super::clean::{check_dir, clean_dirs};
+    use crate::ext::PathExt;
+    use camino::Utf8Path;
+
+    let conf = Config::test_load(
+        Opts::default(),
+        "examples",
+        "examples/project/Cargo.toml",
+        false,
+    );
+    let dirs: Vec<String> = clean_dirs(&conf)
+        .iter()
+        .map(|dir| dir.unbase(&conf.working_dir).unwrap().to_string())
+        .collect();
+    assert_eq!(
+        dirs,
+        [
+            "target/front",
+            "target/server",
+            "target/site",
+            "target/leptos/example"
+        ]
+    );
+
+    let working_dir = Utf8Path::new("/app");
+    assert!(check_dir(Utf8Path::new("/app/target/site"), working_dir, false).is_ok());
+    assert!(check_dir(Utf8Path::new("/srv/site"), working_dir, false).is_err());
+    assert!(check_dir(Utf8Path::new("/srv/site"), working_dir, true).is_ok());
+    assert!(check_dir(Utf8Path::new("/app"), working_dir, true).is_err());
+    assert!(check_dir(Utf8Path::new("/"), working_dir, true).is_err());
+}
+
+#[test]
+fn test_export_out_dir() {
+    use super::export::{check_out_dir, normalized};
+    use camino::Utf8Path;
+
+    let site_root = Utf8Path::new("/app/target/site");
+    let working_dir = Utf8Path::new("/app");
+    let check =
+        |out_dir: &str| check_out_dir(&normalized(Utf8Path::new(out_dir)), site_root, working_dir);
+
+    assert_eq!(
+        normalized(Utf8Path::new("/app/./target/../dist")),
+        "/app/dist"
+    );
+    assert!(check("/app/dist").is_ok());
+    assert!(check("/app/target/export").is_ok());
+    assert!(check("/app/target/site/dist").is_err());
+    assert!(check("/app/target/site").is_err());
+    assert!(check("/app/target").is_err());
+    assert!(check("/app/target/site/../..").is_err());
+    assert!(check("/").is_err());
+}
+
+#[tokio::test]
+async fn test_export_files() {
+    use super::export::{remove_stale, site_files};
+    use camino::Utf8PathBuf;
+
+    let dir = temp_dir::TempDir::new().unwrap();
+    let root = Utf8PathBuf::from_path_buf(dir.path().to_path_buf()).unwrap();
+    let site = root.join("site");
+    let out = root.join("dist");
+    for file in ["index.html", ".well-known/security.txt", "pkg/app.wasm"] {
+        let path = site.join(file);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, file).unwrap();
+    }
+    // a partial write of another build
+    std::fs::write(site.join("pkg/.app.js.1234.tmp"), "").unwrap();
+
+    let files = site_files(&site).await.unwrap();
+    let rels: Vec<&str> = files.iter().map(|(rel, _)| rel.as_str()).collect();
+    assert_eq!(
+        rels,
+        [".well-known/security.txt", "index.html", "pkg/app.wasm"]
+    );
+
+    for stale in [
+        "old.html",
+        "pkg/old.wasm",
+        "old/app.js",
+        ".well-known/security.txt",
+    ] {
+        let path = out.join(stale);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, "").unwrap();
+    }
+    assert_eq!(remove_stale(&out, &files).await.unwrap(), 3);
+    assert!(out.join(".well-known/security.txt").exists());
+    assert!(!out.join("old").exists());
+    assert!(!out.join("pkg/old.wasm").exists());
+    assert!(out.join("pkg").exists());
+}
+
+#[test]
+fn test_bundle() {
+    use super::bundle::{check_output, default_output, env_file};
+    use crate::config::BundleFormat;
+    use camino::Utf8Path;
+
+    let conf = Config::test_load(
+        Opts::default(),
+        "examples",
+        "examples/project/Cargo.toml",
+        false,
+    );
+    let proj = &conf.projects[0];
+    let output = |format| default_output(proj, format).to_string();
+    assert_eq!(output(BundleFormat::Tar), "target/bundle/example.tar.gz");
+    assert_eq!(output(BundleFormat::Zip), "target/bundle/example.zip");
+    assert_eq!(output(BundleFormat::Dir), "target/bundle/example");
+    assert_eq!(
+        env_file(proj).lines().skip(2).collect::<Vec<_>>(),
+        [
+            "LEPTOS_OUTPUT_NAME=example",
+            "LEPTOS_SITE_ROOT=site",
+            "LEPTOS_SITE_PKG_DIR=pkg",
+            "LEPTOS_SITE_ADDR=127.0.0.1:3000",
+            "LEPTOS_ENV=PROD",
+        ]
+    );
+
+    let (site_root, working_dir) = (Utf8Path::new("/app/target/site"), Utf8Path::new("/app"));
+    let check = |output| check_output(Utf8Path::new(output), site_root, working_dir);
+    assert!(check("/app/target/bundle/example").is_ok());
+    assert!(check("/app/target/site/bundle").is_err());
+    assert!(check("/app/target").is_err());
+    assert!(check("/").is_err());
+}
+
+#[cfg(feature = "cli")]
+#[test]
+fn test_new_git_template() {
+    use super::new::{git_url, template_dir_name, GitRef};
+
+    assert_eq!(
+        git_url("leptos-rs/start-axum"),
+        "https://github.com/leptos-rs/start-axum"
+    );
+    assert_eq!(
+        git_url("git@git.example.com:templates/leptos.git"),
+        "git@git.example.com:templates/leptos.git"
+    );
+    assert_eq!(
+        git_url("https://gitlab.com/me/templates"),
+        "https://gitlab.com/me/templates"
+    );
+
+    let url = "git@git.example.com:templates/leptos.git";
+    let name = template_dir_name(url, None);
+    assert!(name.starts_with("leptos-"), "{name}");
+    assert_eq!(name, template_dir_name(url, None));
+    assert_ne!(name, template_dir_name(url, Some(GitRef::Branch("main"))));
+    assert_ne!(
+        template_dir_name(url, Some(GitRef::Branch("v1"))),
+        template_dir_name(url, Some(GitRef::Tag("v1")))
+    );
+}
+
+#[cfg(feature = "cli")]
+#[test]
+fn test_new_placeholders() {
+    use super::new::{check_crate_name, parse_define};
+
+    assert!(check_crate_name("my-shop").is_ok());
+    assert!(check_crate_name("shop_2").is_ok());
+    assert!(check_crate_name("MyShop").is_ok());
+    assert!(check_crate_name("2shop").is_err());
+    assert!(check_crate_name("my shop").is_err());
+    assert!(check_crate_name("crate").is_err());
+    assert!(check_crate_name("").is_err());
+
+    assert_eq!(parse_define("css=tailwind").unwrap(), ("css", "tailwind"));
+    assert_eq!(parse_define("title=a=b").unwrap(), ("title", "a=b"));
+    assert!(parse_define("css").is_err());
+    assert!(parse_define("=tailwind").is_err());
+}
+
+#[cfg(feature = "cli")]
+#[test]
+fn test_new_flavors() {
+    use super::new::{check_flavor, Flavor};
+    use camino::Utf8Path;
+
+    let builtin = Utf8Path::new("templates/leptos");
+    for flavor in [Flavor::Csr, Flavor::Ssr, Flavor::Islands] {
+        assert!(check_flavor(builtin, flavor).is_ok(), "{flavor:?}");
+    }
+    let err = check_flavor(Utf8Path::new("examples/project"), Flavor::Csr).unwrap_err();
+    assert!(err.to_string().contains("has no flavors"), "{err}");
+}
+
+#[cfg(feature = "cli")]
+#[test]
+fn test_new_check_leptos_project() {
+    use super::new::check_leptos_project;
+    use camino::Utf8PathBuf;
+
+    let dir = temp_dir::TempDir::new().unwrap();
+    let root = Utf8PathBuf::from_path_buf(dir.path().to_path_buf()).unwrap();
+    let check = |manifest: &str| {
+        std::fs::write(root.join("Cargo.toml"), manifest).unwrap();
+        check_leptos_project(&root, "me/template")
+    };
+
+    assert!(check("[package]\nname = \"app\"\n[package.metadata.leptos]\n").is_ok());
+    assert!(check("[workspace]\n[[workspace.metadata.leptos]]\nname = \"app\"\n").is_ok());
+    let err = check("[package]\nname = \"app\"\n").unwrap_err();
+    assert!(
+        err.to_string()
+            .starts_with("The template me/template isn't a leptos project"),
+        "{err}"
+    );
+}
+
+#[cfg(feature = "cli")]
+#[test]
+fn test_init_section() {
+    use super::init::{InitConfig, Layout};
+
+    let mut config = InitConfig {
+        name: "shop".to_string(),
+        layout: Layout::Workspace {
+            bin_package: Some("server".to_string()),
+            lib_package: "front".to_string(),
+        },
+        csr: false,
+        style_file: "style/main.scss".into(),
+        assets_dir: "public".into(),
+        tailwind: true,
+        lib_features: vec!["hydrate".to_string()],
+        bin_features: vec!["ssr".to_string()],
+    };
+    assert_eq!(
+        config.section(),
+        r#"[[workspace.metadata.leptos]]
+name = "shop"
+bin-package = "server"
+lib-package = "front"
+style-file = "style/main.scss"
+assets-dir = "public"
+tailwind-input-file = "style/tailwind.css"
+tailwind-config-file = "tailwind.config.js"
+bin-features = ["ssr"]
+bin-default-features = false
+lib-features = ["hydrate"]
+lib-default-features = false
+"#
+    );
+    let files: Vec<String> = config.files().iter().map(|(p, _)| p.to_string()).collect();
+    assert_eq!(
+        files,
+        [
+            "style/main.scss",
+            "style/tailwind.css",
+            "tailwind.config.js"
+        ]
+    );
+
+    config.layout = Layout::Package;
+    config.csr = true;
+    config.tailwind = false;
+    config.lib_features = vec![];
+    config.bin_features = vec![];
+    assert_eq!(
+        config.section(),
+        r#"[package.metadata.leptos]
+output-name = "shop"
+csr = true
+style-file = "style/main.scss"
+assets-dir = "public"
+"#
+    );
+}
+
+#[cfg(feature = "cli")]
+#[test]
+fn test_init_line_diff() {
+    use super::init::line_diff;
+
+    assert_eq!(
+        line_diff("a\nb\nc\n", "a\nx\nc\nd\n"),
+        "  a\n- b\n+ x\n  c\n+ d"
+    );
+    assert_eq!(line_diff("", "a"), "+ a");
+}
+
+#[cfg(target_family = "unix")]
+#[tokio::test]
+async fn interrupted_tests_stop() {
+    use super::test::run_tests;
+    use crate::{compile::Change, ext::sync::CommandResult, signal::Interrupt};
+    use std::time::Duration;
+
+    // a test binary that hangs, with a forked sleeper keeping its output pipes open
+    let mut cmd = Command::new("sh");
+    cmd.args(["-c", "sleep 30 & wait"]);
+    tokio::spawn(async {
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        Interrupt::clear_source_changes().await;
+        Interrupt::send_async(&[Change::DotEnv]).await;
+    });
+
+    let run = run_tests(
+        "fake",
+        cmd,
+        String::new(),
+        String::new(),
+        Some("[fake]"),
+        None,
+    );
+    let res = tokio::time::timeout(Duration::from_secs(10), run)
+        .await
+        .expect("the interrupted tests didn't stop")
+        .unwrap();
+    assert!(matches!(res, CommandResult::Interrupted));
+    Interrupt::clear_source_changes().await;
+}
+
+#[test]
+fn test_is_playwright() {
+    assert!(is_playwright("npx playwright test"));
+    assert!(is_playwright("pnpm exec playwright test"));
+    assert!(is_playwright("node_modules/.bin/playwright test"));
+    assert!(!is_playwright("npx cypress run"));
+    assert!(!is_playwright("npm run playwright-tests"));
+}
+
+#[test]
+fn test_report_check_e2e() {
+    let result = |name: &str, result, e2e| ProjectResult {
+        name: name.to_string(),
+        result,
+        e2e,
+        time: std::time::Duration::ZERO,
+    };
+    let report = TestReport {
+        projects: vec![
+            result("app", TestResult::Passed, Some(TestResult::Passed)),
+            result("lib", TestResult::Skipped, None),
+        ],
+    };
+    assert!(report.check().is_ok());
+
+    let report = TestReport {
+        projects: vec![
+            result("app", TestResult::Passed, Some(TestResult::Failed)),
+            result("admin", TestResult::Failed, Some(TestResult::Passed)),
+        ],
+    };
+    assert_eq!(
+        report.check().unwrap_err().to_string(),
+        "Tests failed for app (e2e), admin"
+    );
+}
+
+#[test]
+fn test_fmt_rs_files() {
+    let lines = strings(&[
+        "/work/app/src/lib.rs",
+        "Diff in /work/app/src/main.rs at line 3:",
+        "❌ src/pages/home.rs",
+        "formatted 2 files",
+    ]);
+    let files = rs_files(&lines, camino::Utf8Path::new("/work/app"));
+    assert_eq!(
+        files.iter().map(|file| file.as_str()).collect::<Vec<_>>(),
+        ["src/lib.rs", "src/main.rs", "src/pages/home.rs"]
+    );
+}
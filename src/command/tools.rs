@@ -0,0 +1,200 @@
+use std::{
+    collections::BTreeSet,
+    time::{Duration, SystemTime},
+};
+
+use clap::{Args, Subcommand};
+
+use crate::{
+    config::Config,
+    ext::{
+        anyhow::{bail, Context, Result},
+        exe::{cached_tools, get_cache_dir, CachedTool, Exe, ToolVersions},
+        format_bytes,
+    },
+    logger::GRAY,
+};
+
+#[derive(Debug, Clone, Args, PartialEq, Eq)]
+pub struct ToolsCommand {
+    #[command(subcommand)]
+    pub action: ToolsAction,
+}
+
+#[derive(Debug, Clone, Subcommand, PartialEq, Eq)]
+pub enum ToolsAction {
+    /// Print the cached versions of each tool, with their sizes and paths.
+    List,
+    /// Download the versions that the projects use (the pinned ones, else the defaults of
+    /// cargo-leptos) when they aren't cached or the cached binary is corrupted, e.g. before
+    /// working `--offline`. Uses the tool-urls, download-proxy and tool-checksums of the config.
+    #[command(visible_alias = "prefetch")]
+    Update {
+        /// The tools to update, like sass or wasm-opt. Defaults to the tools that the builds of
+        /// the projects use.
+        tools: Vec<String>,
+    },
+    /// Remove the cached versions that no project uses.
+    Clean {
+        /// Also remove the versions that the projects use when they were downloaded more
+        /// than DAYS days ago. They are downloaded again when needed.
+        #[arg(long, value_name = "DAYS")]
+        older_than: Option<u64>,
+    },
+}
+
+impl ToolsCommand {
+    /// update needs the project for its versions, list and clean work without one
+    pub fn needs_project(&self) -> bool {
+        matches!(self.action, ToolsAction::Update { .. })
+    }
+
+    pub async fn run(&self, config: Option<&Config>) -> Result<()> {
+        match &self.action {
+            ToolsAction::List => list(config),
+            ToolsAction::Update { tools } => {
+                let config = config.context("cargo leptos tools update needs a leptos project")?;
+                update(config, tools).await
+            }
+            ToolsAction::Clean { older_than } => clean(config, *older_than),
+        }
+    }
+}
+
+/// The tool versions of the projects, or the defaults without a project
+fn versions(config: Option<&Config>) -> Vec<ToolVersions> {
+    match config {
+        Some(config) => config
+            .projects
+            .iter()
+            .map(|proj| proj.tool_versions.clone())
+            .collect(),
+        None => vec![ToolVersions::default()],
+    }
+}
+
+/// the cache dir names of the versions used by the projects
+fn used(config: Option<&Config>) -> BTreeSet<String> {
+    let mut used = BTreeSet::new();
+    for versions in versions(config) {
+        // none on an unsupported OS or architecture
+        for meta in Exe::ALL.iter().filter_map(|exe| exe.meta(&versions).ok()) {
+            used.insert(meta.get_name());
+        }
+    }
+    used
+}
+
+fn list(config: Option<&Config>) -> Result<()> {
+    let cached = cached_tools()?;
+    let used = used(config);
+    println!("Tools cached in {}", get_cache_dir()?.display());
+    for exe in Exe::ALL {
+        let versions: Vec<&CachedTool> = cached.iter().filter(|tool| tool.exe == exe).collect();
+        if versions.is_empty() {
+            println!("{}: not cached", exe.name());
+            continue;
+        }
+        println!("{}:", exe.name());
+        for tool in versions {
+            let in_use = if used.contains(&tool.name()) {
+                " (used)"
+            } else {
+                ""
+            };
+            println!(
+                "  {}{in_use} {} {}",
+                tool.version,
+                format_bytes(tool.size),
+                GRAY.paint(tool.dir.to_string_lossy())
+            );
+        }
+    }
+    Ok(())
+}
+
+async fn update(config: &Config, names: &[String]) -> Result<()> {
+    let mut tools = Vec::new();
+    for name in names {
+        let Some(exe) = Exe::ALL.into_iter().find(|exe| exe.name() == name) else {
+            let names: Vec<&str> = Exe::ALL.iter().map(Exe::name).collect();
+            bail!(
+                "Unknown tool {name:?}, expected one of {}",
+                names.join(", ")
+            );
+        };
+        tools.push(exe);
+    }
+
+    if tools.is_empty() {
+        tools = used_tools(config);
+    }
+
+    let mut updated = BTreeSet::new();
+    for versions in versions(Some(config)) {
+        for &exe in &tools {
+            let meta = exe.meta(&versions)?;
+            if !updated.insert(meta.get_name()) {
+                continue;
+            }
+            let was_cached = meta.is_cached();
+            let path = meta
+                .cached()
+                .await
+                .context(format!("Could not update {}", meta.get_name()))?;
+            let state = if was_cached { "is cached" } else { "installed" };
+            log::info!(
+                "Tools {} {state} {}",
+                meta.get_name(),
+                GRAY.paint(path.to_string_lossy())
+            );
+        }
+    }
+    Ok(())
+}
+
+/// The tools that the builds of the projects can use
+fn used_tools(config: &Config) -> Vec<Exe> {
+    let mut exes = vec![Exe::WasmOpt];
+    let projects = &config.projects;
+    let sass = projects.iter().any(|proj| {
+        let ext = proj.style.file.as_ref().and_then(|f| f.source.extension());
+        matches!(ext, Some("sass" | "scss"))
+    });
+    if sass {
+        exes.push(Exe::Sass);
+    }
+    if projects.iter().any(|proj| proj.style.tailwind.is_some()) {
+        exes.push(Exe::Tailwind);
+    }
+    if projects.iter().any(|proj| proj.assets.optimize_images) {
+        exes.push(Exe::Oxipng);
+    }
+    exes
+}
+
+fn clean(config: Option<&Config>, older_than: Option<u64>) -> Result<()> {
+    let used = used(config);
+    let cutoff =
+        older_than.map(|days| SystemTime::now() - Duration::from_secs(days * 24 * 60 * 60));
+    let mut freed = 0;
+    for tool in cached_tools()? {
+        let expired = match (cutoff, tool.modified) {
+            (Some(cutoff), Some(modified)) => modified < cutoff,
+            _ => false,
+        };
+        if used.contains(&tool.name()) && !expired {
+            continue;
+        }
+        std::fs::remove_dir_all(&tool.dir)
+            .context(format!("Could not remove {}", tool.dir.display()))?;
+        log::info!(
+            "Tools removed {} {}",
+            tool.name(),
+            GRAY.paint(format_bytes(tool.size))
+        );
+        freed += tool.size;
+    }
+    log::info!("Tools cleaned, {} freed", format_bytes(freed));
+    Ok(())
+}
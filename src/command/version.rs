@@ -0,0 +1,73 @@
+use serde::Serialize;
+
+use crate::{
+    config::Config,
+    ext::{
+        anyhow::Result,
+        exe::{get_cache_dir, Exe, ToolSource, ToolStatus, WASM_BINDGEN_VERSION},
+    },
+    logger::GRAY,
+};
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) struct VersionInfo {
+    pub cargo_leptos: &'static str,
+    pub wasm_bindgen_cli_support: &'static str,
+    pub cache_dir: Option<String>,
+    pub tools: Vec<ToolStatus>,
+}
+
+/// Prints the version of cargo-leptos, with `verbose` the one of the compiled-in
+/// wasm-bindgen and the tools that a build would use, for the bug reports. The tools are
+/// looked up with the config when there is a project, and never downloaded.
+pub async fn version(config: Option<&Config>, verbose: bool, json: bool) -> Result<()> {
+    if !verbose && !json {
+        println!("cargo-leptos {}", env!("CARGO_PKG_VERSION"));
+        return Ok(());
+    }
+    let info = version_info(config).await?;
+    if json {
+        println!("{}", serde_json::to_string_pretty(&info)?);
+        return Ok(());
+    }
+    println!("cargo-leptos {}", info.cargo_leptos);
+    println!("wasm-bindgen-cli-support {}", info.wasm_bindgen_cli_support);
+    if let Some(dir) = &info.cache_dir {
+        println!("Tools cached in {dir}");
+    }
+    for tool in &info.tools {
+        let version = tool.version.as_deref().unwrap_or("(unknown version)");
+        let source = match tool.source {
+            ToolSource::System => "system",
+            ToolSource::Cached => "cached",
+            ToolSource::NotCached => "not cached",
+        };
+        let path = match &tool.path {
+            Some(path) => format!(" {}", GRAY.paint(path.to_string_lossy())),
+            None => String::new(),
+        };
+        println!("{} {version}: {source}{path}", tool.name);
+    }
+    Ok(())
+}
+
+pub(crate) async fn version_info(config: Option<&Config>) -> Result<VersionInfo> {
+    // the first project's, like the tools of a workspace mostly are the same
+    let versions = config
+        .and_then(|config| config.projects.first())
+        .map(|proj| proj.tool_versions.clone())
+        .unwrap_or_default();
+    let mut tools = Vec::new();
+    for exe in Exe::ALL {
+        tools.push(exe.status(&versions).await?);
+    }
+    Ok(VersionInfo {
+        cargo_leptos: env!("CARGO_PKG_VERSION"),
+        wasm_bindgen_cli_support: WASM_BINDGEN_VERSION,
+        cache_dir: get_cache_dir()
+            .ok()
+            .map(|dir| dir.to_string_lossy().to_string()),
+        tools,
+    })
+}
@@ -1,26 +1,51 @@
-use std::sync::Arc;
+use std::{collections::HashMap, sync::Arc};
 
 use crate::{
-    compile::{self},
-    config::Project,
+    compile::{self, BuildTimings, Change, ChangeSet, RebuildRecorder, WatchStats},
+    config::{Project, ServeOpts},
     ext::anyhow::Context,
-    service,
-    signal::{Interrupt, Outcome, Product, ProductSet, ReloadSignal, ServerRestart},
+    logger::{with_fields, LogFields, GRAY},
+    service::{self, notification::BuildNotifier},
+    signal::{
+        Interrupt, Outcome, Product, ProductSet, ReloadSignal, ServerRestart, Stage, StatusEvent,
+        StatusSignal,
+    },
 };
-use anyhow::Result;
+use anyhow::{bail, Result};
+use itertools::Itertools;
 use leptos_hot_reload::ViewMacros;
-use tokio::try_join;
+use tokio::{
+    sync::broadcast::{self, error::TryRecvError},
+    task::{JoinError, JoinHandle},
+    try_join,
+};
 
 use super::build::build_proj;
 
-pub async fn watch(proj: &Arc<Project>) -> Result<()> {
+/// The number of changed files in the log line of a rebuild, all of them are logged at debug
+const LOGGED_FILES: usize = 5;
+
+pub async fn watch(proj: &Arc<Project>, opts: &ServeOpts) -> Result<()> {
+    if opts.no_build {
+        bail!("The --no-build flag is only for serve, watch builds on each change");
+    }
+    Interrupt::set_build_strategy(proj.build_strategy);
+    if opts.stats {
+        let jsonl = opts
+            .stats_jsonl
+            .as_ref()
+            .map(|file| proj.working_dir.join(file));
+        WatchStats::enable(jsonl);
+    }
     // even if the build fails, we continue
-    build_proj(proj).await?;
+    let built = build_proj(proj).await?;
 
     // but if ctrl-c is pressed, we stop
     if Interrupt::is_shutdown_requested().await {
         return Ok(());
     }
+    let mut notifier = BuildNotifier::new(proj);
+    notifier.build_done(built);
 
     let view_macros = if proj.hot_reload {
         // build initial set of view macros for patching
@@ -36,68 +61,263 @@ pub async fn watch(proj: &Arc<Project>) -> Result<()> {
         let _patch = service::patch::spawn(proj, &view_macros).await?;
     }
 
-    service::serve::spawn(proj).await;
+    let server = service::serve::spawn(proj).await;
     service::reload::spawn(proj).await;
+    service::status::spawn(proj);
+    service::proxy::spawn(proj).await;
+    // the browser isn't opened on a failed build, which would show an error page
+    if let Some(path) = opts.open.as_ref().filter(|_| built) {
+        service::open::spawn(proj, path);
+    }
+    let keys = service::keys::spawn(proj, opts.open.clone());
 
-    let res = run_loop(proj).await;
+    let res = run_loop(proj, &mut notifier).await;
+    if opts.stats {
+        WatchStats::log_summary();
+    }
     if res.is_err() {
         Interrupt::request_shutdown().await;
     }
+    // the terminal is restored before exiting
+    if let Some(keys) = keys {
+        keys.await.dot()?;
+    }
+    // the server is in its own process group and stops on the shutdown request
+    server.await.dot()??;
     res
 }
 
-pub async fn run_loop(proj: &Arc<Project>) -> Result<()> {
+pub async fn run_loop(proj: &Arc<Project>, notifier: &mut BuildNotifier) -> Result<()> {
     let mut int = Interrupt::subscribe_any();
+    let mut dotenvs = proj.read_dotenvs().unwrap_or_default();
+    let queue = Interrupt::is_queuing_changes();
+    // the changes made during the last build, with the queue build-strategy
+    let mut queued = 0;
     loop {
-        log::debug!("Watch waiting for changes");
-        int.recv().await.dot()?;
+        if queued == 0 {
+            log::debug!("Watch waiting for changes");
+            int.recv().await.dot()?;
+        }
 
         if Interrupt::is_shutdown_requested().await {
             log::debug!("Shutting down");
             return Ok(());
         }
 
-        let changes = Interrupt::get_source_changes().await;
+        let changes = if queue {
+            Interrupt::take_source_changes().await
+        } else {
+            Interrupt::get_source_changes().await
+        };
+        if queue && changes.is_empty() {
+            queued = 0;
+            continue;
+        }
+        StatusSignal::build_started(&changes);
+        log_changed_files(&changes);
+        let timings = BuildTimings::start();
+        let recorder = WatchStats::rebuild_started();
+        with_fields(LogFields::project(proj), || log::debug!("Build started"));
 
         let server_hdl = compile::server(proj, &changes).await;
         let front_hdl = compile::front(proj, &changes).await;
         let assets_hdl = compile::assets(proj, &changes, false).await;
         let style_hdl = compile::style(proj, &changes).await;
 
-        let (serve, front, assets, style) =
-            try_join!(server_hdl, front_hdl, assets_hdl, style_hdl)?;
+        let rec = recorder.as_ref();
+        let (serve, front, assets, style) = try_join!(
+            finished(Stage::Server, server_hdl, rec),
+            finished(Stage::Front, front_hdl, rec),
+            finished(Stage::Assets, assets_hdl, rec),
+            finished(Stage::Style, style_hdl, rec)
+        )?;
 
         let outcomes = vec![serve?, front?, assets?, style?];
 
         let failed = outcomes.iter().any(|outcome| *outcome == Outcome::Failed);
         let interrupted = outcomes.iter().any(|outcome| *outcome == Outcome::Stopped);
 
+        let fields = LogFields::project(proj).duration(timings.elapsed());
+        if let Some(recorder) = recorder {
+            recorder.finished(if failed {
+                "failed"
+            } else if interrupted {
+                "interrupted"
+            } else {
+                "built"
+            });
+        }
         if failed {
-            log::warn!("Build failed");
-            Interrupt::clear_source_changes().await;
+            with_fields(fields.outcome("failed"), || log::warn!("Build failed"));
+            ReloadSignal::send_build_status(false);
+            notifier.build_done(false);
+            if !queue {
+                Interrupt::clear_source_changes().await;
+            }
         } else if interrupted {
-            log::info!("Build interrupted. Restarting.");
+            with_fields(fields.outcome("interrupted"), || {
+                log::info!("Build interrupted. Restarting.")
+            });
+            StatusSignal::send(StatusEvent::BuildInterrupted);
         } else {
+            timings.finished(proj);
             let set = ProductSet::from(outcomes);
+            let mut hashes_changed = false;
 
             if set.is_empty() {
                 log::trace!("Build step done with no changes");
             } else {
                 log::trace!("Build step done with changes: {set}");
+                if let Err(e) = compile::site_manifest(proj, false).await {
+                    log::error!("Build could not write the site manifest: {e:#}");
+                }
+                match compile::hash_files(proj).await {
+                    Ok(changed) => hashes_changed = changed,
+                    Err(e) => log::error!("Build could not write the hash file: {e:#}"),
+                }
             }
+            ReloadSignal::send_build_status(true);
+            notifier.build_done(true);
 
-            if set.only_style() {
-                ReloadSignal::send_style();
-                log::info!("Watch updated style")
+            let dotenv = changes.dotenv_changed() && reread_dotenvs(proj, &mut dotenvs);
+            // what references the base path is rebuilt, which also restarts the server
+            let rebase = dotenv && proj.site.update_base_path(&dotenvs);
+            // a csr project has no server to restart, its browser is reloaded instead
+            let restart = changes.need_server_restart() && proj.bin.is_some();
+            let reload = changes.need_browser_reload() || changes.need_server_restart();
+
+            if rebase {
+                log::info!(
+                    "Watch site-base-path changed to {:?}. Rebuilding",
+                    proj.site.base_path()
+                );
+                if proj.bin.is_some() {
+                    ServerRestart::send();
+                } else {
+                    compile::csr_index(proj).await?;
+                }
             } else if set.contains(&Product::Server) {
                 // send product change, then the server will send the reload once it has restarted
                 ServerRestart::send();
                 log::info!("Watch updated {set}. Server restarting")
+            } else if restart {
+                ServerRestart::send();
+                log::info!("Watch additional files changed. Server restarting")
+            } else if hashes_changed && proj.bin.is_some() {
+                // the server reads the hashes when it starts
+                ServerRestart::send();
+                log::info!("Watch updated {set}. Server restarting for the new hashes")
+            } else if dotenv {
+                ServerRestart::send();
+                log::info!("Watch {} changed. Server restarting", proj.dotenv_file)
+            } else if set.only_style() && !reload {
+                ReloadSignal::send_style();
+                log::info!("Watch updated style")
             } else if set.contains_any(&[Product::Front, Product::Assets]) {
                 ReloadSignal::send_full();
                 log::info!("Watch updated {set}")
+            } else if reload {
+                ReloadSignal::send_full();
+                log::info!("Watch additional files changed. Browser reloading")
+            }
+            if !queue {
+                Interrupt::clear_source_changes().await;
+            }
+            if rebase {
+                Interrupt::send_async(&[Change::Conf]).await;
+            }
+        }
+
+        if queue {
+            queued = count_queued(&mut int);
+            if queued > 0 {
+                log::info!(
+                    "Watch {queued} changes queued during the build, building them at once ({} rebuilds saved)",
+                    queued - 1
+                );
             }
-            Interrupt::clear_source_changes().await;
         }
     }
 }
+
+/// Sends the stage-finished event as soon as the step is done, while the others still run,
+/// and times it for --stats
+async fn finished(
+    stage: Stage,
+    hdl: JoinHandle<Result<Outcome<Product>>>,
+    recorder: Option<&RebuildRecorder>,
+) -> Result<Result<Outcome<Product>>, JoinError> {
+    let res = hdl.await;
+    if let Ok(Ok(outcome)) = &res {
+        StatusSignal::stage_finished(stage, outcome);
+        if let Some(recorder) = recorder {
+            recorder.stage_finished(stage, outcome);
+        }
+    }
+    res
+}
+
+/// The change notifications received during the build, which are built together
+fn count_queued(int: &mut broadcast::Receiver<()>) -> u64 {
+    let mut count = 0;
+    loop {
+        match int.try_recv() {
+            Ok(()) => count += 1,
+            Err(TryRecvError::Lagged(missed)) => count += missed,
+            Err(_) => return count,
+        }
+    }
+}
+
+/// Reads the changed .env file and logs the names of the changed vars, not their values
+/// which might be secrets. false when it can't be read, the server then keeps running.
+fn reread_dotenvs(proj: &Project, dotenvs: &mut Vec<(String, String)>) -> bool {
+    let new = match proj.read_dotenvs() {
+        Ok(new) => new,
+        Err(e) => {
+            log::error!("Watch {e:#}. The server keeps running with the previous env");
+            return false;
+        }
+    };
+    let old: HashMap<_, _> = dotenvs.iter().cloned().collect();
+    let updated: HashMap<_, _> = new.iter().cloned().collect();
+
+    let added = updated.keys().filter(|k| !old.contains_key(*k));
+    let removed = old.keys().filter(|k| !updated.contains_key(*k));
+    let changed = updated
+        .iter()
+        .filter(|(k, v)| old.get(*k).map_or(false, |o| o != *v))
+        .map(|(k, _)| k);
+
+    let added = added.sorted().join(", ");
+    let removed = removed.sorted().join(", ");
+    let changed = changed.sorted().join(", ");
+    for (what, names) in [("added", added), ("removed", removed), ("changed", changed)] {
+        if !names.is_empty() {
+            log::info!("Watch .env {what} {names}");
+        }
+    }
+    *dotenvs = new;
+    true
+}
+
+/// Why the rebuild started: the files that the watcher found changed, with their pipelines
+fn log_changed_files(changes: &ChangeSet) {
+    let files = changes.files();
+    if files.is_empty() {
+        return;
+    }
+    let shown = files.iter().take(LOGGED_FILES).join(", ");
+    if files.len() > LOGGED_FILES {
+        log::info!(
+            "Watch rebuilding for {shown} and {} more",
+            files.len() - LOGGED_FILES
+        );
+        log::debug!(
+            "Watch changed files {}",
+            GRAY.paint(files.iter().join(", "))
+        );
+    } else {
+        log::info!("Watch rebuilding for {shown}");
+    }
+}
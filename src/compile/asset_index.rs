@@ -0,0 +1,81 @@
+use std::collections::HashMap;
+use std::fs::Metadata;
+use std::time::UNIX_EPOCH;
+
+use camino::Utf8Path;
+use serde::{Deserialize, Serialize};
+
+use crate::ext::anyhow::{Context, Result};
+use crate::ext::PathBufExt;
+use crate::fs;
+
+/// The size, modification time and content hash of the assets at the last sync, keyed
+/// by source path. It is persisted between builds so that only the assets which
+/// metadata changed are hashed again.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct AssetIndex {
+    files: HashMap<String, IndexEntry>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IndexEntry {
+    size: u64,
+    /// seconds and nanoseconds since the unix epoch, if the platform provides it
+    mtime: Option<(u64, u32)>,
+    /// hash of the source content
+    pub hash: u64,
+    /// identifies how the file was processed (copied, optimized or which transform)
+    pub process: u64,
+}
+
+impl AssetIndex {
+    /// Reads the index. A missing or unreadable index is treated as empty,
+    /// which only means that all assets are hashed.
+    pub async fn load(file: &Utf8Path) -> Self {
+        if !file.exists() {
+            return Self::default();
+        }
+        let index = fs::read(file)
+            .await
+            .and_then(|data| serde_json::from_slice(&data).context("Invalid asset index"));
+        index.unwrap_or_else(|e| {
+            log::debug!("Assets ignoring index {file}: {e}");
+            Self::default()
+        })
+    }
+
+    pub async fn save(&self, file: &Utf8Path) -> Result<()> {
+        fs::create_dir_all(file.to_path_buf().without_last()).await?;
+        let data = serde_json::to_vec(self).context("Could not serialize the asset index")?;
+        fs::write(file, data).await
+    }
+
+    pub fn get(&self, src: &Utf8Path) -> Option<IndexEntry> {
+        self.files.get(src.as_str()).copied()
+    }
+
+    pub fn insert(&mut self, src: &Utf8Path, entry: IndexEntry) {
+        self.files.insert(src.to_string(), entry);
+    }
+}
+
+impl IndexEntry {
+    pub fn new(meta: &Metadata, hash: u64, process: u64) -> Self {
+        Self {
+            size: meta.len(),
+            mtime: mtime(meta),
+            hash,
+            process,
+        }
+    }
+
+    /// true if the file has the same size and modification time as when indexed
+    pub fn same_metadata(&self, meta: &Metadata) -> bool {
+        self.mtime.is_some() && self.size == meta.len() && self.mtime == mtime(meta)
+    }
+}
+
+fn mtime(meta: &Metadata) -> Option<(u64, u32)> {
+    let since_epoch = meta.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+    Some((since_epoch.as_secs(), since_epoch.subsec_nanos()))
+}
@@ -1,14 +1,27 @@
+use std::collections::{HashSet, VecDeque};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 
-use super::ChangeSet;
-use crate::config::Project;
-use crate::ext::anyhow::{Context, Result};
+use super::asset_index::{AssetIndex, IndexEntry};
+use super::{image, transform, ChangeSet, Step};
+use crate::config::{AssetsConfig, Project};
+use crate::ext::anyhow::{anyhow, Context, Result};
 use crate::service::notify::Watched;
-use crate::service::site::SourcedSiteFile;
+use crate::service::site::{SiteFile, SourcedSiteFile};
 use crate::signal::{Outcome, Product};
-use crate::{ext::PathExt, fs, logger::GRAY};
+use crate::{
+    ext::{format_bytes, PathBufExt, PathExt},
+    fs,
+    logger::GRAY,
+};
 use camino::{Utf8Path, Utf8PathBuf};
-use tokio::task::JoinHandle;
+use tokio::{sync::Semaphore, task::JoinHandle};
+
+/// The maximum number of nested symlinked dirs that are followed
+pub(crate) const MAX_SYMLINK_DEPTH: usize = 16;
+
+/// The maximum number of asset files synced at the same time
+const MAX_CONCURRENT_COPIES: usize = 32;
 
 pub async fn assets(
     proj: &Arc<Project>,
@@ -23,21 +36,40 @@ pub async fn assets(
              return Ok(Outcome::Success(Product::None));
         };
         let dest_root = &proj.site.root_dir;
+        let mut stats = SyncStats::default();
 
         let change = if first_sync {
             log::trace!("Assets starting full resync");
-            resync(&assets.dir, dest_root).await?;
+            resync(&proj, assets, dest_root, &mut stats).await?;
             true
         } else {
             let mut changed = false;
             for watched in changes.asset_iter() {
                 log::trace!("Assets processing {watched:?}");
                 let change =
-                    update_asset(&proj, watched.clone(), &assets.dir, dest_root, &[]).await?;
+                    update_asset(&proj, watched.clone(), assets, dest_root, &[], &mut stats)
+                        .await?;
                 changed |= change;
+                if stats.interrupted {
+                    break;
+                }
             }
             changed
         };
+        if stats.interrupted {
+            log::debug!("Assets interrupted");
+            return Ok(Outcome::Stopped);
+        }
+        if stats.failed {
+            log::warn!("Assets failed");
+            return Ok(Outcome::Failed);
+        }
+        if stats.bytes_saved > 0 {
+            log::info!(
+                "Assets image optimization saved {}",
+                format_bytes(stats.bytes_saved)
+            );
+        }
         if change {
             log::debug!("Assets finished (with changes)");
             Ok(Outcome::Success(Product::Assets))
@@ -48,31 +80,129 @@ pub async fn assets(
     })
 }
 
-async fn update_asset(
+#[derive(Debug, Default)]
+struct SyncStats {
+    /// bytes saved by the image optimization
+    bytes_saved: u64,
+    copied: usize,
+    /// files that were already up to date
+    skipped: usize,
+    interrupted: bool,
+    failed: bool,
+}
+
+impl SyncStats {
+    fn merge(&mut self, other: Self) {
+        self.bytes_saved += other.bytes_saved;
+        self.copied += other.copied;
+        self.skipped += other.skipped;
+        self.interrupted |= other.interrupted;
+        self.failed |= other.failed;
+    }
+}
+
+/// true if the file is not copied verbatim but transformed or optimized
+fn is_processed(proj: &Project, assets: &AssetsConfig, path: &Utf8Path) -> bool {
+    assets.transform_for(path).is_some()
+        || (proj.release && assets.optimize_images && image::is_optimizable(path))
+}
+
+/// the site location of the asset, which differs from `dest` for transformed assets
+fn site_dest(assets: &AssetsConfig, src: &Utf8Path, dest: Utf8PathBuf) -> Utf8PathBuf {
+    match assets.transform_for(src) {
+        Some(transform) => dest.with_extension(&transform.output_extension),
+        None => dest,
+    }
+}
+
+/// Copies an asset file to the site, running it through its asset-transform or
+/// optimizing it first if it is an image and image optimization applies.
+/// Returns true if the site file changed.
+async fn copy_file(
     proj: &Project,
+    assets: &AssetsConfig,
+    from: &Utf8Path,
+    to: &Utf8Path,
+    stats: &mut SyncStats,
+) -> Result<bool> {
+    if let Some(t) = assets.transform_for(from) {
+        let cache_dir = proj.cache_dir().join("transforms");
+        let timeout = proj.command_timeouts.get("asset-transform");
+        let data = match transform::transform(t, from, &cache_dir, timeout).await? {
+            Outcome::Success(data) => data,
+            Outcome::Stopped => {
+                stats.interrupted = true;
+                return Ok(false);
+            }
+            Outcome::Failed => {
+                stats.failed = true;
+                return Ok(false);
+            }
+        };
+        let dest = site_dest(assets, from, to.to_path_buf());
+        let file = SiteFile {
+            site: dest.unbase(&proj.site.root_dir)?,
+            dest,
+        };
+        let changed = proj.site.updated_with(&file, &data).await?;
+        proj.site.update_mode(&file.dest, Some(from)).await?;
+        return Ok(changed);
+    }
+
+    if proj.release && assets.optimize_images && image::is_optimizable(from) {
+        let cache_dir = proj.cache_dir().join("images");
+        let timeout = proj.command_timeouts.get("image");
+        match image::copy_optimized(from, to, &cache_dir, timeout).await? {
+            Some(saved) => stats.bytes_saved += saved,
+            None => {
+                stats.interrupted = true;
+                return Ok(true);
+            }
+        }
+    } else {
+        fs::copy_atomic(from, to).await?;
+    }
+    proj.site.update_mode(to, Some(from)).await?;
+    Ok(true)
+}
+
+async fn update_asset(
+    proj: &Arc<Project>,
     watched: Watched,
-    src_root: &Utf8Path,
+    assets: &AssetsConfig,
     dest_root: &Utf8Path,
     reserved: &[Utf8PathBuf],
+    stats: &mut SyncStats,
 ) -> Result<bool> {
+    let src_root = &assets.dir;
     if let Some(path) = watched.path() {
         if reserved.contains(path) {
             log::warn!("Assets reserved filename for Leptos. Please remove {path:?}");
             return Ok(false);
         }
+        if let Some(pattern) = assets.ignored_by(path, path.is_dir()) {
+            log::debug!(
+                "Assets skipping {} (assets-ignore {pattern:?})",
+                GRAY.paint(path.as_str())
+            );
+            return Ok(false);
+        }
     }
     Ok(match watched {
         Watched::Create(f) => {
             let to = f.rebase(src_root, dest_root)?;
-            if f.is_dir() {
-                fs::copy_dir_all(f, to).await?;
+            if assets.preserve_symlinks && f.as_std_path().is_symlink() {
+                fs::copy_symlink(&f, &to).await?;
+            } else if f.is_dir() {
+                fs::create_dir_all(&to).await?;
+                mirror(proj, assets, &f, dest_root, reserved, stats).await?;
             } else {
-                fs::copy(&f, &to).await?;
+                copy_file(proj, assets, &f, &to, stats).await?;
             }
             true
         }
         Watched::Remove(f) => {
-            let path = f.rebase(src_root, dest_root)?;
+            let path = site_dest(assets, &f, f.rebase(src_root, dest_root)?);
             if path.is_dir() {
                 fs::remove_dir_all(&path)
                     .await
@@ -84,6 +214,17 @@ async fn update_asset(
             }
             false
         }
+        Watched::Rename(from, to)
+            if assets.transform_for(&from).is_some() || assets.transform_for(&to).is_some() =>
+        {
+            let old = site_dest(assets, &from, from.rebase(src_root, dest_root)?);
+            if old.exists() {
+                fs::remove_file(&old).await?;
+            }
+            let dest = to.rebase(src_root, dest_root)?;
+            copy_file(proj, assets, &to, &dest, stats).await?;
+            true
+        }
         Watched::Rename(from, to) => {
             let from = from.rebase(src_root, dest_root)?;
             let to = to.rebase(src_root, dest_root)?;
@@ -98,17 +239,77 @@ async fn update_asset(
                 dest: f.rebase(src_root, dest_root)?,
                 site: f.unbase(src_root)?,
             };
-            proj.site.updated(&file).await?
+            if is_processed(proj, assets, &f) {
+                // the site file differs from the source when transformed or optimized
+                fs::create_dir_all(file.dest.clone().without_last()).await?;
+                copy_file(proj, assets, &f, &file.dest, stats).await?
+            } else {
+                proj.site.updated(&file).await?
+            }
         }
         Watched::Rescan => {
-            resync(src_root, dest_root).await?;
+            resync(proj, assets, dest_root, stats).await?;
             true
         }
     })
 }
 
-pub fn reserved(src: &Utf8Path) -> Vec<Utf8PathBuf> {
-    vec![src.join("index.html"), src.join("pkg")]
+/// The index.html of client-side-rendered projects is a regular asset
+pub fn reserved(proj: &Project, src: &Utf8Path) -> Vec<Utf8PathBuf> {
+    let mut reserved = if proj.bin.is_none() {
+        vec![src.join("pkg")]
+    } else {
+        vec![src.join("index.html"), src.join("pkg")]
+    };
+    if let Ok(manifest) = proj.site_manifest.strip_prefix(&proj.site.root_dir) {
+        reserved.push(src.join(manifest));
+    }
+    reserved
+}
+
+/// Generates the index.html of a client-side-rendered project, which loads the pkg js
+/// and wasm, unless the assets dir has one.
+pub async fn csr_index(proj: &Project) -> Result<bool> {
+    let has_index = proj
+        .assets
+        .as_ref()
+        .map_or(false, |assets| assets.dir.join("index.html").exists());
+    if has_index {
+        return Ok(false);
+    }
+    let file = SiteFile {
+        dest: proj.site.root_dir.join("index.html"),
+        site: Utf8PathBuf::from("index.html"),
+    };
+
+    let style = match &proj.style.file {
+        Some(style) => format!(
+            "\n    <link rel=\"stylesheet\" href=\"{}\">",
+            proj.site.link(&style.site)
+        ),
+        None => String::new(),
+    };
+    let html = format!(
+        r#"<!DOCTYPE html>
+<html>
+  <head>
+    <meta charset="utf-8">
+    <meta name="viewport" content="width=device-width, initial-scale=1">
+    <title>{name}</title>{style}
+    <script type="module">
+      import init from "{js}";
+      init("{wasm}");
+    </script>
+  </head>
+  <body></body>
+</html>
+"#,
+        name = proj.name,
+        js = proj.site.link(&proj.lib.js_file.site),
+        wasm = proj.site.link(&proj.lib.wasm_file.site),
+    );
+    log::debug!("Assets generating {}", GRAY.paint(file.dest.as_str()));
+    proj.site.updated_with(&file, html.as_bytes()).await
 }
 
 // pub async fn update(config: &Config) -> Result<()> {
@@ -123,65 +324,336 @@ pub fn reserved(src: &Utf8Path) -> Vec<Utf8PathBuf> {
 //     Ok(())
 // }
 
-async fn resync(src: &Utf8Path, dest: &Utf8Path) -> Result<()> {
-    clean_dest(dest)
+async fn resync(
+    proj: &Arc<Project>,
+    assets: &AssetsConfig,
+    dest: &Utf8Path,
+    stats: &mut SyncStats,
+) -> Result<()> {
+    let step = Step::start(proj, "assets");
+    let src = &assets.dir;
+    let reserved = reserved(proj, src);
+    let plan = plan(assets, src, dest, &reserved).context(format!("Reading {src:?}"))?;
+
+    fs::create_dir_all(dest).await?;
+    remove_stale(proj, assets, dest, &plan)
         .await
         .context(format!("Cleaning {dest:?}"))?;
-    let reserved = reserved(src);
-    mirror(src, dest, &reserved)
+
+    let index_file = proj.cache_dir().join("assets-index.json");
+    let index = AssetIndex::load(&index_file).await;
+    let mut synced = AssetIndex::default();
+    apply(proj, plan, &index, &mut synced, stats)
         .await
-        .context(format!("Mirroring {src:?} -> {dest:?}"))
+        .context(format!("Mirroring {src:?} -> {dest:?}"))?;
+    synced.save(&index_file).await?;
+
+    step.finished(|elapsed| {
+        log::info!(
+            "Assets synced {} copied, {} skipped in {elapsed:.2?}",
+            stats.copied,
+            stats.skipped
+        )
+    });
+    Ok(())
+}
+
+/// Removes everything from the site that is not part of the plan, except for
+/// the reserved index.html, the pkg dir and the site manifest which are handled by the
+/// other steps.
+async fn remove_stale(
+    proj: &Project,
+    assets: &AssetsConfig,
+    dest: &Utf8Path,
+    plan: &Plan,
+) -> Result<()> {
+    let expected_dirs: HashSet<&Utf8PathBuf> = plan.dirs.iter().collect();
+    let expected_files: HashSet<Utf8PathBuf> = plan
+        .files
+        .iter()
+        .map(|(from, to)| site_dest(assets, from, to.clone()))
+        .collect();
+    let pkg_dir = proj.site.root_relative_pkg_dir();
+    let index_html = dest.join("index.html");
+
+    let mut dirs = vec![dest.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        let mut entries = fs::read_dir(&dir).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let path = Utf8PathBuf::from_path_buf(entry.path())
+                .map_err(|p| anyhow!("Non utf8 path {p:?}"))?;
+            if path == pkg_dir || path == index_html || path == proj.site_manifest {
+                continue;
+            }
+            let file_type = entry.file_type().await?;
+
+            if file_type.is_dir() {
+                if expected_dirs.contains(&path) || pkg_dir.starts_with(&path) {
+                    dirs.push(path);
+                } else {
+                    log::debug!("Assets removing folder {}", GRAY.paint(path.as_str()));
+                    fs::remove_dir_all(path).await?;
+                }
+            } else if file_type.is_symlink() || !expected_files.contains(&path) {
+                // preserved symlinks are always re-created, since they are cheap to copy
+                log::debug!("Assets removing file {}", GRAY.paint(path.as_str()));
+                fs::remove_file(path).await?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The site entries to create for an assets dir, as (source, destination)
+#[derive(Debug, Default)]
+struct Plan {
+    dirs: Vec<Utf8PathBuf>,
+    links: Vec<(Utf8PathBuf, Utf8PathBuf)>,
+    files: Vec<(Utf8PathBuf, Utf8PathBuf)>,
+}
+
+/// Copies the content of `src_dir` (the assets dir or one of its sub dirs) to the
+/// corresponding location under `dest_root`. Only used for newly created dirs, so
+/// there is no index to compare with.
+async fn mirror(
+    proj: &Arc<Project>,
+    assets: &AssetsConfig,
+    src_dir: &Utf8Path,
+    dest_root: &Utf8Path,
+    reserved: &[Utf8PathBuf],
+    stats: &mut SyncStats,
+) -> Result<()> {
+    let plan = plan(assets, src_dir, dest_root, reserved)?;
+    apply(
+        proj,
+        plan,
+        &AssetIndex::default(),
+        &mut AssetIndex::default(),
+        stats,
+    )
+    .await
 }
 
-async fn clean_dest(dest: &Utf8Path) -> Result<()> {
-    let mut entries = fs::read_dir(dest).await?;
-    while let Some(entry) = entries.next_entry().await? {
-        let path = entry.path();
+/// Lists the content of `src_dir` (the assets dir or one of its sub dirs) together with
+/// the corresponding location under `dest_root`, skipping reserved and ignored paths.
+///
+/// Symlinks are followed and their target's content is listed, unless
+/// `assets-preserve-symlinks` is set in which case the link itself is copied.
+fn plan(
+    assets: &AssetsConfig,
+    src_dir: &Utf8Path,
+    dest_root: &Utf8Path,
+    reserved: &[Utf8PathBuf],
+) -> Result<Plan> {
+    let src_root = &assets.dir;
+    let mut plan = Plan::default();
+    // the dir to read and the number of symlinked dirs followed to get there
+    let mut dirs = VecDeque::new();
+    dirs.push_back((src_dir.to_path_buf(), 0));
+
+    while let Some((dir, link_depth)) = dirs.pop_front() {
+        let mut entries = dir.read_dir_utf8()?;
+        while let Some(Ok(entry)) = entries.next() {
+            let from = entry.path().to_path_buf();
+            let to = from.rebase(src_root, dest_root)?;
+            if reserved.contains(&from) {
+                log::warn!("Assets reserved filename for Leptos. Please remove {from:?}");
+                continue;
+            }
+
+            let file_type = entry.file_type()?;
+            let is_link = file_type.is_symlink();
+            let is_dir = if is_link {
+                from.is_dir()
+            } else {
+                file_type.is_dir()
+            };
 
-        if entry.file_type().await?.is_dir() {
-            if entry.file_name() != "pkg" {
+            if let Some(pattern) = assets.ignored_by(&from, is_dir) {
                 log::debug!(
-                    "Assets removing folder {}",
-                    GRAY.paint(path.to_string_lossy())
+                    "Assets skipping {} (assets-ignore {pattern:?})",
+                    GRAY.paint(from.as_str())
                 );
-                fs::remove_dir_all(path).await?;
+                continue;
+            }
+
+            if is_link {
+                if assets.preserve_symlinks {
+                    plan.links.push((from, to));
+                    continue;
+                }
+                let Ok(target) = from.canonicalize_utf8() else {
+                    let target = std::fs::read_link(&from).unwrap_or_default();
+                    log::warn!(
+                        "Assets skipping dangling symlink {} -> {}",
+                        from,
+                        target.display()
+                    );
+                    continue;
+                };
+                if is_dir {
+                    if link_depth >= MAX_SYMLINK_DEPTH {
+                        log::warn!(
+                            "Assets not following symlink {from} -> {target}: more than {MAX_SYMLINK_DEPTH} nested symlinked dirs"
+                        );
+                        continue;
+                    }
+                    if dir.canonicalize_utf8()?.starts_with(&target) {
+                        log::warn!(
+                            "Assets not following symlink {from} -> {target}: it points to one of its parent dirs"
+                        );
+                        continue;
+                    }
+                    plan.dirs.push(to);
+                    dirs.push_back((from, link_depth + 1));
+                    continue;
+                }
+            }
+
+            if is_dir {
+                plan.dirs.push(to);
+                dirs.push_back((from, link_depth));
+            } else {
+                plan.files.push((from, to));
             }
-        } else if entry.file_name() != "index.html" {
-            log::debug!(
-                "Assets removing file {}",
-                GRAY.paint(path.to_string_lossy())
-            );
-            fs::remove_file(path).await?;
         }
     }
-    Ok(())
+    Ok(plan)
+}
+
+/// Creates the planned dirs and symlinks and syncs the files concurrently.
+/// The index entries of the synced files are added to `synced`.
+async fn apply(
+    proj: &Arc<Project>,
+    plan: Plan,
+    index: &AssetIndex,
+    synced: &mut AssetIndex,
+    stats: &mut SyncStats,
+) -> Result<()> {
+    for dir in &plan.dirs {
+        fs::create_dir_all(dir).await?;
+    }
+    for (from, to) in &plan.links {
+        log::debug!(
+            "Assets copy symlink {} -> {}",
+            GRAY.paint(from.as_str()),
+            GRAY.paint(to.as_str())
+        );
+        fs::copy_symlink(from, to).await?;
+    }
+
+    let permits = Arc::new(Semaphore::new(MAX_CONCURRENT_COPIES));
+    // set by an interrupted or a failed copy, so that no more copies are started
+    let stopped = Arc::new(AtomicBool::new(false));
+    let mut tasks = Vec::with_capacity(plan.files.len());
+    for (from, to) in plan.files {
+        let permit = permits.clone().acquire_owned().await?;
+        if stopped.load(Ordering::Relaxed) {
+            break;
+        }
+        let prev = index.get(&from);
+        let proj = proj.clone();
+        let stopped = stopped.clone();
+        tasks.push(tokio::spawn(async move {
+            let _permit = permit;
+            let res = sync_file(&proj, &from, &to, prev).await;
+            let stop = match &res {
+                Ok((_, stats)) => stats.interrupted,
+                Err(_) => true,
+            };
+            if stop {
+                stopped.store(true, Ordering::Relaxed);
+            }
+            res.map(|(entry, stats)| (from, entry, stats))
+        }));
+    }
+
+    // all the copies are awaited before the first error is returned, so that none of them
+    // writes to the site once the stage has failed
+    let mut first_error = None;
+    for task in tasks {
+        let res = match task.await {
+            Ok(res) => res,
+            Err(e) => Err(e.into()),
+        };
+        match res {
+            Ok((from, entry, file_stats)) => {
+                if let Some(entry) = entry {
+                    synced.insert(&from, entry);
+                }
+                stats.merge(file_stats);
+            }
+            Err(e) => {
+                first_error.get_or_insert(e);
+            }
+        }
+    }
+    match first_error {
+        Some(e) => Err(e),
+        None => Ok(()),
+    }
 }
 
-async fn mirror(src_root: &Utf8Path, dest_root: &Utf8Path, reserved: &[Utf8PathBuf]) -> Result<()> {
-    let mut entries = src_root.read_dir_utf8()?;
-    while let Some(Ok(entry)) = entries.next() {
-        let from = entry.path().to_path_buf();
-        let to = from.rebase(src_root, dest_root)?;
-        if reserved.contains(&from) {
-            log::warn!("");
-            continue;
+/// Copies the asset file unless the index, or the site file, shows that it is up to date.
+/// Returns the index entry for the file, or None if it couldn't be synced.
+async fn sync_file(
+    proj: &Project,
+    from: &Utf8Path,
+    to: &Utf8Path,
+    prev: Option<IndexEntry>,
+) -> Result<(Option<IndexEntry>, SyncStats)> {
+    let assets = proj.assets.as_ref().dot()?;
+    let mut stats = SyncStats::default();
+    let dest = site_dest(assets, from, to.to_path_buf());
+    let process = process_key(proj, assets, from);
+    let prev = prev.filter(|prev| prev.process == process && dest.exists());
+    let meta = fs::metadata(from).await?;
+
+    if let Some(prev) = prev {
+        if !proj.assets_verify && prev.same_metadata(&meta) {
+            stats.skipped += 1;
+            return Ok((Some(prev), stats));
         }
+    }
 
-        if entry.file_type()?.is_dir() {
-            log::debug!(
-                "Assets copy folder {} -> {}",
-                GRAY.paint(from.as_str()),
-                GRAY.paint(to.as_str())
-            );
-            fs::copy_dir_all(from, to).await?;
-        } else {
-            log::debug!(
-                "Assets copy file {} -> {}",
-                GRAY.paint(from.as_str()),
-                GRAY.paint(to.as_str())
-            );
-            fs::copy(from, to).await?;
+    let hash = seahash::hash(&fs::read(from).await?);
+    let indexed = prev.map(|prev| prev.hash) == Some(hash) && !proj.assets_verify;
+    let up_to_date = if !dest.exists() {
+        false
+    } else if indexed {
+        true
+    } else if process != 0 {
+        // the site file differs from the source when transformed or optimized
+        false
+    } else {
+        seahash::hash(&fs::read(&dest).await?) == hash
+    };
+
+    if up_to_date {
+        stats.skipped += 1;
+    } else {
+        log::debug!(
+            "Assets copy file {} -> {}",
+            GRAY.paint(from.as_str()),
+            GRAY.paint(to.as_str())
+        );
+        copy_file(proj, assets, from, to, &mut stats).await?;
+        if stats.interrupted || stats.failed {
+            return Ok((None, stats));
         }
+        stats.copied += 1;
+    }
+    Ok((Some(IndexEntry::new(&meta, hash, process)), stats))
+}
+
+/// Identifies how the file is processed: 0 when copied verbatim, otherwise a hash of
+/// the asset-transform or image optimization that applies.
+fn process_key(proj: &Project, assets: &AssetsConfig, path: &Utf8Path) -> u64 {
+    if let Some(t) = assets.transform_for(path) {
+        seahash::hash(format!("transform {} {}", t.command, t.output_extension).as_bytes())
+    } else if is_processed(proj, assets, path) {
+        seahash::hash(b"optimize")
+    } else {
+        0
     }
-    Ok(())
 }
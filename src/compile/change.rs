@@ -1,4 +1,8 @@
-use std::vec;
+use std::{fmt::Display, vec};
+
+use camino::Utf8PathBuf;
+use itertools::Itertools;
+use serde::Serialize;
 
 use crate::service::notify::Watched;
 
@@ -14,56 +18,174 @@ pub enum Change {
     Style,
     /// Cargo.toml changed
     Conf,
+    /// a watch-additional-files entry with the restart-server action changed
+    ServerRestart,
+    /// a watch-additional-files entry with the reload-browser-only action changed
+    BrowserReload,
+    /// the .env file changed
+    DotEnv,
+}
+
+impl Change {
+    /// The name of the change in the build events
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::BinSource => "bin-source",
+            Self::LibSource => "lib-source",
+            Self::Asset(_) => "asset",
+            Self::Style => "style",
+            Self::Conf => "conf",
+            Self::ServerRestart => "server-restart",
+            Self::BrowserReload => "browser-reload",
+            Self::DotEnv => "dotenv",
+        }
+    }
+
+    /// What the change rebuilds, restarts or reloads
+    pub fn pipeline(&self) -> &'static str {
+        match self {
+            Self::BinSource | Self::ServerRestart | Self::DotEnv => "server",
+            Self::LibSource => "front",
+            Self::Asset(_) => "assets",
+            Self::Style => "style",
+            Self::Conf => "all",
+            Self::BrowserReload => "reload",
+        }
+    }
+}
+
+/// A file that the watcher found changed, with the pipelines of its changes. The reason
+/// of a rebuild, which shows the editor temp files and the generated files to ignore.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ChangedFile {
+    /// relative to the workspace root
+    pub path: Utf8PathBuf,
+    /// modified, created, removed or renamed
+    pub kind: &'static str,
+    pub pipelines: Vec<&'static str>,
+}
+
+impl ChangedFile {
+    /// None for a rescan, which has no file
+    pub fn new(watched: &Watched, changes: &[Change]) -> Option<Self> {
+        let (path, kind) = match watched {
+            Watched::Write(path) => (path, "modified"),
+            Watched::Create(path) => (path, "created"),
+            Watched::Remove(path) => (path, "removed"),
+            Watched::Rename(_, to) => (to, "renamed"),
+            Watched::Rescan => return None,
+        };
+        Some(Self {
+            path: path.clone(),
+            kind,
+            pipelines: changes.iter().map(Change::pipeline).unique().collect(),
+        })
+    }
+}
+
+impl Display for ChangedFile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} ({}: {})",
+            self.path,
+            self.kind,
+            self.pipelines.join(", ")
+        )
+    }
 }
 
 #[derive(Debug, Default, Clone)]
-pub struct ChangeSet(Vec<Change>);
+pub struct ChangeSet {
+    changes: Vec<Change>,
+    /// the files that the watcher found changed, in that order
+    files: Vec<ChangedFile>,
+}
 
 impl ChangeSet {
     pub fn all_changes() -> Self {
-        Self(vec![
-            Change::BinSource,
-            Change::LibSource,
-            Change::Style,
-            Change::Conf,
-            Change::Asset(Watched::Rescan),
-        ])
+        Self {
+            changes: vec![
+                Change::BinSource,
+                Change::LibSource,
+                Change::Style,
+                Change::Conf,
+                Change::Asset(Watched::Rescan),
+            ],
+            files: Vec::new(),
+        }
     }
 
     pub fn is_empty(&self) -> bool {
-        self.0.is_empty()
+        self.changes.is_empty()
     }
 
     pub fn clear(&mut self) {
-        self.0.clear()
+        self.changes.clear();
+        self.files.clear();
     }
 
     pub fn need_server_build(&self) -> bool {
-        self.0.contains(&Change::BinSource) || self.0.contains(&Change::Conf)
+        self.changes.contains(&Change::BinSource) || self.changes.contains(&Change::Conf)
     }
 
     pub fn need_front_build(&self) -> bool {
-        self.0.contains(&Change::LibSource) || self.0.contains(&Change::Conf)
+        self.changes.contains(&Change::LibSource) || self.changes.contains(&Change::Conf)
+    }
+
+    pub fn need_server_restart(&self) -> bool {
+        self.changes.contains(&Change::ServerRestart)
+    }
+
+    pub fn need_browser_reload(&self) -> bool {
+        self.changes.contains(&Change::BrowserReload)
+    }
+
+    pub fn dotenv_changed(&self) -> bool {
+        self.changes.contains(&Change::DotEnv)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Change> {
+        self.changes.iter()
     }
 
     pub fn asset_iter(&self) -> impl Iterator<Item = &Watched> {
-        self.0.iter().filter_map(|change| match change {
+        self.changes.iter().filter_map(|change| match change {
             Change::Asset(a) => Some(a),
             _ => None,
         })
     }
 
     pub fn need_style_build(&self, css_files: bool, css_in_source: bool) -> bool {
-        (css_files && self.0.contains(&Change::Style))
-            || (css_in_source && self.0.contains(&Change::LibSource))
+        (css_files && self.changes.contains(&Change::Style))
+            || (css_in_source && self.changes.contains(&Change::LibSource))
     }
 
     pub fn add(&mut self, change: Change) -> bool {
-        if !self.0.contains(&change) {
-            self.0.push(change);
+        if !self.changes.contains(&change) {
+            self.changes.push(change);
             true
         } else {
             false
         }
     }
+
+    pub fn files(&self) -> &[ChangedFile] {
+        &self.files
+    }
+
+    /// A file changed again has its last kind, with the pipelines of both changes
+    pub fn add_file(&mut self, file: ChangedFile) {
+        match self.files.iter_mut().find(|f| f.path == file.path) {
+            Some(found) => {
+                found.kind = file.kind;
+                for pipeline in file.pipelines {
+                    if !found.pipelines.contains(&pipeline) {
+                        found.pipelines.push(pipeline);
+                    }
+                }
+            }
+            None => self.files.push(file),
+        }
+    }
 }
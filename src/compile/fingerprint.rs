@@ -0,0 +1,213 @@
+use std::{collections::BTreeMap, hash::Hasher, path::Path, time::UNIX_EPOCH};
+
+use camino::Utf8PathBuf;
+use seahash::SeaHasher;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config::Project,
+    ext::{
+        anyhow::{Context, Result},
+        fs,
+    },
+    logger::GRAY,
+};
+
+/// The fingerprints of the stages of the last `cargo leptos build`, with the ones of its config
+/// and of the site it wrote
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Stored {
+    config: u64,
+    site: u64,
+    stages: BTreeMap<String, u64>,
+}
+
+/// Lets the `build` command skip the stages whose inputs are the ones of the last build, like
+/// wasm-bindgen and wasm-opt when the wasm of cargo is unchanged. The inputs are hashed by
+/// their path, size and modification time, like cargo does, together with the config.
+pub struct Fingerprints {
+    /// empty with --force, or when the config or the site changed since the last build, as
+    /// the files of the old config would be left in the site, or the site was written by watch
+    previous: BTreeMap<String, u64>,
+    current: BTreeMap<String, u64>,
+}
+
+impl Fingerprints {
+    pub async fn load(proj: &Project, force: bool) -> Self {
+        let previous = if force {
+            BTreeMap::new()
+        } else {
+            match Self::read(proj).await {
+                Some(stored) if stored.config != config_hasher(proj).finish() => {
+                    log::debug!("Build fingerprints ignored, the config changed");
+                    BTreeMap::new()
+                }
+                Some(stored) if stored.site == site_hash(proj) => stored.stages,
+                Some(_) => {
+                    log::debug!(
+                        "Build fingerprints ignored, the site changed since the last build"
+                    );
+                    BTreeMap::new()
+                }
+                None => BTreeMap::new(),
+            }
+        };
+        Self {
+            previous,
+            current: BTreeMap::new(),
+        }
+    }
+
+    async fn read(proj: &Project) -> Option<Stored> {
+        let data = fs::read(file(proj)).await.ok()?;
+        serde_json::from_slice(&data).ok()
+    }
+
+    /// Whether the site of the last build is still there, so that it needs no cleaning
+    pub fn has_previous(&self) -> bool {
+        !self.previous.is_empty()
+    }
+
+    /// Records the inputs of the stage, and tells whether they are those of the last build
+    /// while its outputs still exist
+    pub fn up_to_date(&mut self, stage: &str, inputs: u64, outputs: &[&Utf8PathBuf]) -> bool {
+        self.current.insert(stage.to_string(), inputs);
+        self.previous.get(stage) == Some(&inputs) && outputs.iter().all(|path| path.exists())
+    }
+
+    /// Persists the fingerprints after a successful build, with the one of its site
+    pub async fn save(self, proj: &Project) -> Result<()> {
+        let stored = Stored {
+            config: config_hasher(proj).finish(),
+            site: site_hash(proj),
+            stages: self.current,
+        };
+        let file = file(proj);
+        fs::create_dir_all(file.parent().unwrap_or(&proj.target_dir)).await?;
+        fs::write(&file, serde_json::to_vec(&stored).dot()?).await?;
+        log::trace!(
+            "Build fingerprints written to {}",
+            GRAY.paint(file.as_str())
+        );
+        Ok(())
+    }
+
+    /// After a failed build, whose stages may have written part of their files
+    pub async fn remove(proj: &Project) -> Result<()> {
+        let file = file(proj);
+        if file.exists() {
+            fs::remove_file(&file).await?;
+        }
+        Ok(())
+    }
+}
+
+fn file(proj: &Project) -> Utf8PathBuf {
+    proj.cache_dir().join("fingerprints.json")
+}
+
+/// The wasm of the cargo build, which wasm-bindgen and wasm-opt process
+pub fn front_inputs(proj: &Project) -> u64 {
+    let mut hasher = config_hasher(proj);
+    hash_path(&mut hasher, proj.lib.wasm_file.source.as_std_path());
+    hasher.finish()
+}
+
+/// The style file with the files beside it, like the sass partials, and with tailwind its
+/// input and config files and the sources that it scans for the classes
+pub fn style_inputs(proj: &Project) -> u64 {
+    let mut hasher = config_hasher(proj);
+    if let Some(file) = &proj.style.file {
+        let dir = file.source.parent().unwrap_or(&file.source);
+        hash_path(&mut hasher, dir.as_std_path());
+    }
+    if let Some(tailwind) = &proj.style.tailwind {
+        hash_path(&mut hasher, tailwind.input_file.as_std_path());
+        hash_path(&mut hasher, tailwind.config_file.as_std_path());
+        let bin_paths = proj.bin.iter().flat_map(|bin| &bin.src_paths);
+        for path in proj.lib.src_paths.iter().chain(bin_paths) {
+            hash_path(&mut hasher, path.as_std_path());
+        }
+    }
+    hasher.finish()
+}
+
+pub fn assets_inputs(proj: &Project) -> u64 {
+    let mut hasher = config_hasher(proj);
+    if let Some(assets) = &proj.assets {
+        hash_path(&mut hasher, assets.dir.as_std_path());
+    }
+    hasher.finish()
+}
+
+/// The stages also depend on the config: the manifests and the env vars it is read from, the
+/// options of the command line and the version of cargo-leptos
+fn config_hasher(proj: &Project) -> SeaHasher {
+    let mut hasher = SeaHasher::new();
+    hasher.write(env!("CARGO_PKG_VERSION").as_bytes());
+    let config = format!(
+        "{:?} {:?} {:?} {:?} {} {:?} {:?}",
+        proj.lib,
+        proj.bin,
+        proj.style,
+        proj.assets,
+        proj.release,
+        proj.hash_file,
+        proj.tool_versions
+    );
+    hasher.write(config.as_bytes());
+    let mut envs: Vec<(String, String)> = std::env::vars()
+        .filter(|(key, _)| key.starts_with("LEPTOS_"))
+        .collect();
+    envs.sort();
+    for (key, value) in envs {
+        hasher.write(format!("{key}={value}").as_bytes());
+    }
+    let bin_dir = proj.bin.as_ref().map(|bin| &bin.abs_dir);
+    for dir in [Some(&proj.working_dir), Some(&proj.lib.abs_dir), bin_dir]
+        .into_iter()
+        .flatten()
+    {
+        hash_path(&mut hasher, dir.join("Cargo.toml").as_std_path());
+    }
+    hasher
+}
+
+/// The site as the last build left it, which the skipped stages rely on
+fn site_hash(proj: &Project) -> u64 {
+    let mut hasher = SeaHasher::new();
+    hash_path(&mut hasher, proj.site.root_dir.as_std_path());
+    hasher.finish()
+}
+
+/// Hashes the path with its size and modification time, or those of the files of a dir, in
+/// the order of their names. The temp files of the writes in progress are left out.
+pub(super) fn hash_path(hasher: &mut SeaHasher, path: &Path) {
+    hasher.write(path.to_string_lossy().as_bytes());
+    let Ok(metadata) = std::fs::metadata(path) else {
+        hasher.write(b"missing");
+        return;
+    };
+    if metadata.is_dir() {
+        let mut entries: Vec<_> = std::fs::read_dir(path)
+            .into_iter()
+            .flatten()
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| !fs::is_tmp_path(path))
+            .collect();
+        entries.sort();
+        for entry in entries {
+            hash_path(hasher, &entry);
+        }
+        return;
+    }
+    hasher.write_u64(metadata.len());
+    if let Some(modified) = metadata
+        .modified()
+        .ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+    {
+        hasher.write_u128(modified.as_nanos());
+    }
+}
@@ -1,18 +1,22 @@
 use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::Duration;
 
-use super::ChangeSet;
+use super::{ChangeSet, Step};
 use crate::config::Project;
 use crate::ext::fs;
-use crate::ext::sync::{wait_interruptible, CommandResult};
+use crate::ext::sync::{
+    capture_errors, new_process_group, pipe_cargo_stderr, retry_failures, wait_interruptible,
+    CommandResult,
+};
 use crate::service::site::SiteFile;
-use crate::signal::{Interrupt, Outcome, Product};
+use crate::signal::{Interrupt, Outcome, Product, ReloadSignal};
 use crate::{
     ext::{
         anyhow::{Context, Result},
-        exe::Exe,
+        exe::{Exe, ToolVersions},
     },
-    logger::GRAY,
+    logger::{self, GRAY},
 };
 use camino::{Utf8Path, Utf8PathBuf};
 use tokio::process::Child;
@@ -31,44 +35,77 @@ pub async fn front(
             return Ok(Outcome::Success(Product::None));
         }
 
-        fs::create_dir_all(&proj.site.root_relative_pkg_dir()).await?;
+        match front_cargo(&proj).await? {
+            Outcome::Success(()) => bindgen(&proj).await.dot(),
+            Outcome::Stopped => Ok(Outcome::Stopped),
+            Outcome::Failed => Ok(Outcome::Failed),
+        }
+    })
+}
 
-        let (envs, line, process) = front_cargo_process("build", true, &proj)?;
+/// The cargo build of the wasm, without the wasm-bindgen and wasm-opt steps that process it
+pub async fn front_cargo(proj: &Project) -> Result<Outcome<()>> {
+    fs::create_dir_all(&proj.site.root_relative_pkg_dir()).await?;
 
-        match wait_interruptible("Cargo", process, Interrupt::subscribe_any()).await? {
-            CommandResult::Interrupted => return Ok(Outcome::Stopped),
-            CommandResult::Failure(_) => return Ok(Outcome::Failed),
-            _ => {}
-        }
-        log::debug!("Cargo envs: {}", GRAY.paint(envs));
-        log::info!("Cargo finished {}", GRAY.paint(line));
+    let step = Step::start(proj, "front");
+    let (envs, line, mut process) = front_cargo_process("build", &["--lib"], true, proj)?;
+    let errors = capture_errors(&mut process);
 
-        bindgen(&proj).await.dot()
-    })
+    let timeout = proj.command_timeouts.get("cargo");
+    match wait_interruptible("Cargo", process, Interrupt::subscribe_build(), timeout).await? {
+        CommandResult::Interrupted => return Ok(Outcome::Stopped),
+        CommandResult::Failure(_) => {
+            ReloadSignal::add_build_errors(errors.await.unwrap_or_default());
+            return Ok(Outcome::Failed);
+        }
+        _ => {}
+    }
+    log::debug!("Cargo envs: {}", GRAY.paint(envs));
+    step.finished(|elapsed| log::info!("Cargo finished in {elapsed:.1?} {}", GRAY.paint(line)));
+    let wasm = &proj.lib.wasm_file.source;
+    if !wasm.exists() {
+        log::error!(
+            "Front no wasm at {wasm} after the cargo build. Is the crate-type of the lib-package {} missing \"cdylib\"?",
+            proj.lib.name
+        );
+        return Ok(Outcome::Failed);
+    }
+    Ok(Outcome::Success(()))
 }
 
 pub fn front_cargo_process(
     cmd: &str,
+    targets: &[&str],
     wasm: bool,
     proj: &Project,
 ) -> Result<(String, String, Child)> {
     let mut command = Command::new("cargo");
-    let (envs, line) = build_cargo_front_cmd(cmd, wasm, proj, &mut command);
+    let (envs, line) = build_cargo_front_cmd(cmd, targets, wasm, proj, &mut command);
+    // killed with the rustc processes on interrupt
+    new_process_group(&mut command);
+    // the errors are shown in the browser
+    if proj.captures_build_errors() {
+        pipe_cargo_stderr(&mut command);
+    }
     Ok((envs, line, command.spawn()?))
 }
 
+/// The targets are the cargo target selection, like `--lib` or `--doc`
 pub fn build_cargo_front_cmd(
     cmd: &str,
+    targets: &[&str],
     wasm: bool,
     proj: &Project,
     command: &mut Command,
 ) -> (String, String) {
-    let mut args = vec![
-        cmd.to_string(),
-        format!("--package={}", proj.lib.name.as_str()),
-        "--lib".to_string(),
-        "--target-dir=target/front".to_string(),
-    ];
+    // the cmd can be a sub command, like "nextest run"
+    let mut args: Vec<String> = cmd.split(' ').map(str::to_string).collect();
+    args.push(format!("--package={}", proj.lib.name.as_str()));
+    args.extend(targets.iter().map(|t| t.to_string()));
+    // cargo keeps one resolution of the features by target dir, so the front and the server,
+    // which are often the same package with the hydrate and the ssr features, have their own
+    // target dir: the incompatible deps of the features never meet in a build
+    args.push("--target-dir=target/front".to_string());
     if wasm {
         args.push("--target=wasm32-unknown-unknown".to_string());
     }
@@ -82,6 +119,7 @@ pub fn build_cargo_front_cmd(
     }
 
     proj.lib.profile.add_to_args(&mut args);
+    logger::add_cargo_color(&mut args);
 
     let envs = proj.to_envs();
 
@@ -96,9 +134,10 @@ pub fn build_cargo_front_cmd(
     (envs_str, line)
 }
 
-async fn bindgen(proj: &Project) -> Result<Outcome<Product>> {
+pub async fn bindgen(proj: &Project) -> Result<Outcome<Product>> {
     let wasm_file = &proj.lib.wasm_file;
-    let interrupt = Interrupt::subscribe_any();
+    let interrupt = Interrupt::subscribe_build();
+    let step = Step::start(proj, "wasm-bindgen");
 
     // see:
     // https://github.com/rustwasm/wasm-bindgen/blob/main/crates/cli-support/src/lib.rs#L95
@@ -110,15 +149,43 @@ async fn bindgen(proj: &Project) -> Result<Outcome<Product>> {
         .generate_output()
         .dot()?;
 
-    bindgen.wasm_mut().emit_wasm_file(&wasm_file.dest).dot()?;
-    log::trace!("Front wrote wasm to {:?}", wasm_file.dest.as_str());
+    // written and optimized beside the site file, and then moved over it, so that the
+    // browser never loads a partial wasm
+    let tmp_wasm = Utf8PathBuf::try_from(fs::tmp_path(wasm_file.dest.as_std_path())).dot()?;
+    bindgen.wasm_mut().emit_wasm_file(&tmp_wasm).dot()?;
+    log::trace!("Front wrote wasm to {:?}", tmp_wasm.as_str());
+    step.finished(|elapsed| log::info!("Front wasm-bindgen finished in {elapsed:.1?}"));
     if proj.release {
-        match optimize(&wasm_file.dest, interrupt).await.dot()? {
-            CommandResult::Interrupted => return Ok(Outcome::Stopped),
-            CommandResult::Failure(_) => return Ok(Outcome::Failed),
-            _ => {}
+        let step = Step::start(proj, "wasm-opt");
+        let timeout = proj.command_timeouts.get("wasm-opt");
+        let optimized = retry_failures(
+            "wasm-opt",
+            proj.tool_retries,
+            interrupt.resubscribe(),
+            || {
+                optimize(
+                    &tmp_wasm,
+                    &proj.tool_versions,
+                    interrupt.resubscribe(),
+                    timeout,
+                )
+            },
+        );
+        let stopped = match optimized.await.dot() {
+            Ok(CommandResult::Interrupted) => Ok(Some(Outcome::Stopped)),
+            Ok(CommandResult::Failure(_)) => Ok(Some(Outcome::Failed)),
+            Ok(_) => Ok(None),
+            Err(e) => Err(e),
+        };
+        if !matches!(stopped, Ok(None)) {
+            _ = fs::remove_file(&tmp_wasm).await;
+        }
+        if let Some(outcome) = stopped? {
+            return Ok(outcome);
         }
+        step.finished(|elapsed| log::info!("Front wasm-opt finished in {elapsed:.1?}"));
     }
+    fs::rename_over(tmp_wasm.as_std_path(), wasm_file.dest.as_std_path()).await?;
 
     let mut js_changed = false;
 
@@ -148,16 +215,18 @@ async fn bindgen(proj: &Project) -> Result<Outcome<Product>> {
 
 async fn optimize(
     file: &Utf8Path,
+    versions: &ToolVersions,
     interrupt: broadcast::Receiver<()>,
+    timeout: Option<Duration>,
 ) -> Result<CommandResult<()>> {
-    let wasm_opt = Exe::WasmOpt.get().await.dot()?;
+    let wasm_opt = Exe::WasmOpt.get(versions).await.dot()?;
 
     let args = [file.as_str(), "-Os", "-o", file.as_str()];
-    let process = Command::new(wasm_opt)
-        .args(args)
-        .spawn()
-        .context("Could not spawn command")?;
-    wait_interruptible("wasm-opt", process, interrupt).await
+    let mut cmd = Command::new(wasm_opt);
+    cmd.args(args);
+    new_process_group(&mut cmd);
+    let process = cmd.spawn().context("Could not spawn command")?;
+    wait_interruptible("wasm-opt", process, interrupt, timeout).await
 }
 
 async fn write_snippets(proj: &Project, snippets: &HashMap<String, Vec<String>>) -> Result<bool> {
@@ -0,0 +1,61 @@
+use camino::{Utf8Path, Utf8PathBuf};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    config::Project,
+    ext::{anyhow::Result, fs},
+    logger::GRAY,
+    service::site::{Site, SiteFile},
+};
+
+/// With hash-files, writes the hashes of the wasm, js and css files of the site to the hash
+/// file. It is called after the front and style stages, so the wasm is the one optimized by
+/// wasm-opt in release builds.
+///
+/// Returns true if the hash file changed.
+pub async fn hash_files(proj: &Project) -> Result<bool> {
+    let Some(hash_file) = &proj.hash_file else {
+        return Ok(false);
+    };
+    let files = [
+        proj.lib.wasm_file.as_site_file(),
+        proj.lib.js_file.clone(),
+        proj.style.site_file.clone(),
+    ];
+    write_hash_file(&proj.site, hash_file, &files).await
+}
+
+/// Writes a `<site path>:sha256:<hex>` line for each of the files that exist, like the css
+/// file when there is a style file. The hash file is only written when a hash changed, so
+/// that it doesn't trigger a reload when it is watched.
+pub(crate) async fn write_hash_file(
+    site: &Site,
+    hash_file: &Utf8Path,
+    files: &[SiteFile],
+) -> Result<bool> {
+    let mut content = String::new();
+    for file in files {
+        if !file.dest.exists() {
+            continue;
+        }
+        let data = fs::read(&file.dest).await?;
+        content.push_str(&format!(
+            "{}:sha256:{:x}\n",
+            file.site.as_str().replace('\\', "/"),
+            Sha256::digest(&data)
+        ));
+    }
+
+    let file = SiteFile {
+        dest: hash_file.to_path_buf(),
+        site: Utf8PathBuf::from(hash_file.as_str()),
+    };
+    let changed = site.updated_with(&file, content.as_bytes()).await?;
+    if changed {
+        log::debug!(
+            "Build wrote the hash file {}",
+            GRAY.paint(hash_file.as_str())
+        );
+    }
+    Ok(changed)
+}
@@ -0,0 +1,167 @@
+use std::time::Duration;
+
+use camino::Utf8Path;
+use tokio::process::Command;
+
+use crate::{
+    ext::{
+        anyhow::{Context, Result},
+        exe::{Exe, ToolVersions},
+        fs,
+        sync::{new_process_group, wait_interruptible, CommandResult},
+    },
+    logger::GRAY,
+    signal::Interrupt,
+};
+
+pub fn is_optimizable(path: &Utf8Path) -> bool {
+    matches!(
+        path.extension().map(|e| e.to_lowercase()).as_deref(),
+        Some("png" | "jpg" | "jpeg" | "svg")
+    )
+}
+
+/// Copies the image losslessly optimized. The optimized content is cached by the hash of the
+/// source, and the source is copied unmodified when optimizing doesn't make it smaller.
+///
+/// Returns the number of bytes saved or None if interrupted.
+pub async fn copy_optimized(
+    from: &Utf8Path,
+    to: &Utf8Path,
+    cache_dir: &Utf8Path,
+    timeout: Option<Duration>,
+) -> Result<Option<u64>> {
+    let data = fs::read(from).await?;
+    let ext = from.extension().unwrap_or_default().to_lowercase();
+    let hash = seahash::hash(&data);
+    let cached = cache_dir.join(format!("{hash:016x}.{ext}"));
+
+    if cached.exists() {
+        let optimized = fs::read(&cached).await?;
+        fs::write_atomic(to, &optimized).await?;
+        log::trace!("Image using cached {}", GRAY.paint(cached.as_str()));
+        return Ok(Some(data.len().saturating_sub(optimized.len()) as u64));
+    }
+    fs::create_dir_all(cache_dir).await?;
+
+    let optimized = if ext == "svg" {
+        Some(minify_svg(&String::from_utf8_lossy(&data)).into_bytes())
+    } else {
+        // the tool writes to a temp file, so that an interrupted or failed run doesn't leave
+        // a partial image in the cache
+        let partial = cache_dir.join(format!(".{hash:016x}.{}.tmp.{ext}", std::process::id()));
+        let Some((name, mut cmd)) = tool_command(&ext, from, &partial).await? else {
+            log::debug!("Image no optimizer found, copying {from} unmodified");
+            fs::copy_atomic(from, to).await?;
+            return Ok(Some(0));
+        };
+        log::trace!("Image running {}", GRAY.paint(format!("{cmd:?}")));
+        new_process_group(&mut cmd);
+        let process = cmd
+            .spawn()
+            .context(format!("Could not spawn command {name}"))?;
+        let res = wait_interruptible(name, process, Interrupt::subscribe_build(), timeout).await;
+        let optimized = match &res {
+            Ok(CommandResult::Success(_)) => Some(fs::read(&partial).await),
+            Ok(CommandResult::Failure(_)) => {
+                log::warn!("Image {name} failed on {from}, copying it unmodified");
+                None
+            }
+            Ok(CommandResult::Interrupted) | Err(_) => None,
+        };
+        if partial.exists() {
+            fs::remove_file(&partial).await?;
+        }
+        if matches!(res?, CommandResult::Interrupted) {
+            return Ok(None);
+        }
+        optimized.transpose()?
+    };
+
+    // never make a file bigger, and cache the decision as well
+    let content = match optimized {
+        Some(optimized) if optimized.len() < data.len() => optimized,
+        Some(_) => {
+            log::debug!(
+                "Image kept {} unmodified, optimizing doesn't make it smaller",
+                GRAY.paint(from.as_str())
+            );
+            data.clone()
+        }
+        None => data.clone(),
+    };
+    fs::write_atomic(&cached, &content).await?;
+    fs::write_atomic(to, &content).await?;
+
+    let saved = (data.len() - content.len()) as u64;
+    if saved > 0 {
+        log::debug!(
+            "Image optimized {} saving {saved} bytes",
+            GRAY.paint(from.as_str())
+        );
+    }
+    Ok(Some(saved))
+}
+
+/// The command optimizing the png or jpg file `from` into `output`.
+/// jpegtran is optional and only used when found on the PATH.
+async fn tool_command(
+    ext: &str,
+    from: &Utf8Path,
+    output: &Utf8Path,
+) -> Result<Option<(&'static str, Command)>> {
+    if ext == "png" {
+        let mut cmd = Command::new(Exe::Oxipng.get(&ToolVersions::default()).await.dot()?);
+        cmd.args([
+            "--opt",
+            "2",
+            "--quiet",
+            "--out",
+            output.as_str(),
+            from.as_str(),
+        ]);
+        Ok(Some(("oxipng", cmd)))
+    } else if let Ok(jpegtran) = which::which("jpegtran") {
+        let mut cmd = Command::new(jpegtran);
+        cmd.args([
+            "-copy",
+            "all",
+            "-optimize",
+            "-outfile",
+            output.as_str(),
+            from.as_str(),
+        ]);
+        Ok(Some(("jpegtran", cmd)))
+    } else {
+        Ok(None)
+    }
+}
+
+/// Conservative lossless svg minification: removes comments and indentation.
+/// Files with text, scripts or styles are left as they are since whitespace can matter there.
+fn minify_svg(svg: &str) -> String {
+    const KEEP: [&str; 5] = ["<text", "<script", "<style", "xml:space", "<![CDATA["];
+    if KEEP.iter().any(|k| svg.contains(k)) {
+        return svg.to_string();
+    }
+
+    let mut stripped = String::with_capacity(svg.len());
+    let mut rest = svg;
+    while let Some(start) = rest.find("<!--") {
+        stripped.push_str(&rest[..start]);
+        rest = match rest[start..].find("-->") {
+            Some(end) => &rest[start + end + 3..],
+            None => "",
+        };
+    }
+    stripped.push_str(rest);
+
+    let mut out = String::with_capacity(stripped.len());
+    for line in stripped.lines().map(str::trim).filter(|l| !l.is_empty()) {
+        if !out.is_empty() && !(out.ends_with('>') && line.starts_with('<')) {
+            out.push(' ');
+        }
+        out.push_str(line);
+    }
+    out
+}
@@ -0,0 +1,113 @@
+use std::io::Write;
+
+use camino::{Utf8Path, Utf8PathBuf};
+use flate2::{write::GzEncoder, Compression};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+use crate::{
+    config::Project,
+    ext::{
+        anyhow::{anyhow, Context, Result},
+        fs, PathExt,
+    },
+    logger::GRAY,
+    signal::Stage,
+};
+
+/// The version of the site manifest, increased on incompatible changes of its schema
+pub const MANIFEST_VERSION: u32 = 1;
+
+#[derive(Debug, Serialize)]
+struct Manifest {
+    version: u32,
+    files: Vec<ManifestFile>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ManifestFile {
+    /// relative to the site-root, with `/` separators
+    pub path: String,
+    pub size: u64,
+    pub sha256: String,
+    pub stage: Stage,
+    /// the gzip compressed size, for the site summary
+    #[serde(skip)]
+    pub gzip_size: Option<u64>,
+}
+
+/// Writes the site-manifest, listing the files of the site with their size, sha256 and the
+/// build stage that wrote them. The files are listed as they are after the build, so the
+/// removed ones don't appear. Returns the listed files, with their gzip size when asked
+/// for, as the file content is read anyway.
+pub async fn site_manifest(proj: &Project, gzip: bool) -> Result<Vec<ManifestFile>> {
+    let root = &proj.site.root_dir;
+    let mut files = Vec::new();
+    let mut dirs = vec![root.clone()];
+    while let Some(dir) = dirs.pop() {
+        let mut entries = fs::read_dir(&dir).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let path = Utf8PathBuf::from_path_buf(entry.path())
+                .map_err(|p| anyhow!("Non utf8 path {p:?}"))?;
+            // following the symlinks of the preserved asset symlinks
+            let metadata = fs::metadata(&path).await?;
+            if metadata.is_dir() {
+                if !entry.file_type().await?.is_symlink() {
+                    dirs.push(path);
+                }
+                continue;
+            }
+            if path == proj.site_manifest || fs::is_tmp_path(path.as_std_path()) {
+                continue;
+            }
+            let site = path.unbase(root)?;
+            let data = fs::read(&path).await?;
+            files.push(ManifestFile {
+                path: site
+                    .components()
+                    .map(|c| c.as_str())
+                    .collect::<Vec<_>>()
+                    .join("/"),
+                size: metadata.len(),
+                sha256: format!("{:x}", Sha256::digest(&data)),
+                stage: stage(proj, &site),
+                gzip_size: gzip.then(|| gzip_size(&data)),
+            });
+        }
+    }
+    files.sort_by(|a, b| a.path.cmp(&b.path));
+
+    let manifest = Manifest {
+        version: MANIFEST_VERSION,
+        files,
+    };
+    let json = serde_json::to_vec_pretty(&manifest).context("Could not serialize the manifest")?;
+    fs::write_atomic(&proj.site_manifest, json).await?;
+    log::debug!(
+        "Build wrote the manifest of {} site files {}",
+        manifest.files.len(),
+        GRAY.paint(proj.site_manifest.as_str())
+    );
+    Ok(manifest.files)
+}
+
+/// The size with the default compression, which is what the servers mostly use
+fn gzip_size(data: &[u8]) -> u64 {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    match encoder.write_all(data).and_then(|_| encoder.finish()) {
+        Ok(compressed) => compressed.len() as u64,
+        Err(_) => data.len() as u64,
+    }
+}
+
+fn stage(proj: &Project, site: &Utf8Path) -> Stage {
+    if *site == proj.style.site_file.site {
+        Stage::Style
+    } else if site.starts_with(&proj.site.pkg_dir)
+        || (proj.bin.is_none() && site.as_str() == "index.html")
+    {
+        Stage::Front
+    } else {
+        Stage::Assets
+    }
+}
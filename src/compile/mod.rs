@@ -1,16 +1,32 @@
 #[cfg(test)]
 mod tests;
 
+mod asset_index;
 mod assets;
 mod change;
+mod fingerprint;
 mod front;
+mod hash_files;
+mod image;
+mod manifest;
 mod sass;
 mod server;
+mod stats;
 mod style;
+mod summary;
 mod tailwind;
+mod timings;
+mod transform;
 
-pub use assets::assets;
-pub use change::{Change, ChangeSet};
-pub use front::{front, front_cargo_process};
-pub use server::{server, server_cargo_process};
+pub use assets::{assets, csr_index, MAX_SYMLINK_DEPTH};
+pub use change::{Change, ChangeSet, ChangedFile};
+pub use fingerprint::{assets_inputs, front_inputs, style_inputs, Fingerprints};
+pub use front::{bindgen, build_cargo_front_cmd, front, front_cargo, front_cargo_process};
+pub use hash_files::hash_files;
+pub use manifest::{site_manifest, ManifestFile};
+pub use server::{build_cargo_lib_ssr_cmd, build_cargo_server_cmd, server, server_cargo_process};
+pub use stats::{RebuildRecorder, WatchStats};
 pub use style::style;
+pub use summary::{site_summary, SiteSummary};
+pub use tailwind::DEFAULT_TAILWIND_CONFIG;
+pub use timings::{BuildTimings, Step};
@@ -1,35 +1,50 @@
 use crate::{
     ext::{
         anyhow::{Context, Result},
-        sync::{wait_piped_interruptible, CommandResult, OutputExt},
+        sync::{retry_failures, wait_piped_interruptible, CommandResult, OutputExt},
     },
-    logger::GRAY,
+    logger::{self, GRAY},
     signal::{Interrupt, Outcome},
 };
 use tokio::process::Command;
 
-use crate::{ext::Exe, service::site::SourcedSiteFile};
+use super::Step;
+use crate::{config::Project, ext::Exe, service::site::SourcedSiteFile};
 
-pub async fn compile_sass(style_file: &SourcedSiteFile, optimise: bool) -> Result<Outcome<String>> {
+/// Compiles the style file, without source map in release
+pub async fn compile_sass(style_file: &SourcedSiteFile, proj: &Project) -> Result<Outcome<String>> {
     let mut args = vec![style_file.source.as_str()];
-    optimise.then(|| args.push("--no-source-map"));
+    proj.release.then(|| args.push("--no-source-map"));
 
-    let exe = Exe::Sass.get().await.dot()?;
-
-    let mut cmd = Command::new(exe);
-    cmd.args(&args);
+    let exe = Exe::Sass.get(&proj.tool_versions).await.dot()?;
 
     log::trace!(
         "Style running {}",
         GRAY.paint(format!("sass {}", args.join(" ")))
     );
 
-    match wait_piped_interruptible("Tailwind", cmd, Interrupt::subscribe_any()).await? {
-        CommandResult::Success(output) => Ok(Outcome::Success(output.stdout())),
+    let step = Step::start(proj, "sass");
+    let timeout = proj.command_timeouts.get("sass");
+    let res = retry_failures(
+        "Sass",
+        proj.tool_retries,
+        Interrupt::subscribe_build(),
+        || {
+            let mut cmd = Command::new(&exe);
+            cmd.args(&args);
+            wait_piped_interruptible("Sass", cmd, Interrupt::subscribe_build(), timeout)
+        },
+    )
+    .await?;
+    match res {
+        CommandResult::Success(output) => {
+            step.finished(|elapsed| log::info!("Sass finished in {elapsed:.1?}"));
+            Ok(Outcome::Success(output.stdout()))
+        }
         CommandResult::Interrupted => Ok(Outcome::Stopped),
         CommandResult::Failure(output) => {
             log::warn!("Tailwind failed with:");
-            println!("{}", output.stderr());
+            logger::print_captured("sass", &output.stderr(), true);
             Ok(Outcome::Failed)
         }
     }
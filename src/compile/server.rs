@@ -1,12 +1,14 @@
 use std::sync::Arc;
 
-use super::ChangeSet;
+use super::{ChangeSet, Step};
 use crate::{
     config::Project,
     ext::anyhow::{Context, Result},
-    ext::sync::{wait_interruptible, CommandResult},
-    logger::GRAY,
-    signal::{Interrupt, Outcome, Product},
+    ext::sync::{
+        capture_errors, new_process_group, pipe_cargo_stderr, wait_interruptible, CommandResult,
+    },
+    logger::{self, GRAY},
+    signal::{Interrupt, Outcome, Product, ReloadSignal},
 };
 use tokio::{
     process::{Child, Command},
@@ -21,20 +23,28 @@ pub async fn server(
     let changes = changes.clone();
 
     tokio::spawn(async move {
+        let Some(bin) = &proj.bin else {
+            return Ok(Outcome::Success(Product::None));
+        };
         if !changes.need_server_build() {
             return Ok(Outcome::Success(Product::None));
         }
 
-        let (envs, line, process) = server_cargo_process("build", &proj)?;
+        let step = Step::start(&proj, "server");
+        let (envs, line, mut process) = server_cargo_process("build", &[], &proj)?;
+        let errors = capture_errors(&mut process);
 
-        match wait_interruptible("Cargo", process, Interrupt::subscribe_any()).await? {
+        let timeout = proj.command_timeouts.get("cargo");
+        match wait_interruptible("Cargo", process, Interrupt::subscribe_build(), timeout).await? {
             CommandResult::Success(_) => {
                 log::debug!("Cargo envs: {}", GRAY.paint(envs));
-                log::info!("Cargo finished {}", GRAY.paint(line));
+                step.finished(|elapsed| {
+                    log::info!("Cargo finished in {elapsed:.1?} {}", GRAY.paint(line))
+                });
 
                 let changed = proj
                     .site
-                    .did_external_file_change(&proj.bin.exe_file)
+                    .did_external_file_change(&bin.exe_file)
                     .await
                     .dot()?;
                 if changed {
@@ -46,43 +56,119 @@ pub async fn server(
                 }
             }
             CommandResult::Interrupted => Ok(Outcome::Stopped),
-            CommandResult::Failure(_) => Ok(Outcome::Failed),
+            CommandResult::Failure(_) => {
+                ReloadSignal::add_build_errors(errors.await.unwrap_or_default());
+                Ok(Outcome::Failed)
+            }
         }
     })
 }
 
-pub fn server_cargo_process(cmd: &str, proj: &Project) -> Result<(String, String, Child)> {
+pub fn server_cargo_process(
+    cmd: &str,
+    targets: &[&str],
+    proj: &Project,
+) -> Result<(String, String, Child)> {
     let mut command = Command::new("cargo");
-    let (envs, line) = build_cargo_server_cmd(cmd, proj, &mut command);
+    let (envs, line) = build_cargo_server_cmd(cmd, targets, proj, &mut command);
+    // killed with the rustc processes on interrupt
+    new_process_group(&mut command);
+    // the errors are shown in the browser
+    if proj.captures_build_errors() {
+        pipe_cargo_stderr(&mut command);
+    }
     Ok((envs, line, command.spawn()?))
 }
 
+/// The targets are the cargo target selection, like `--bins` or `--doc`.
+/// When building, the bin-target is selected, or the bin-example
 pub fn build_cargo_server_cmd(
     cmd: &str,
+    targets: &[&str],
     proj: &Project,
     command: &mut Command,
 ) -> (String, String) {
-    let mut args = vec![
-        cmd.to_string(),
-        format!("--package={}", proj.bin.name.as_str()),
-    ];
-    if cmd != "test" {
-        args.push(format!("--bin={}", proj.bin.target))
+    let bin = proj
+        .bin
+        .as_ref()
+        .expect("building the server of a project without bin");
+    // the cmd can be a sub command, like "nextest run"
+    let mut args: Vec<String> = cmd.split(' ').map(str::to_string).collect();
+    args.push(format!("--package={}", bin.name.as_str()));
+    if cmd != "test" && cmd != "nextest run" {
+        if bin.example {
+            args.push(format!("--example={}", bin.target))
+        } else {
+            args.push(format!("--bin={}", bin.target))
+        }
+    }
+    args.extend(targets.iter().map(|t| t.to_string()));
+    // apart from the front, see build_cargo_front_cmd
+    args.push("--target-dir=target/server".to_string());
+    if let Some(triple) = &bin.target_triple {
+        args.push(format!("--target={triple}"));
     }
+
+    if !bin.default_features {
+        args.push("--no-default-features".to_string());
+    }
+
+    if !bin.features.is_empty() {
+        args.push(format!("--features={}", bin.features.join(",")));
+    }
+
+    bin.profile.add_to_args(&mut args);
+    // the same profile for all the builds of target/server, whose deps are shared
+    args.extend(bin.profile_config_args());
+    logger::add_cargo_color(&mut args);
+
+    let envs = proj.to_envs();
+
+    let envs_str = envs
+        .iter()
+        .map(|(name, val)| format!("{name}={val}"))
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    command.args(&args).envs(envs);
+    let line = format!("cargo {}", args.join(" "));
+    (envs_str, line)
+}
+
+/// The lib package built with the bin features, like when it is compiled into the server.
+/// Uses the server's target dir so that the dependencies are shared with the server build.
+pub fn build_cargo_lib_ssr_cmd(
+    cmd: &str,
+    targets: &[&str],
+    proj: &Project,
+    command: &mut Command,
+) -> (String, String) {
+    let bin = proj
+        .bin
+        .as_ref()
+        .expect("building the ssr lib of a project without bin");
+    // the cmd can be a sub command, like "nextest run"
+    let mut args: Vec<String> = cmd.split(' ').map(str::to_string).collect();
+    args.push(format!("--package={}", proj.lib.name.as_str()));
+    args.extend(targets.iter().map(|t| t.to_string()));
+    // apart from the front, see build_cargo_front_cmd
     args.push("--target-dir=target/server".to_string());
-    if let Some(triple) = &proj.bin.target_triple {
+    if let Some(triple) = &bin.target_triple {
         args.push(format!("--target={triple}"));
     }
 
-    if !proj.bin.default_features {
+    if !bin.default_features {
         args.push("--no-default-features".to_string());
     }
 
-    if !proj.bin.features.is_empty() {
-        args.push(format!("--features={}", proj.bin.features.join(",")));
+    if !bin.features.is_empty() {
+        args.push(format!("--features={}", bin.features.join(",")));
     }
 
-    proj.bin.profile.add_to_args(&mut args);
+    bin.profile.add_to_args(&mut args);
+    // the same profile for all the builds of target/server, whose deps are shared
+    args.extend(bin.profile_config_args());
+    logger::add_cargo_color(&mut args);
 
     let envs = proj.to_envs();
 
@@ -0,0 +1,261 @@
+use std::{
+    fs::OpenOptions,
+    io::Write,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use camino::Utf8PathBuf;
+use serde::Serialize;
+
+use crate::signal::{Outcome, Product, Stage, StageOutcome};
+
+lazy_static::lazy_static! {
+  /// set by `watch --stats`, nothing is recorded without it
+  static ref ENABLED: AtomicBool = AtomicBool::new(false);
+  static ref STATS: Mutex<WatchStats> = Mutex::new(WatchStats::default());
+}
+
+/// The rebuilds of `watch --stats`, for the summary printed by the `s` key and on exit
+#[derive(Debug, Default)]
+pub struct WatchStats {
+    rebuilds: Vec<Rebuild>,
+    /// the paths changed since the last rebuild started
+    paths: Vec<Utf8PathBuf>,
+    /// where each rebuild is appended as a JSON line
+    jsonl: Option<Utf8PathBuf>,
+}
+
+/// A rebuild of the watch mode, as appended to the JSON lines file
+#[derive(Debug, Clone, Serialize)]
+pub struct Rebuild {
+    /// the unix time of the start of the rebuild, in seconds
+    pub time: u64,
+    /// the changed paths that triggered it, empty for the `r` key
+    pub paths: Vec<Utf8PathBuf>,
+    pub stages: Vec<StageTime>,
+    pub duration_ms: u64,
+    /// built, failed or interrupted
+    pub outcome: &'static str,
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct StageTime {
+    pub stage: Stage,
+    pub outcome: StageOutcome,
+    /// from the start of the rebuild, as the stages run together
+    pub duration_ms: u64,
+}
+
+impl StageTime {
+    /// The stages that were unchanged didn't need to run
+    fn ran(&self) -> bool {
+        matches!(self.outcome, StageOutcome::Built | StageOutcome::Failed)
+    }
+}
+
+impl WatchStats {
+    pub fn enable(jsonl: Option<Utf8PathBuf>) {
+        STATS.lock().unwrap().jsonl = jsonl;
+        ENABLED.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_enabled() -> bool {
+        ENABLED.load(Ordering::Relaxed)
+    }
+
+    /// The changed paths of the watcher, for the next rebuild
+    pub fn add_paths(paths: impl IntoIterator<Item = Utf8PathBuf>) {
+        if !Self::is_enabled() {
+            return;
+        }
+        let mut stats = STATS.lock().unwrap();
+        for path in paths {
+            if !stats.paths.contains(&path) {
+                stats.paths.push(path);
+            }
+        }
+    }
+
+    /// None unless the stats are enabled
+    pub fn rebuild_started() -> Option<RebuildRecorder> {
+        if !Self::is_enabled() {
+            return None;
+        }
+        let paths = std::mem::take(&mut STATS.lock().unwrap().paths);
+        Some(RebuildRecorder {
+            started: Instant::now(),
+            time: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+            paths,
+            stages: Mutex::new(Vec::new()),
+        })
+    }
+
+    /// Logs the summary table of the rebuilds so far
+    pub fn log_summary() {
+        if !Self::is_enabled() {
+            log::info!("Stats are only recorded with watch --stats");
+            return;
+        }
+        let stats = STATS.lock().unwrap();
+        log::info!("Stats {}", summary(&stats.rebuilds));
+    }
+
+    fn push(rebuild: Rebuild) {
+        let mut stats = STATS.lock().unwrap();
+        if let Some(file) = &stats.jsonl {
+            if let Err(e) = append_line(file, &rebuild) {
+                log::warn!("Stats could not append to {file}: {e}");
+            }
+        }
+        stats.rebuilds.push(rebuild);
+    }
+}
+
+/// Times the stages of a rebuild, which finish in any order
+pub struct RebuildRecorder {
+    started: Instant,
+    time: u64,
+    paths: Vec<Utf8PathBuf>,
+    stages: Mutex<Vec<StageTime>>,
+}
+
+impl RebuildRecorder {
+    pub fn stage_finished(&self, stage: Stage, outcome: &Outcome<Product>) {
+        self.stages.lock().unwrap().push(StageTime {
+            stage,
+            outcome: outcome.into(),
+            duration_ms: millis(self.started.elapsed()),
+        });
+    }
+
+    /// The paths of an interrupted rebuild are kept for the one that restarts it
+    pub fn finished(self, outcome: &'static str) {
+        if outcome == "interrupted" {
+            WatchStats::add_paths(self.paths.iter().cloned());
+        }
+        WatchStats::push(Rebuild {
+            time: self.time,
+            paths: self.paths,
+            stages: self.stages.into_inner().unwrap(),
+            duration_ms: millis(self.started.elapsed()),
+            outcome,
+        });
+    }
+}
+
+fn millis(duration: Duration) -> u64 {
+    duration.as_millis().try_into().unwrap_or(u64::MAX)
+}
+
+fn append_line(file: &Utf8PathBuf, rebuild: &Rebuild) -> std::io::Result<()> {
+    if let Some(dir) = file.parent().filter(|dir| !dir.as_str().is_empty()) {
+        std::fs::create_dir_all(dir)?;
+    }
+    let mut line = serde_json::to_string(rebuild)?;
+    line.push('\n');
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(file)?
+        .write_all(line.as_bytes())
+}
+
+/// The table of the rebuild times, with the ones of each stage when it ran, like
+///
+/// ```text
+/// 12 rebuilds, 1 failed, 2 interrupted
+///            runs     mean      p50      p95      max
+///   total      10     3.2s     2.9s     6.1s     7.0s
+///   front       8     2.4s     2.2s     4.8s     5.0s
+/// slowest stage: front
+/// ```
+///
+/// The interrupted rebuilds are only counted, their times being those of a part of the build.
+pub(crate) fn summary(rebuilds: &[Rebuild]) -> String {
+    let failed = rebuilds.iter().filter(|r| r.outcome == "failed").count();
+    let interrupted = rebuilds
+        .iter()
+        .filter(|r| r.outcome == "interrupted")
+        .count();
+    let mut out = format!(
+        "{} rebuilds, {failed} failed, {interrupted} interrupted",
+        rebuilds.len()
+    );
+    let completed: Vec<&Rebuild> = rebuilds
+        .iter()
+        .filter(|r| r.outcome != "interrupted")
+        .collect();
+    if completed.is_empty() {
+        return out;
+    }
+
+    out.push_str(&format!(
+        "\n{:8} {:>6} {:>8} {:>8} {:>8} {:>8}",
+        "", "runs", "mean", "p50", "p95", "max"
+    ));
+    let totals = completed.iter().map(|r| r.duration_ms).collect();
+    out.push_str(&row("total", totals));
+
+    let mut slowest: Option<(&str, u64)> = None;
+    for stage in [Stage::Front, Stage::Assets, Stage::Style, Stage::Server] {
+        let times: Vec<u64> = completed
+            .iter()
+            .flat_map(|r| &r.stages)
+            .filter(|s| s.ran() && s.stage == stage)
+            .map(|s| s.duration_ms)
+            .collect();
+        if times.is_empty() {
+            continue;
+        }
+        let name = stage_name(stage);
+        let mean = times.iter().sum::<u64>() / times.len() as u64;
+        if slowest.map_or(true, |(_, slowest)| mean > slowest) {
+            slowest = Some((name, mean));
+        }
+        out.push_str(&row(name, times));
+    }
+    if let Some((name, _)) = slowest {
+        out.push_str(&format!("\nslowest stage: {name}"));
+    }
+    out
+}
+
+fn row(name: &str, mut times: Vec<u64>) -> String {
+    times.sort_unstable();
+    let mean = times.iter().sum::<u64>() / times.len() as u64;
+    let max = times[times.len() - 1];
+    format!(
+        "\n  {name:6} {:>6} {:>8} {:>8} {:>8} {:>8}",
+        times.len(),
+        format_ms(mean),
+        format_ms(percentile(&times, 50)),
+        format_ms(percentile(&times, 95)),
+        format_ms(max)
+    )
+}
+
+/// The nearest rank of the sorted times
+pub(crate) fn percentile(sorted: &[u64], percent: usize) -> u64 {
+    let rank = (sorted.len() * percent + 99) / 100;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+fn format_ms(ms: u64) -> String {
+    format!("{:.1?}", Duration::from_millis(ms))
+}
+
+fn stage_name(stage: Stage) -> &'static str {
+    match stage {
+        Stage::Front => "front",
+        Stage::Assets => "assets",
+        Stage::Style => "style",
+        Stage::Server => "server",
+    }
+}
@@ -46,7 +46,7 @@ fn build_sass(proj: &Arc<Project>) -> JoinHandle<Result<Outcome<String>>> {
             .await
             .dot()?;
         match style_file.source.extension() {
-            Some("sass") | Some("scss") => compile_sass(&style_file, proj.release)
+            Some("sass") | Some("scss") => compile_sass(&style_file, &proj)
                 .await
                 .context(format!("compile sass/scss: {}", &style_file)),
             Some("css") => Ok(Outcome::Success(
@@ -0,0 +1,132 @@
+use serde::Serialize;
+
+use super::manifest::ManifestFile;
+use crate::{
+    config::{MessageFormat, Project},
+    ext::{format_bytes, StrAdditions},
+    logger::GRAY,
+    signal::{StatusEvent, StatusSignal},
+};
+
+/// The number of largest files listed in the summary
+const LARGEST: usize = 5;
+
+/// The sizes of the site files by type, with the largest files
+#[derive(Debug, Clone, Serialize)]
+pub struct SiteSummary {
+    pub groups: Vec<SummaryGroup>,
+    pub largest: Vec<SummaryFile>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct SummaryGroup {
+    pub kind: &'static str,
+    pub files: usize,
+    pub size: u64,
+    pub gzip_size: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SummaryFile {
+    pub path: String,
+    pub size: u64,
+}
+
+impl SiteSummary {
+    pub fn new(files: &[ManifestFile]) -> Self {
+        let mut groups: Vec<SummaryGroup> = ["wasm", "js", "css", "assets"]
+            .into_iter()
+            .map(|kind| SummaryGroup {
+                kind,
+                files: 0,
+                size: 0,
+                gzip_size: 0,
+            })
+            .collect();
+        for file in files {
+            let group = &mut groups[group_of(&file.path)];
+            group.files += 1;
+            group.size += file.size;
+            group.gzip_size += file.gzip_size.unwrap_or(file.size);
+        }
+
+        let mut largest: Vec<&ManifestFile> = files.iter().collect();
+        largest.sort_by(|a, b| b.size.cmp(&a.size).then_with(|| a.path.cmp(&b.path)));
+        let largest = largest
+            .into_iter()
+            .take(LARGEST)
+            .map(|file| SummaryFile {
+                path: file.path.clone(),
+                size: file.size,
+            })
+            .collect();
+        Self { groups, largest }
+    }
+
+    /// The table of the groups with the total, and the largest files
+    pub fn table(&self) -> String {
+        let mut lines = vec![format!(
+            "{}{}{}{}",
+            "".pad_left_to(8),
+            "files".pad_left_to(7),
+            "size".pad_left_to(12),
+            "gzip".pad_left_to(12)
+        )];
+        let row = |kind: &str, files: usize, size: u64, gzip_size: u64| {
+            format!(
+                "{}{}{}{}",
+                kind.pad_left_to(8),
+                files.to_string().pad_left_to(7),
+                format_bytes(size).pad_left_to(12),
+                format_bytes(gzip_size).pad_left_to(12)
+            )
+        };
+        for group in &self.groups {
+            lines.push(row(group.kind, group.files, group.size, group.gzip_size));
+        }
+        lines.push(row(
+            "total",
+            self.groups.iter().map(|g| g.files).sum(),
+            self.groups.iter().map(|g| g.size).sum(),
+            self.groups.iter().map(|g| g.gzip_size).sum(),
+        ));
+        if !self.largest.is_empty() {
+            lines.push("largest:".to_string());
+            for file in &self.largest {
+                lines.push(format!(
+                    "{} {}",
+                    format_bytes(file.size).pad_left_to(19),
+                    file.path
+                ));
+            }
+        }
+        lines.join("\n")
+    }
+}
+
+/// The index of the group of the site file: wasm, js, css or assets
+fn group_of(path: &str) -> usize {
+    let ext = path.rsplit_once('.').map(|(_, ext)| ext.to_lowercase());
+    match ext.as_deref() {
+        Some("wasm") => 0,
+        Some("js" | "mjs") => 1,
+        Some("css") => 2,
+        _ => 3,
+    }
+}
+
+/// Prints the summary of the site files listed by the manifest, or sends it as a
+/// site-summary event with `--message-format json`
+pub fn site_summary(proj: &Project, files: &[ManifestFile]) {
+    let summary = SiteSummary::new(files);
+    if proj.message_format == MessageFormat::Json {
+        StatusSignal::send(StatusEvent::SiteSummary(summary));
+    } else {
+        log::info!(
+            "Site summary of {}:\n{}",
+            GRAY.paint(proj.site.root_dir.as_str()),
+            summary.table()
+        );
+    }
+}
@@ -1,29 +1,52 @@
 use anyhow::Result;
 use tokio::process::Command;
 
+use super::Step;
 use crate::{
     config::{Project, TailwindConfig},
     ext::{
         anyhow::Context,
+        exe::ToolVersions,
         fs,
-        sync::{wait_piped_interruptible, CommandResult, OutputExt},
+        sync::{retry_failures, wait_piped_interruptible, CommandResult, OutputExt},
         Exe,
     },
-    logger::GRAY,
+    logger::{self, GRAY},
     signal::{Interrupt, Outcome},
 };
 
-pub async fn compile_tailwind(
-    _proj: &Project,
-    tw_conf: &TailwindConfig,
-) -> Result<Outcome<String>> {
+pub async fn compile_tailwind(proj: &Project, tw_conf: &TailwindConfig) -> Result<Outcome<String>> {
     if !tw_conf.config_file.exists() {
         create_default_tailwind_config(tw_conf).await?;
     }
 
-    let (line, process) = tailwind_process("tailwind", tw_conf).await?;
-
-    match wait_piped_interruptible("Tailwind", process, Interrupt::subscribe_any()).await? {
+    let step = Step::start(proj, "tailwind");
+    let (line, process) = tailwind_process("tailwind", tw_conf, &proj.tool_versions).await?;
+    let mut first = Some(process);
+    let timeout = proj.command_timeouts.get("tailwind");
+    let res = retry_failures(
+        "Tailwind",
+        proj.tool_retries,
+        Interrupt::subscribe_build(),
+        || {
+            let process = first.take();
+            async move {
+                // a command runs once, the retries get a new one
+                let process = match process {
+                    Some(process) => process,
+                    None => {
+                        tailwind_process("tailwind", tw_conf, &proj.tool_versions)
+                            .await?
+                            .1
+                    }
+                };
+                wait_piped_interruptible("Tailwind", process, Interrupt::subscribe_build(), timeout)
+                    .await
+            }
+        },
+    )
+    .await?;
+    match res {
         CommandResult::Success(output) => {
             let done = output
                 .stderr()
@@ -33,28 +56,31 @@ pub async fn compile_tailwind(
                 .unwrap_or(false);
 
             if done {
-                log::info!("Tailwind finished {}", GRAY.paint(line));
+                step.finished(|elapsed| {
+                    log::info!("Tailwind finished in {elapsed:.1?} {}", GRAY.paint(line))
+                });
                 Ok(Outcome::Success(output.stdout()))
             } else {
-                log::warn!("Tailwind failed {}", GRAY.paint(line));
-                println!("{}\n{}", output.stdout(), output.stderr());
+                step.failed(|elapsed| {
+                    log::warn!("Tailwind failed in {elapsed:.1?} {}", GRAY.paint(line))
+                });
+                logger::print_captured("tailwind", &output.stdout(), false);
+                logger::print_captured("tailwind", &output.stderr(), true);
                 Ok(Outcome::Failed)
             }
         }
         CommandResult::Interrupted => Ok(Outcome::Stopped),
         CommandResult::Failure(output) => {
             log::warn!("Tailwind failed");
-            if output.has_stdout() {
-                println!("{}", output.stdout());
-            }
-            println!("{}", output.stderr());
+            logger::print_captured("tailwind", &output.stdout(), false);
+            logger::print_captured("tailwind", &output.stderr(), true);
             Ok(Outcome::Failed)
         }
     }
 }
 
-async fn create_default_tailwind_config(tw_conf: &TailwindConfig) -> Result<()> {
-    let contents = r##"/** @type {import('tailwindcss').Config} */
+/// The tailwind config generated when the tailwind-config-file is missing, and by `init`
+pub const DEFAULT_TAILWIND_CONFIG: &str = r##"/** @type {import('tailwindcss').Config} */
     module.exports = {
       content: {
         relative: true,
@@ -66,11 +92,17 @@ async fn create_default_tailwind_config(tw_conf: &TailwindConfig) -> Result<()>
       plugins: [],
     }
     "##;
-    fs::write(&tw_conf.config_file, contents).await
+
+async fn create_default_tailwind_config(tw_conf: &TailwindConfig) -> Result<()> {
+    fs::write(&tw_conf.config_file, DEFAULT_TAILWIND_CONFIG).await
 }
 
-pub async fn tailwind_process(cmd: &str, tw_conf: &TailwindConfig) -> Result<(String, Command)> {
-    let tailwind = Exe::Tailwind.get().await.dot()?;
+pub async fn tailwind_process(
+    cmd: &str,
+    tw_conf: &TailwindConfig,
+    versions: &ToolVersions,
+) -> Result<(String, Command)> {
+    let tailwind = Exe::Tailwind.get(versions).await.dot()?;
 
     let args: Vec<&str> = vec![
         "--input",
@@ -5,29 +5,16 @@ use crate::{
 use insta::assert_display_snapshot;
 use tokio::process::Command;
 
-use super::server::build_cargo_server_cmd;
+use super::server::{build_cargo_lib_ssr_cmd, build_cargo_server_cmd};
 
 fn release_opts() -> Opts {
     Opts {
         release: true,
-        hot_reload: false,
-        project: None,
-        verbose: 0,
-        features: Vec::new(),
-        bin_features: Vec::new(),
-        lib_features: Vec::new(),
+        ..Default::default()
     }
 }
 fn dev_opts() -> Opts {
-    Opts {
-        release: false,
-        hot_reload: false,
-        project: None,
-        verbose: 0,
-        features: Vec::new(),
-        bin_features: Vec::new(),
-        lib_features: Vec::new(),
-    }
+    Opts::default()
 }
 
 #[test]
@@ -36,13 +23,14 @@ fn test_project_dev() {
     let conf = Config::test_load(cli, "examples", "examples/project/Cargo.toml", true);
 
     let mut command = Command::new("cargo");
-    let (envs, cargo) = build_cargo_server_cmd("build", &conf.projects[0], &mut command);
+    let (envs, cargo) = build_cargo_server_cmd("build", &[], &conf.projects[0], &mut command);
 
     const ENV_REF: &str = "\
     LEPTOS_OUTPUT_NAME=example \
     LEPTOS_SITE_ROOT=target/site \
     LEPTOS_SITE_PKG_DIR=pkg \
     LEPTOS_SITE_ADDR=127.0.0.1:3000 \
+    LEPTOS_SITE_ADDRS=127.0.0.1:3000 \
     LEPTOS_RELOAD_PORT=3001 \
     LEPTOS_LIB_DIR=. \
     LEPTOS_BIN_DIR=. \
@@ -52,7 +40,8 @@ fn test_project_dev() {
     assert_display_snapshot!(cargo, @"cargo build --package=example --bin=example --target-dir=target/server --no-default-features --features=ssr");
 
     let mut command = Command::new("cargo");
-    let (_, cargo) = build_cargo_front_cmd("build", true, &conf.projects[0], &mut command);
+    let (_, cargo) =
+        build_cargo_front_cmd("build", &["--lib"], true, &conf.projects[0], &mut command);
 
     assert_display_snapshot!(cargo, @"cargo build --package=example --lib --target-dir=target/front --target=wasm32-unknown-unknown --no-default-features --features=hydrate");
 }
@@ -63,16 +52,28 @@ fn test_project_release() {
     let conf = Config::test_load(cli, "examples", "examples/project/Cargo.toml", true);
 
     let mut command = Command::new("cargo");
-    let (_, cargo) = build_cargo_server_cmd("build", &conf.projects[0], &mut command);
+    let (_, cargo) = build_cargo_server_cmd("build", &[], &conf.projects[0], &mut command);
 
     assert_display_snapshot!(cargo, @"cargo build --package=example --bin=example --target-dir=target/server --no-default-features --features=ssr --release");
 
     let mut command = Command::new("cargo");
-    let (_, cargo) = build_cargo_front_cmd("build", true, &conf.projects[0], &mut command);
+    let (_, cargo) =
+        build_cargo_front_cmd("build", &["--lib"], true, &conf.projects[0], &mut command);
 
     assert_display_snapshot!(cargo, @"cargo build --package=example --lib --target-dir=target/front --target=wasm32-unknown-unknown --no-default-features --features=hydrate --release");
 }
 
+#[test]
+fn test_project_lib_ssr() {
+    let cli = dev_opts();
+    let conf = Config::test_load(cli, "examples", "examples/project/Cargo.toml", true);
+
+    let mut command = Command::new("cargo");
+    let (_, cargo) = build_cargo_lib_ssr_cmd("test", &["--lib"], &conf.projects[0], &mut command);
+
+    assert_display_snapshot!(cargo, @"cargo test --package=example --lib --target-dir=target/server --no-default-features --features=ssr");
+}
+
 #[test]
 fn test_workspace_project1() {
     const ENV_REF: &str = if cfg!(windows) {
@@ -81,6 +82,7 @@ fn test_workspace_project1() {
     LEPTOS_SITE_ROOT=target/site/project1 \
     LEPTOS_SITE_PKG_DIR=pkg \
     LEPTOS_SITE_ADDR=127.0.0.1:3000 \
+    LEPTOS_SITE_ADDRS=127.0.0.1:3000 \
     LEPTOS_RELOAD_PORT=3001 \
     LEPTOS_LIB_DIR=project1\\front \
     LEPTOS_BIN_DIR=project1\\server \
@@ -91,6 +93,7 @@ fn test_workspace_project1() {
     LEPTOS_SITE_ROOT=target/site/project1 \
     LEPTOS_SITE_PKG_DIR=pkg \
     LEPTOS_SITE_ADDR=127.0.0.1:3000 \
+    LEPTOS_SITE_ADDRS=127.0.0.1:3000 \
     LEPTOS_RELOAD_PORT=3001 \
     LEPTOS_LIB_DIR=project1/front \
     LEPTOS_BIN_DIR=project1/server \
@@ -101,14 +104,15 @@ fn test_workspace_project1() {
     let conf = Config::test_load(cli, "examples", "examples/workspace/Cargo.toml", true);
 
     let mut command = Command::new("cargo");
-    let (envs, cargo) = build_cargo_server_cmd("build", &conf.projects[0], &mut command);
+    let (envs, cargo) = build_cargo_server_cmd("build", &[], &conf.projects[0], &mut command);
 
     assert_eq!(ENV_REF, envs);
 
     assert_display_snapshot!(cargo, @"cargo build --package=server-package --bin=server-package --target-dir=target/server --no-default-features");
 
     let mut command = Command::new("cargo");
-    let (envs, cargo) = build_cargo_front_cmd("build", true, &conf.projects[0], &mut command);
+    let (envs, cargo) =
+        build_cargo_front_cmd("build", &["--lib"], true, &conf.projects[0], &mut command);
 
     assert_eq!(ENV_REF, envs);
 
@@ -121,12 +125,443 @@ fn test_workspace_project2() {
     let conf = Config::test_load(cli, "examples", "examples/workspace/Cargo.toml", true);
 
     let mut command = Command::new("cargo");
-    let (_, cargo) = build_cargo_server_cmd("build", &conf.projects[1], &mut command);
+    let (_, cargo) = build_cargo_server_cmd("build", &[], &conf.projects[1], &mut command);
 
     assert_display_snapshot!(cargo, @"cargo build --package=project2 --bin=project2 --target-dir=target/server --no-default-features --features=ssr");
 
     let mut command = Command::new("cargo");
-    let (_, cargo) = build_cargo_front_cmd("build", true, &conf.projects[1], &mut command);
+    let (_, cargo) =
+        build_cargo_front_cmd("build", &["--lib"], true, &conf.projects[1], &mut command);
 
     assert_display_snapshot!(cargo, @"cargo build --package=project2 --lib --target-dir=target/front --target=wasm32-unknown-unknown --no-default-features --features=hydrate");
 }
+
+#[test]
+fn test_site_summary() {
+    use super::{manifest::ManifestFile, SiteSummary};
+    use crate::signal::{Stage, StatusEvent, StatusSignal};
+
+    let file = |path: &str, size: u64, gzip_size: u64| ManifestFile {
+        path: path.to_string(),
+        size,
+        sha256: String::new(),
+        stage: Stage::Assets,
+        gzip_size: Some(gzip_size),
+    };
+    let files = [
+        file("pkg/app.wasm", 3000, 1000),
+        file("pkg/app.js", 400, 100),
+        file("pkg/app.css", 200, 50),
+        file("favicon.ico", 100, 90),
+        file("img/a.png", 600, 600),
+        file("img/b.png", 500, 500),
+        file("img/c.png", 50, 50),
+    ];
+    let summary = SiteSummary::new(&files);
+    let groups: Vec<_> = summary
+        .groups
+        .iter()
+        .map(|g| (g.kind, g.files, g.size, g.gzip_size))
+        .collect();
+    assert_eq!(
+        groups,
+        [
+            ("wasm", 1, 3000, 1000),
+            ("js", 1, 400, 100),
+            ("css", 1, 200, 50),
+            ("assets", 4, 1250, 1240)
+        ]
+    );
+    let largest: Vec<_> = summary.largest.iter().map(|f| f.path.as_str()).collect();
+    assert_eq!(
+        largest,
+        [
+            "pkg/app.wasm",
+            "img/a.png",
+            "img/b.png",
+            "pkg/app.js",
+            "pkg/app.css"
+        ]
+    );
+
+    let message = StatusSignal::message(&StatusEvent::SiteSummary(summary));
+    assert!(message.starts_with(r#"{"version":1,"event":"site-summary","groups":[{"kind":"wasm","files":1,"size":3000,"gzip-size":1000}"#));
+}
+
+#[tokio::test]
+async fn test_hash_file() {
+    use super::hash_files::write_hash_file;
+    use crate::{
+        config::ProjectConfig,
+        service::site::{Site, SiteFile},
+    };
+    use camino::Utf8PathBuf;
+    use sha2::{Digest, Sha256};
+
+    let dir = temp_dir::TempDir::new().unwrap();
+    let root = Utf8PathBuf::from_path_buf(dir.path().to_path_buf()).unwrap();
+    let config: ProjectConfig =
+        serde_json::from_value(serde_json::json!({ "site-root": root.join("site") })).unwrap();
+    let site = Site::new(&config);
+    let wasm = SiteFile {
+        dest: root.join("site/pkg/app.wasm"),
+        site: Utf8PathBuf::from("pkg/app.wasm"),
+    };
+    let css = SiteFile {
+        dest: root.join("site/pkg/app.css"),
+        site: Utf8PathBuf::from("pkg/app.css"),
+    };
+    let files = [wasm.clone(), css];
+    let hash_file = root.join("server/hash.txt");
+    let line = |data: &[u8]| format!("pkg/app.wasm:sha256:{:x}\n", Sha256::digest(data));
+
+    // the missing css file has no line
+    std::fs::create_dir_all(root.join("site/pkg")).unwrap();
+    std::fs::write(&wasm.dest, b"optimized").unwrap();
+    assert!(write_hash_file(&site, &hash_file, &files).await.unwrap());
+    assert_eq!(
+        std::fs::read_to_string(&hash_file).unwrap(),
+        line(b"optimized")
+    );
+
+    // unchanged hashes don't rewrite the file
+    let modified = std::fs::metadata(&hash_file).unwrap().modified().unwrap();
+    assert!(!write_hash_file(&site, &hash_file, &files).await.unwrap());
+    assert_eq!(
+        std::fs::metadata(&hash_file).unwrap().modified().unwrap(),
+        modified
+    );
+
+    std::fs::write(&wasm.dest, b"optimized again").unwrap();
+    assert!(write_hash_file(&site, &hash_file, &files).await.unwrap());
+    assert_eq!(
+        std::fs::read_to_string(&hash_file).unwrap(),
+        line(b"optimized again")
+    );
+}
+
+#[test]
+fn test_fingerprint_hash_path() {
+    use std::hash::Hasher;
+
+    use super::fingerprint::hash_path;
+
+    let dir = temp_dir::TempDir::new().unwrap();
+    let hash = || {
+        let mut hasher = seahash::SeaHasher::new();
+        hash_path(&mut hasher, dir.path());
+        hasher.finish()
+    };
+    std::fs::write(dir.path().join("main.scss"), "a {}").unwrap();
+    let first = hash();
+    assert_eq!(first, hash());
+
+    // the temp files of the writes in progress are left out
+    std::fs::write(dir.path().join(".main.scss.1.tmp"), "").unwrap();
+    assert_eq!(first, hash());
+
+    std::fs::create_dir(dir.path().join("partials")).unwrap();
+    std::fs::write(dir.path().join("partials/_b.scss"), "b {}").unwrap();
+    let with_partial = hash();
+    assert_ne!(first, with_partial);
+
+    std::fs::write(dir.path().join("partials/_b.scss"), "b { c: d }").unwrap();
+    assert_ne!(with_partial, hash());
+}
+
+#[test]
+fn test_watch_stats_summary() {
+    use super::stats::{percentile, summary, Rebuild, StageTime};
+    use crate::signal::{Stage, StageOutcome};
+
+    assert_eq!(percentile(&[1, 2, 3, 4], 50), 2);
+    assert_eq!(percentile(&[1, 2, 3, 4], 95), 4);
+    assert_eq!(percentile(&[7], 50), 7);
+
+    let rebuild = |outcome, front, style| Rebuild {
+        time: 0,
+        paths: vec!["src/lib.rs".into()],
+        stages: vec![
+            StageTime {
+                stage: Stage::Front,
+                outcome: StageOutcome::Built,
+                duration_ms: front,
+            },
+            StageTime {
+                stage: Stage::Style,
+                outcome: StageOutcome::Unchanged,
+                duration_ms: style,
+            },
+        ],
+        duration_ms: front + 100,
+        outcome,
+    };
+    let rebuilds = [
+        rebuild("built", 1000, 1),
+        rebuild("failed", 3000, 1),
+        rebuild("interrupted", 200, 1),
+    ];
+    assert_display_snapshot!(summary(&rebuilds), @r###"
+    3 rebuilds, 1 failed, 1 interrupted
+               runs     mean      p50      p95      max
+      total       2     2.1s     1.1s     3.1s     3.1s
+      front       2     2.0s     1.0s     3.0s     3.0s
+    slowest stage: front
+    "###);
+}
+
+#[test]
+fn test_project_bin_example() {
+    let cli = dev_opts();
+    let mut conf = Config::test_load(cli, "examples", "examples/project/Cargo.toml", true);
+    let proj = std::sync::Arc::get_mut(&mut conf.projects[0]).unwrap();
+    let bin = proj.bin.as_mut().unwrap();
+    bin.target = "axum".to_string();
+    bin.example = true;
+
+    let mut command = Command::new("cargo");
+    let (_, cargo) = build_cargo_server_cmd("build", &[], &conf.projects[0], &mut command);
+
+    assert_display_snapshot!(cargo, @"cargo build --package=example --example=axum --target-dir=target/server --no-default-features --features=ssr");
+}
+
+#[test]
+fn test_project_bin_strip() {
+    use crate::{config::SplitDebuginfo, ext::PathBufExt};
+
+    let cli = release_opts();
+    let mut conf = Config::test_load(cli, "examples", "examples/project/Cargo.toml", true);
+    let proj = std::sync::Arc::get_mut(&mut conf.projects[0]).unwrap();
+    let bin = proj.bin.as_mut().unwrap();
+    bin.strip = true;
+    bin.split_debuginfo = Some(SplitDebuginfo::Packed);
+
+    let mut command = Command::new("cargo");
+    let (_, cargo) = build_cargo_server_cmd("build", &[], &conf.projects[0], &mut command);
+    assert_display_snapshot!(cargo, @r###"cargo build --package=example --bin=example --target-dir=target/server --no-default-features --features=ssr --release --config=profile.release.strip=true --config=profile.release.split-debuginfo="packed""###);
+
+    let bin = conf.projects[0].bin.as_ref().unwrap();
+    let debuginfo = bin.debuginfo_path().unwrap();
+    let expected = if cfg!(target_vendor = "apple") {
+        "target/server/release/example.dSYM"
+    } else if cfg!(windows) {
+        "target/server/release/example.pdb"
+    } else {
+        "target/server/release/example.dwp"
+    };
+    assert_eq!(debuginfo.test_string(), expected);
+}
+
+/// Loads a client-side-rendered package written to a temp dir, with the given
+/// `[package.metadata.leptos]` lines, whose site, assets and target dirs are in the temp dir
+fn assets_project(
+    root: &camino::Utf8Path,
+    cli: Opts,
+    metadata: &str,
+) -> std::sync::Arc<crate::config::Project> {
+    let manifest = format!(
+        r#"[package]
+name = "app"
+version = "0.1.0"
+edition = "2021"
+
+[lib]
+crate-type = ["cdylib", "rlib"]
+
+[package.metadata.leptos]
+csr = true
+site-root = '{root}/site'
+assets-dir = '{root}/assets'
+{metadata}
+"#
+    );
+    std::fs::create_dir_all(root.join("src")).unwrap();
+    std::fs::write(root.join("src/lib.rs"), "").unwrap();
+    std::fs::write(root.join("Cargo.toml"), manifest).unwrap();
+
+    let mut conf = Config::load(cli, root, &root.join("Cargo.toml"), false).unwrap();
+    let mut proj = conf.projects.remove(0);
+    // keeps the cache and the index out of the repo
+    std::sync::Arc::get_mut(&mut proj).unwrap().target_dir = root.join("target");
+    proj
+}
+
+async fn sync_assets(proj: &std::sync::Arc<crate::config::Project>) {
+    use super::{assets, ChangeSet};
+    use crate::signal::Outcome;
+
+    let outcome = assets(proj, &ChangeSet::default(), true)
+        .await
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(matches!(outcome, Outcome::Success(_)), "{outcome:?}");
+}
+
+fn temp_root() -> (temp_dir::TempDir, camino::Utf8PathBuf) {
+    let dir = temp_dir::TempDir::new().unwrap();
+    let root = camino::Utf8PathBuf::from_path_buf(dir.path().canonicalize().unwrap()).unwrap();
+    (dir, root)
+}
+
+fn write_file(path: camino::Utf8PathBuf, data: &str) {
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    std::fs::write(path, data).unwrap();
+}
+
+#[tokio::test]
+async fn test_assets_ignore() {
+    let (_dir, root) = temp_root();
+    write_file(root.join("assets/logo.svg"), "<svg/>");
+    write_file(root.join("assets/logo.psd"), "layers");
+    write_file(root.join("assets/originals/logo.png"), "png");
+    write_file(root.join("assets/img/a.png"), "png");
+    let proj = assets_project(
+        &root,
+        Opts::default(),
+        r#"assets-ignore = ["*.psd", "originals/**"]"#,
+    );
+
+    sync_assets(&proj).await;
+
+    let site = root.join("site");
+    assert!(site.join("logo.svg").exists());
+    assert!(site.join("img/a.png").exists());
+    assert!(!site.join("logo.psd").exists());
+    assert!(!site.join("originals/logo.png").exists());
+}
+
+#[cfg(target_family = "unix")]
+#[tokio::test]
+async fn test_assets_symlinks() {
+    use std::os::unix::fs::symlink;
+
+    let (_dir, root) = temp_root();
+    write_file(root.join("vendor/icons/a.svg"), "<svg/>");
+    write_file(root.join("vendor/logo.svg"), "<svg/>");
+    std::fs::create_dir_all(root.join("assets")).unwrap();
+    symlink(root.join("vendor/icons"), root.join("assets/icons")).unwrap();
+    symlink(root.join("vendor/logo.svg"), root.join("assets/logo.svg")).unwrap();
+    // a dangling link is skipped with a warning, without failing the sync
+    symlink(
+        root.join("vendor/missing.svg"),
+        root.join("assets/missing.svg"),
+    )
+    .unwrap();
+    let site = root.join("site");
+
+    // followed: the targets are copied
+    let proj = assets_project(&root, Opts::default(), "");
+    sync_assets(&proj).await;
+    for path in ["icons", "logo.svg"] {
+        assert!(!site.join(path).as_std_path().is_symlink(), "{path}");
+    }
+    assert_eq!(
+        std::fs::read_to_string(site.join("icons/a.svg")).unwrap(),
+        "<svg/>"
+    );
+    assert_eq!(
+        std::fs::read_to_string(site.join("logo.svg")).unwrap(),
+        "<svg/>"
+    );
+    assert!(!site.join("missing.svg").exists());
+
+    // preserved: the links are copied
+    let proj = assets_project(&root, Opts::default(), "assets-preserve-symlinks = true");
+    sync_assets(&proj).await;
+    for path in ["icons", "logo.svg"] {
+        assert!(site.join(path).as_std_path().is_symlink(), "{path}");
+    }
+    assert_eq!(
+        std::fs::read_link(site.join("logo.svg")).unwrap(),
+        root.join("vendor/logo.svg").into_std_path_buf()
+    );
+}
+
+#[tokio::test]
+async fn test_assets_index() {
+    let (_dir, root) = temp_root();
+    write_file(root.join("assets/style.css"), "a {}");
+    let site_file = root.join("site/style.css");
+
+    let proj = assets_project(&root, Opts::default(), "");
+    sync_assets(&proj).await;
+    assert_eq!(std::fs::read_to_string(&site_file).unwrap(), "a {}");
+    assert!(root.join("target/leptos/app/assets-index.json").exists());
+
+    // the source is unchanged according to the index, so the site file isn't compared
+    std::fs::write(&site_file, "tampered").unwrap();
+    sync_assets(&proj).await;
+    assert_eq!(std::fs::read_to_string(&site_file).unwrap(), "tampered");
+
+    // --assets-verify compares the content of the files
+    let cli = Opts {
+        assets_verify: true,
+        ..Default::default()
+    };
+    let proj = assets_project(&root, cli, "");
+    sync_assets(&proj).await;
+    assert_eq!(std::fs::read_to_string(&site_file).unwrap(), "a {}");
+}
+
+#[cfg(target_family = "unix")]
+#[tokio::test]
+async fn test_asset_transform_cache() {
+    use super::transform::transform;
+    use crate::signal::Outcome;
+
+    let (_dir, root) = temp_root();
+    let input = root.join("assets/notes.md");
+    write_file(input.clone(), "# notes");
+    let runs = root.join("runs");
+    let metadata = format!(
+        r#"[[package.metadata.leptos.asset-transform]]
+glob = "*.md"
+command = "sh -c 'echo run >> {runs} && cat \"$0\"' {{input}}"
+output-extension = "html""#
+    );
+    let proj = assets_project(&root, Opts::default(), &metadata);
+    let conf = &proj.assets.as_ref().unwrap().transforms[0];
+    let cache_dir = root.join("target/transforms");
+    let run = || async {
+        match transform(conf, &input, &cache_dir, None).await.unwrap() {
+            Outcome::Success(data) => String::from_utf8(data).unwrap(),
+            outcome => panic!("{outcome:?}"),
+        }
+    };
+    let run_count = || std::fs::read_to_string(&runs).unwrap().lines().count();
+
+    assert_eq!(run().await, "# notes");
+    assert_eq!(run_count(), 1);
+
+    // the same input is read from the cache
+    assert_eq!(run().await, "# notes");
+    assert_eq!(run_count(), 1);
+
+    std::fs::write(&input, "# more notes").unwrap();
+    assert_eq!(run().await, "# more notes");
+    assert_eq!(run_count(), 2);
+}
+
+#[cfg(target_family = "unix")]
+#[tokio::test]
+async fn test_asset_transform_failure_not_cached() {
+    use super::transform::transform;
+    use crate::signal::Outcome;
+
+    let (_dir, root) = temp_root();
+    let input = root.join("assets/notes.md");
+    write_file(input.clone(), "# notes");
+    let metadata = r#"[[package.metadata.leptos.asset-transform]]
+glob = "*.md"
+command = "sh -c 'echo partial > \"$0\" && exit 1' {output}"
+output-extension = "html""#;
+    let proj = assets_project(&root, Opts::default(), metadata);
+    let conf = &proj.assets.as_ref().unwrap().transforms[0];
+    let cache_dir = root.join("target/transforms");
+
+    let outcome = transform(conf, &input, &cache_dir, None).await.unwrap();
+    assert!(matches!(outcome, Outcome::Failed), "{outcome:?}");
+    // neither the output nor the temp file is left in the cache
+    assert_eq!(std::fs::read_dir(&cache_dir).unwrap().count(), 0);
+}
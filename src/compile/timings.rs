@@ -0,0 +1,87 @@
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    config::Project,
+    logger::{with_fields, LogFields},
+};
+
+lazy_static::lazy_static! {
+  /// the steps finished during the current build, for its roll-up
+  static ref STEPS: Mutex<Vec<(&'static str, Duration)>> = Mutex::new(Vec::new());
+}
+
+/// A timed step of the build, like the cargo build of the front or wasm-opt
+pub struct Step {
+    name: &'static str,
+    fields: LogFields,
+    started: Instant,
+}
+
+impl Step {
+    pub fn start(proj: &Project, name: &'static str) -> Self {
+        Self {
+            name,
+            fields: LogFields::project(proj).stage(name),
+            started: Instant::now(),
+        }
+    }
+
+    /// Logs the completion of the step with its elapsed time, which the JSON log lines have
+    /// as duration, and keeps the time for the roll-up of the build
+    pub fn finished(self, log: impl FnOnce(Duration)) {
+        let elapsed = self.started.elapsed();
+        STEPS.lock().unwrap().push((self.name, elapsed));
+        with_fields(self.fields.duration(elapsed), || log(elapsed));
+    }
+
+    /// Logs the failure of the step with its elapsed time, which isn't in the roll-up
+    pub fn failed(self, log: impl FnOnce(Duration)) {
+        let elapsed = self.started.elapsed();
+        let fields = self.fields.duration(elapsed).outcome("failed");
+        with_fields(fields, || log(elapsed));
+    }
+}
+
+/// The times of the steps of a build, from the start of the build
+pub struct BuildTimings {
+    started: Instant,
+}
+
+impl BuildTimings {
+    /// Forgets the steps of the previous build
+    pub fn start() -> Self {
+        STEPS.lock().unwrap().clear();
+        Self {
+            started: Instant::now(),
+        }
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    /// Logs the time of the build with the ones of its steps, like
+    /// `Build finished in 14.2s (front 8.4s • wasm-opt 3.1s • server 2.2s • sass 0.5s)`
+    pub fn finished(self, proj: &Project) {
+        let elapsed = self.started.elapsed();
+        let steps = std::mem::take(&mut *STEPS.lock().unwrap());
+        let mut fields = LogFields::project(proj).outcome("built").duration(elapsed);
+        for (name, duration) in &steps {
+            fields = fields.step(*name, *duration);
+        }
+        let steps = steps
+            .iter()
+            .map(|(name, duration)| format!("{name} {duration:.1?}"))
+            .collect::<Vec<_>>();
+        with_fields(fields, || {
+            if steps.is_empty() {
+                log::info!("Build finished in {elapsed:.1?}")
+            } else {
+                log::info!("Build finished in {elapsed:.1?} ({})", steps.join(" • "))
+            }
+        });
+    }
+}
@@ -0,0 +1,98 @@
+use std::time::Duration;
+
+use camino::Utf8Path;
+use tokio::process::Command;
+
+use crate::{
+    config::AssetTransform,
+    ext::{
+        anyhow::{anyhow, Result},
+        fs,
+        sync::{wait_piped_interruptible, CommandResult, OutputExt},
+    },
+    logger::{self, GRAY},
+    signal::{Interrupt, Outcome},
+};
+
+/// Runs the asset-transform command on the input file and returns the transformed content.
+/// The result is cached by the hash of the input content and the command.
+pub async fn transform(
+    transform: &AssetTransform,
+    input: &Utf8Path,
+    cache_dir: &Utf8Path,
+    timeout: Option<Duration>,
+) -> Result<Outcome<Vec<u8>>> {
+    let data = fs::read(input).await?;
+    let hash = seahash::hash(&[data.as_slice(), transform.command.as_bytes()].concat());
+    let output = cache_dir.join(format!("{hash:016x}.{}", transform.output_extension));
+
+    if output.exists() {
+        log::trace!(
+            "Transform using cached {} for {input}",
+            GRAY.paint(output.as_str())
+        );
+        return Ok(Outcome::Success(fs::read(&output).await?));
+    }
+    fs::create_dir_all(cache_dir).await?;
+
+    // the command writes to a temp file which is moved into the cache when it succeeds, so
+    // that an interrupted or failed run doesn't leave a partial output that is a cache hit.
+    // It keeps the extension, for the tools that pick the output format by it
+    let partial = cache_dir.join(format!(
+        ".{hash:016x}.{}.tmp.{}",
+        std::process::id(),
+        transform.output_extension
+    ));
+    let uses_output = transform.command.contains("{output}");
+    // each placeholder stays in its arg, also with the spaces of the paths
+    let mut parts = transform.args.iter().map(|part| {
+        part.replace("{input}", input.as_str())
+            .replace("{output}", partial.as_str())
+    });
+    let exe = parts
+        .next()
+        .ok_or_else(|| anyhow!("Invalid asset-transform command {:?}", transform.command))?;
+
+    // wait_piped_interruptible runs it in its own process group, so that an interrupt also
+    // stops the processes that it started
+    let mut cmd = Command::new(&exe);
+    cmd.args(parts);
+
+    let line = transform
+        .command
+        .replace("{input}", input.as_str())
+        .replace("{output}", partial.as_str());
+    log::debug!("Transform running {}", GRAY.paint(&line));
+
+    match wait_piped_interruptible("Transform", cmd, Interrupt::subscribe_build(), timeout).await? {
+        CommandResult::Success(out) => {
+            if uses_output {
+                fs::rename(&partial, &output).await?;
+            } else {
+                fs::write_atomic(&output, &out.stdout).await?;
+            }
+            Ok(Outcome::Success(fs::read(&output).await?))
+        }
+        CommandResult::Interrupted => {
+            remove_partial(&partial).await?;
+            Ok(Outcome::Stopped)
+        }
+        CommandResult::Failure(out) => {
+            remove_partial(&partial).await?;
+            log::warn!(
+                "Transform failed for {input} (asset-transform {:?}) {}",
+                transform.glob,
+                GRAY.paint(&line)
+            );
+            logger::print_captured("asset-transform", &out.stderr(), true);
+            Ok(Outcome::Failed)
+        }
+    }
+}
+
+async fn remove_partial(partial: &Utf8Path) -> Result<()> {
+    if partial.exists() {
+        fs::remove_file(partial).await?;
+    }
+    Ok(())
+}
@@ -1,25 +1,122 @@
-use camino::Utf8PathBuf;
+use camino::{Utf8Path, Utf8PathBuf};
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use serde::Deserialize;
 
-use crate::ext::PathBufExt;
+use crate::ext::{
+    anyhow::{ensure, Context, Result},
+    split_args, PathBufExt, PathExt,
+};
 
 use super::ProjectConfig;
 
 pub struct AssetsConfig {
     pub dir: Utf8PathBuf,
+    /// gitignore-style patterns, relative to the assets dir, for files that are never copied
+    pub ignore: Gitignore,
+    /// copy symlinks as links instead of copying the content of their targets
+    pub preserve_symlinks: bool,
+    /// losslessly optimize png, jpg and svg files in release builds
+    pub optimize_images: bool,
+    pub transforms: Vec<AssetTransform>,
+}
+
+/// A `[[package.metadata.leptos.asset-transform]]` entry
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct AssetTransformConfig {
+    /// gitignore-style glob, relative to the assets dir, of the files to transform
+    pub glob: String,
+    /// command template with the {input} and {output} placeholders, split into its args
+    /// like a shell does with its quotes. The command's stdout is used as output when there
+    /// is no {output}
+    pub command: String,
+    /// extension of the transformed files
+    pub output_extension: String,
+}
+
+pub struct AssetTransform {
+    pub glob: String,
+    matcher: Gitignore,
+    pub command: String,
+    /// the exe and the args of the command, with the placeholders
+    pub args: Vec<String>,
+    pub output_extension: String,
 }
 
 impl AssetsConfig {
-    pub fn resolve(config: &ProjectConfig) -> Option<Self> {
+    pub fn resolve(config: &ProjectConfig) -> Result<Option<Self>> {
         let Some(assets_dir) = &config
             .assets_dir else {
-                return None;
+                return Ok(None);
             };
 
-        Some(Self {
-            // relative to the configuration file
-            dir: config.config_dir.join(assets_dir),
+        // relative to the configuration file
+        let dir = config.config_dir.join(assets_dir);
+
+        let mut builder = GitignoreBuilder::new(&dir);
+        for pattern in &config.assets_ignore {
+            builder
+                .add_line(None, pattern)
+                .context(format!("Invalid assets-ignore pattern {pattern:?}"))?;
+        }
+        let ignore = builder
+            .build()
+            .context("Could not build the assets-ignore patterns")?;
+
+        let mut transforms = Vec::new();
+        for transform in &config.asset_transform {
+            let mut builder = GitignoreBuilder::new(&dir);
+            builder
+                .add_line(None, &transform.glob)
+                .context(format!("Invalid asset-transform glob {:?}", transform.glob))?;
+            let args = split_args(&transform.command).context("Invalid asset-transform command")?;
+            ensure!(
+                !args.is_empty(),
+                "The asset-transform command of {:?} is empty",
+                transform.glob
+            );
+            transforms.push(AssetTransform {
+                glob: transform.glob.clone(),
+                matcher: builder.build().dot()?,
+                command: transform.command.clone(),
+                args,
+                output_extension: transform.output_extension.clone(),
+            });
+        }
+
+        Ok(Some(Self {
+            dir,
+            ignore,
+            preserve_symlinks: config.assets_preserve_symlinks,
+            optimize_images: config.assets_optimize_images,
+            transforms,
+        }))
+    }
+
+    /// Returns the first asset-transform which glob matches the file
+    pub fn transform_for(&self, path: &Utf8Path) -> Option<&AssetTransform> {
+        let rel = path.unbase(&self.dir).ok()?;
+        self.transforms.iter().find(|t| {
+            t.matcher
+                .matched_path_or_any_parents(&rel, false)
+                .is_ignore()
         })
     }
+
+    /// Returns the assets-ignore pattern that excludes the path, if any.
+    /// The path is relative to the working dir and expected to be inside the assets dir.
+    pub fn ignored_by(&self, path: &Utf8Path, is_dir: bool) -> Option<&str> {
+        let Ok(rel) = path.unbase(&self.dir) else {
+            return None
+        };
+        if rel == "." {
+            return None;
+        }
+        match self.ignore.matched_path_or_any_parents(&rel, is_dir) {
+            ignore::Match::Ignore(glob) => Some(glob.original()),
+            _ => None,
+        }
+    }
 }
 
 impl std::fmt::Debug for AssetsConfig {
@@ -1,5 +1,8 @@
+use std::fmt;
+
 use camino::Utf8PathBuf;
-use cargo_metadata::{Metadata, Target};
+use cargo_metadata::{Metadata, Package, Target};
+use serde::Deserialize;
 
 use crate::{
     config::Opts,
@@ -17,12 +20,38 @@ pub struct BinPackage {
     pub rel_dir: Utf8PathBuf,
     pub exe_file: Utf8PathBuf,
     pub target: String,
+    /// the target is an example of the package, built with `--example`
+    pub example: bool,
     pub features: Vec<String>,
     pub default_features: bool,
-    /// all source paths, including path dependencies'
+    /// all source paths, the path dependencies' then the package's own src dir
     pub src_paths: Vec<Utf8PathBuf>,
+    /// the src dirs of the path dependencies, which `src_paths` starts with
+    pub dependency_dirs: Vec<Utf8PathBuf>,
     pub profile: Profile,
     pub target_triple: Option<String>,
+    /// the strip and split-debuginfo of the release profiles
+    pub strip: bool,
+    pub split_debuginfo: Option<SplitDebuginfo>,
+}
+
+/// The split-debuginfo of the cargo profiles
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SplitDebuginfo {
+    Off,
+    Packed,
+    Unpacked,
+}
+
+impl fmt::Display for SplitDebuginfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Off => write!(f, "off"),
+            Self::Packed => write!(f, "packed"),
+            Self::Unpacked => write!(f, "unpacked"),
+        }
+    }
 }
 
 impl BinPackage {
@@ -47,7 +76,7 @@ impl BinPackage {
         let packages = metadata.workspace_packages();
         let package = packages
             .iter()
-            .find(|p| p.name == name && p.has_bin_target())
+            .find(|p| p.name == name && (p.has_bin_target() || config.bin_example.is_some()))
             .ok_or_else(|| anyhow!(r#"Could not find the project bin-package "{name}""#,))?;
 
         let package = (*package).clone();
@@ -58,16 +87,29 @@ impl BinPackage {
             .filter(|t| t.is_bin())
             .collect::<Vec<&Target>>();
 
-        let target: Target = if !&config.bin_target.is_empty() {
+        let target: Target = if let Some(example) = &config.bin_example {
+            if !config.bin_target.is_empty() {
+                bail!("Set either bin-target or bin-example for member {name}, not both");
+            }
+            package
+                .targets
+                .iter()
+                .find(|t| t.is_example() && &t.name == example)
+                .ok_or_else(|| example_not_found(example, &package))?
+                .clone()
+        } else if !&config.bin_target.is_empty() {
             targets
                 .into_iter()
                 .find(|t| t.name == config.bin_target)
-                .ok_or_else(|| target_not_found(config.bin_target.as_str()))?
+                .ok_or_else(|| target_not_found(config.bin_target.as_str(), &package))?
                 .clone()
         } else if targets.len() == 1 {
             targets[0].clone()
         } else if targets.is_empty() {
-            bail!("No bin targets found for member {name}");
+            bail!(
+                "No bin targets found for member {name}. {}",
+                available_targets(&package)
+            );
         } else {
             return Err(many_targets_found(&name));
         };
@@ -90,28 +132,89 @@ impl BinPackage {
             if let Some(triple) = &config.bin_target_triple {
                 file = file.join(triple)
             };
-            file.join(profile.to_string())
-                .join(&name)
-                .with_extension(file_ext)
+            file = file.join(profile.to_string());
+            // cargo puts the examples in their own dir, named after the example
+            let file = if config.bin_example.is_some() {
+                file.join("examples").join(&target.name)
+            } else {
+                file.join(&name)
+            };
+            file.with_extension(file_ext)
         };
 
-        let mut src_paths = metadata.src_path_dependencies(&package.id);
+        let dependency_dirs = metadata.src_path_dependencies(&package.id);
+        let mut src_paths = dependency_dirs.clone();
         if rel_dir == "." {
             src_paths.push("src".into());
         } else {
             src_paths.push(rel_dir.join("src"));
         }
+        if config.bin_example.is_some() {
+            if rel_dir == "." {
+                src_paths.push("examples".into());
+            } else {
+                src_paths.push(rel_dir.join("examples"));
+            }
+        }
         Ok(Self {
             name,
             abs_dir,
             rel_dir,
             exe_file,
             target: target.name.to_string(),
+            example: config.bin_example.is_some(),
             features,
             default_features: config.bin_default_features,
             src_paths,
+            dependency_dirs,
             profile,
             target_triple: config.bin_target_triple.clone(),
+            strip: config.bin_strip,
+            split_debuginfo: config.bin_split_debuginfo,
+        })
+    }
+
+    /// The `--config` args of bin-strip and bin-split-debuginfo, for the release and the named
+    /// profiles. Set on the profile, cargo only links the exe again when its inputs changed,
+    /// unlike a strip of the exe after the build.
+    pub fn profile_config_args(&self) -> Vec<String> {
+        let profile = match &self.profile {
+            Profile::Debug => return Vec::new(),
+            Profile::Release => "release",
+            Profile::Named(name) => name.as_str(),
+        };
+        let mut args = Vec::new();
+        if self.strip {
+            args.push(format!("--config=profile.{profile}.strip=true"));
+        }
+        if let Some(split) = self.split_debuginfo {
+            args.push(format!(
+                "--config=profile.{profile}.split-debuginfo=\"{split}\""
+            ));
+        }
+        args
+    }
+
+    /// The debug info that the packed split-debuginfo writes beside the exe: the .dwp file
+    /// on linux, the .dSYM dir on macos and the .pdb file on windows
+    pub fn debuginfo_path(&self) -> Option<Utf8PathBuf> {
+        if self.split_debuginfo != Some(SplitDebuginfo::Packed)
+            || matches!(self.profile, Profile::Debug)
+        {
+            return None;
+        }
+        let target = self.target_triple.as_deref();
+        let (apple, windows) = match target {
+            Some(triple) => (triple.contains("apple"), triple.contains("windows")),
+            None => (cfg!(target_vendor = "apple"), cfg!(target_os = "windows")),
+        };
+        let exe = &self.exe_file;
+        Some(if apple {
+            Utf8PathBuf::from(format!("{exe}.dSYM"))
+        } else if windows {
+            exe.with_extension("pdb")
+        } else {
+            Utf8PathBuf::from(format!("{exe}.dwp"))
         })
     }
 }
@@ -144,8 +247,38 @@ fn many_targets_found(pkg: &str) -> Error {
         r#"Several bin targets found for member "{pkg}", please specify which one to use with: [[workspace.metadata.leptos]] bin-target = "name""#
     )
 }
-fn target_not_found(target: &str) -> Error {
+fn target_not_found(target: &str, package: &Package) -> Error {
+    anyhow!(
+        r#"Could not find the target specified: [[workspace.metadata.leptos]] bin-target = "{target}". {}"#,
+        available_targets(package)
+    )
+}
+fn example_not_found(example: &str, package: &Package) -> Error {
     anyhow!(
-        r#"Could not find the target specified: [[workspace.metadata.leptos]] bin-target = "{target}""#,
+        r#"Could not find the example specified: [[workspace.metadata.leptos]] bin-example = "{example}". {}"#,
+        available_targets(package)
+    )
+}
+
+/// The bin and example targets of the package, that the server can be built from
+fn available_targets(package: &Package) -> String {
+    let names = |is: fn(&Target) -> bool| {
+        let names: Vec<_> = package
+            .targets
+            .iter()
+            .filter(|t| is(t))
+            .map(|t| t.name.as_str())
+            .collect();
+        if names.is_empty() {
+            "none".to_string()
+        } else {
+            names.join(", ")
+        }
+    };
+    format!(
+        "The package {} has the bin targets: {}, and the example targets: {}",
+        package.name,
+        names(Target::is_bin),
+        names(Target::is_example)
     )
 }
@@ -1,8 +1,12 @@
-use crate::command::NewCommand;
+#[cfg(feature = "cli")]
+use crate::command::{CleanCommand, InitCommand, NewCommand, ToolsCommand};
 use camino::Utf8PathBuf;
+#[cfg(feature = "cli")]
 use clap::{Parser, Subcommand, ValueEnum};
+use std::{net::SocketAddr, str::FromStr};
 
-#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "cli", derive(ValueEnum))]
 pub enum Log {
     /// WASM build (wasm, wasm-opt, walrus)
     Wasm,
@@ -10,40 +14,446 @@ pub enum Log {
     Server,
 }
 
-#[derive(Debug, Clone, Parser, PartialEq, Default)]
+/// When the logs and the output of the commands are colored, as `--color`
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(ValueEnum))]
+pub enum ColorMode {
+    /// When stderr is a terminal, unless NO_COLOR is set, or when CLICOLOR_FORCE is set
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+/// How the logs are printed, as `--log-format`
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(ValueEnum))]
+pub enum LogFormat {
+    /// The colored lines, tagged by their first word
+    #[default]
+    Human,
+    /// One JSON object per line without the colors, also for the output of the commands, for
+    /// the log aggregators
+    Json,
+}
+
+/// What the watch mode does with the changes made during a build
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(ValueEnum))]
+pub enum BuildStrategy {
+    /// Stop the build and start a new one with all the changes
+    #[default]
+    Interrupt,
+    /// Finish the build, then build the changes made meanwhile at once
+    Queue,
+}
+
+/// How the build events are printed
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(ValueEnum))]
+pub enum MessageFormat {
+    /// Only the logs
+    #[default]
+    Human,
+    /// The events as JSON lines on stdout, as the status endpoint sends them
+    Json,
+}
+
+/// What `bundle` writes, as `--format`
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(ValueEnum))]
+pub enum BundleFormat {
+    /// A directory, synced like the --out-dir of build
+    #[default]
+    Dir,
+    /// A gzipped tarball
+    Tar,
+    Zip,
+}
+
+/// Where the watch mode serves the build events, as `--status-addr`
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum StatusAddr {
+    Off,
+    Addr(SocketAddr),
+}
+
+impl FromStr for StatusAddr {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "off" => Ok(Self::Off),
+            addr => addr
+                .parse()
+                .map(Self::Addr)
+                .map_err(|e| format!("expected an address like 127.0.0.1:3002 or off: {e}")),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "cli", derive(Parser))]
 pub struct Opts {
     /// Build artifacts in release mode, with optimizations.
-    #[arg(short, long)]
+    #[cfg_attr(feature = "cli", arg(short, long))]
     pub release: bool,
 
     /// Turn on partial hot-reloading. Requires rust nightly [beta]
-    #[arg(long)]
+    #[cfg_attr(feature = "cli", arg(long))]
     pub hot_reload: bool,
 
     /// Which project to use, from a list of projects defined in a workspace
-    #[arg(short, long)]
+    #[cfg_attr(feature = "cli", arg(short, long))]
     pub project: Option<String>,
 
     /// The features to use when compiling all targets
-    #[arg(long)]
+    #[cfg_attr(feature = "cli", arg(long))]
     pub features: Vec<String>,
 
     /// The features to use when compiling the lib target
-    #[arg(long)]
+    #[cfg_attr(feature = "cli", arg(long))]
     pub lib_features: Vec<String>,
 
     /// The features to use when compiling the bin target
-    #[arg(long)]
+    #[cfg_attr(feature = "cli", arg(long))]
     pub bin_features: Vec<String>,
 
+    /// Compare the content of all assets with the site, instead of trusting the file size and modification time.
+    #[cfg_attr(feature = "cli", arg(long))]
+    pub assets_verify: bool,
+
+    /// Also print the build events (build started, stage finished, build finished with the
+    /// compiler errors, server restarts) as JSON lines on stdout.
+    #[cfg_attr(feature = "cli", arg(long, value_enum, default_value_t))]
+    pub message_format: MessageFormat,
+
+    /// Use the downloaded tools (sass, tailwind, wasm-opt, ...) that have no known checksum
+    /// without verifying them. A checksum mismatch still fails.
+    #[cfg_attr(feature = "cli", arg(long))]
+    pub insecure_skip_checksum: bool,
+
+    /// Always use the downloaded tools instead of the ones of the PATH, overriding the
+    /// prefer-system-tools config.
+    #[cfg_attr(feature = "cli", arg(long))]
+    pub no_system_tools: bool,
+
+    /// Never download the tools, only use the cached ones and the system tools, and fail at
+    /// once when one is missing. Also makes cargo work offline. Env: LEPTOS_OFFLINE
+    #[cfg_attr(feature = "cli", arg(long))]
+    pub offline: bool,
+
+    /// Print the sizes of the site files by type (wasm, JS, CSS and assets) with the largest
+    /// files at the end of the build, as done for the release builds anyway. It is a
+    /// site-summary event with `--message-format json`.
+    #[cfg_attr(feature = "cli", arg(long))]
+    pub summary: bool,
+
+    /// The seconds that the external commands, cargo included, may run before they are killed,
+    /// overriding the command-timeout-secs and the command-timeouts. 0 for no timeout.
+    #[cfg_attr(feature = "cli", arg(long, value_name = "SECS"))]
+    pub command_timeout: Option<u64>,
+
+    /// An argument for the server bin, like `--bin-args=--verbose`. Can be given several times.
+    #[cfg_attr(
+        feature = "cli",
+        arg(long, allow_hyphen_values = true, value_name = "ARG")
+    )]
+    pub bin_args: Vec<String>,
+
+    /// Fail when the leptos version of the project wasn't tested with this cargo-leptos,
+    /// instead of warning, like the require-compatible-version config.
+    #[cfg_attr(feature = "cli", arg(long))]
+    pub strict_version_check: bool,
+
     /// Verbosity (none: info, errors & warnings, -v: verbose, --vv: very verbose).
-    #[arg(short, action = clap::ArgAction::Count)]
+    #[cfg_attr(feature = "cli", arg(short, action = clap::ArgAction::Count))]
     pub verbose: u8,
+
+    /// The options of serve and watch, taken from their [`ServeOpts`]
+    #[cfg_attr(feature = "cli", arg(skip))]
+    pub watch: WatchOpts,
+}
+
+/// The options that only serve and watch take
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "cli", derive(Parser))]
+pub struct WatchOpts {
+    /// Serve the dev proxy, the reload websocket and the static site of a csr project over TLS, with the tls-cert and tls-key or a generated self-signed certificate.
+    #[cfg_attr(feature = "cli", arg(long))]
+    pub https: bool,
+
+    /// The milliseconds that the watcher waits after the last file change before rebuilding,
+    /// overriding the watch-debounce.
+    #[cfg_attr(feature = "cli", arg(long, value_name = "MS"))]
+    pub debounce: Option<u64>,
+
+    /// Also rebuild on changes to files that the .gitignore, .ignore and .leptosignore files exclude.
+    #[cfg_attr(feature = "cli", arg(long))]
+    pub no_gitignore: bool,
+
+    /// Watch the files by polling them every MS milliseconds (defaults to 1000), for file systems
+    /// without change notifications like Docker volumes and network file systems. Polling reads
+    /// the metadata of all the watched files at each interval, which costs CPU time in large trees.
+    #[cfg_attr(feature = "cli", arg(long, value_name = "MS", num_args = 0..=1, default_missing_value = "1000"))]
+    pub poll: Option<u64>,
+
+    /// Don't read the watch mode's keybindings (r: rebuild, o: open, c: clear, q: quit) from
+    /// the terminal.
+    #[cfg_attr(feature = "cli", arg(long))]
+    pub no_tui_keys: bool,
+
+    /// Show a desktop notification when a build of the watch mode fails, and when it succeeds
+    /// again, overriding the notifications config.
+    #[cfg_attr(feature = "cli", arg(long))]
+    pub notify: bool,
+
+    /// Pass the output of the server through as is, without the server-output-prefix and the
+    /// timestamps.
+    #[cfg_attr(feature = "cli", arg(long))]
+    pub raw_server_output: bool,
+
+    /// In watch mode, interrupt the build on changes or queue the changes until it is done,
+    /// so that frequent saves don't keep a build from finishing.
+    #[cfg_attr(feature = "cli", arg(long, value_enum, default_value_t))]
+    pub build_strategy: BuildStrategy,
+
+    /// Where watch serves the build events for editors, as versioned JSON messages: a /status
+    /// websocket, and the last status at /status.json. On the reload port by default, on the
+    /// given address, or not at all with "off".
+    #[cfg_attr(feature = "cli", arg(long, value_name = "ADDR|off"))]
+    pub status_addr: Option<StatusAddr>,
 }
 
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "cli", derive(Parser))]
+pub struct TestOpts {
+    /// Also run the lib's wasm tests in a headless browser, with wasm-bindgen-test-runner.
+    #[cfg_attr(feature = "cli", arg(long))]
+    pub wasm: bool,
+
+    /// Run the native tests with cargo-nextest instead of cargo test.
+    #[cfg_attr(feature = "cli", arg(long))]
+    pub nextest: bool,
+
+    /// Stop at the first failed test run instead of running the tests of the remaining projects.
+    #[cfg_attr(feature = "cli", arg(long))]
+    pub fail_fast: bool,
+
+    /// Also run the lib tests with the bin features (the ssr side) applied to the lib package.
+    #[cfg_attr(feature = "cli", arg(long))]
+    pub feature_matrix: bool,
+
+    /// Also run the doctests of the lib package.
+    #[cfg_attr(feature = "cli", arg(long, conflicts_with_all = ["no_doc", "doc_only"]))]
+    pub doc: bool,
+
+    /// Don't run any doctests.
+    #[cfg_attr(feature = "cli", arg(long, conflicts_with = "doc_only"))]
+    pub no_doc: bool,
+
+    /// Only run the doctests, of the lib package and, when it is the same package, the bin.
+    #[cfg_attr(feature = "cli", arg(long))]
+    pub doc_only: bool,
+
+    /// Rerun the tests when the sources change: the server tests on bin changes and the
+    /// front tests on lib changes. Runs the tests of the current project.
+    #[cfg_attr(feature = "cli", arg(long))]
+    pub watch: bool,
+
+    /// After the tests, also run the end2end suite of each project that has one: build,
+    /// serve and run the suite against the server, as `end-to-end` does.
+    #[cfg_attr(feature = "cli", arg(long, conflicts_with = "watch"))]
+    pub e2e: bool,
+
+    /// Only run the end2end suites of --e2e, without the cargo tests.
+    #[cfg_attr(feature = "cli", arg(long, conflicts_with = "watch"))]
+    pub e2e_only: bool,
+
+    /// The end2end suite of --e2e. Defaults to the only suite or the one named "default".
+    #[cfg_attr(feature = "cli", arg(long))]
+    pub e2e_suite: Option<String>,
+
+    /// The number of projects that are tested at the same time, with the output prefixed by
+    /// the project name. The projects share the target dirs, so mostly the test execution
+    /// runs in parallel while cargo's lock on the target dir serializes the builds.
+    #[cfg_attr(feature = "cli", arg(long, default_value_t = 1))]
+    pub jobs: usize,
+
+    /// Extra arguments for `cargo test`, given after `--`. Test names and test harness
+    /// flags (like --nocapture) are passed after cargo's own `--` separator.
+    #[cfg_attr(feature = "cli", arg(last = true))]
+    pub args: Vec<String>,
+
+    #[cfg_attr(feature = "cli", command(flatten))]
+    pub opts: Opts,
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "cli", derive(Parser))]
+pub struct FmtOpts {
+    /// Only check the formatting of both tools without writing the files, failing with the
+    /// list of the unformatted ones, as for CI.
+    #[cfg_attr(feature = "cli", arg(long))]
+    pub check: bool,
+
+    #[cfg_attr(feature = "cli", command(flatten))]
+    pub opts: Opts,
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "cli", derive(Parser))]
+pub struct DocOpts {
+    /// Open the docs in the browser when they are written, the lib's and the bin's.
+    #[cfg_attr(feature = "cli", arg(long))]
+    pub open: bool,
+
+    /// Don't document the dependencies.
+    #[cfg_attr(feature = "cli", arg(long))]
+    pub no_deps: bool,
+
+    #[cfg_attr(feature = "cli", command(flatten))]
+    pub opts: Opts,
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "cli", derive(Parser))]
+pub struct BuildOpts {
+    /// After a successful build, sync the site into the dir, creating it when needed and
+    /// removing the files that aren't in the site anymore. With several projects, each one
+    /// is exported to a subdir with its name.
+    #[cfg_attr(feature = "cli", arg(long, value_name = "DIR"))]
+    pub out_dir: Option<Utf8PathBuf>,
+
+    /// Also export the server exe into the --out-dir.
+    #[cfg_attr(feature = "cli", arg(long, requires = "out_dir"))]
+    pub include_exe: bool,
+
+    /// Run all the stages, ignoring the fingerprints of the last build. Without it, the
+    /// front, style and assets stages whose inputs are unchanged are skipped.
+    #[cfg_attr(feature = "cli", arg(long))]
+    pub force: bool,
+
+    #[cfg_attr(feature = "cli", command(flatten))]
+    pub opts: Opts,
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "cli", derive(Parser))]
+pub struct ServeOpts {
+    /// Open the browser at the path (defaults to /) once the site is up, after the first
+    /// successful build. Setting the LEPTOS_NO_OPEN env var disables it.
+    #[cfg_attr(feature = "cli", arg(long, value_name = "PATH", num_args = 0..=1, default_missing_value = "/"))]
+    pub open: Option<String>,
+
+    /// Run all the projects of the workspace, each in its own cargo-leptos process with the
+    /// output prefixed by the project name. The projects need distinct ports.
+    #[cfg_attr(feature = "cli", arg(long, conflicts_with = "project"))]
+    pub all_projects: bool,
+
+    /// Serve the artifacts of the last build, with the same --release, instead of building.
+    /// Fails when they are missing. Only for serve.
+    #[cfg_attr(feature = "cli", arg(long))]
+    pub no_build: bool,
+
+    /// Record the changed paths, the stages and the times of each rebuild, for a summary
+    /// with the mean, p50 and p95 times printed by the `s` key and on exit. Only for watch.
+    #[cfg_attr(feature = "cli", arg(long))]
+    pub stats: bool,
+
+    /// Also append each rebuild of --stats as a JSON line to the file (defaults to
+    /// target/.leptos-stats.jsonl).
+    #[cfg_attr(feature = "cli", arg(long, value_name = "FILE", requires = "stats", num_args = 0..=1, default_missing_value = "target/.leptos-stats.jsonl"))]
+    pub stats_jsonl: Option<Utf8PathBuf>,
+
+    #[cfg_attr(feature = "cli", command(flatten))]
+    pub watch: WatchOpts,
+
+    #[cfg_attr(feature = "cli", command(flatten))]
+    pub opts: Opts,
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "cli", derive(Parser))]
+pub struct RunOpts {
+    #[cfg_attr(feature = "cli", command(flatten))]
+    pub opts: Opts,
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "cli", derive(Parser))]
+pub struct BundleOpts {
+    /// Bundle the artifacts of the last `build --release` instead of building.
+    #[cfg_attr(feature = "cli", arg(long))]
+    pub no_build: bool,
+
+    /// A directory, a .tar.gz or a .zip archive.
+    #[cfg_attr(feature = "cli", arg(long, value_enum, default_value_t))]
+    pub format: BundleFormat,
+
+    /// Where the bundle is written. Defaults to target/bundle/<project>, with the .tar.gz or
+    /// .zip extension for the archives.
+    #[cfg_attr(feature = "cli", arg(long, value_name = "PATH"))]
+    pub output: Option<Utf8PathBuf>,
+
+    /// Leave the site manifest out of the site dir of the bundle.
+    #[cfg_attr(feature = "cli", arg(long))]
+    pub no_manifest: bool,
+
+    /// Also bundle the debug info of the server exe, split out of it with
+    /// bin-split-debuginfo = "packed".
+    #[cfg_attr(feature = "cli", arg(long))]
+    pub debuginfo: bool,
+
+    #[cfg_attr(feature = "cli", command(flatten))]
+    pub opts: Opts,
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "cli", derive(Parser))]
+pub struct EndToEndOpts {
+    /// The end2end suite to run. Defaults to the only suite or the one named "default".
+    #[cfg_attr(feature = "cli", arg(long))]
+    pub suite: Option<String>,
+
+    /// Don't build and serve, run the tests against an already running server.
+    #[cfg_attr(feature = "cli", arg(long))]
+    pub no_serve: bool,
+
+    /// The url of the server to test with --no-serve. Defaults to the site-addr.
+    #[cfg_attr(feature = "cli", arg(long, requires = "no_serve"))]
+    pub url: Option<String>,
+
+    /// Install the browsers of Playwright and their system deps before running a Playwright
+    /// suite, with `npx playwright install --with-deps` in the end2end-dir.
+    #[cfg_attr(feature = "cli", arg(long))]
+    pub install_deps: bool,
+
+    /// Extra arguments for the end2end command, given after `--`.
+    #[cfg_attr(feature = "cli", arg(last = true))]
+    pub args: Vec<String>,
+
+    #[cfg_attr(feature = "cli", command(flatten))]
+    pub opts: Opts,
+}
+
+#[cfg(feature = "cli")]
 #[derive(Debug, Parser)]
-#[clap(version)]
+#[clap(version, disable_version_flag = true, arg_required_else_help = true)]
 pub struct Cli {
+    /// Print the version. With -v, also the version of the compiled-in wasm-bindgen and the
+    /// versions and paths of the system and cached tools, which aren't downloaded.
+    #[arg(short = 'V', long)]
+    pub version: bool,
+
+    /// Print the --version as JSON, with the tools.
+    #[arg(long, requires = "version")]
+    pub json: bool,
+
+    /// More verbose --version, or the verbosity of the command as its -v.
+    #[arg(short, action = clap::ArgAction::Count)]
+    pub verbose: u8,
+
     /// Path to Cargo.toml.
     #[arg(long)]
     pub manifest_path: Option<Utf8PathBuf>,
@@ -52,34 +462,98 @@ pub struct Cli {
     #[arg(long)]
     pub log: Vec<Log>,
 
+    /// Print the logs as human readable lines, or as JSON lines with the timestamp, the level,
+    /// the target and the fields like the project, the stage and the duration.
+    #[arg(long, value_enum, default_value_t)]
+    pub log_format: LogFormat,
+
+    /// Prefix the log lines with the wall-clock time.
+    #[arg(long)]
+    pub log_timestamps: bool,
+
+    /// Color the logs and the output of cargo and the server: when stderr is a terminal with
+    /// auto, which follows the NO_COLOR and CLICOLOR_FORCE env vars.
+    #[arg(long, value_enum, default_value_t, global = true)]
+    pub color: ColorMode,
+
+    /// Also write the logs and the output of the commands to the file, without the colors.
+    /// The file is truncated at the start, unless --log-file-append.
+    #[arg(long, env = "LEPTOS_LOG_FILE", value_name = "PATH")]
+    pub log_file: Option<Utf8PathBuf>,
+
+    /// Append to the --log-file instead of truncating it.
+    #[arg(long)]
+    pub log_file_append: bool,
+
     #[command(subcommand)]
-    pub command: Commands,
+    pub command: Option<Commands>,
 }
 
+#[cfg(feature = "cli")]
 impl Cli {
     pub fn opts(&self) -> Option<Opts> {
-        use Commands::{Build, EndToEnd, New, Serve, Test, Watch};
-        match &self.command {
-            New(_) => None,
-            Build(opts) | Serve(opts) | Test(opts) | EndToEnd(opts) | Watch(opts) => {
-                Some(opts.clone())
-            }
+        use Commands::{
+            Build, Bundle, Clean, Doc, EndToEnd, Fmt, Init, New, Run, Serve, Test, Tools, Watch,
+        };
+        match self.command.as_ref()? {
+            New(_) | Init(_) | Tools(_) | Clean(_) => None,
+            Build(build) => Some(build.opts.clone()),
+            // a deployment is a release build
+            Bundle(bundle) => Some(Opts {
+                release: true,
+                ..bundle.opts.clone()
+            }),
+            Run(run) => Some(run.opts.clone()),
+            Doc(doc) => Some(doc.opts.clone()),
+            Fmt(fmt) => Some(fmt.opts.clone()),
+            Serve(serve) | Watch(serve) => Some(Opts {
+                watch: serve.watch.clone(),
+                ..serve.opts.clone()
+            }),
+            Test(test) => Some(test.opts.clone()),
+            EndToEnd(e2e) => Some(e2e.opts.clone()),
         }
     }
 }
 
+#[cfg(feature = "cli")]
 #[derive(Debug, Subcommand, PartialEq)]
 pub enum Commands {
     /// Build the server (feature ssr) and the client (wasm with feature hydrate).
-    Build(Opts),
+    Build(BuildOpts),
+    /// Build in release mode, then assemble the server exe, the site and a .env.production
+    /// with the env vars of the server into a directory or an archive for the deployment.
+    Bundle(BundleOpts),
     /// Run the cargo tests for app, client and server.
-    Test(Opts),
+    Test(TestOpts),
+    /// Document the lib for wasm with the lib-features and the bin with the bin-features, like
+    /// they are built.
+    Doc(DocOpts),
+    /// Format the packages of the projects with cargo fmt, then the view! macros of their
+    /// sources with leptosfmt. Both follow their config files, rustfmt.toml and leptosfmt.toml.
+    Fmt(FmtOpts),
     /// Start the server and end-2-end tests.
-    EndToEnd(Opts),
+    ///
+    /// The end2end-cmd is run with the env vars of the server and with BASE_URL (the url of
+    /// the server, e.g. http://127.0.0.1:3000), PORT (its port), LEPTOS_SITE_ADDR and
+    /// LEPTOS_SITE_ROOT (the absolute path to the site files).
+    EndToEnd(EndToEndOpts),
     /// Serve. Defaults to hydrate mode.
-    Serve(Opts),
+    Serve(ServeOpts),
     /// Serve and automatically reload when files change.
-    Watch(Opts),
+    Watch(ServeOpts),
+    /// Build and run the server once, without the watcher and the reload websocket, exiting
+    /// with the exit code of the server. Ctrl-c stops the server gracefully.
+    Run(RunOpts),
     /// WIP: Start wizard for creating a new project (using cargo-generate). Ask at Leptos discord before using.
     New(NewCommand),
+    /// Add the leptos config to an existing cargo package or workspace, with a style file and
+    /// an assets dir. Asks for the values that aren't given as flags.
+    Init(InitCommand),
+    /// List, update or clean the external tools (wasm-opt, sass, tailwindcss, ...) cached by
+    /// cargo-leptos.
+    Tools(ToolsCommand),
+    /// Remove the outputs of cargo-leptos: the target dirs of the front and the server builds
+    /// and the site-root, without the rest of the target dir.
+    Clean(CleanCommand),
 }
@@ -0,0 +1,72 @@
+use std::{collections::BTreeMap, time::Duration};
+
+use crate::ext::anyhow::{bail, Result};
+
+use super::{Opts, ProjectConfig};
+
+/// The commands that can have a timeout in the command-timeouts, the ones marked false having
+/// no timeout by default: the cargo builds and tests can take long, as end2end tests can.
+pub const TIMED_COMMANDS: &[(&str, bool)] = &[
+    ("cargo", false),
+    ("wasm-opt", true),
+    ("sass", true),
+    ("tailwind", true),
+    ("image", true),
+    ("asset-transform", true),
+    ("end2end", false),
+];
+
+/// How long the external commands may run before they are killed
+#[derive(Debug, Clone, Default)]
+pub struct CommandTimeouts {
+    /// the command-timeout-secs, for the commands without a timeout of their own
+    default: Option<Duration>,
+    /// the command-timeouts, by command
+    commands: BTreeMap<String, Option<Duration>>,
+    /// the --command-timeout, for all the commands
+    cli: Option<Option<Duration>>,
+}
+
+impl CommandTimeouts {
+    pub fn resolve(cli: &Opts, config: &ProjectConfig) -> Result<Self> {
+        if let Some(command) = config
+            .command_timeouts
+            .keys()
+            .find(|command| !TIMED_COMMANDS.iter().any(|(name, _)| name == command))
+        {
+            let names: Vec<&str> = TIMED_COMMANDS.iter().map(|(name, _)| *name).collect();
+            bail!(
+                "Unknown command {command:?} in the command-timeouts, expected one of {}",
+                names.join(", ")
+            );
+        }
+        Ok(Self {
+            default: secs(config.command_timeout_secs),
+            commands: config
+                .command_timeouts
+                .iter()
+                .map(|(command, timeout)| (command.clone(), secs(*timeout)))
+                .collect(),
+            cli: cli.command_timeout.map(secs),
+        })
+    }
+
+    /// The timeout of the command from [TIMED_COMMANDS], None when it may run without limit
+    pub fn get(&self, command: &str) -> Option<Duration> {
+        if let Some(timeout) = self.cli {
+            return timeout;
+        }
+        if let Some(timeout) = self.commands.get(command) {
+            return *timeout;
+        }
+        match TIMED_COMMANDS.iter().find(|(name, _)| *name == command) {
+            Some((_, true)) => self.default,
+            _ => None,
+        }
+    }
+}
+
+/// 0 is no timeout
+fn secs(secs: u64) -> Option<Duration> {
+    (secs > 0).then(|| Duration::from_secs(secs))
+}
@@ -1,28 +1,34 @@
-use super::ProjectConfig;
-use crate::ext::anyhow::Result;
+use super::{ProjectConfig, SiteAddrConfig};
+use crate::ext::{anyhow::Result, exe::TOOL_URL_ENVS};
 use camino::{Utf8Path, Utf8PathBuf};
 use std::{env, fs};
 
 pub fn load_dotenvs(directory: &Utf8Path) -> Result<Option<Vec<(String, String)>>> {
+    match find_dotenv(directory) {
+        Some(file) => Ok(Some(read_dotenv(&file)?)),
+        None => Ok(None),
+    }
+}
+
+/// The .env file of the directory or of the closest parent directory that has one
+pub fn find_dotenv(directory: &Utf8Path) -> Option<Utf8PathBuf> {
     let candidate = directory.join(".env");
 
     if let Ok(metadata) = fs::metadata(&candidate) {
         if metadata.is_file() {
-            let mut dotenvs = vec![];
-            for entry in dotenvy::from_path_iter(&candidate)? {
-                let (key, val) = entry?;
-                dotenvs.push((key, val));
-            }
-
-            return Ok(Some(dotenvs));
+            return Some(candidate);
         }
     }
+    find_dotenv(directory.parent()?)
+}
 
-    if let Some(parent) = directory.parent() {
-        load_dotenvs(parent)
-    } else {
-        Ok(None)
+pub fn read_dotenv(file: &Utf8Path) -> Result<Vec<(String, String)>> {
+    let mut dotenvs = vec![];
+    for entry in dotenvy::from_path_iter(file)? {
+        let (key, val) = entry?;
+        dotenvs.push((key, val));
     }
+    Ok(dotenvs)
 }
 
 pub fn overlay_env(conf: &mut ProjectConfig, dotenvs: Option<Vec<(String, String)>>) -> Result<()> {
@@ -35,18 +41,50 @@ pub fn overlay_env(conf: &mut ProjectConfig, dotenvs: Option<Vec<(String, String
 
 fn overlay(conf: &mut ProjectConfig, envs: impl Iterator<Item = (String, String)>) -> Result<()> {
     for (key, val) in envs {
+        if let Some((tool, _)) = TOOL_URL_ENVS.iter().find(|(_, env)| *env == key) {
+            conf.tool_urls.insert(tool.to_string(), val);
+            continue;
+        }
         match key.as_str() {
             "LEPTOS_OUTPUT_NAME" => conf.output_name = val,
             "LEPTOS_SITE_ROOT" => conf.site_root = Utf8PathBuf::from(val),
             "LEPTOS_SITE_PKG_DIR" => conf.site_pkg_dir = Utf8PathBuf::from(val),
+            "LEPTOS_SITE_BASE_PATH" => conf.site_base_path = val,
             "LEPTOS_STYLE_FILE" => conf.style_file = Some(Utf8PathBuf::from(val)),
             "LEPTOS_ASSETS_DIR" => conf.assets_dir = Some(Utf8PathBuf::from(val)),
-            "LEPTOS_SITE_ADDR" => conf.site_addr = val.parse()?,
+            "LEPTOS_SITE_ADDR" => conf.site_addr_config = SiteAddrConfig::One(val),
             "LEPTOS_RELOAD_PORT" => conf.reload_port = val.parse()?,
+            "LEPTOS_RELOAD_HEALTH_PATH" => conf.reload_health_path = Some(val),
+            "LEPTOS_RELOAD_HEALTH_TIMEOUT" => conf.reload_health_timeout = val.parse()?,
+            "LEPTOS_PROXY_ADDR" => conf.proxy_addr = Some(val.parse()?),
+            "LEPTOS_WATCH_DEBOUNCE" => conf.watch_debounce = val.parse()?,
+            "LEPTOS_SHUTDOWN_GRACE_PERIOD" => conf.shutdown_grace_period = val.parse()?,
+            "LEPTOS_WATCH_ERROR_OVERLAY" => conf.watch_error_overlay = val.parse()?,
+            "LEPTOS_WATCH_AUTO_RELOAD" => conf.watch_auto_reload = val.parse()?,
+            "LEPTOS_NOTIFICATIONS" => conf.notifications = val.parse()?,
+            "LEPTOS_SERVER_OUTPUT_PREFIX" => conf.server_output_prefix = val,
+            "LEPTOS_SERVER_OUTPUT_TIMESTAMPS" => conf.server_output_timestamps = val.parse()?,
+            "LEPTOS_REUSE_LISTENER" => conf.reuse_listener = val.parse()?,
             "LEPTOS_END2END_CMD" => conf.end2end_cmd = Some(val),
             "LEPTOS_END2END_DIR" => conf.end2end_dir = Some(Utf8PathBuf::from(val)),
+            "LEPTOS_END2END_HEALTH_PATH" => conf.end2end_health_path = Some(val),
+            "LEPTOS_END2END_WAIT_SECS" => conf.end2end_wait_secs = val.parse()?,
             "LEPTOS_BROWSERQUERY" => conf.browserquery = val,
             "LEPTOS_BIN_TARGET_TRIPLE" => conf.bin_target_triple = Some(val),
+            "LEPTOS_TOOL_DOWNLOAD_TOKEN" => conf.tool_download_token = Some(val),
+            "LEPTOS_PREFER_SYSTEM_TOOLS" => conf.prefer_system_tools = val.parse()?,
+            "LEPTOS_DOWNLOAD_PROXY" => conf.download_proxy = Some(val),
+            "LEPTOS_WASM_OPT_VERSION" => conf.wasm_opt_version = Some(val),
+            "LEPTOS_SASS_VERSION" => conf.sass_version = Some(val),
+            "LEPTOS_TAILWIND_VERSION" => conf.tailwind_version = Some(val),
+            "LEPTOS_CACHE_DIR" => conf.tool_cache_dir = Some(Utf8PathBuf::from(val)),
+            "LEPTOS_COPY_SERVER_EXE" => conf.copy_server_exe = val.parse()?,
+            "LEPTOS_SERVER_EXE_DIR" => conf.server_exe_dir = Some(Utf8PathBuf::from(val)),
+            "LEPTOS_SITE_MANIFEST" => conf.site_manifest = Utf8PathBuf::from(val),
+            "LEPTOS_HASH_FILES" => conf.hash_files = val.parse()?,
+            "LEPTOS_HASH_FILE_NAME" => conf.hash_file_name = Utf8PathBuf::from(val),
+            "LEPTOS_COMMAND_TIMEOUT_SECS" => conf.command_timeout_secs = val.parse()?,
+            "LEPTOS_TOOL_RETRIES" => conf.tool_retries = val.parse()?,
             _ if key.starts_with("LEPTOS_") => {
                 log::warn!("Env {key} is not used by cargo-leptos")
             }
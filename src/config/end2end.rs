@@ -1,32 +1,129 @@
-use camino::Utf8PathBuf;
+use std::collections::BTreeMap;
+use std::time::Duration;
 
-use crate::ext::PathBufExt;
+use camino::{Utf8Path, Utf8PathBuf};
+use itertools::Itertools;
+use serde::Deserialize;
+
+use crate::ext::{
+    anyhow::{anyhow, bail, Result},
+    PathBufExt,
+};
 
 use super::ProjectConfig;
 
+/// A `[package.metadata.leptos.end2end.<name>]` suite
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct End2EndSuiteConfig {
+    pub cmd: String,
+    /// defaults to the end2end-dir
+    pub dir: Option<Utf8PathBuf>,
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+}
+
 pub struct End2EndConfig {
+    /// sorted by name. The legacy end2end-cmd is the suite named "default"
+    pub suites: Vec<End2EndSuite>,
+    /// the .env vars and the end2end-env, for all suites
+    pub env: Vec<(String, String)>,
+    pub health_path: Option<String>,
+    /// how long to wait for the server to be ready before launching the tests
+    pub wait: Duration,
+}
+
+pub struct End2EndSuite {
+    pub name: String,
     pub cmd: String,
     pub dir: Utf8PathBuf,
+    pub env: Vec<(String, String)>,
 }
 
 impl End2EndConfig {
-    pub fn resolve(config: &ProjectConfig) -> Option<Self> {
-        let Some(cmd) = &config.end2end_cmd else {
-          return None
+    pub const DEFAULT_SUITE: &'static str = "default";
+
+    /// The dirs are relative to the package dir, the one of the bin package or of the lib
+    /// package of a csr project, relative to the workspace root
+    pub fn resolve(config: &ProjectConfig, package_dir: &Utf8Path) -> Result<Option<Self>> {
+        let resolve_dir = |dir: &Option<Utf8PathBuf>| match dir {
+            Some(dir) if package_dir == "." => dir.clone(),
+            Some(dir) => package_dir.join(dir),
+            None => package_dir.to_path_buf(),
         };
+        let default_dir = resolve_dir(&config.end2end_dir);
 
-        let dir = config.end2end_dir.to_owned().unwrap_or_default();
+        let mut suites = Vec::new();
+        if let Some(cmd) = &config.end2end_cmd {
+            if config.end2end.contains_key(Self::DEFAULT_SUITE) {
+                bail!("The end2end-cmd and the end2end.default suite cannot both be set");
+            }
+            suites.push(End2EndSuite {
+                name: Self::DEFAULT_SUITE.to_string(),
+                cmd: cmd.clone(),
+                dir: default_dir.clone(),
+                env: Vec::new(),
+            });
+        }
+        for (name, suite) in &config.end2end {
+            suites.push(End2EndSuite {
+                name: name.clone(),
+                cmd: suite.cmd.clone(),
+                dir: match &suite.dir {
+                    Some(_) => resolve_dir(&suite.dir),
+                    None => default_dir.clone(),
+                },
+                env: suite.env.clone().into_iter().collect(),
+            });
+        }
+        if suites.is_empty() {
+            return Ok(None);
+        }
+        suites.sort_by(|a, b| a.name.cmp(&b.name));
 
-        Some(Self {
-            cmd: cmd.clone(),
-            dir: dir.clone(),
-        })
+        let mut env = config.dotenvs.clone();
+        env.extend(config.end2end_env.clone());
+
+        Ok(Some(Self {
+            suites,
+            env,
+            health_path: config.end2end_health_path.clone(),
+            wait: Duration::from_secs(config.end2end_wait_secs),
+        }))
+    }
+
+    /// The suite with the given name, or when none is given the only one
+    /// or the one named "default"
+    pub fn suite(&self, name: Option<&str>) -> Result<&End2EndSuite> {
+        let names = self.suites.iter().map(|s| &s.name).join(", ");
+        match name {
+            Some(name) => self
+                .suites
+                .iter()
+                .find(|s| s.name == name)
+                .ok_or_else(|| anyhow!("No end2end suite named {name:?}. Available: {names}")),
+            None if self.suites.len() == 1 => Ok(&self.suites[0]),
+            None => self
+                .suites
+                .iter()
+                .find(|s| s.name == Self::DEFAULT_SUITE)
+                .ok_or_else(|| {
+                    anyhow!("Several end2end suites are defined, select one with --suite: {names}")
+                }),
+        }
     }
 }
 
 impl std::fmt::Debug for End2EndConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("").field("suites", &self.suites).finish()
+    }
+}
+
+impl std::fmt::Debug for End2EndSuite {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("")
+            .field("name", &self.name)
             .field("cmd", &self.cmd)
             .field("dir", &self.dir.test_string())
             .finish()
@@ -0,0 +1,126 @@
+use std::collections::HashSet;
+
+use cargo_metadata::{
+    semver::{Version, VersionReq},
+    Metadata,
+};
+use itertools::Itertools;
+
+use crate::ext::{
+    anyhow::{bail, Result},
+    ResolveExt,
+};
+
+/// The leptos versions that the releases of cargo-leptos were tested with, the newest last.
+/// A newer release of leptos gets a row when this release is tested with it.
+const COMPATIBILITY: &[Tested] = &[Tested {
+    leptos: ">=0.2.0, <0.6.0",
+    cargo_leptos: env!("CARGO_PKG_VERSION"),
+}];
+
+/// The crates whose version decides the env vars and the features that the project expects
+const CRATES: [&str; 2] = ["leptos", "leptos_config"];
+
+struct Tested {
+    leptos: &'static str,
+    cargo_leptos: &'static str,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Compatibility {
+    Tested,
+    /// with the cargo-leptos release tested with that version, None when there is none
+    Untested {
+        recommended: Option<&'static str>,
+    },
+}
+
+/// The compatibility of this cargo-leptos with the leptos version, whose pre-releases are
+/// taken as their release
+pub(crate) fn compatibility(leptos: &Version) -> Compatibility {
+    let release = Version::new(leptos.major, leptos.minor, leptos.patch);
+    let tested = COMPATIBILITY.iter().find(|tested| {
+        VersionReq::parse(tested.leptos)
+            .map(|req| req.matches(&release))
+            .unwrap_or_default()
+    });
+    match tested {
+        Some(tested) if tested.cargo_leptos == env!("CARGO_PKG_VERSION") => Compatibility::Tested,
+        Some(tested) => Compatibility::Untested {
+            recommended: Some(tested.cargo_leptos),
+        },
+        None => Compatibility::Untested { recommended: None },
+    }
+}
+
+/// Warns when the leptos crates that the lib package resolved to weren't tested with this
+/// cargo-leptos, or fails when strict. Only reads the cargo metadata: no network is needed.
+pub(crate) fn check(metadata: &Metadata, lib_package: &str, strict: bool) -> Result<()> {
+    if skip_env() {
+        return Ok(());
+    }
+    let (Some(resolve), Some(lib)) = (
+        &metadata.resolve,
+        metadata
+            .workspace_packages()
+            .into_iter()
+            .find(|package| package.name == lib_package),
+    ) else {
+        return Ok(());
+    };
+    let mut deps = HashSet::new();
+    resolve.deps_for(&lib.id, &mut deps);
+
+    let untested = metadata
+        .packages
+        .iter()
+        .filter(|package| CRATES.contains(&package.name.as_str()) && deps.contains(&package.id))
+        .filter_map(|package| match compatibility(&package.version) {
+            Compatibility::Tested => None,
+            Compatibility::Untested { recommended } => {
+                Some((package.name.as_str(), &package.version, recommended))
+            }
+        })
+        .sorted_by_key(|(name, ..)| *name)
+        .collect::<Vec<_>>();
+    let Some((_, _, recommended)) = untested.first() else {
+        return Ok(());
+    };
+    let versions = untested
+        .iter()
+        .map(|(name, version, _)| format!("{name} {version}"))
+        .join(", ");
+    let message = untested_message(lib_package, &versions, *recommended);
+    if strict {
+        bail!("{message}. Without require-compatible-version and --strict-version-check it is only a warning");
+    }
+    log::warn!("Config {message}");
+    Ok(())
+}
+
+pub(crate) fn untested_message(
+    lib_package: &str,
+    versions: &str,
+    recommended: Option<&str>,
+) -> String {
+    let tested = COMPATIBILITY
+        .iter()
+        .find(|tested| tested.cargo_leptos == env!("CARGO_PKG_VERSION"))
+        .map_or("none", |tested| tested.leptos);
+    let advice = match recommended {
+        Some(release) => format!("use cargo-leptos {release}, which was tested with it"),
+        None => "use a cargo-leptos release that supports it".to_string(),
+    };
+    format!(
+        "The {lib_package} package uses {versions}, which cargo-leptos {} wasn't tested with \
+        (tested: leptos {tested}). Mismatched versions give missing LEPTOS_* env vars and \
+        hydration errors: {advice}",
+        env!("CARGO_PKG_VERSION")
+    )
+}
+
+/// LEPTOS_SKIP_VERSION_CHECK=1 turns the check off
+fn skip_env() -> bool {
+    std::env::var("LEPTOS_SKIP_VERSION_CHECK")
+        .map_or(false, |val| matches!(val.trim(), "1" | "true"))
+}
@@ -1,13 +1,13 @@
 use crate::{
     config::Opts,
     ext::{
-        anyhow::{anyhow, Result},
-        MetadataExt, PathBufExt, PathExt,
+        anyhow::{anyhow, Error, Result},
+        MetadataExt, PackageExt, PathBufExt, PathExt,
     },
     service::site::{SiteFile, SourcedSiteFile},
 };
 use camino::Utf8PathBuf;
-use cargo_metadata::Metadata;
+use cargo_metadata::{Metadata, Package};
 
 use super::{project::ProjectDefinition, Profile, ProjectConfig};
 
@@ -21,7 +21,10 @@ pub struct LibPackage {
     pub features: Vec<String>,
     pub default_features: bool,
     pub output_name: String,
+    /// all source paths, the path dependencies' then the package's own src dir
     pub src_paths: Vec<Utf8PathBuf>,
+    /// the src dirs of the path dependencies, which `src_paths` starts with
+    pub dependency_dirs: Vec<Utf8PathBuf>,
     pub profile: Profile,
 }
 
@@ -44,6 +47,9 @@ impl LibPackage {
             .iter()
             .find(|p| p.name == *name)
             .ok_or_else(|| anyhow!(r#"Could not find the project lib-package "{name}""#,))?;
+        if package.cdylib_target().is_none() {
+            return Err(missing_cdylib(package));
+        }
 
         let mut features = if !cli.lib_features.is_empty() {
             cli.lib_features.clone()
@@ -86,7 +92,8 @@ impl LibPackage {
             SiteFile { dest, site }
         };
 
-        let mut src_deps = metadata.src_path_dependencies(&package.id);
+        let dependency_dirs = metadata.src_path_dependencies(&package.id);
+        let mut src_deps = dependency_dirs.clone();
         if rel_dir == "." {
             src_deps.push("src".into());
         } else {
@@ -102,6 +109,7 @@ impl LibPackage {
             default_features: config.lib_default_features,
             output_name,
             src_paths: src_deps,
+            dependency_dirs,
             profile,
         })
     }
@@ -130,3 +138,14 @@ impl std::fmt::Debug for LibPackage {
             .finish_non_exhaustive()
     }
 }
+
+/// Without a cdylib, cargo builds no wasm for wasm-bindgen, which then fails with an error that
+/// doesn't tell why
+fn missing_cdylib(package: &Package) -> Error {
+    anyhow!(
+        "The lib-package {} has no cdylib library target, which the wasm of the front is built from. \
+        Add to {}:\n\n[lib]\ncrate-type = [\"cdylib\", \"rlib\"]\n\nThe rlib is for the server, which uses the lib as a dependency.",
+        package.name,
+        package.manifest_path
+    )
+}
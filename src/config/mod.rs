@@ -4,19 +4,38 @@ mod tests;
 mod assets;
 mod bin_package;
 mod cli;
+mod command_timeout;
 mod dotenvs;
 mod end2end;
+mod leptos_version;
 mod lib_package;
 mod profile;
 mod project;
+mod proxy;
+mod site_addr;
+mod site_file_mode;
 mod style;
 mod tailwind;
+mod test_runner;
+mod tls;
+mod wasm_test;
+mod watch_files;
 
 use std::{fmt::Debug, sync::Arc};
 
-pub use self::cli::{Cli, Commands, Log, Opts};
+pub use self::assets::{AssetTransform, AssetsConfig};
+pub use self::bin_package::SplitDebuginfo;
+pub use self::cli::{
+    BuildOpts, BuildStrategy, BundleFormat, BundleOpts, ColorMode, DocOpts, EndToEndOpts, FmtOpts,
+    Log, LogFormat, MessageFormat, Opts, RunOpts, ServeOpts, StatusAddr, TestOpts, WatchOpts,
+};
+#[cfg(feature = "cli")]
+pub use self::cli::{Cli, Commands};
+pub use self::command_timeout::CommandTimeouts;
+pub use self::end2end::{End2EndConfig, End2EndSuite};
 use crate::ext::{
     anyhow::{Context, Result},
+    exe::ToolSettings,
     MetadataExt,
 };
 use anyhow::bail;
@@ -24,8 +43,15 @@ use camino::{Utf8Path, Utf8PathBuf};
 use cargo_metadata::Metadata;
 pub use profile::Profile;
 pub use project::{Project, ProjectConfig};
+pub use proxy::{ProxyConfig, ProxyRoute};
+pub use site_addr::SiteAddrConfig;
+pub use site_file_mode::SiteFileMode;
 pub use style::StyleConfig;
 pub use tailwind::TailwindConfig;
+pub use test_runner::TestRunner;
+pub use tls::TlsConfig;
+pub use wasm_test::{WasmTestBrowser, WasmTestConfig};
+pub use watch_files::{WatchAction, WatchFile};
 
 pub struct Config {
     /// absolute path to the working dir
@@ -86,6 +112,37 @@ impl Config {
         Self::load(cli, &cwd, &manifest_path, watch).unwrap()
     }
 
+    /// How the tools are found and downloaded, from the config of all the projects
+    pub fn tool_settings(&self) -> ToolSettings {
+        let projects = &self.projects;
+        ToolSettings {
+            prefer_system: projects.iter().all(|proj| proj.prefer_system_tools),
+            no_system: self.cli.no_system_tools,
+            system: projects
+                .iter()
+                .flat_map(|proj| proj.system_tools.clone())
+                .collect(),
+            checksums: projects
+                .iter()
+                .flat_map(|proj| proj.tool_checksums.clone())
+                .collect(),
+            skip_unknown: self.cli.insecure_skip_checksum,
+            urls: projects
+                .iter()
+                .flat_map(|proj| proj.tool_urls.clone())
+                .collect(),
+            token: projects
+                .iter()
+                .find_map(|proj| proj.tool_download_token.clone()),
+            proxy: projects.iter().find_map(|proj| proj.download_proxy.clone()),
+            offline: self.cli.offline || offline_env(),
+            cache_dir: projects
+                .iter()
+                .find_map(|proj| proj.tool_cache_dir.clone())
+                .map(Into::into),
+        }
+    }
+
     pub fn current_project(&self) -> Result<Arc<Project>> {
         if self.projects.len() == 1 {
             Ok(self.projects[0].clone())
@@ -102,3 +159,11 @@ fn names(projects: &[Arc<Project>]) -> String {
         .collect::<Vec<_>>()
         .join(", ")
 }
+
+/// LEPTOS_OFFLINE=1, or the CARGO_NET_OFFLINE=true that makes cargo work offline
+fn offline_env() -> bool {
+    ["LEPTOS_OFFLINE", "CARGO_NET_OFFLINE"]
+        .iter()
+        .filter_map(|name| std::env::var(name).ok())
+        .any(|val| matches!(val.trim(), "1" | "true"))
+}
@@ -1,8 +1,9 @@
 use crate::{
     config::lib_package::LibPackage,
     ext::{
-        anyhow::{bail, ensure, Result},
-        PackageExt, PathBufExt, PathExt,
+        anyhow::{bail, ensure, Context, Result},
+        exe::{SystemToolConfig, ToolChecksums, ToolVersions, TOOL_URL_ENVS},
+        MetadataExt, PackageExt, PathBufExt, PathExt,
     },
     logger::GRAY,
     service::site::Site,
@@ -10,40 +11,137 @@ use crate::{
 use camino::{Utf8Path, Utf8PathBuf};
 use cargo_metadata::{Metadata, Package};
 use serde::Deserialize;
-use std::{fmt::Debug, net::SocketAddr, sync::Arc};
+use std::{collections::BTreeMap, fmt::Debug, net::SocketAddr, sync::Arc, time::Duration};
 
 use super::{
-    assets::AssetsConfig,
-    bin_package::BinPackage,
-    cli::Opts,
-    dotenvs::{load_dotenvs, overlay_env},
-    end2end::End2EndConfig,
+    assets::{AssetTransformConfig, AssetsConfig},
+    bin_package::{BinPackage, SplitDebuginfo},
+    cli::{BuildStrategy, MessageFormat, Opts, StatusAddr},
+    command_timeout::CommandTimeouts,
+    dotenvs::{find_dotenv, load_dotenvs, overlay_env, read_dotenv},
+    end2end::{End2EndConfig, End2EndSuiteConfig},
+    leptos_version,
+    proxy::{ProxyConfig, ProxyRouteConfig},
+    site_addr::SiteAddrConfig,
+    site_file_mode::{SiteFileMode, SiteFileModeConfig},
     style::StyleConfig,
+    test_runner::TestRunner,
+    tls::TlsConfig,
+    wasm_test::{WasmTestBrowser, WasmTestConfig},
+    watch_files::{WatchFile, WatchFileConfig},
 };
 
 pub struct Project {
     /// absolute path to the working dir
     pub working_dir: Utf8PathBuf,
+    /// the cargo target dir, relative to the working dir
+    pub target_dir: Utf8PathBuf,
     pub name: String,
     pub lib: LibPackage,
-    pub bin: BinPackage,
+    /// None for client-side-rendered projects, that are served as static files
+    pub bin: Option<BinPackage>,
     pub style: StyleConfig,
     pub watch: bool,
     pub release: bool,
     pub hot_reload: bool,
+    /// hash all assets instead of trusting the size and mtime from the asset index
+    pub assets_verify: bool,
+    /// print the site summary at the end of the builds
+    pub summary: bool,
     pub site: Arc<Site>,
     pub end2end: Option<End2EndConfig>,
     pub assets: Option<AssetsConfig>,
     pub js_dir: Utf8PathBuf,
+    pub wasm_test: WasmTestConfig,
+    pub test_runner: TestRunner,
+    /// also test the lib with the bin features (the ssr side)
+    pub test_feature_matrix: bool,
+    pub proxy: Option<ProxyConfig>,
+    pub tls: Option<TlsConfig>,
+    /// the time the server has to stop after the SIGTERM before it is killed
+    pub shutdown_grace_period: Duration,
+    /// the quiet period after the last file change before rebuilding
+    pub watch_debounce: Duration,
+    pub watch_additional_files: Vec<WatchFile>,
+    /// drop the file changes excluded by the .gitignore, .ignore and .leptosignore files
+    pub watch_gitignore: bool,
+    /// the interval of the polling watcher, None for the native one
+    pub watch_poll: Option<Duration>,
+    /// watch the src dirs of the path dependencies, which are in the src paths anyway
+    pub watch_dependencies: bool,
+    /// the .env file, which might not exist. Its vars are given to the server
+    pub dotenv_file: Utf8PathBuf,
+    /// show the errors of a failed build in the browser
+    pub watch_error_overlay: bool,
+    /// reload the browser after a build, and on reconnecting if it missed one
+    pub watch_auto_reload: bool,
+    /// read the keybindings in watch mode, when running in a terminal
+    pub watch_keys: bool,
+    /// show desktop notifications of the failed builds in watch mode
+    pub notifications: bool,
+    /// printed before the lines of the server, None for passing them through
+    pub server_output_prefix: Option<String>,
+    pub server_output_timestamps: bool,
+    pub build_strategy: BuildStrategy,
+    /// the site-addr socket is bound by cargo-leptos and passed to the server with LISTEN_FDS
+    pub reuse_listener: bool,
+    /// polled after a server restart before reloading the browser, a TCP connect when None
+    pub reload_health_path: Option<String>,
+    /// the browser is reloaded anyway when the restarted server isn't ready after it
+    pub reload_health_timeout: Duration,
+    pub message_format: MessageFormat,
+    /// where the watch mode serves the build events, None for the reload port
+    pub status_addr: Option<StatusAddr>,
+    /// the sha256 of the tool downloads without a known checksum
+    pub tool_checksums: ToolChecksums,
+    /// the url templates of the tool mirrors, by tool
+    pub tool_urls: BTreeMap<String, String>,
+    pub tool_download_token: Option<String>,
+    /// use the tools of the PATH instead of downloading them
+    pub prefer_system_tools: bool,
+    pub system_tools: BTreeMap<String, SystemToolConfig>,
+    pub download_proxy: Option<String>,
+    pub tool_versions: ToolVersions,
+    /// the dir of the downloaded tools, instead of the user cache dir
+    pub tool_cache_dir: Option<Utf8PathBuf>,
+    /// where the release builds copy the server exe, with copy-server-exe
+    pub server_exe_dir: Option<Utf8PathBuf>,
+    /// the manifest of the site files, written after each successful build
+    pub site_manifest: Utf8PathBuf,
+    /// with hash-files, the file with the hashes of the front and style files
+    pub hash_file: Option<Utf8PathBuf>,
+    pub command_timeouts: CommandTimeouts,
+    /// the number of retries of a failed wasm-opt, sass or tailwind
+    pub tool_retries: u32,
+    /// the args of the server exe, from --bin-args
+    pub bin_args: Vec<String>,
+}
+
+impl Project {
+    /// Reads the proxy routes again from the Cargo.toml, returning whether they changed
+    pub fn reload_proxy(&self) -> Result<bool> {
+        let Some(proxy) = &self.proxy else {
+            return Ok(false);
+        };
+        let metadata = Metadata::load_cleaned(&self.working_dir.join("Cargo.toml"))?;
+        let Some((_, config)) = ProjectDefinition::parse(&metadata)?
+            .into_iter()
+            .find(|(def, _)| def.name == self.name)
+        else {
+            bail!("The project {} is no longer in the Cargo.toml", self.name);
+        };
+        proxy.reload(&config)
+    }
 }
 
 impl Debug for Project {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.debug_struct("Project")
-            .field("name", &self.name)
-            .field("lib", &self.lib)
-            .field("bin", &self.bin)
-            .field("style", &self.style)
+        let mut f = f.debug_struct("Project");
+        f.field("name", &self.name).field("lib", &self.lib);
+        if let Some(bin) = &self.bin {
+            f.field("bin", bin);
+        }
+        f.field("style", &self.style)
             .field("watch", &self.watch)
             .field("release", &self.release)
             .field("hot_reload", &self.hot_reload)
@@ -69,7 +167,22 @@ impl Project {
                 config.output_name = project.name.to_string();
             }
 
-            let lib = LibPackage::resolve(cli, &metadata, &project, &config)?;
+            let mut lib = LibPackage::resolve(cli, &metadata, &project, &config)?;
+            let strict = cli.strict_version_check || config.require_compatible_version;
+            leptos_version::check(metadata, &lib.name, strict)?;
+
+            let mut bin = if config.csr {
+                None
+            } else {
+                Some(BinPackage::resolve(cli, &metadata, &project, &config)?)
+            };
+            if let Some(bin) = &mut bin {
+                separate_default_features(&mut lib, bin);
+            }
+
+            let hash_file = config.hash_files.then(|| hash_file(&config, bin.as_ref()));
+            let package_dir = bin.as_ref().map_or(&lib.rel_dir, |bin| &bin.rel_dir);
+            let end2end = End2EndConfig::resolve(&config, package_dir)?;
 
             let js_dir = config
                 .js_dir
@@ -78,24 +191,78 @@ impl Project {
 
             let proj = Project {
                 working_dir: metadata.workspace_root.clone(),
+                target_dir: metadata.rel_target_dir(),
                 name: project.name.clone(),
                 lib,
-                bin: BinPackage::resolve(cli, &metadata, &project, &config)?,
+                bin,
                 style: StyleConfig::new(&config)?,
                 watch,
                 release: cli.release,
                 hot_reload: cli.hot_reload,
-                site: Arc::new(Site::new(&config)),
-                end2end: End2EndConfig::resolve(&config),
-                assets: AssetsConfig::resolve(&config),
+                assets_verify: cli.assets_verify,
+                summary: cli.summary || cli.release,
+                site: Arc::new(Site::new(&config).with_file_modes(SiteFileMode::resolve(&config)?)),
+                end2end,
+                assets: AssetsConfig::resolve(&config)?,
                 js_dir,
+                wasm_test: WasmTestConfig::new(&config),
+                test_runner: config.test_runner,
+                test_feature_matrix: config.test_feature_matrix,
+                proxy: ProxyConfig::resolve(&config)?,
+                tls: TlsConfig::resolve(cli, &config)?,
+                shutdown_grace_period: Duration::from_secs(config.shutdown_grace_period),
+                watch_debounce: Duration::from_millis(
+                    cli.watch.debounce.unwrap_or(config.watch_debounce),
+                ),
+                watch_additional_files: WatchFile::resolve(&config)?,
+                watch_gitignore: !cli.watch.no_gitignore,
+                watch_poll: cli.watch.poll.map(Duration::from_millis),
+                watch_dependencies: config.watch_dependencies,
+                dotenv_file: find_dotenv(&config.config_dir)
+                    .unwrap_or_else(|| config.config_dir.join(".env")),
+                watch_error_overlay: config.watch_error_overlay,
+                watch_auto_reload: config.watch_auto_reload,
+                watch_keys: !cli.watch.no_tui_keys,
+                notifications: cli.watch.notify || config.notifications,
+                server_output_prefix: Some(config.server_output_prefix.clone())
+                    .filter(|prefix| !prefix.is_empty() && !cli.watch.raw_server_output),
+                server_output_timestamps: config.server_output_timestamps
+                    && !cli.watch.raw_server_output,
+                build_strategy: cli.watch.build_strategy,
+                reuse_listener: config.reuse_listener,
+                reload_health_path: config.reload_health_path.clone(),
+                reload_health_timeout: Duration::from_secs(config.reload_health_timeout),
+                message_format: cli.message_format,
+                status_addr: cli.watch.status_addr,
+                tool_checksums: config.tool_checksums.clone(),
+                tool_urls: config.tool_urls.clone(),
+                tool_download_token: config.tool_download_token.clone(),
+                prefer_system_tools: config.prefer_system_tools,
+                system_tools: config.system_tools.clone(),
+                download_proxy: config.download_proxy.clone(),
+                tool_versions: config.tool_versions.clone(),
+                tool_cache_dir: config
+                    .tool_cache_dir
+                    .as_ref()
+                    .map(|dir| config.config_dir.join(dir)),
+                server_exe_dir: config.copy_server_exe.then(|| server_exe_dir(&config)),
+                site_manifest: config.site_root.join(&config.site_manifest),
+                hash_file,
+                command_timeouts: CommandTimeouts::resolve(cli, &config)?,
+                tool_retries: config.tool_retries,
+                bin_args: cli.bin_args.clone(),
             };
             resolved.push(Arc::new(proj));
         }
 
         let projects_in_cwd = resolved
             .iter()
-            .filter(|p| p.bin.abs_dir.starts_with(&cwd) || p.lib.abs_dir.starts_with(&cwd))
+            .filter(|p| {
+                p.lib.abs_dir.starts_with(&cwd)
+                    || p.bin
+                        .as_ref()
+                        .map_or(false, |b| b.abs_dir.starts_with(&cwd))
+            })
             .collect::<Vec<_>>();
 
         if projects_in_cwd.len() == 1 {
@@ -105,6 +272,16 @@ impl Project {
         }
     }
 
+    /// dir for the files cached by cargo-leptos between builds
+    pub fn cache_dir(&self) -> Utf8PathBuf {
+        self.target_dir.join("leptos").join(&self.name)
+    }
+
+    /// The compiler errors are kept for the browser overlay and the build events
+    pub fn captures_build_errors(&self) -> bool {
+        self.watch || self.message_format == MessageFormat::Json
+    }
+
     /// env vars to use when running external command
     pub fn to_envs(&self) -> Vec<(&'static str, String)> {
         let mut vec = vec![
@@ -112,15 +289,45 @@ impl Project {
             ("LEPTOS_SITE_ROOT", self.site.root_dir.to_string()),
             ("LEPTOS_SITE_PKG_DIR", self.site.pkg_dir.to_string()),
             ("LEPTOS_SITE_ADDR", self.site.addr.to_string()),
+            ("LEPTOS_SITE_ADDRS", self.site.addrs_string()),
             ("LEPTOS_RELOAD_PORT", self.site.reload.port().to_string()),
             ("LEPTOS_LIB_DIR", self.lib.rel_dir.to_string()),
-            ("LEPTOS_BIN_DIR", self.bin.rel_dir.to_string()),
         ];
+        if let Some(bin) = &self.bin {
+            vec.push(("LEPTOS_BIN_DIR", bin.rel_dir.to_string()))
+        }
+        let base_path = self.site.base_path();
+        if !base_path.is_empty() {
+            vec.push(("LEPTOS_SITE_BASE_PATH", base_path))
+        }
         if self.watch {
             vec.push(("LEPTOS_WATCH", "ON".to_string()))
         }
+        if self.tls.is_some() {
+            vec.push(("LEPTOS_RELOAD_WS_PROTOCOL", "wss".to_string()))
+        }
+        if let Some(hash_file) = &self.hash_file {
+            vec.push(("LEPTOS_HASH_FILES", "true".to_string()));
+            vec.push((
+                "LEPTOS_HASH_FILE_NAME",
+                self.working_dir.join(hash_file).to_string(),
+            ));
+        }
         vec
     }
+
+    /// The vars of the .env file that are not set in the environment, which takes precedence
+    pub fn read_dotenvs(&self) -> Result<Vec<(String, String)>> {
+        if !self.dotenv_file.exists() {
+            return Ok(Vec::new());
+        }
+        let vars = read_dotenv(&self.dotenv_file)
+            .context(format!("Could not read {}", self.dotenv_file))?;
+        Ok(vars
+            .into_iter()
+            .filter(|(key, _)| std::env::var_os(key).is_none())
+            .collect())
+    }
 }
 
 #[derive(Deserialize, Debug)]
@@ -128,30 +335,128 @@ impl Project {
 pub struct ProjectConfig {
     #[serde(default)]
     pub output_name: String,
-    #[serde(default = "default_site_addr")]
+    #[serde(rename = "site-addr", default)]
+    pub site_addr_config: SiteAddrConfig,
+    /// the first of the site-addr addresses, that is given to the server
+    #[serde(skip, default = "default_site_addr")]
     pub site_addr: SocketAddr,
+    /// all the site-addr addresses, to listen on
+    #[serde(skip)]
+    pub site_addrs: Vec<SocketAddr>,
     #[serde(default = "default_site_root")]
     pub site_root: Utf8PathBuf,
     #[serde(default = "default_pkg_dir")]
     pub site_pkg_dir: Utf8PathBuf,
+    /// the path prefix the site is served under, like "/shop"
+    #[serde(default)]
+    pub site_base_path: String,
+    /// the site-base-path before the env vars are applied
+    #[serde(skip)]
+    pub site_base_path_conf: String,
     pub style_file: Option<Utf8PathBuf>,
     pub tailwind_input_file: Option<Utf8PathBuf>,
     pub tailwind_config_file: Option<Utf8PathBuf>,
     /// assets dir. content will be copied to the target/site dir
     pub assets_dir: Option<Utf8PathBuf>,
+    /// gitignore-style patterns of files in the assets dir that are not copied to the site
+    #[serde(default)]
+    pub assets_ignore: Vec<String>,
+    /// copy symlinks in the assets dir as links instead of the content they point to
+    #[serde(default)]
+    pub assets_preserve_symlinks: bool,
+    /// losslessly optimize the png, jpg and svg assets in release builds
+    #[serde(default)]
+    pub assets_optimize_images: bool,
+    /// commands that assets matching a glob are transformed with instead of being copied
+    #[serde(default)]
+    pub asset_transform: Vec<AssetTransformConfig>,
+    /// client-side-rendered project without a server bin. The site is served as static files
+    #[serde(default)]
+    pub csr: bool,
     /// js dir. changes triggers rebuilds.
     pub js_dir: Option<Utf8PathBuf>,
     #[serde(default = "default_reload_port")]
     pub reload_port: u16,
+    /// path that is requested after a server restart until it answers with a success status
+    /// before the browser is reloaded. A TCP connect to the site-addr is used otherwise
+    pub reload_health_path: Option<String>,
+    /// the number of seconds to wait for the restarted server before reloading anyway
+    #[serde(default = "default_reload_health_timeout")]
+    pub reload_health_timeout: u64,
+    /// the address of the dev proxy in front of the server
+    pub proxy_addr: Option<SocketAddr>,
+    /// path prefixes that the dev proxy forwards to other servers
+    #[serde(default)]
+    pub proxy: Vec<ProxyRouteConfig>,
+    /// also watch the src dirs of the path dependencies of the lib and bin packages
+    #[serde(default = "default_watch_dependencies")]
+    pub watch_dependencies: bool,
+    /// paths or globs outside of the sources and assets that are watched, with the action
+    /// that their changes trigger
+    #[serde(default)]
+    pub watch_additional_files: Vec<WatchFileConfig>,
+    /// the milliseconds without file changes after which the watch mode rebuilds
+    #[serde(default = "default_watch_debounce")]
+    pub watch_debounce: u64,
+    /// the number of seconds the server has to stop gracefully before it is killed
+    #[serde(default = "default_shutdown_grace_period")]
+    pub shutdown_grace_period: u64,
+    /// show an overlay with the compiler errors in the browser when a build fails
+    #[serde(default = "default_watch_error_overlay")]
+    pub watch_error_overlay: bool,
+    /// reload the browser when a build is done
+    #[serde(default = "default_watch_auto_reload")]
+    pub watch_auto_reload: bool,
+    /// show a desktop notification when a watch mode build fails, and when it is fixed
+    #[serde(default)]
+    pub notifications: bool,
+    /// printed before each line of the server output, none when empty
+    #[serde(default = "default_server_output_prefix")]
+    pub server_output_prefix: String,
+    /// print the time before each line of the server output
+    #[serde(default)]
+    pub server_output_timestamps: bool,
+    /// bind the site-addr and pass the socket to the server, keeping it open across restarts
+    #[serde(default)]
+    pub reuse_listener: bool,
+    /// pem certificate for --https, a self-signed one is generated when not set
+    pub tls_cert: Option<Utf8PathBuf>,
+    /// pem private key of the tls-cert
+    pub tls_key: Option<Utf8PathBuf>,
     /// command for launching end-2-end integration tests
     pub end2end_cmd: Option<String>,
     /// the dir used when launching end-2-end integration tests
     pub end2end_dir: Option<Utf8PathBuf>,
+    /// env vars for the end-2-end tests
+    #[serde(default)]
+    pub end2end_env: BTreeMap<String, String>,
+    /// named end-2-end test suites, in addition to the end2end-cmd
+    #[serde(default)]
+    pub end2end: BTreeMap<String, End2EndSuiteConfig>,
+    /// path that is requested until it answers with a success status before the
+    /// end-2-end tests are launched. A TCP connect to the site-addr is used otherwise
+    pub end2end_health_path: Option<String>,
+    /// the number of seconds to wait for the server to be ready
+    #[serde(default = "default_end2end_wait_secs")]
+    pub end2end_wait_secs: u64,
     #[serde(default = "default_browserquery")]
     pub browserquery: String,
+    /// what runs the native tests: cargo or nextest
+    #[serde(default)]
+    pub test_runner: TestRunner,
+    /// also run the lib tests with the bin features applied to the lib package
+    #[serde(default)]
+    pub test_feature_matrix: bool,
+    /// the browser that the wasm tests are run in
+    #[serde(default)]
+    pub wasm_test_browser: WasmTestBrowser,
+    /// the webdriver for the wasm-test-browser, found on the PATH when not set
+    pub wasm_test_driver: Option<Utf8PathBuf>,
     /// the bin target to use for building the server
     #[serde(default)]
     pub bin_target: String,
+    /// the example target to build as the server, instead of a bin target
+    pub bin_example: Option<String>,
     /// the bin output target triple to use for building the server
     pub bin_target_triple: Option<String>,
     #[serde(default)]
@@ -164,9 +469,71 @@ pub struct ProjectConfig {
     pub bin_features: Vec<String>,
     #[serde(default)]
     pub bin_default_features: bool,
+    /// strips the symbols of the server exe of the release builds
+    #[serde(default)]
+    pub bin_strip: bool,
+    /// splits the debug info out of the server exe of the release builds
+    pub bin_split_debuginfo: Option<SplitDebuginfo>,
+    /// fail instead of warning when the leptos version wasn't tested with cargo-leptos
+    #[serde(default)]
+    pub require_compatible_version: bool,
+    /// the sha256 of the downloaded tools, by name-version and os-arch
+    #[serde(default)]
+    pub tool_checksums: ToolChecksums,
+    /// url templates of mirrors of the tool downloads, by tool
+    #[serde(default)]
+    pub tool_urls: BTreeMap<String, String>,
+    /// the bearer token of the mirrors, only from the env
+    #[serde(skip)]
+    pub tool_download_token: Option<String>,
+    /// use the tools found on the PATH instead of downloading them
+    #[serde(default = "default_prefer_system_tools")]
+    pub prefer_system_tools: bool,
+    /// the prefer-system-tools override and the accepted versions of the system tools, by tool
+    #[serde(default)]
+    pub system_tools: BTreeMap<String, SystemToolConfig>,
+    /// the proxy of the tool downloads, instead of the one of the HTTPS_PROXY env var
+    pub download_proxy: Option<String>,
+    pub wasm_opt_version: Option<String>,
+    pub sass_version: Option<String>,
+    pub tailwind_version: Option<String>,
+    /// the versions above, with the ones of the workspace-level leptos-tool-versions
+    #[serde(skip)]
+    pub tool_versions: ToolVersions,
+    /// relative to the Cargo.toml of the project
+    pub tool_cache_dir: Option<Utf8PathBuf>,
+    /// copy the server exe of the release builds to the server-exe-dir
+    #[serde(default)]
+    pub copy_server_exe: bool,
+    /// the dir of the site-root by default
+    pub server_exe_dir: Option<Utf8PathBuf>,
+    /// relative to the site-root
+    #[serde(default = "default_site_manifest")]
+    pub site_manifest: Utf8PathBuf,
+    /// the modes forced on the site files matching a glob
+    #[serde(default)]
+    pub site_file_mode: Vec<SiteFileModeConfig>,
+    /// write the hashes of the wasm, js and css files to the hash-file-name for the server
+    #[serde(default)]
+    pub hash_files: bool,
+    /// beside the server exe
+    #[serde(default = "default_hash_file_name")]
+    pub hash_file_name: Utf8PathBuf,
+    /// the seconds that the post-processing tools may run, 0 for no timeout
+    #[serde(default = "default_command_timeout_secs")]
+    pub command_timeout_secs: u64,
+    /// the timeouts in seconds by command, like cargo, 0 for no timeout
+    #[serde(default)]
+    pub command_timeouts: BTreeMap<String, u64>,
+    /// retry wasm-opt, sass and tailwind when they fail
+    #[serde(default)]
+    pub tool_retries: u32,
 
     #[serde(skip)]
     pub config_dir: Utf8PathBuf,
+    /// the vars of the .env file, if any
+    #[serde(skip)]
+    pub dotenvs: Vec<(String, String)>,
 
     // Profiles
     pub lib_profile_dev: Option<String>,
@@ -179,7 +546,9 @@ impl ProjectConfig {
     fn parse(dir: &Utf8Path, metadata: &serde_json::Value) -> Result<Self> {
         let mut conf: ProjectConfig = serde_json::from_value(metadata.clone())?;
         conf.config_dir = dir.to_path_buf();
+        conf.site_base_path_conf = conf.site_base_path.clone();
         let dotenvs = load_dotenvs(dir)?;
+        conf.dotenvs = dotenvs.clone().unwrap_or_default();
         overlay_env(&mut conf, dotenvs)?;
         if conf.site_root == "/" || conf.site_root == "." {
             bail!(
@@ -187,7 +556,11 @@ impl ProjectConfig {
                 conf.site_root
             );
         }
-        if conf.site_addr.port() == conf.reload_port {
+        check_tools("tool-urls", conf.tool_urls.keys())?;
+        check_tools("system-tools", conf.system_tools.keys())?;
+        conf.site_addrs = conf.site_addr_config.resolve()?;
+        conf.site_addr = conf.site_addrs[0];
+        if conf.site_addrs.iter().any(|a| a.port() == conf.reload_port) {
             bail!(
                 "The site-addr port and reload-port cannot be the same: {}",
                 conf.reload_port
@@ -197,10 +570,73 @@ impl ProjectConfig {
     }
 }
 
+/// the keys of a config table by tool need to be tool names
+fn check_tools<'a>(key: &str, mut tools: impl Iterator<Item = &'a String>) -> Result<()> {
+    if let Some(tool) =
+        tools.find(|tool| !TOOL_URL_ENVS.iter().any(|(name, _)| *name == tool.as_str()))
+    {
+        let names: Vec<&str> = TOOL_URL_ENVS.iter().map(|(name, _)| *name).collect();
+        bail!(
+            "Unknown tool {tool:?} in the {key}, expected one of {}",
+            names.join(", ")
+        );
+    }
+    Ok(())
+}
+
+/// the server-exe-dir, else the dir of the site-root, so that the exe is beside the site
+fn server_exe_dir(config: &ProjectConfig) -> Utf8PathBuf {
+    if let Some(dir) = &config.server_exe_dir {
+        return dir.clone();
+    }
+    site_root_dir(config)
+}
+
+/// When the lib and the bin are the same package, its default features are the same for the
+/// front and the server, so they would bring the ssr deps into the wasm or the hydrate ones
+/// into the server. A side with explicit features is then built without the default ones.
+fn separate_default_features(lib: &mut LibPackage, bin: &mut BinPackage) {
+    if lib.name != bin.name {
+        return;
+    }
+    if lib.default_features && !lib.features.is_empty() {
+        log::warn!(
+            "Config lib-default-features ignored, the default features of {} would also be those of the server",
+            lib.name
+        );
+        lib.default_features = false;
+    }
+    if bin.default_features && !bin.features.is_empty() {
+        log::warn!(
+            "Config bin-default-features ignored, the default features of {} would also be those of the front",
+            bin.name
+        );
+        bin.default_features = false;
+    }
+}
+
+/// the hash file is beside the server exe, where Leptos looks for it. A client-side-rendered
+/// project has it beside the site-root, which isn't watched either.
+fn hash_file(config: &ProjectConfig, bin: Option<&BinPackage>) -> Utf8PathBuf {
+    let dir = bin
+        .and_then(|bin| bin.exe_file.parent())
+        .map_or_else(|| site_root_dir(config), Utf8Path::to_path_buf);
+    dir.join(&config.hash_file_name)
+}
+
+fn site_root_dir(config: &ProjectConfig) -> Utf8PathBuf {
+    match config.site_root.parent() {
+        Some(parent) if parent != "" => parent.to_path_buf(),
+        _ => Utf8PathBuf::from("."),
+    }
+}
+
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "kebab-case")]
 pub struct ProjectDefinition {
     name: String,
+    /// not needed for client-side-rendered projects
+    #[serde(default)]
     pub bin_package: String,
     pub lib_package: String,
 }
@@ -228,13 +664,8 @@ impl ProjectDefinition {
         let conf = ProjectConfig::parse(dir, metadata)?;
 
         ensure!(
-            package.cdylib_target().is_some(),
-            "Cargo.toml has leptos metadata but is missing a cdylib library target. {}",
-            GRAY.paint(package.manifest_path.as_str())
-        );
-        ensure!(
-            package.has_bin_target(),
-            "Cargo.toml has leptos metadata but is missing a bin target. {}",
+            conf.csr || package.has_bin_target() || conf.bin_example.is_some(),
+            "Cargo.toml has leptos metadata but is missing a bin target. Set csr = true for a client-side-rendered project. {}",
             GRAY.paint(package.manifest_path.as_str())
         );
 
@@ -264,6 +695,22 @@ impl ProjectDefinition {
                 found.push(Self::from_project(package, metadata, &dir)?);
             }
         }
+
+        let workspace_versions: ToolVersions =
+            match metadata.workspace_metadata.get("leptos-tool-versions") {
+                Some(versions) => serde_json::from_value(versions.clone())
+                    .context("Invalid [workspace.metadata.leptos-tool-versions]")?,
+                None => ToolVersions::default(),
+            };
+        for (_, conf) in &mut found {
+            conf.tool_versions = ToolVersions {
+                wasm_opt_version: conf.wasm_opt_version.clone(),
+                sass_version: conf.sass_version.clone(),
+                tailwind_version: conf.tailwind_version.clone(),
+            }
+            .or(&workspace_versions);
+            conf.tool_versions.validate()?;
+        }
         Ok(found)
     }
 }
@@ -284,10 +731,58 @@ fn default_site_root() -> Utf8PathBuf {
     Utf8PathBuf::from("target").join("site")
 }
 
+fn default_site_manifest() -> Utf8PathBuf {
+    Utf8PathBuf::from(".leptos-manifest.json")
+}
+
+fn default_hash_file_name() -> Utf8PathBuf {
+    Utf8PathBuf::from("hash.txt")
+}
+
+fn default_command_timeout_secs() -> u64 {
+    600
+}
+
 fn default_reload_port() -> u16 {
     3001
 }
 
+fn default_reload_health_timeout() -> u64 {
+    30
+}
+
+fn default_watch_dependencies() -> bool {
+    true
+}
+
+fn default_watch_debounce() -> u64 {
+    200
+}
+
+fn default_shutdown_grace_period() -> u64 {
+    5
+}
+
+fn default_watch_error_overlay() -> bool {
+    true
+}
+
+fn default_watch_auto_reload() -> bool {
+    true
+}
+
+fn default_server_output_prefix() -> String {
+    "[server]".to_string()
+}
+
+fn default_end2end_wait_secs() -> u64 {
+    30
+}
+
 fn default_browserquery() -> String {
     "defaults".to_string()
 }
+
+fn default_prefer_system_tools() -> bool {
+    true
+}
@@ -0,0 +1,154 @@
+use std::{net::SocketAddr, sync::RwLock};
+
+use camino::Utf8PathBuf;
+use itertools::Itertools;
+use reqwest::Url;
+use serde::Deserialize;
+
+use crate::ext::{
+    anyhow::{ensure, Context, Result},
+    sync::connectable,
+};
+
+use super::ProjectConfig;
+
+/// A `[[package.metadata.leptos.proxy]]` entry
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct ProxyRouteConfig {
+    /// the path prefix of the requests that are proxied, like "/api"
+    pub path: String,
+    /// the http url that the requests are forwarded to, like "http://localhost:8080"
+    pub target: String,
+    /// also forward websocket upgrades
+    #[serde(default)]
+    pub ws: bool,
+}
+
+/// The dev proxy in front of the server
+#[derive(Debug)]
+pub struct ProxyConfig {
+    /// the address the proxy listens on. None for a csr project without a proxy-addr, whose
+    /// static server forwards the routes itself
+    pub addr: Option<SocketAddr>,
+    /// sorted with the longest path first. Watch reads them again when the manifest changes
+    pub routes: RwLock<Vec<ProxyRoute>>,
+    /// the url of the site server, that gets the requests matching no route
+    pub default: String,
+    /// the Cargo.toml with the routes, relative to the workspace root
+    pub manifest: Utf8PathBuf,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProxyRoute {
+    /// without trailing slash
+    pub path: String,
+    /// without trailing slash
+    pub target: String,
+    pub ws: bool,
+}
+
+impl ProxyConfig {
+    pub fn resolve(config: &ProjectConfig) -> Result<Option<Self>> {
+        // the static server of a csr project forwards the routes without a proxy-addr
+        let csr_routes = config.csr && !config.proxy.is_empty();
+        if config.proxy_addr.is_none() && !csr_routes {
+            if !config.proxy.is_empty() {
+                log::warn!("Proxy routes are configured but not used, as there is no proxy-addr");
+            }
+            return Ok(None);
+        }
+        if let Some(addr) = config.proxy_addr {
+            ensure!(
+                config.site_addrs.iter().all(|a| a.port() != addr.port())
+                    && addr.port() != config.reload_port,
+                "The proxy-addr port cannot be the same as the site-addr port or the reload-port: {}",
+                addr.port()
+            );
+        }
+
+        Ok(Some(Self {
+            addr: config.proxy_addr,
+            routes: RwLock::new(resolve_routes(config)?),
+            default: format!("http://{}", connectable(config.site_addr)),
+            manifest: config.config_dir.join("Cargo.toml"),
+        }))
+    }
+
+    /// Replaces the routes with the ones of the config read again, returning whether they
+    /// changed. The proxy-addr is only read at startup.
+    pub fn reload(&self, config: &ProjectConfig) -> Result<bool> {
+        let routes = resolve_routes(config)?;
+        if config.proxy_addr != self.addr {
+            log::warn!("Proxy keeps its address until cargo-leptos is restarted");
+        }
+        let mut current = self.routes.write().unwrap();
+        if *current == routes {
+            return Ok(false);
+        }
+        *current = routes;
+        Ok(true)
+    }
+
+    /// The route for the path, None when it goes to the site server
+    pub fn route(&self, path: &str) -> Option<ProxyRoute> {
+        self.routes
+            .read()
+            .unwrap()
+            .iter()
+            .find(|route| {
+                route.path.is_empty()
+                    || path == route.path
+                    || path
+                        .strip_prefix(&route.path)
+                        .map_or(false, |rest| rest.starts_with('/'))
+            })
+            .cloned()
+    }
+
+    /// The url that the request is forwarded to and whether websockets are passed through
+    pub fn upstream(&self, path_and_query: &str) -> (String, bool) {
+        let path = path_and_query.split('?').next().unwrap_or_default();
+        match self.route(path) {
+            Some(route) => (format!("{}{path_and_query}", route.target), route.ws),
+            None => (format!("{}{path_and_query}", self.default), true),
+        }
+    }
+
+    /// The routes as logged, with the site server for the others when there is a proxy-addr
+    pub fn routes_string(&self) -> String {
+        let routes = self.routes.read().unwrap();
+        let default = self.addr.map(|_| format!("/** -> {}", self.default));
+        routes
+            .iter()
+            .map(|route| format!("{}/** -> {}", route.path, route.target))
+            .chain(default)
+            .join(", ")
+    }
+}
+
+fn resolve_routes(config: &ProjectConfig) -> Result<Vec<ProxyRoute>> {
+    let mut routes = Vec::new();
+    for route in &config.proxy {
+        ensure!(
+            route.path.starts_with('/'),
+            "The proxy path {:?} should start with a /",
+            route.path
+        );
+        let target = Url::parse(&route.target)
+            .context(format!("Invalid proxy target {:?}", route.target))?;
+        ensure!(
+            target.scheme() == "http",
+            "The proxy target {} is not supported, only http targets are",
+            route.target
+        );
+        routes.push(ProxyRoute {
+            path: route.path.trim_end_matches('/').to_string(),
+            target: target.as_str().trim_end_matches('/').to_string(),
+            ws: route.ws,
+        });
+    }
+    // the most specific route is the one that matches
+    routes.sort_by(|a, b| b.path.len().cmp(&a.path.len()));
+    Ok(routes)
+}
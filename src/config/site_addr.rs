@@ -0,0 +1,52 @@
+use std::net::{SocketAddr, ToSocketAddrs};
+
+use serde::Deserialize;
+
+use crate::ext::anyhow::{bail, Context, Result};
+
+/// The `site-addr`: an address, or a hostname with a port that can resolve to several, as
+/// a single string that can also be a comma separated list, or as a list
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum SiteAddrConfig {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl Default for SiteAddrConfig {
+    fn default() -> Self {
+        Self::One("127.0.0.1:3000".to_string())
+    }
+}
+
+impl SiteAddrConfig {
+    /// The addresses in the configured order, without duplicates. The hostnames are
+    /// resolved here, once, so that all the servers agree on the addresses.
+    pub fn resolve(&self) -> Result<Vec<SocketAddr>> {
+        let entries: Vec<&str> = match self {
+            Self::One(entry) => entry.split(',').collect(),
+            Self::Many(entries) => entries.iter().map(String::as_str).collect(),
+        };
+        let mut addrs: Vec<SocketAddr> = Vec::new();
+        for entry in entries.into_iter().map(str::trim).filter(|e| !e.is_empty()) {
+            let resolved = match entry.parse::<SocketAddr>() {
+                Ok(addr) => vec![addr],
+                Err(_) => entry
+                    .to_socket_addrs()
+                    .context(format!(
+                        "Invalid site-addr {entry:?}, expected an address like 127.0.0.1:3000 or a host with a port like localhost:3000"
+                    ))?
+                    .collect(),
+            };
+            for addr in resolved {
+                if !addrs.contains(&addr) {
+                    addrs.push(addr);
+                }
+            }
+        }
+        if addrs.is_empty() {
+            bail!("The site-addr has no address");
+        }
+        Ok(addrs)
+    }
+}
@@ -0,0 +1,67 @@
+use camino::Utf8Path;
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use serde::Deserialize;
+
+use crate::ext::anyhow::{bail, Context, Result};
+
+use super::ProjectConfig;
+
+/// A `[[package.metadata.leptos.site-file-mode]]` entry
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct SiteFileModeConfig {
+    /// gitignore-style glob, relative to the site-root, of the files that get the mode
+    pub glob: String,
+    /// the octal unix mode, like "755"
+    pub mode: String,
+}
+
+/// The mode forced on the site files matching the glob, instead of the one of their source
+pub struct SiteFileMode {
+    pub glob: String,
+    matcher: Gitignore,
+    pub mode: u32,
+}
+
+impl std::fmt::Debug for SiteFileMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SiteFileMode")
+            .field("glob", &self.glob)
+            .field("mode", &format_args!("{:o}", self.mode))
+            .finish_non_exhaustive()
+    }
+}
+
+impl SiteFileMode {
+    pub fn resolve(config: &ProjectConfig) -> Result<Vec<Self>> {
+        let mut resolved = Vec::new();
+        for entry in &config.site_file_mode {
+            let mut builder = GitignoreBuilder::new(&config.site_root);
+            builder
+                .add_line(None, &entry.glob)
+                .context(format!("Invalid site-file-mode glob {:?}", entry.glob))?;
+            resolved.push(Self {
+                glob: entry.glob.clone(),
+                matcher: builder.build().dot()?,
+                mode: parse_mode(&entry.mode)?,
+            });
+        }
+        Ok(resolved)
+    }
+
+    /// true if the site file, relative to the site-root, matches the glob
+    pub fn matches(&self, site: &Utf8Path) -> bool {
+        self.matcher
+            .matched_path_or_any_parents(site, false)
+            .is_ignore()
+    }
+}
+
+/// An octal mode like "755" or "0o755"
+pub(crate) fn parse_mode(mode: &str) -> Result<u32> {
+    let digits = mode.trim_start_matches("0o");
+    match u32::from_str_radix(digits, 8) {
+        Ok(parsed) if parsed <= 0o7777 => Ok(parsed),
+        _ => bail!("Invalid site-file-mode {mode:?}, expected an octal mode like \"755\""),
+    }
+}
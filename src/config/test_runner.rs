@@ -0,0 +1,27 @@
+use serde::Deserialize;
+
+use crate::ext::anyhow::{bail, Result};
+
+/// What runs the native tests of `cargo leptos test`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TestRunner {
+    #[default]
+    Cargo,
+    Nextest,
+}
+
+impl TestRunner {
+    /// The cargo (sub) command for running the tests. Fails if cargo-nextest isn't installed.
+    pub fn cargo_cmd(&self) -> Result<&'static str> {
+        match self {
+            Self::Cargo => Ok("test"),
+            Self::Nextest => {
+                if which::which("cargo-nextest").is_err() {
+                    bail!("cargo-nextest is required for running the tests with nextest but was not found. Install it with `cargo install --locked cargo-nextest`, see https://nexte.st")
+                }
+                Ok("nextest run")
+            }
+        }
+    }
+}
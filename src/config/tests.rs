@@ -2,13 +2,8 @@ use super::Config;
 
 fn opts(project: Option<&str>) -> crate::config::Opts {
     crate::config::Opts {
-        release: false,
-        hot_reload: false,
         project: project.map(|s| s.to_string()),
-        verbose: 0,
-        features: Vec::new(),
-        bin_features: Vec::new(),
-        lib_features: Vec::new(),
+        ..Default::default()
     }
 }
 
@@ -61,3 +56,451 @@ fn test_workspace_in_subdir_project2() {
 
     insta::assert_debug_snapshot!(conf);
 }
+
+#[test]
+fn test_end2end_suite() {
+    let cli = opts(None);
+
+    let conf = Config::test_load(cli, "examples", "examples/project/Cargo.toml", true);
+
+    let e2e = conf.projects[0].end2end.as_ref().unwrap();
+    assert_eq!(e2e.suite(None).unwrap().name, "default");
+    assert_eq!(
+        e2e.suite(Some("default")).unwrap().cmd,
+        "npx playwright test"
+    );
+    assert!(e2e.suite(Some("smoke")).is_err());
+}
+
+#[test]
+fn test_proxy_upstream() {
+    use super::{ProxyConfig, ProxyRoute};
+
+    let proxy = ProxyConfig {
+        addr: Some("127.0.0.1:3002".parse().unwrap()),
+        routes: std::sync::RwLock::new(vec![
+            ProxyRoute {
+                path: "/api/v2".to_string(),
+                target: "http://localhost:8081".to_string(),
+                ws: true,
+            },
+            ProxyRoute {
+                path: "/api".to_string(),
+                target: "http://localhost:8080".to_string(),
+                ws: false,
+            },
+        ]),
+        default: "http://127.0.0.1:3000".to_string(),
+        manifest: "Cargo.toml".into(),
+    };
+
+    assert_eq!(
+        proxy.upstream("/api/users?id=1"),
+        ("http://localhost:8080/api/users?id=1".to_string(), false)
+    );
+    assert_eq!(
+        proxy.upstream("/api/v2/ws"),
+        ("http://localhost:8081/api/v2/ws".to_string(), true)
+    );
+    assert_eq!(
+        proxy.upstream("/api"),
+        ("http://localhost:8080/api".to_string(), false)
+    );
+    assert_eq!(
+        proxy.upstream("/apis"),
+        ("http://127.0.0.1:3000/apis".to_string(), true)
+    );
+}
+
+#[test]
+fn test_proxy_reload() {
+    use super::{ProjectConfig, ProxyConfig};
+
+    let config = |routes: serde_json::Value| -> ProjectConfig {
+        serde_json::from_value(serde_json::json!({ "csr": true, "proxy": routes })).unwrap()
+    };
+    // a csr project forwards the routes from its static server, without a proxy-addr
+    let proxy = ProxyConfig::resolve(&config(serde_json::json!([
+        { "path": "/api", "target": "http://localhost:8080" }
+    ])))
+    .unwrap()
+    .unwrap();
+    assert_eq!(proxy.addr, None);
+    assert_eq!(proxy.routes_string(), "/api/** -> http://localhost:8080");
+    assert!(proxy.route("/api/users").is_some());
+
+    let reloaded = config(serde_json::json!([
+        { "path": "/api", "target": "http://localhost:8080" },
+        { "path": "/auth", "target": "http://localhost:9000" }
+    ]));
+    assert!(proxy.reload(&reloaded).unwrap());
+    assert!(!proxy.reload(&reloaded).unwrap());
+    assert!(proxy.route("/auth/login").is_some());
+
+    let invalid = config(serde_json::json!([{ "path": "api", "target": "http://localhost" }]));
+    assert!(proxy.reload(&invalid).is_err());
+    assert!(proxy.route("/auth/login").is_some());
+}
+
+#[test]
+fn test_watch_additional_files() {
+    use super::{ProjectConfig, WatchAction, WatchFile};
+    use camino::Utf8Path;
+
+    let config: ProjectConfig = serde_json::from_value(serde_json::json!({
+        "watch-additional-files": [
+            "config",
+            { "path": "templates/**/*.html", "action": "reload-browser-only" }
+        ]
+    }))
+    .unwrap();
+    let files = WatchFile::resolve(&config).unwrap();
+
+    assert_eq!(files[0].watched, "config");
+    assert_eq!(files[0].action, WatchAction::RestartServer);
+    assert!(files[0].matches(Utf8Path::new("config/app.toml")));
+    assert!(!files[0].matches(Utf8Path::new("src/config/app.toml")));
+
+    assert_eq!(files[1].watched, "templates");
+    assert_eq!(files[1].action, WatchAction::ReloadBrowserOnly);
+    assert!(files[1].matches(Utf8Path::new("templates/mail/welcome.html")));
+    assert!(!files[1].matches(Utf8Path::new("templates/mail/welcome.txt")));
+}
+
+#[test]
+fn test_site_addrs() {
+    use super::SiteAddrConfig;
+
+    let config: SiteAddrConfig =
+        serde_json::from_value(serde_json::json!(["127.0.0.1:3000", "[::1]:3000"])).unwrap();
+    assert_eq!(
+        config.resolve().unwrap(),
+        vec![
+            "127.0.0.1:3000".parse().unwrap(),
+            "[::1]:3000".parse().unwrap()
+        ]
+    );
+
+    let config = SiteAddrConfig::One("0.0.0.0:3000, [::]:3000,0.0.0.0:3000".to_string());
+    assert_eq!(
+        config.resolve().unwrap(),
+        vec![
+            "0.0.0.0:3000".parse().unwrap(),
+            "[::]:3000".parse().unwrap()
+        ]
+    );
+
+    assert!(SiteAddrConfig::One("127.0.0.1".to_string())
+        .resolve()
+        .is_err());
+    assert!(SiteAddrConfig::One(" ".to_string()).resolve().is_err());
+}
+
+#[test]
+fn test_site_base_path() {
+    use super::ProjectConfig;
+    use crate::service::site::Site;
+    use camino::Utf8Path;
+
+    let config: ProjectConfig = serde_json::from_value(serde_json::json!({
+        "site-base-path": "shop/"
+    }))
+    .unwrap();
+    let site = Site::new(&config);
+    assert_eq!(site.base_path(), "/shop");
+    assert_eq!(site.link(Utf8Path::new("pkg/app.js")), "/shop/pkg/app.js");
+
+    let config: ProjectConfig = serde_json::from_value(serde_json::json!({
+        "site-base-path": "/"
+    }))
+    .unwrap();
+    let site = Site::new(&config);
+    assert_eq!(site.base_path(), "");
+    assert_eq!(site.link(Utf8Path::new("pkg/app.js")), "/pkg/app.js");
+}
+
+#[test]
+fn test_site_file_mode() {
+    use super::site_file_mode::parse_mode;
+
+    assert_eq!(parse_mode("755").unwrap(), 0o755);
+    assert_eq!(parse_mode("0o644").unwrap(), 0o644);
+    assert!(parse_mode("rwx").is_err());
+    assert!(parse_mode("77777").is_err());
+}
+
+#[cfg(target_family = "unix")]
+#[tokio::test]
+async fn test_site_file_permissions() {
+    use super::{ProjectConfig, SiteFileMode};
+    use crate::service::site::{Site, SiteFile, SourcedSiteFile};
+    use camino::Utf8PathBuf;
+    use std::{fs::Permissions, os::unix::fs::PermissionsExt};
+
+    let dir = temp_dir::TempDir::new().unwrap();
+    let root = Utf8PathBuf::from_path_buf(dir.path().to_path_buf()).unwrap();
+    let mode = |path: &Utf8PathBuf| std::fs::metadata(path).unwrap().permissions().mode() & 0o7777;
+
+    let source = root.join("run.cgi");
+    std::fs::write(&source, "#!/bin/sh").unwrap();
+    std::fs::set_permissions(&source, Permissions::from_mode(0o755)).unwrap();
+    let link = root.join("link.cgi");
+    std::os::unix::fs::symlink(&source, &link).unwrap();
+
+    let config: ProjectConfig = serde_json::from_value(serde_json::json!({
+        "site-root": root.join("site"),
+        "site-file-mode": [{ "glob": "*.sh", "mode": "700" }]
+    }))
+    .unwrap();
+    let site = Site::new(&config).with_file_modes(SiteFileMode::resolve(&config).unwrap());
+
+    let file = SourcedSiteFile {
+        source: source.clone(),
+        dest: root.join("site/cgi-bin/run.cgi"),
+        site: Utf8PathBuf::from("cgi-bin/run.cgi"),
+    };
+    assert!(site.updated(&file).await.unwrap());
+    assert_eq!(mode(&file.dest), 0o755);
+
+    // an unchanged content gets the mode of the source back
+    std::fs::set_permissions(&file.dest, Permissions::from_mode(0o644)).unwrap();
+    assert!(!site.updated(&file).await.unwrap());
+    assert_eq!(mode(&file.dest), 0o755);
+
+    // a symlinked source gives the permissions of its target
+    let linked = SourcedSiteFile {
+        source: link,
+        dest: root.join("site/linked.cgi"),
+        site: Utf8PathBuf::from("linked.cgi"),
+    };
+    assert!(site.updated(&linked).await.unwrap());
+    assert_eq!(mode(&linked.dest), 0o755);
+
+    // the site-file-mode is forced, also on an unchanged content
+    let script = SiteFile {
+        dest: root.join("site/run.sh"),
+        site: Utf8PathBuf::from("run.sh"),
+    };
+    assert!(site.updated_with(&script, b"#!/bin/sh").await.unwrap());
+    assert_eq!(mode(&script.dest), 0o700);
+    std::fs::set_permissions(&script.dest, Permissions::from_mode(0o644)).unwrap();
+    assert!(!site.updated_with(&script, b"#!/bin/sh").await.unwrap());
+    assert_eq!(mode(&script.dest), 0o700);
+}
+
+#[test]
+fn test_command_timeouts() {
+    use super::{CommandTimeouts, ProjectConfig};
+    use std::time::Duration;
+
+    let config: ProjectConfig = serde_json::from_value(serde_json::json!({
+        "command-timeout-secs": 60,
+        "command-timeouts": { "wasm-opt": 0, "end2end": 1800 }
+    }))
+    .unwrap();
+    let timeouts = CommandTimeouts::resolve(&opts(None), &config).unwrap();
+    assert_eq!(timeouts.get("cargo"), None);
+    assert_eq!(timeouts.get("tailwind"), Some(Duration::from_secs(60)));
+    assert_eq!(timeouts.get("wasm-opt"), None);
+    assert_eq!(timeouts.get("end2end"), Some(Duration::from_secs(1800)));
+
+    // the --command-timeout is for all the commands
+    let cli = crate::config::Opts {
+        command_timeout: Some(7200),
+        ..opts(None)
+    };
+    let timeouts = CommandTimeouts::resolve(&cli, &config).unwrap();
+    assert_eq!(timeouts.get("cargo"), Some(Duration::from_secs(7200)));
+    assert_eq!(timeouts.get("wasm-opt"), Some(Duration::from_secs(7200)));
+
+    let config: ProjectConfig = serde_json::from_value(serde_json::json!({
+        "command-timeouts": { "wasm_opt": 60 }
+    }))
+    .unwrap();
+    assert!(CommandTimeouts::resolve(&opts(None), &config).is_err());
+}
+
+/// Writes the files of the packages into a temp dir, and loads the config of its Cargo.toml
+fn load_packages(
+    files: &[(&str, &str)],
+) -> (temp_dir::TempDir, crate::ext::anyhow::Result<Config>) {
+    use camino::Utf8PathBuf;
+
+    let dir = temp_dir::TempDir::new().unwrap();
+    let root = Utf8PathBuf::from_path_buf(dir.path().to_path_buf()).unwrap();
+    for (path, content) in files {
+        let path = root.join(path);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, content).unwrap();
+    }
+    let manifest = root.join("Cargo.toml");
+    let config = Config::load(opts(None), &root, &manifest, false);
+    (dir, config)
+}
+
+#[test]
+fn test_lib_without_cdylib() {
+    let (_dir, config) = load_packages(&[
+        (
+            "Cargo.toml",
+            r#"
+            [package]
+            name = "app"
+            version = "0.1.0"
+
+            [package.metadata.leptos]
+            "#,
+        ),
+        ("src/lib.rs", ""),
+        ("src/main.rs", "fn main() {}"),
+    ]);
+    let err = format!("{:#}", config.err().unwrap());
+    assert!(err.contains("The lib-package app has no cdylib"), "{err}");
+    assert!(
+        err.contains("[lib]\ncrate-type = [\"cdylib\", \"rlib\"]"),
+        "{err}"
+    );
+}
+
+#[test]
+fn test_lib_package_without_cdylib() {
+    let (_dir, config) = load_packages(&[
+        (
+            "Cargo.toml",
+            r#"
+            [workspace]
+            members = ["front", "server"]
+
+            [[workspace.metadata.leptos]]
+            name = "app"
+            bin-package = "server"
+            lib-package = "front"
+            "#,
+        ),
+        (
+            "front/Cargo.toml",
+            "[package]\nname = \"front\"\nversion = \"0.1.0\"\n",
+        ),
+        ("front/src/lib.rs", ""),
+        (
+            "server/Cargo.toml",
+            "[package]\nname = \"server\"\nversion = \"0.1.0\"\n",
+        ),
+        ("server/src/main.rs", "fn main() {}"),
+    ]);
+    let err = format!("{:#}", config.err().unwrap());
+    assert!(err.contains("The lib-package front has no cdylib"), "{err}");
+    let manifest = std::path::Path::new("front").join("Cargo.toml");
+    assert!(err.contains(&manifest.display().to_string()), "{err}");
+}
+
+#[test]
+fn test_same_package_default_features() {
+    let conf = Config::test_load(
+        opts(None),
+        "examples",
+        "examples/split_features/Cargo.toml",
+        false,
+    );
+    let proj = &conf.projects[0];
+    let bin = proj.bin.as_ref().unwrap();
+    assert!(!proj.lib.default_features);
+    assert_eq!(proj.lib.features, ["hydrate"]);
+    assert!(!bin.default_features);
+    assert_eq!(bin.features, ["ssr"]);
+}
+
+#[test]
+fn test_leptos_compatibility() {
+    use super::leptos_version::{compatibility, Compatibility};
+    use cargo_metadata::semver::Version;
+
+    let of = |version| compatibility(&Version::parse(version).unwrap());
+    assert_eq!(of("0.2.1"), Compatibility::Tested);
+    assert_eq!(of("0.5.0-rc2"), Compatibility::Tested);
+    assert_eq!(of("0.5.9"), Compatibility::Tested);
+    let untested = Compatibility::Untested { recommended: None };
+    assert_eq!(of("0.1.3"), untested);
+    assert_eq!(of("0.6.0-beta"), untested);
+    assert_eq!(of("1.0.0"), untested);
+}
+
+#[test]
+fn test_require_compatible_version() {
+    let (_dir, config) = load_packages(&[
+        (
+            "Cargo.toml",
+            r#"
+            [package]
+            name = "app"
+            version = "0.1.0"
+
+            [lib]
+            crate-type = ["cdylib", "rlib"]
+
+            [dependencies]
+            leptos = { path = "leptos" }
+
+            [package.metadata.leptos]
+            require-compatible-version = true
+            "#,
+        ),
+        ("src/lib.rs", ""),
+        ("src/main.rs", "fn main() {}"),
+        (
+            "leptos/Cargo.toml",
+            "[package]\nname = \"leptos\"\nversion = \"0.7.0\"\n",
+        ),
+        ("leptos/src/lib.rs", ""),
+    ]);
+    let err = format!("{:#}", config.err().unwrap());
+    assert!(
+        err.contains("The app package uses leptos 0.7.0, which cargo-leptos"),
+        "{err}"
+    );
+    assert!(err.contains("tested: leptos >=0.2.0, <0.6.0"), "{err}");
+}
+
+#[test]
+fn test_workspace_end2end_dir() {
+    let (_dir, config) = load_packages(&[
+        (
+            "Cargo.toml",
+            r#"
+            [workspace]
+            members = ["front", "server"]
+
+            [[workspace.metadata.leptos]]
+            name = "app"
+            bin-package = "server"
+            lib-package = "front"
+            end2end-cmd = "npx playwright test"
+            end2end-dir = "end2end"
+            end2end.smoke = { cmd = "npm run smoke" }
+            "#,
+        ),
+        (
+            "front/Cargo.toml",
+            r#"
+            [package]
+            name = "front"
+            version = "0.1.0"
+
+            [lib]
+            crate-type = ["cdylib", "rlib"]
+            "#,
+        ),
+        ("front/src/lib.rs", ""),
+        (
+            "server/Cargo.toml",
+            "[package]\nname = \"server\"\nversion = \"0.1.0\"\n",
+        ),
+        ("server/src/main.rs", "fn main() {}"),
+    ]);
+    use crate::ext::PathBufExt;
+
+    let config = config.unwrap();
+    let end2end = config.projects[0].end2end.as_ref().unwrap();
+    let dirs: Vec<_> = end2end.suites.iter().map(|s| s.dir.test_string()).collect();
+    assert_eq!(dirs, ["server/end2end", "server/end2end"]);
+}
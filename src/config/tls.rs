@@ -0,0 +1,61 @@
+use std::net::IpAddr;
+
+use camino::Utf8PathBuf;
+
+use crate::ext::anyhow::{bail, Result};
+
+use super::{Opts, ProjectConfig};
+
+/// Serving the proxy and the reload websocket over TLS, with `--https`
+#[derive(Debug)]
+pub struct TlsConfig {
+    /// the tls-cert and tls-key pem files. A self-signed certificate is generated when not set
+    pub cert_key: Option<(Utf8PathBuf, Utf8PathBuf)>,
+    /// the host names and IPs of the generated certificate
+    pub hosts: Vec<String>,
+}
+
+impl TlsConfig {
+    pub fn resolve(cli: &Opts, config: &ProjectConfig) -> Result<Option<Self>> {
+        if !cli.watch.https {
+            return Ok(None);
+        }
+        if config.proxy_addr.is_none() {
+            log::warn!("TLS is only used by the reload websocket. Set a proxy-addr for serving the site over https");
+        }
+        // relative to the configuration file
+        let cert_key = match (&config.tls_cert, &config.tls_key) {
+            (Some(cert), Some(key)) => {
+                Some((config.config_dir.join(cert), config.config_dir.join(key)))
+            }
+            (None, None) => None,
+            _ => bail!("The tls-cert and tls-key need to be set together"),
+        };
+
+        let mut hosts = vec![
+            "localhost".to_string(),
+            "127.0.0.1".to_string(),
+            "::1".to_string(),
+        ];
+        for addr in config.site_addrs.iter().copied().chain(config.proxy_addr) {
+            let ip = addr.ip();
+            if !ip.is_unspecified() && !ip.is_loopback() && !hosts.contains(&ip.to_string()) {
+                hosts.push(ip.to_string());
+            }
+        }
+        Ok(Some(Self { cert_key, hosts }))
+    }
+
+    /// The hosts parsed as IPs, and the others
+    pub fn ips_and_names(&self) -> (Vec<IpAddr>, Vec<String>) {
+        let mut ips = Vec::new();
+        let mut names = Vec::new();
+        for host in &self.hosts {
+            match host.parse() {
+                Ok(ip) => ips.push(ip),
+                Err(_) => names.push(host.clone()),
+            }
+        }
+        (ips, names)
+    }
+}
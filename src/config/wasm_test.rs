@@ -0,0 +1,54 @@
+use camino::Utf8PathBuf;
+use serde::Deserialize;
+
+use super::ProjectConfig;
+
+/// The browser used by `cargo leptos test --wasm`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WasmTestBrowser {
+    #[default]
+    Chrome,
+    Firefox,
+    Safari,
+}
+
+impl WasmTestBrowser {
+    /// the webdriver executable
+    pub fn driver(&self) -> &'static str {
+        match self {
+            Self::Chrome => "chromedriver",
+            Self::Firefox => "geckodriver",
+            Self::Safari => "safaridriver",
+        }
+    }
+
+    /// the env var with the webdriver path read by wasm-bindgen-test-runner
+    pub fn driver_env(&self) -> &'static str {
+        match self {
+            Self::Chrome => "CHROMEDRIVER",
+            Self::Firefox => "GECKODRIVER",
+            Self::Safari => "SAFARIDRIVER",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct WasmTestConfig {
+    pub browser: WasmTestBrowser,
+    /// the webdriver to use, found on the PATH when not set
+    pub driver: Option<Utf8PathBuf>,
+}
+
+impl WasmTestConfig {
+    pub fn new(config: &ProjectConfig) -> Self {
+        Self {
+            browser: config.wasm_test_browser,
+            // relative to the configuration file
+            driver: config
+                .wasm_test_driver
+                .as_ref()
+                .map(|driver| config.config_dir.join(driver)),
+        }
+    }
+}
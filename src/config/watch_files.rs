@@ -0,0 +1,106 @@
+use std::fmt::{self, Display};
+
+use camino::{Utf8Path, Utf8PathBuf};
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use serde::Deserialize;
+
+use crate::ext::anyhow::{Context, Result};
+
+use super::ProjectConfig;
+
+/// A `watch-additional-files` entry: a path or glob, or a table with the action
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum WatchFileConfig {
+    Path(String),
+    WithAction {
+        path: String,
+        #[serde(default)]
+        action: WatchAction,
+    },
+}
+
+/// What a change of an additional watched file triggers
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum WatchAction {
+    RebuildFront,
+    #[default]
+    RestartServer,
+    ReloadBrowserOnly,
+}
+
+impl Display for WatchAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::RebuildFront => write!(f, "rebuild-front"),
+            Self::RestartServer => write!(f, "restart-server"),
+            Self::ReloadBrowserOnly => write!(f, "reload-browser-only"),
+        }
+    }
+}
+
+pub struct WatchFile {
+    /// as configured, for the logs
+    pub pattern: String,
+    /// the dir or file that is watched: the part of the pattern before the first glob
+    pub watched: Utf8PathBuf,
+    matcher: Gitignore,
+    root: Utf8PathBuf,
+    pub action: WatchAction,
+}
+
+impl fmt::Debug for WatchFile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WatchFile")
+            .field("pattern", &self.pattern)
+            .field("watched", &self.watched)
+            .field("action", &self.action)
+            .finish_non_exhaustive()
+    }
+}
+
+impl WatchFile {
+    pub fn resolve(config: &ProjectConfig) -> Result<Vec<Self>> {
+        let mut resolved = Vec::new();
+        for entry in &config.watch_additional_files {
+            let (pattern, action) = match entry {
+                WatchFileConfig::Path(path) => (path, WatchAction::default()),
+                WatchFileConfig::WithAction { path, action } => (path, *action),
+            };
+            // relative to the configuration file
+            let root = config.config_dir.clone();
+            let mut builder = GitignoreBuilder::new(&root);
+            // anchored to the project dir, like a path
+            builder
+                .add_line(None, &format!("/{}", pattern.trim_start_matches('/')))
+                .context(format!("Invalid watch-additional-files entry {pattern:?}"))?;
+            resolved.push(Self {
+                pattern: pattern.clone(),
+                watched: root.join(literal_prefix(pattern)),
+                matcher: builder.build().dot()?,
+                root,
+                action,
+            });
+        }
+        Ok(resolved)
+    }
+
+    /// true if the path, relative to the working dir, is the entry's file or inside its dir
+    pub fn matches(&self, path: &Utf8Path) -> bool {
+        path.starts_with(&self.root)
+            && self
+                .matcher
+                .matched_path_or_any_parents(path, path.is_dir())
+                .is_ignore()
+    }
+}
+
+/// The components of the pattern before the first one with a glob
+fn literal_prefix(pattern: &str) -> Utf8PathBuf {
+    pattern
+        .split('/')
+        .filter(|c| !c.is_empty())
+        .take_while(|c| !c.contains(['*', '?', '[', '{']))
+        .collect()
+}
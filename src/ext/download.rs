@@ -0,0 +1,283 @@
+use std::{
+    env,
+    time::{Duration, Instant},
+};
+
+use bytes::{Bytes, BytesMut};
+use crossterm::tty::IsTty;
+use reqwest::{Client, Proxy, RequestBuilder};
+
+use crate::{
+    ext::{
+        anyhow::{bail, Context, Result},
+        format_bytes,
+        sync::{backoff, sleep_interruptible},
+        StrAdditions,
+    },
+    logger::{self, GRAY, INFO_GREEN},
+    signal::Interrupt,
+};
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(30);
+/// for the whole download, the biggest tools are about 100 MB
+const DOWNLOAD_TIMEOUT: Duration = Duration::from_secs(600);
+const ATTEMPTS: u32 = 3;
+/// doubled at each attempt, with some jitter
+const RETRY_DELAY: Duration = Duration::from_secs(1);
+/// of the progress bar on a terminal
+const REDRAW_INTERVAL: Duration = Duration::from_millis(100);
+/// of the progress log lines, which are also logged at every 10%
+const LOG_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Downloads the url, through the configured proxy or else the one of the HTTPS_PROXY or
+/// HTTP_PROXY env vars that the NO_PROXY doesn't exclude. Connection failures, timeouts and
+/// server errors are retried with a growing delay, and the errors tell which proxy was used.
+/// A checksum mismatch isn't retried, as it is checked by the caller. The progress of the
+/// named downloads is shown, and they are stopped by a shutdown, also between the attempts.
+pub(crate) async fn download(
+    url: &str,
+    configured_proxy: Option<&str>,
+    token: Option<&str>,
+    name: Option<&str>,
+) -> Result<Bytes> {
+    let proxy = configured_proxy
+        .map(str::to_string)
+        .or_else(|| env_proxy(url));
+    let via = match &proxy {
+        Some(proxy) => format!("through the proxy {}", redact(proxy)),
+        None => "without a proxy".to_string(),
+    };
+
+    // the proxy is chosen above, so that the errors can tell it. The GitHub API requires
+    // a user agent.
+    let mut builder = Client::builder()
+        .user_agent(concat!("cargo-leptos/", env!("CARGO_PKG_VERSION")))
+        .connect_timeout(CONNECT_TIMEOUT)
+        .timeout(DOWNLOAD_TIMEOUT)
+        .no_proxy();
+    if let Some(proxy) = &proxy {
+        // with the basic auth of the url, if any
+        let proxy = Proxy::all(proxy).context(format!("Invalid proxy {}", redact(proxy)))?;
+        builder = builder.proxy(proxy);
+    }
+    let client = builder.build().dot()?;
+
+    let mut shutdown = Interrupt::subscribe_shutdown();
+    let mut attempt = 1;
+    loop {
+        let mut request = client.get(url);
+        if let Some(token) = token {
+            request = request.bearer_auth(token);
+        }
+        let result = tokio::select! {
+            result = fetch(request, name) => result,
+            _ = shutdown.recv() => bail!("Download stopped by the shutdown"),
+        };
+        match result {
+            Ok(data) => return Ok(data),
+            Err(e) if attempt < ATTEMPTS && is_retryable(&e) => {
+                let delay = backoff(RETRY_DELAY, attempt);
+                log::warn!(
+                    "Install download {via} failed: {e}. Retrying in {delay:.1?} ({attempt}/{ATTEMPTS})"
+                );
+                if !sleep_interruptible(delay, &mut shutdown).await {
+                    bail!("Download stopped by the shutdown");
+                }
+                attempt += 1;
+            }
+            Err(e) => {
+                let tries = if attempt > 1 {
+                    format!(" after {attempt} attempts")
+                } else {
+                    String::new()
+                };
+                return Err(e).context(format!("Download failed {via}{tries}"));
+            }
+        }
+    }
+}
+
+async fn fetch(request: RequestBuilder, name: Option<&str>) -> reqwest::Result<Bytes> {
+    let mut response = request.send().await?.error_for_status()?;
+    let Some(name) = name else {
+        return response.bytes().await;
+    };
+    let total = response.content_length();
+    let mut data = BytesMut::with_capacity(total.unwrap_or_default() as usize);
+    let mut progress = Progress::new(name, total);
+    while let Some(chunk) = response.chunk().await? {
+        data.extend_from_slice(&chunk);
+        progress.update(data.len() as u64);
+    }
+    progress.finish();
+    Ok(data.freeze())
+}
+
+/// The progress of a download: a bar when stderr is a terminal, else (or in the JSON log
+/// format) a log line every 10% or every few seconds
+struct Progress<'a> {
+    name: &'a str,
+    total: Option<u64>,
+    received: u64,
+    tty: bool,
+    start: Instant,
+    /// of the last redraw or log line
+    last: Instant,
+    /// the tenths logged already
+    tenths: u64,
+}
+
+impl<'a> Progress<'a> {
+    fn new(name: &'a str, total: Option<u64>) -> Self {
+        let now = Instant::now();
+        Self {
+            name,
+            total,
+            received: 0,
+            // the log lines are kept whole in the JSON log format
+            tty: std::io::stderr().is_tty() && !logger::is_json(),
+            start: now,
+            last: now,
+            tenths: 0,
+        }
+    }
+
+    fn update(&mut self, received: u64) {
+        self.received = received;
+        let now = Instant::now();
+        if self.tty {
+            if now - self.last >= REDRAW_INTERVAL {
+                self.last = now;
+                logger::show_progress(&self.bar());
+            }
+            return;
+        }
+        let tenths = self.percent().map_or(0, |percent| percent / 10);
+        if tenths > self.tenths || now - self.last >= LOG_INTERVAL {
+            self.tenths = tenths;
+            self.last = now;
+            log::info!("Install downloading {}", self.status());
+        }
+    }
+
+    fn percent(&self) -> Option<u64> {
+        self.total
+            .filter(|total| *total > 0)
+            .map(|total| (self.received * 100 / total).min(100))
+    }
+
+    fn rate(&self) -> String {
+        let secs = self.start.elapsed().as_secs_f64().max(0.001);
+        format!("{}/s", format_bytes((self.received as f64 / secs) as u64))
+    }
+
+    /// like "sass-1.58.3 45% 2.1 MiB of 4.6 MiB, 1.2 MiB/s"
+    fn status(&self) -> String {
+        let received = format_bytes(self.received);
+        match (self.percent(), self.total) {
+            (Some(percent), Some(total)) => format!(
+                "{} {percent}% {received} of {}, {}",
+                self.name,
+                format_bytes(total),
+                self.rate()
+            ),
+            _ => format!("{} {received}, {}", self.name, self.rate()),
+        }
+    }
+
+    fn bar(&self) -> String {
+        const WIDTH: u64 = 30;
+        let bar = match self.percent() {
+            Some(percent) => {
+                let done = (percent * WIDTH / 100) as usize;
+                format!(
+                    "[{}{}] ",
+                    "=".repeat(done),
+                    " ".repeat(WIDTH as usize - done)
+                )
+            }
+            None => String::new(),
+        };
+        format!(
+            "{} {bar}{}",
+            INFO_GREEN.paint("Install".pad_left_to(12)),
+            self.status()
+        )
+    }
+
+    fn finish(self) {
+        logger::clear_progress();
+        log::info!(
+            "Install downloaded {} {} in {:.1}s",
+            self.name,
+            GRAY.paint(format_bytes(self.received)),
+            self.start.elapsed().as_secs_f64()
+        );
+    }
+}
+
+/// an interrupted download doesn't leave its bar behind
+impl Drop for Progress<'_> {
+    fn drop(&mut self) {
+        if self.tty {
+            logger::clear_progress();
+        }
+    }
+}
+
+fn is_retryable(e: &reqwest::Error) -> bool {
+    match e.status() {
+        Some(status) => status.is_server_error(),
+        None => !e.is_builder(),
+    }
+}
+
+fn env_var(names: &[&str]) -> Option<String> {
+    names
+        .iter()
+        .find_map(|name| env::var(name).ok())
+        .filter(|val| !val.is_empty())
+}
+
+/// The proxy of the env for the url, as curl reads it
+fn env_proxy(url: &str) -> Option<String> {
+    let url = reqwest::Url::parse(url).ok()?;
+    if let Some(no_proxy) = env_var(&["NO_PROXY", "no_proxy"]) {
+        if no_proxy_matches(&no_proxy, url.host_str()?) {
+            return None;
+        }
+    }
+    match url.scheme() {
+        "https" => env_var(&["HTTPS_PROXY", "https_proxy"]),
+        _ => env_var(&["HTTP_PROXY", "http_proxy"]),
+    }
+    .or_else(|| env_var(&["ALL_PROXY", "all_proxy"]))
+}
+
+/// Whether the host is excluded by the comma separated NO_PROXY, where "*" matches all the
+/// hosts and "example.com" or ".example.com" match the domain and its subdomains
+pub(crate) fn no_proxy_matches(no_proxy: &str, host: &str) -> bool {
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    no_proxy
+        .split(',')
+        .map(|entry| entry.trim().trim_start_matches('.'))
+        .filter(|entry| !entry.is_empty())
+        .any(|entry| {
+            entry == "*"
+                || host.eq_ignore_ascii_case(entry)
+                || host
+                    .to_ascii_lowercase()
+                    .ends_with(&format!(".{}", entry.to_ascii_lowercase()))
+        })
+}
+
+/// the proxy url without its password
+pub(crate) fn redact(proxy: &str) -> String {
+    match reqwest::Url::parse(proxy) {
+        Ok(mut url) if url.password().is_some() => {
+            _ = url.set_password(Some("***"));
+            url.to_string()
+        }
+        _ => proxy.to_string(),
+    }
+}
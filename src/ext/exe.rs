@@ -3,23 +3,317 @@ use crate::{
     logger::GRAY,
 };
 use bytes::Bytes;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use std::{
+    cmp::Ordering,
+    collections::{BTreeMap, HashMap, HashSet},
     fs::{self, File},
     io::{Cursor, Write},
     path::{Path, PathBuf},
+    sync::{Mutex, RwLock},
+    time::SystemTime,
 };
 use zip::ZipArchive;
 
-use super::util::os_arch;
+use super::{download::download, util::Platform};
 
 #[cfg(target_family = "unix")]
 use std::os::unix::prelude::PermissionsExt;
 
+/// The version of the wasm-bindgen-cli-support compiled in, as locked in the Cargo.lock,
+/// which the wasm-bindgen-test-runner also needs
+pub const WASM_BINDGEN_VERSION: &str = "0.2.84";
+
+/// The sha256 of the downloads of the default versions, as (name-version, os-arch, digest).
+/// The digests are those of the release assets, not of the extracted binaries.
+const KNOWN_CHECKSUMS: &[(&str, &str, &str)] = &[];
+
+/// The embedded checksum of the download of the name-version for the os-arch
+pub(crate) fn known_checksum(name: &str, platform: &str) -> Option<&'static str> {
+    KNOWN_CHECKSUMS
+        .iter()
+        .find(|(n, p, _)| *n == name && *p == platform)
+        .map(|(_, _, digest)| *digest)
+}
+
+/// The tool-checksums of the config, by name-version and os-arch
+pub type ToolChecksums = BTreeMap<String, BTreeMap<String, String>>;
+
+/// The env vars of the tool-urls, by tool
+pub const TOOL_URL_ENVS: &[(&str, &str)] = &[
+    ("cargo-generate", "LEPTOS_CARGO_GENERATE_URL"),
+    ("sass", "LEPTOS_SASS_URL"),
+    ("wasm-opt", "LEPTOS_WASM_OPT_URL"),
+    ("tailwindcss", "LEPTOS_TAILWIND_URL"),
+    ("oxipng", "LEPTOS_OXIPNG_URL"),
+    (
+        "wasm-bindgen-test-runner",
+        "LEPTOS_WASM_BINDGEN_TEST_RUNNER_URL",
+    ),
+];
+
+/// The bearer token sent to the tool-urls
+pub const TOOL_DOWNLOAD_TOKEN_ENV: &str = "LEPTOS_TOOL_DOWNLOAD_TOKEN";
+
+/// The dir of the downloaded tools, instead of the user cache dir
+pub const CACHE_DIR_ENV: &str = "LEPTOS_CACHE_DIR";
+
+lazy_static::lazy_static! {
+  static ref TOOL_SETTINGS: RwLock<ToolSettings> = RwLock::new(ToolSettings::default());
+  /// the cached binaries whose digest was checked by this process
+  static ref VERIFIED: Mutex<HashSet<PathBuf>> = Mutex::new(HashSet::new());
+  /// the binary chosen for each tool and version by this process
+  static ref CHOSEN: Mutex<HashMap<String, PathBuf>> = Mutex::new(HashMap::new());
+}
+
+const SASS_VERSION: &str = "1.58.3";
+const WASM_OPT_VERSION: &str = "version_112";
+const TAILWIND_VERSION: &str = "v3.3.1";
+
+/// The pinned versions of the tools, from the config of the project or else of the
+/// workspace-level leptos-tool-versions
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct ToolVersions {
+    /// like "112" or "version_112"
+    pub wasm_opt_version: Option<String>,
+    /// like "1.58.3"
+    pub sass_version: Option<String>,
+    /// like "3.3.1" or "v3.3.1"
+    pub tailwind_version: Option<String>,
+}
+
+impl ToolVersions {
+    /// The unset versions are taken from the defaults
+    pub fn or(self, defaults: &ToolVersions) -> Self {
+        Self {
+            wasm_opt_version: self
+                .wasm_opt_version
+                .or_else(|| defaults.wasm_opt_version.clone()),
+            sass_version: self.sass_version.or_else(|| defaults.sass_version.clone()),
+            tailwind_version: self
+                .tailwind_version
+                .or_else(|| defaults.tailwind_version.clone()),
+        }
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        let versions = [
+            ("wasm-opt-version", &self.wasm_opt_version),
+            ("sass-version", &self.sass_version),
+            ("tailwind-version", &self.tailwind_version),
+        ];
+        for (key, version) in versions {
+            if let Some(version) = version {
+                if parse_version(version).is_none() {
+                    bail!("Invalid {key} {version:?}, expected a version like 1.58.3");
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The version of wasm-opt as named by the binaryen releases, and whether it is pinned
+    fn wasm_opt(&self) -> (String, bool) {
+        match &self.wasm_opt_version {
+            Some(version) => {
+                let number = version.trim().trim_start_matches("version_");
+                (format!("version_{number}"), true)
+            }
+            None => (WASM_OPT_VERSION.to_string(), false),
+        }
+    }
+
+    fn sass(&self) -> (String, bool) {
+        match &self.sass_version {
+            Some(version) => (version.trim().trim_start_matches('v').to_string(), true),
+            None => (SASS_VERSION.to_string(), false),
+        }
+    }
+
+    /// as named by the tailwindcss releases, starting with a v
+    fn tailwind(&self) -> (String, bool) {
+        match &self.tailwind_version {
+            Some(version) => (format!("v{}", version.trim().trim_start_matches('v')), true),
+            None => (TAILWIND_VERSION.to_string(), false),
+        }
+    }
+}
+
+/// The system-tools config of a tool
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct SystemToolConfig {
+    /// overrides prefer-system-tools for the tool
+    pub prefer: Option<bool>,
+    /// the lowest version of the system tool that is used
+    pub min_version: Option<String>,
+    /// the version that the system tool must have, like "1.58" for any 1.58.x
+    pub version: Option<String>,
+    /// the binary to use instead of looking for it on the PATH
+    pub path: Option<PathBuf>,
+}
+
+/// How the tools are found and downloaded, from the config. Without a config, as for `new`,
+/// the system tools are preferred and the tool-urls and the token are taken from the env.
+pub struct ToolSettings {
+    /// prefer-system-tools
+    pub prefer_system: bool,
+    /// `--no-system-tools`, which overrides the system-tools, for using the managed tools
+    pub no_system: bool,
+    /// the system-tools, by tool
+    pub system: BTreeMap<String, SystemToolConfig>,
+    /// the tool-checksums
+    pub checksums: ToolChecksums,
+    /// `--insecure-skip-checksum`
+    pub skip_unknown: bool,
+    /// the tool-urls: url templates of the mirrors, by tool
+    pub urls: BTreeMap<String, String>,
+    /// the bearer token for the mirrors
+    pub token: Option<String>,
+    /// the download-proxy, else the one of the env
+    pub proxy: Option<String>,
+    /// `--offline`, LEPTOS_OFFLINE or CARGO_NET_OFFLINE: the tools are never downloaded
+    pub offline: bool,
+    /// the tool-cache-dir
+    pub cache_dir: Option<PathBuf>,
+}
+
+impl Default for ToolSettings {
+    fn default() -> Self {
+        Self {
+            prefer_system: true,
+            no_system: false,
+            system: BTreeMap::new(),
+            checksums: ToolChecksums::new(),
+            skip_unknown: false,
+            urls: BTreeMap::new(),
+            token: None,
+            proxy: None,
+            offline: false,
+            cache_dir: None,
+        }
+    }
+}
+
+pub fn set_tool_settings(settings: ToolSettings) {
+    *TOOL_SETTINGS.write().unwrap() = settings;
+}
+
+/// Expands the `{version}`, `{target}` and `{filename}` placeholders of a tool-url
+pub(crate) fn expand_url(
+    template: &str,
+    version: &str,
+    target: &str,
+    filename: &str,
+) -> Result<String> {
+    let url = template
+        .replace("{version}", version)
+        .replace("{target}", target)
+        .replace("{filename}", filename);
+    if url.contains('{') || url.contains('}') {
+        bail!(
+            "Unknown placeholder in the tool-url {template:?}, which gives {url:?}. The placeholders are {{version}}, {{target}} and {{filename}}"
+        );
+    }
+    Ok(url)
+}
+
+/// The numbers of the first version in the output of `--version`, like [1, 58, 3] for
+/// "1.58.3 compiled with dart2js" or [112] for "wasm-opt version 112 (version_112)"
+pub(crate) fn parse_version(output: &str) -> Option<Vec<u64>> {
+    let version = output
+        .split(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .map(|word| word.trim_matches('.'))
+        .find(|word| !word.is_empty())?;
+    version.split('.').map(|n| n.parse().ok()).collect()
+}
+
+/// Whether the version of a system tool is acceptable for the system-tools config of the tool
+pub(crate) fn version_accepted(found: &[u64], config: &SystemToolConfig) -> Result<bool> {
+    let parse = |key: &str, version: &str| {
+        parse_version(version).context(format!("Invalid system-tools {key} {version:?}"))
+    };
+    if let Some(min) = &config.min_version {
+        if cmp_versions(found, &parse("min-version", min)?) == Ordering::Less {
+            return Ok(false);
+        }
+    }
+    if let Some(version) = &config.version {
+        if !found.starts_with(&parse("version", version)?) {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+/// compares the versions as if the shorter one had trailing zeros
+fn cmp_versions(a: &[u64], b: &[u64]) -> Ordering {
+    (0..a.len().max(b.len()))
+        .map(|i| a.get(i).unwrap_or(&0).cmp(b.get(i).unwrap_or(&0)))
+        .find(|ordering| ordering.is_ne())
+        .unwrap_or(Ordering::Equal)
+}
+
+fn format_version(version: &[u64]) -> String {
+    version
+        .iter()
+        .map(u64::to_string)
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
+fn sha256_hex(data: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(data))
+}
+
+/// Checks the downloaded data against the expected sha256. Without one the download is
+/// refused, unless `skip_unknown` is set, which is logged.
+pub(crate) fn check_checksum(
+    name: &str,
+    expected: Option<&str>,
+    data: &[u8],
+    skip_unknown: bool,
+) -> Result<()> {
+    let actual = sha256_hex(data);
+    match expected {
+        Some(expected) if expected.trim().eq_ignore_ascii_case(&actual) => {
+            log::debug!("Install verified {name} {}", GRAY.paint(format!("sha256 {actual}")));
+            Ok(())
+        }
+        Some(expected) => bail!(
+            "Checksum mismatch of the {name} download: expected sha256 {}, got {actual}",
+            expected.trim()
+        ),
+        None if skip_unknown => {
+            log::warn!(
+                "Install INSECURE: using the {name} download without verifying it, as --insecure-skip-checksum is set. sha256 {actual}"
+            );
+            Ok(())
+        }
+        None => bail!(
+            "No known checksum for the {name} download, which has the sha256 {actual}. Once checked against the release, add it to the tool-checksums of the config, or use --insecure-skip-checksum"
+        ),
+    }
+}
+
 #[derive(Debug)]
 pub struct ExeMeta {
     name: &'static str,
-    version: &'static str,
+    version: String,
+    /// the version is pinned by the config
+    pinned: bool,
+    /// the GitHub repo and the tag of the release
+    repo: &'static str,
+    tag: String,
+    /// the default url, on GitHub
     url: String,
+    /// the platform as named by the release assets
+    target: String,
+    platform: Platform,
+    /// whether the release has a build for the platform
+    available: bool,
     exe: String,
     manual: &'static str,
 }
@@ -29,15 +323,174 @@ impl ExeMeta {
         which::which(&self.name).ok()
     }
 
-    fn get_name(&self) -> String {
+    /// The tool of the system-tools path or else of the PATH, when the system tools are preferred
+    /// and its version is accepted
+    async fn system_tool(&self) -> Result<Option<PathBuf>> {
+        let mut config = {
+            let settings = TOOL_SETTINGS.read().unwrap();
+            let config = settings.system.get(self.name).cloned().unwrap_or_default();
+            let prefer = config.prefer.unwrap_or(settings.prefer_system);
+            if settings.no_system || (config.path.is_none() && !prefer) {
+                log::debug!("Command not looking for {} on the PATH", self.name);
+                return Ok(None);
+            }
+            config
+        };
+        // a pinned version is also required from the system tool, unless configured otherwise
+        if self.pinned && config.min_version.is_none() && config.version.is_none() {
+            config.version = Some(self.version.clone());
+        }
+        let path = match &config.path {
+            Some(path) if path.exists() => path.clone(),
+            Some(path) => bail!(
+                "The system-tools path of {} doesn't exist: {}",
+                self.name,
+                path.display()
+            ),
+            None => match self.from_global_path() {
+                Some(path) => path,
+                None => return Ok(None),
+            },
+        };
+        if config.min_version.is_none() && config.version.is_none() {
+            return Ok(Some(path));
+        }
+        let output = tokio::process::Command::new(&path)
+            .arg("--version")
+            .output()
+            .await
+            .context(format!("Could not run {path:?} --version"))?;
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let Some(found) = parse_version(&stdout) else {
+            log::info!(
+                "Command ignoring {} as its version could not be read from {:?}",
+                path.display(),
+                stdout.trim()
+            );
+            return Ok(None);
+        };
+        if version_accepted(&found, &config)? {
+            Ok(Some(path))
+        } else {
+            log::info!(
+                "Command ignoring the system {} {} {}, which doesn't match the system-tools {}",
+                self.name,
+                format_version(&found),
+                GRAY.paint(path.to_string_lossy()),
+                [
+                    config.min_version.map(|v| format!("min-version {v}")),
+                    config.version.map(|v| format!("version {v}")),
+                ]
+                .into_iter()
+                .flatten()
+                .collect::<Vec<_>>()
+                .join(" and ")
+            );
+            Ok(None)
+        }
+    }
+
+    /// the name and version, which is also the dir of the tool in the cache
+    pub fn get_name(&self) -> String {
         format!("{}-{}", &self.name, &self.version)
     }
 
-    async fn cached(&self) -> Result<PathBuf> {
+    /// the name of the release asset, which is also used for finding out the archive type
+    fn filename(&self) -> &str {
+        self.url.rsplit('/').next().unwrap_or_default()
+    }
+
+    /// The url given by the tool-url of the mirror, if any, else the default one
+    fn download_url(&self, settings: &ToolSettings) -> Result<(String, bool)> {
+        let configured = settings.urls.get(self.name).cloned().or_else(|| {
+            TOOL_URL_ENVS
+                .iter()
+                .find(|(name, _)| *name == self.name)
+                .and_then(|(_, env)| std::env::var(env).ok())
+        });
+        match configured {
+            Some(template) => {
+                let url = expand_url(&template, &self.version, &self.target, self.filename())
+                    .context(format!("Invalid tool-url of {}", self.name))?;
+                Ok((url, true))
+            }
+            None => Ok((self.url.clone(), false)),
+        }
+    }
+
+    /// When a pinned version has no release asset for this platform, the message listing the
+    /// assets of its release
+    async fn missing_platform(&self) -> Option<String> {
+        if !self.pinned {
+            return None;
+        }
+        let url = format!(
+            "https://api.github.com/repos/{}/releases/tags/{}",
+            self.repo, self.tag
+        );
+        let proxy = TOOL_SETTINGS.read().unwrap().proxy.clone();
+        let data = match download(&url, proxy.as_deref(), None, None).await {
+            Ok(data) => data,
+            Err(e) => {
+                log::debug!(
+                    "Install could not list the release assets of {}: {e:#}",
+                    self.get_name()
+                );
+                return None;
+            }
+        };
+        let release: serde_json::Value = serde_json::from_slice(&data).ok()?;
+        let assets: Vec<&str> = release["assets"]
+            .as_array()?
+            .iter()
+            .filter_map(|asset| asset["name"].as_str())
+            .collect();
+        if assets.contains(&self.filename()) {
+            return None;
+        }
+        Some(format!(
+            "The pinned {} {} has no release for {} ({}), its release has: {}",
+            self.name,
+            self.version,
+            self.platform,
+            self.filename(),
+            assets.join(", ")
+        ))
+    }
+
+    /// The configured checksum of the download for this platform, or else the known one
+    fn expected_checksum(&self, settings: &ToolSettings) -> Option<String> {
+        let name = self.get_name();
+        let platform = format!("{}-{}", self.platform.os, self.platform.arch);
+        if let Some(digest) = settings
+            .checksums
+            .get(&name)
+            .and_then(|by_platform| by_platform.get(&platform))
+        {
+            return Some(digest.clone());
+        }
+        known_checksum(&name, &platform).map(str::to_string)
+    }
+
+    /// The binary in the cache, downloaded when it isn't there or is corrupted
+    pub async fn cached(&self) -> Result<PathBuf> {
         let cache_dir = get_cache_dir()?.join(self.get_name());
         self._with_cache_dir(&cache_dir).await
     }
 
+    pub fn is_cached(&self) -> bool {
+        self.cached_path().is_some()
+    }
+
+    /// The binary in the cache, without downloading it
+    fn cached_path(&self) -> Option<PathBuf> {
+        let exe_dir = get_cache_dir()
+            .ok()?
+            .join(self.get_name())
+            .join(self.get_name());
+        Some(exe_dir.join(&self.exe)).filter(|path| path.exists())
+    }
+
     async fn _with_cache_dir(&self, cache_dir: &Path) -> Result<PathBuf> {
         let exe_dir = cache_dir.join(self.get_name());
         let c = ExeCache {
@@ -51,6 +504,31 @@ impl ExeMeta {
     pub async fn with_cache_dir(&self, cache_dir: &Path) -> Result<PathBuf> {
         self._with_cache_dir(cache_dir).await
     }
+
+    /// The binary in the cache dir of [`Self::with_cache_dir`]
+    #[cfg(test)]
+    pub fn cache_path(&self, cache_dir: &Path) -> PathBuf {
+        cache_dir.join(self.get_name()).join(&self.exe)
+    }
+}
+
+/// The binary of a tool that a build would use, as `--version -v` prints it
+#[derive(Debug, Clone, Serialize)]
+pub struct ToolStatus {
+    pub name: &'static str,
+    /// none when the version of the system tool could not be read
+    pub version: Option<String>,
+    pub source: ToolSource,
+    pub path: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ToolSource {
+    System,
+    Cached,
+    /// downloaded when a build needs it
+    NotCached,
 }
 
 pub struct ExeCache<'a> {
@@ -69,38 +547,96 @@ impl<'a> ExeCache<'a> {
         Ok(exe_path)
     }
 
-    async fn fetch_archive(&self) -> Result<Bytes> {
-        log::debug!(
-            "Install downloading {} {}",
-            self.meta.name,
-            GRAY.paint(&self.meta.url)
-        );
-        let data = reqwest::get(&self.meta.url).await?.bytes().await?;
-        Ok(data)
+    /// the sha256 of the binary, stored next to it after the download
+    fn digest_path(exe_path: &Path) -> PathBuf {
+        let mut path = exe_path.as_os_str().to_owned();
+        path.push(".sha256");
+        PathBuf::from(path)
+    }
+
+    /// Compares the cached binary with the digest stored at the download, once per process.
+    /// A binary cached before the digests were stored gets its digest stored the first time.
+    fn verify_cached(&self, exe_path: &Path) -> Result<()> {
+        if VERIFIED.lock().unwrap().contains(exe_path) {
+            return Ok(());
+        }
+        let digest_path = Self::digest_path(exe_path);
+        let actual = sha256_hex(&fs::read(exe_path).dot()?);
+        match fs::read_to_string(&digest_path) {
+            Ok(stored) if stored.trim() != actual => bail!(
+                "The cached {exe_path:?} is corrupted: expected sha256 {}, got {actual}",
+                stored.trim()
+            ),
+            Ok(_) => {}
+            Err(_) => {
+                log::debug!(
+                    "Install storing the checksum of the cached {exe_path:?} {}",
+                    GRAY.paint(format!("sha256 {actual}"))
+                );
+                fs::write(&digest_path, &actual)
+                    .context(format!("Could not write {digest_path:?}"))?;
+            }
+        }
+        VERIFIED.lock().unwrap().insert(exe_path.to_path_buf());
+        Ok(())
     }
 
-    fn extract_downloaded(&self, data: &Bytes) -> Result<()> {
-        if self.meta.url.ends_with(".zip") {
-            extract_zip(data, &self.exe_dir)?;
-        } else if self.meta.url.ends_with(".tar.gz") {
-            extract_tar(data, &self.exe_dir)?;
+    fn verify_download(&self, data: &Bytes) -> Result<()> {
+        let settings = TOOL_SETTINGS.read().unwrap();
+        let expected = self.meta.expected_checksum(&settings);
+        check_checksum(
+            &self.meta.get_name(),
+            expected.as_deref(),
+            data,
+            settings.skip_unknown,
+        )
+    }
+
+    fn remove_exe_dir(&self) {
+        if self.exe_dir.exists() {
+            if let Err(e) = fs::remove_dir_all(&self.exe_dir) {
+                log::warn!("Install could not remove {:?}: {e}", self.exe_dir);
+            }
+        }
+    }
+
+    async fn fetch_archive(&self, url: &str, mirrored: bool) -> Result<Bytes> {
+        log::debug!("Install downloading {} {}", self.meta.name, GRAY.paint(url));
+        let (proxy, token) = {
+            let settings = TOOL_SETTINGS.read().unwrap();
+            (settings.proxy.clone(), settings.token.clone())
+        };
+        // only sent to the mirrors
+        let token = token
+            .or_else(|| std::env::var(TOOL_DOWNLOAD_TOKEN_ENV).ok())
+            .filter(|_| mirrored);
+        let name = self.meta.get_name();
+        download(url, proxy.as_deref(), token.as_deref(), Some(&name)).await
+    }
+
+    fn extract_downloaded(&self, data: &Bytes, dir: &Path) -> Result<()> {
+        let filename = self.meta.filename();
+        if filename.ends_with(".zip") {
+            extract_zip(data, dir)?;
+        } else if filename.ends_with(".tar.gz") {
+            extract_tar(data, dir)?;
         } else {
-            self.write_binary(&data)
+            self.write_binary(&data, dir)
                 .context(format!("Could not write binary {}", self.meta.get_name()))?;
         }
 
         log::debug!(
             "Install decompressing {} {}",
             self.meta.name,
-            GRAY.paint(self.exe_dir.to_string_lossy())
+            GRAY.paint(dir.to_string_lossy())
         );
 
         Ok(())
     }
 
-    fn write_binary(&self, data: &Bytes) -> Result<()> {
-        fs::create_dir_all(&self.exe_dir).unwrap();
-        let path = self.exe_dir.join(Path::new(&self.meta.exe));
+    fn write_binary(&self, data: &Bytes, dir: &Path) -> Result<()> {
+        fs::create_dir_all(dir).unwrap();
+        let path = dir.join(Path::new(&self.meta.exe));
         let mut file = File::create(&path).unwrap();
         file.write_all(&data)
             .context(format!("Error writing binary file: {:?}", path))?;
@@ -119,28 +655,106 @@ impl<'a> ExeCache<'a> {
     async fn download(&self) -> Result<PathBuf> {
         log::info!("Command installing {} ...", self.meta.get_name());
 
-        let data = self
-            .fetch_archive()
-            .await
-            .context(format!("Could not download {}", self.meta.get_name()))?;
+        let (url, mirrored) = {
+            let settings = TOOL_SETTINGS.read().unwrap();
+            if settings.offline {
+                let name = self.meta.get_name();
+                bail!(
+                    "{name} is not cached and isn't downloaded when offline. Prefetch it with `cargo leptos tools update {}` while online",
+                    self.meta.name
+                );
+            }
+            self.meta.download_url(&settings)?
+        };
+        if !self.meta.available && !mirrored {
+            let (name, platform) = (self.meta.name, self.meta.platform);
+            bail!(
+                "{} has no release for {platform}. Install {name} and put it on the PATH with prefer-system-tools, or set its path with system-tools = {{ {name} = {{ path = \"...\" }} }}, or set a tool-url of a mirror that has a build for {platform}",
+                self.meta.get_name(),
+            );
+        }
+        let data = match self.fetch_archive(&url, mirrored).await {
+            Ok(data) => data,
+            Err(e) => {
+                let e = e.context(format!(
+                    "Could not download {} from {url}",
+                    self.meta.get_name()
+                ));
+                return Err(match self.meta.missing_platform().await {
+                    Some(available) => e.context(available),
+                    None => e,
+                });
+            }
+        };
 
-        self.extract_downloaded(&data)
-            .context(format!("Could not extract {}", self.meta.get_name()))?;
+        // the data is only in memory until it is verified
+        self.verify_download(&data)?;
 
-        let binary_path = self.exe_in_cache().context(format!(
-            "Binary downloaded and extracted but could still not be found at {:?}",
-            self.exe_dir
-        ))?;
+        // extracted beside the exe dir and then moved into place, so that the other
+        // processes sharing the cache never see a partial extraction
+        let tmp_dir = self.tmp_dir();
+        let installed = self.install(&data, &tmp_dir).context(format!(
+            "Could not install {} from {url}",
+            self.meta.get_name()
+        ));
+        if tmp_dir.exists() {
+            if let Err(e) = fs::remove_dir_all(&tmp_dir) {
+                log::warn!("Install could not remove {tmp_dir:?}: {e}");
+            }
+        }
+        let binary_path = installed?;
+        VERIFIED.lock().unwrap().insert(binary_path.clone());
         log::info!("Command {} installed.", self.meta.get_name());
         Ok(binary_path)
     }
 
+    /// the dir of the extraction by this process
+    fn tmp_dir(&self) -> PathBuf {
+        let mut name = self.exe_dir.as_os_str().to_owned();
+        name.push(format!(".{}.tmp", std::process::id()));
+        PathBuf::from(name)
+    }
+
+    fn install(&self, data: &Bytes, tmp_dir: &Path) -> Result<PathBuf> {
+        if tmp_dir.exists() {
+            fs::remove_dir_all(tmp_dir).dot()?;
+        }
+        self.extract_downloaded(data, tmp_dir)?;
+
+        let tmp_binary = tmp_dir.join(&self.meta.exe);
+        if !tmp_binary.exists() {
+            bail!("Binary downloaded and extracted but could still not be found at {tmp_binary:?}");
+        }
+        let digest = sha256_hex(&fs::read(&tmp_binary).dot()?);
+        fs::write(Self::digest_path(&tmp_binary), digest).dot()?;
+
+        if let Err(e) = fs::rename(tmp_dir, &self.exe_dir) {
+            // another process installed it meanwhile
+            match self.exe_in_cache() {
+                Ok(_) => log::debug!(
+                    "Install {} was installed by another process meanwhile",
+                    self.meta.get_name()
+                ),
+                Err(_) => {
+                    return Err(e)
+                        .context(format!("Could not move {tmp_dir:?} to {:?}", self.exe_dir))
+                }
+            }
+        }
+        self.exe_in_cache()
+    }
+
     async fn get(&self) -> Result<PathBuf> {
         if let Ok(path) = self.exe_in_cache() {
-            Ok(path)
-        } else {
-            self.download().await
+            match self.verify_cached(&path) {
+                Ok(()) => return Ok(path),
+                Err(e) => {
+                    log::warn!("Install {e:#}. Downloading it again");
+                    self.remove_exe_dir();
+                }
+            }
         }
+        self.download().await
     }
 }
 
@@ -171,10 +785,16 @@ fn extract_zip(src: &Bytes, dest: &Path) -> Result<()> {
 /// | Linux    | /home/alice/.cache/NAME           |
 /// | macOS    | /Users/Alice/Library/Caches/NAME  |
 /// | Windows  | C:\Users\Alice\AppData\Local\NAME |
-fn get_cache_dir() -> Result<PathBuf> {
-    let dir = dirs::cache_dir()
-        .ok_or_else(|| anyhow::anyhow!("Cache directory does not exist"))?
-        .join("cargo-leptos");
+///
+/// The tool-cache-dir config or the LEPTOS_CACHE_DIR env var override it.
+pub fn get_cache_dir() -> Result<PathBuf> {
+    let configured = TOOL_SETTINGS.read().unwrap().cache_dir.clone();
+    let dir = match configured.or_else(|| std::env::var_os(CACHE_DIR_ENV).map(PathBuf::from)) {
+        Some(dir) => dir,
+        None => dirs::cache_dir()
+            .ok_or_else(|| anyhow::anyhow!("Cache directory does not exist"))?
+            .join("cargo-leptos"),
+    };
 
     if !dir.exists() {
         std::fs::create_dir_all(&dir).context(format!("Could not create dir {dir:?}"))?;
@@ -183,143 +803,390 @@ fn get_cache_dir() -> Result<PathBuf> {
     Ok(dir)
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Exe {
     CargoGenerate,
     Sass,
     WasmOpt,
     Tailwind,
+    Oxipng,
+    WasmBindgenTestRunner,
+}
+
+/// A version of a tool in the cache
+#[derive(Debug)]
+pub struct CachedTool {
+    pub exe: Exe,
+    pub version: String,
+    /// the dir of the version, named by the tool and the version
+    pub dir: PathBuf,
+    pub size: u64,
+    pub modified: Option<SystemTime>,
+}
+
+impl CachedTool {
+    pub fn name(&self) -> String {
+        format!("{}-{}", self.exe.name(), self.version)
+    }
+}
+
+/// The tool versions in the cache dir, by tool and version
+pub fn cached_tools() -> Result<Vec<CachedTool>> {
+    let mut found = Vec::new();
+    for entry in fs::read_dir(get_cache_dir()?).dot()? {
+        let entry = entry.dot()?;
+        if !entry.file_type().dot()?.is_dir() {
+            continue;
+        }
+        let dir_name = entry.file_name().to_string_lossy().to_string();
+        let Some((exe, version)) = Exe::ALL.into_iter().find_map(|exe| {
+            let version = dir_name.strip_prefix(exe.name())?.strip_prefix('-')?;
+            Some((exe, version.to_string()))
+        }) else {
+            continue;
+        };
+        let dir = entry.path();
+        found.push(CachedTool {
+            exe,
+            version,
+            size: dir_size(&dir),
+            modified: entry.metadata().and_then(|m| m.modified()).ok(),
+            dir,
+        });
+    }
+    found.sort_by(|a, b| (a.exe.name(), &a.version).cmp(&(b.exe.name(), &b.version)));
+    Ok(found)
+}
+
+/// the size of the files in the dir, without following the symlinks
+pub fn dir_size(dir: &Path) -> u64 {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return 0;
+    };
+    entries
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let metadata = fs::symlink_metadata(entry.path()).ok()?;
+            Some(if metadata.is_dir() {
+                dir_size(&entry.path())
+            } else {
+                metadata.len()
+            })
+        })
+        .sum()
 }
 
 impl Exe {
-    pub async fn get(&self) -> Result<PathBuf> {
-        let meta = self.meta()?;
+    pub const ALL: [Exe; 6] = [
+        Exe::CargoGenerate,
+        Exe::Sass,
+        Exe::WasmOpt,
+        Exe::Tailwind,
+        Exe::Oxipng,
+        Exe::WasmBindgenTestRunner,
+    ];
+
+    /// the name of the tool in the config and in the cache
+    pub fn name(&self) -> &'static str {
+        match self {
+            Exe::CargoGenerate => "cargo-generate",
+            Exe::Sass => "sass",
+            Exe::WasmOpt => "wasm-opt",
+            Exe::Tailwind => "tailwindcss",
+            Exe::Oxipng => "oxipng",
+            Exe::WasmBindgenTestRunner => "wasm-bindgen-test-runner",
+        }
+    }
 
-        let path = if let Some(path) = meta.from_global_path() {
+    /// Which binary of the tool would be used, without downloading it: the system one, else the
+    /// cached one, or the version that would be downloaded when it isn't cached
+    pub async fn status(&self, versions: &ToolVersions) -> Result<ToolStatus> {
+        let meta = self.meta(versions)?;
+        if let Some(path) = meta.system_tool().await? {
+            let version = tokio::process::Command::new(&path)
+                .arg("--version")
+                .output()
+                .await
+                .ok()
+                .and_then(|output| parse_version(&String::from_utf8_lossy(&output.stdout)))
+                .map(|version| format_version(&version));
+            return Ok(ToolStatus {
+                name: meta.name,
+                version,
+                source: ToolSource::System,
+                path: Some(path),
+            });
+        }
+        let path = meta.cached_path();
+        Ok(ToolStatus {
+            name: meta.name,
+            version: Some(meta.version.clone()),
+            source: if path.is_some() {
+                ToolSource::Cached
+            } else {
+                ToolSource::NotCached
+            },
+            path,
+        })
+    }
+
+    /// The binary of the tool, with the pinned version if any. The chosen binary is logged the
+    /// first time.
+    pub async fn get(&self, versions: &ToolVersions) -> Result<PathBuf> {
+        let meta = self.meta(versions)?;
+        if let Some(path) = CHOSEN.lock().unwrap().get(&meta.get_name()) {
+            return Ok(path.clone());
+        }
+
+        let path = if let Some(path) = meta.system_tool().await? {
+            let version = if meta.pinned {
+                format!(" {} (pinned)", meta.version)
+            } else {
+                String::new()
+            };
+            log::info!(
+                "Command using the system {}{version} {}",
+                &meta.name,
+                GRAY.paint(path.to_string_lossy())
+            );
             path
         } else {
             if cfg!(feature = "no_downloads") {
                 bail!("{} is required but was not found. Please install it using your OS's tool of choice", &meta.name);
             } else {
-                meta.cached().await.context(meta.manual)?
+                let path = meta.cached().await.context(meta.manual)?;
+                log::info!(
+                    "Command using the downloaded {} {}{} {}",
+                    &meta.name,
+                    &meta.version,
+                    if meta.pinned { " (pinned)" } else { "" },
+                    GRAY.paint(path.to_string_lossy())
+                );
+                path
             }
         };
 
-        log::debug!(
-            "Command using {} {}. {}",
-            &meta.name,
-            &meta.version,
-            GRAY.paint(path.to_string_lossy())
-        );
-
+        CHOSEN.lock().unwrap().insert(meta.get_name(), path.clone());
         Ok(path)
     }
 
-    pub fn meta(&self) -> Result<ExeMeta> {
-        let (target_os, target_arch) = os_arch().unwrap();
+    /// The platform of each tool's release assets, or None when the release has no build that
+    /// runs on the platform. Windows on ARM runs the x86_64 builds emulated, and so does macOS
+    /// on ARM with rosetta. The static musl builds also run on a glibc linux.
+    pub(crate) fn release_target(&self, platform: &Platform) -> Option<&'static str> {
+        let target = match self {
+            Exe::CargoGenerate => match (platform.os, platform.arch, platform.musl) {
+                ("macos", "aarch64", _) => "aarch64-apple-darwin",
+                ("macos", "x86_64", _) => "x86_64-apple-darwin",
+                ("linux", "aarch64", false) => "aarch64-unknown-linux-gnu",
+                ("linux", "x86_64", false) => "x86_64-unknown-linux-gnu",
+                ("windows", _, _) => "x86_64-pc-windows-msvc",
+                _ => return None,
+            },
+            // the dart runtime of the linux builds needs glibc
+            Exe::Sass => match (platform.os, platform.arch, platform.musl) {
+                ("macos", "aarch64", _) => "macos-arm64",
+                ("macos", "x86_64", _) => "macos-x64",
+                ("linux", "aarch64", false) => "linux-arm64",
+                ("linux", "x86_64", false) => "linux-x64",
+                ("windows", _, _) => "windows-x64",
+                _ => return None,
+            },
+            // binaryen has no aarch64 linux build
+            Exe::WasmOpt => match (platform.os, platform.arch) {
+                ("macos", "aarch64") => "arm64-macos",
+                ("macos", "x86_64") => "x86_64-macos",
+                ("linux", "x86_64") => "x86_64-linux",
+                ("windows", _) => "x86_64-windows",
+                _ => return None,
+            },
+            // the standalone cli is a node binary, built for glibc
+            Exe::Tailwind => match (platform.os, platform.arch, platform.musl) {
+                ("macos", "aarch64", _) => "macos-arm64",
+                ("macos", "x86_64", _) => "macos-x64",
+                ("linux", "aarch64", false) => "linux-arm64",
+                ("linux", "x86_64", false) => "linux-x64",
+                ("windows", "aarch64", _) => "windows-arm64",
+                ("windows", "x86_64", _) => "windows-x64",
+                _ => return None,
+            },
+            // there is no aarch64 macos release
+            Exe::Oxipng => match (platform.os, platform.arch, platform.musl) {
+                ("macos", _, _) => "x86_64-apple-darwin",
+                ("linux", "aarch64", false) => "aarch64-unknown-linux-gnu",
+                ("linux", "x86_64", _) => "x86_64-unknown-linux-musl",
+                ("windows", _, _) => "x86_64-pc-windows-msvc",
+                _ => return None,
+            },
+            Exe::WasmBindgenTestRunner => match (platform.os, platform.arch, platform.musl) {
+                ("macos", "aarch64", _) => "aarch64-apple-darwin",
+                ("macos", "x86_64", _) => "x86_64-apple-darwin",
+                ("linux", "aarch64", false) => "aarch64-unknown-linux-gnu",
+                ("linux", "x86_64", _) => "x86_64-unknown-linux-musl",
+                ("windows", _, _) => "x86_64-pc-windows-msvc",
+                _ => return None,
+            },
+        };
+        Some(target)
+    }
+
+    pub fn meta(&self, versions: &ToolVersions) -> Result<ExeMeta> {
+        Ok(self.meta_for(versions, Platform::current()?))
+    }
+
+    /// The meta of the tool for the platform. When the release has no build for it, the
+    /// target is the rust triple of the platform, so that a tool-url mirror can provide one.
+    pub(crate) fn meta_for(&self, versions: &ToolVersions, platform: Platform) -> ExeMeta {
+        let release_target = self.release_target(&platform);
+        let available = release_target.is_some();
+        let target = release_target.map_or_else(|| platform.to_string(), str::to_string);
 
-        let exe = match self {
+        match self {
             Exe::CargoGenerate => {
                 // There's a problem with upgrading cargo-generate because the tar file cannot be extracted
                 // due to missing support for https://github.com/alexcrichton/tar-rs/pull/298
                 // The tar extracts ok, but contains a folder `GNUSparseFile.0` which contains a file `cargo-generate`
                 // that has not been fully extracted.
-                let version = "0.17.3";
-
-                let target = match (target_os, target_arch) {
-                    ("macos", "aarch64") => "aarch64-apple-darwin",
-                    ("linux", "aarch64") => "aarch64-unknown-linux-gnu",
-                    ("macos", "x86_64") => "x86_64-apple-darwin",
-                    ("windows", "x86_64") => "x86_64-pc-windows-msvc",
-                    ("linux", "x86_64") => "x86_64-unknown-linux-gnu",
-                    _ => bail!("No cargo-generate tar binary found for {target_os} {target_arch}"),
-                };
+                let version = "0.17.3".to_string();
 
-                let exe = match target_os {
+                let exe = match platform.os {
                     "windows" => "cargo-generate.exe".to_string(),
                     _ => "cargo-generate".to_string(),
                 };
                 let url = format!("https://github.com/cargo-generate/cargo-generate/releases/download/v{version}/cargo-generate-v{version}-{target}.tar.gz");
                 ExeMeta {
                     name: "cargo-generate",
+                    tag: format!("v{version}"),
                     version,
+                    pinned: false,
+                    repo: "cargo-generate/cargo-generate",
                     url,
+                    target,
+                    platform,
+                    available,
                     exe,
                     manual: "Try manually installing cargo-generate: https://github.com/cargo-generate/cargo-generate#installation"
                 }
             }
             Exe::Sass => {
-                let version = "1.58.3";
-                let url = match (target_os, target_arch) {
-                    ("windows", "x86_64") => format!("https://github.com/sass/dart-sass/releases/download/{version}/dart-sass-{version}-windows-x64.zip"),
-                    ("macos" | "linux", "x86_64") => format!("https://github.com/sass/dart-sass/releases/download/{version}/dart-sass-{version}-{target_os}-x64.tar.gz"),
-                    ("macos" | "linux", "aarch64") => format!("https://github.com/sass/dart-sass/releases/download/{version}/dart-sass-{version}-{target_os}-arm64.tar.gz"),
-                    _ => bail!("No sass tar binary found for {target_os} {target_arch}")
+                let (version, pinned) = versions.sass();
+                let ext = match platform.os {
+                    "windows" => "zip",
+                    _ => "tar.gz",
                 };
-                let exe = match target_os {
+                let url = format!("https://github.com/sass/dart-sass/releases/download/{version}/dart-sass-{version}-{target}.{ext}");
+                let exe = match platform.os {
                     "windows" => "dart-sass/sass.bat".to_string(),
                     _ => "dart-sass/sass".to_string(),
                 };
                 ExeMeta {
                     name: "sass",
+                    tag: version.clone(),
                     version,
+                    pinned,
+                    repo: "sass/dart-sass",
                     url,
+                    target,
+                    platform,
+                    available,
                     exe,
                     manual: "Try manually installing sass: https://sass-lang.com/install",
                 }
             }
             Exe::WasmOpt => {
-                let version = "version_112";
-                let target = match (target_os, target_arch) {
-                    ("linux", _) => "x86_64-linux",
-                    ("windows", _) => "x86_64-windows",
-                    ("macos", "aarch64") => "arm64-macos",
-                    ("macos", "x86_64") => "x86_64-macos",
-                    _ => {
-                        bail!("No wasm-opt tar binary found for {target_os} {target_arch}")
-                    }
-                };
+                let (version, pinned) = versions.wasm_opt();
                 let url = format!("https://github.com/WebAssembly/binaryen/releases/download/{version}/binaryen-{version}-{target}.tar.gz");
 
-                let exe = match target_os {
+                let exe = match platform.os {
                     "windows" => format!("binaryen-{version}/bin/wasm-opt.exe"),
                     _ => format!("binaryen-{version}/bin/wasm-opt"),
                 };
                 ExeMeta {
                     name: "wasm-opt",
+                    tag: version.clone(),
                     version,
+                    pinned,
+                    repo: "WebAssembly/binaryen",
                     url,
+                    target,
+                    platform,
+                    available,
                     exe,
                     manual:
                         "Try manually installing binaryen: https://github.com/WebAssembly/binaryen",
                 }
             }
             Exe::Tailwind => {
-                let version = "v3.3.1";
-                let url = match (target_os, target_arch) {
-                    ("windows", "x86_64") => format!("https://github.com/tailwindlabs/tailwindcss/releases/download/{version}/tailwindcss-windows-x64.exe"),
-                    ("macos", "x86_64") => format!("https://github.com/tailwindlabs/tailwindcss/releases/download/{version}/tailwindcss-macos-x64"),
-                    ("macos", "aarch64") => format!("https://github.com/tailwindlabs/tailwindcss/releases/download/{version}/tailwindcss-macos-arm64"),
-                    ("linux", "x86_64") => format!("https://github.com/tailwindlabs/tailwindcss/releases/download/{version}/tailwindcss-linux-x64"),
-                    ("linux", "aarch64") => format!("https://github.com/tailwindlabs/tailwindcss/releases/download/{version}/tailwindcss-linux-arm64"),
-                    _ => bail!("No tailwind binary found for {target_os} {target_arch}")
-                };
-                let exe = match (target_os, target_arch) {
-                    ("windows", _) => "tailwindcss-windows-x64.exe".to_string(),
-                    ("macos", "x86_64") => "tailwindcss-macos-x64".to_string(),
-                    ("macos", "aarch64") => "tailwindcss-macos-arm64".to_string(),
-                    ("linux", "x86_64") => "tailwindcss-linux-x64".to_string(),
-                    (_, _) => "tailwindcss-linux-arm64".to_string(),
+                let (version, pinned) = versions.tailwind();
+                let exe = match platform.os {
+                    "windows" => format!("tailwindcss-{target}.exe"),
+                    _ => format!("tailwindcss-{target}"),
                 };
+                let url = format!(
+                    "https://github.com/tailwindlabs/tailwindcss/releases/download/{version}/{exe}"
+                );
                 ExeMeta {
                     name: "tailwindcss",
+                    tag: version.clone(),
                     version,
+                    pinned,
+                    repo: "tailwindlabs/tailwindcss",
                     url,
+                    target,
+                    platform,
+                    available,
                     exe,
                     manual: "Try manually installing tailwindcss",
                 }
             }
-        };
-
-        Ok(exe)
+            Exe::Oxipng => {
+                let version = "8.0.0".to_string();
+                let url = match platform.os {
+                    "windows" => format!("https://github.com/shssoichiro/oxipng/releases/download/v{version}/oxipng-{version}-{target}.zip"),
+                    _ => format!("https://github.com/shssoichiro/oxipng/releases/download/v{version}/oxipng-{version}-{target}.tar.gz"),
+                };
+                let exe = match platform.os {
+                    "windows" => format!("oxipng-{version}-{target}/oxipng.exe"),
+                    _ => format!("oxipng-{version}-{target}/oxipng"),
+                };
+                ExeMeta {
+                    name: "oxipng",
+                    tag: format!("v{version}"),
+                    version,
+                    pinned: false,
+                    repo: "shssoichiro/oxipng",
+                    url,
+                    target,
+                    platform,
+                    available,
+                    exe,
+                    manual: "Try manually installing oxipng: https://github.com/shssoichiro/oxipng#installing",
+                }
+            }
+            Exe::WasmBindgenTestRunner => {
+                // same version as the wasm-bindgen-cli-support used for generating the bindings
+                let version = WASM_BINDGEN_VERSION.to_string();
+                let url = format!("https://github.com/rustwasm/wasm-bindgen/releases/download/{version}/wasm-bindgen-{version}-{target}.tar.gz");
+                let exe = match platform.os {
+                    "windows" => {
+                        format!("wasm-bindgen-{version}-{target}/wasm-bindgen-test-runner.exe")
+                    }
+                    _ => format!("wasm-bindgen-{version}-{target}/wasm-bindgen-test-runner"),
+                };
+                ExeMeta {
+                    name: "wasm-bindgen-test-runner",
+                    tag: version.clone(),
+                    version,
+                    pinned: false,
+                    repo: "rustwasm/wasm-bindgen",
+                    url,
+                    target,
+                    platform,
+                    available,
+                    exe,
+                    manual: "Try manually installing wasm-bindgen-cli: https://rustwasm.github.io/wasm-bindgen/wasm-bindgen-test/usage.html",
+                }
+            }
+        }
     }
 }
@@ -0,0 +1,54 @@
+use std::fmt::{self, Display};
+
+use super::anyhow::Error;
+
+/// The exit codes of cargo-leptos, as listed in the README. Any other error exits with 1, like
+/// a failed build.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Exit {
+    /// a build, a test run or an end2end run failed
+    Failed = 1,
+    /// the Cargo.toml or the leptos config is invalid
+    Config = 2,
+    /// stopped by ctrl-c, with the exit code of the shells for SIGINT
+    Interrupted = 130,
+    /// a second ctrl-c killed the child processes without waiting for the shutdown, with the
+    /// exit code of the shells for SIGKILL
+    ForceQuit = 137,
+}
+
+impl Display for Exit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Failed => write!(f, "failed (exit code 1)"),
+            Self::Config => write!(f, "invalid configuration (exit code 2)"),
+            Self::Interrupted => write!(f, "interrupted (exit code 130)"),
+            Self::ForceQuit => write!(f, "force quit (exit code 137)"),
+        }
+    }
+}
+
+impl std::error::Error for Exit {}
+
+/// The exit code of the server exe that `run` exits with, when it isn't 0
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ServerExit(pub i32);
+
+impl Display for ServerExit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "the server exited with code {}", self.0)
+    }
+}
+
+impl std::error::Error for ServerExit {}
+
+/// The exit code for the error: the one of the [Exit] or the [ServerExit] it has, as a cause
+/// or as a context
+pub fn exit_code(error: &Error) -> i32 {
+    if let Some(ServerExit(code)) = error.downcast_ref::<ServerExit>() {
+        return *code;
+    }
+    error
+        .downcast_ref::<Exit>()
+        .map_or(Exit::Failed, |exit| *exit) as i32
+}
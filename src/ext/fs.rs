@@ -1,6 +1,11 @@
 use crate::ext::anyhow::{Context, Result};
 use camino::{Utf8Path, Utf8PathBuf};
-use std::{collections::VecDeque, path::Path};
+use std::{
+    collections::VecDeque,
+    fs::Permissions,
+    path::{Path, PathBuf},
+    time::Duration,
+};
 use tokio::fs::{self, ReadDir};
 
 use super::path::PathExt;
@@ -38,12 +43,117 @@ pub async fn write<P: AsRef<Path>, C: AsRef<[u8]>>(path: P, contents: C) -> Resu
         .context(format!("Could not write to {:?}", path.as_ref()))
 }
 
+/// Writes to a temporary file beside the path and renames it over the path, so that the
+/// readers of the path, like the browser or the server, never see a partial file
+pub async fn write_atomic<P: AsRef<Path>, C: AsRef<[u8]>>(path: P, contents: C) -> Result<()> {
+    let path = path.as_ref();
+    let tmp = tmp_path(path);
+    let mut written = self::write(&tmp, contents).await;
+    // the replaced file keeps its permissions
+    if let (Ok(()), Ok(metadata)) = (&written, fs::metadata(path).await) {
+        written = set_permissions(&tmp, metadata.permissions()).await;
+    }
+    if let Err(e) = written {
+        _ = fs::remove_file(&tmp).await;
+        return Err(e);
+    }
+    rename_over(&tmp, path).await
+}
+
+/// Like [copy], through a temporary file beside the destination, as for [write_atomic]
+pub async fn copy_atomic<P: AsRef<Path>, Q: AsRef<Path>>(from: P, to: Q) -> Result<u64> {
+    let to = to.as_ref();
+    let tmp = tmp_path(to);
+    let copied = match self::copy(&from, &tmp).await {
+        Ok(copied) => copied,
+        Err(e) => {
+            _ = fs::remove_file(&tmp).await;
+            return Err(e);
+        }
+    };
+    rename_over(&tmp, to).await?;
+    Ok(copied)
+}
+
+/// A hidden file in the same dir, as the rename is only atomic within a file system
+pub fn tmp_path(path: &Path) -> PathBuf {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    path.with_file_name(format!(".{name}.{}.tmp", std::process::id()))
+}
+
+/// Whether the file is the temporary file of an atomic write, e.g. of another build
+pub fn is_tmp_path(path: &Path) -> bool {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    name.starts_with('.') && name.ends_with(".tmp")
+}
+
+/// Renames the file over the destination, and removes it when that fails. On Windows, the
+/// rename fails while the destination is open, e.g. by a server reading it, so it is retried
+/// for a moment.
+pub async fn rename_over(from: &Path, to: &Path) -> Result<()> {
+    const ATTEMPTS: u32 = if cfg!(target_os = "windows") { 20 } else { 1 };
+    let mut attempt = 1;
+    loop {
+        match fs::rename(from, to).await {
+            Ok(()) => return Ok(()),
+            Err(e) if attempt < ATTEMPTS && e.kind() == std::io::ErrorKind::PermissionDenied => {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+                attempt += 1;
+            }
+            Err(e) => {
+                _ = fs::remove_file(from).await;
+                return Err(e).context(format!("Could not rename from {from:?} to {to:?}"));
+            }
+        }
+    }
+}
+
 pub async fn read(path: impl AsRef<Path>) -> Result<Vec<u8>> {
     fs::read(&path)
         .await
         .context(format!("Could not read {:?}", path.as_ref()))
 }
 
+pub async fn metadata(path: impl AsRef<Path>) -> Result<std::fs::Metadata> {
+    fs::metadata(&path)
+        .await
+        .context(format!("Could not read metadata of {:?}", path.as_ref()))
+}
+
+pub async fn set_permissions(path: impl AsRef<Path>, perms: Permissions) -> Result<()> {
+    fs::set_permissions(&path, perms).await.context(format!(
+        "Could not set the permissions of {:?}",
+        path.as_ref()
+    ))
+}
+
+/// Gives `to` the permissions of `from`, which are the ones of its target for a symlink
+pub async fn copy_permissions(from: impl AsRef<Path>, to: impl AsRef<Path>) -> Result<()> {
+    let perms = self::metadata(from).await?.permissions();
+    if self::metadata(&to).await?.permissions() != perms {
+        self::set_permissions(to, perms).await?;
+    }
+    Ok(())
+}
+
+/// Sets the unix mode of the file. Only the read-only flag exists on Windows, which is set
+/// when the mode has no write permission.
+pub async fn set_mode(path: impl AsRef<Path>, mode: u32) -> Result<()> {
+    let mut perms = self::metadata(&path).await?.permissions();
+    let old = perms.clone();
+    #[cfg(target_family = "unix")]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        perms.set_mode(mode);
+    }
+    #[cfg(not(target_family = "unix"))]
+    perms.set_readonly(mode & 0o222 == 0);
+    if perms != old {
+        self::set_permissions(path, perms).await?;
+    }
+    Ok(())
+}
+
 pub async fn create_dir(path: impl AsRef<Path>) -> Result<()> {
     log::trace!("FS create_dir {:?}", path.as_ref());
     fs::create_dir(&path)
@@ -69,6 +179,28 @@ pub async fn copy<P: AsRef<Path>, Q: AsRef<Path>>(from: P, to: Q) -> Result<u64>
         .context(format!("copy {:?} to {:?}", from.as_ref(), to.as_ref()))
 }
 
+/// Creates a symlink at `to` pointing to the same target as the symlink `from`.
+pub async fn copy_symlink<P: AsRef<Path>, Q: AsRef<Path>>(from: P, to: Q) -> Result<()> {
+    let (from, to) = (from.as_ref(), to.as_ref());
+    let target = fs::read_link(from)
+        .await
+        .context(format!("Could not read link {from:?}"))?;
+
+    if fs::symlink_metadata(to).await.is_ok() {
+        self::remove_file(to).await?;
+    }
+
+    #[cfg(target_family = "unix")]
+    let res = fs::symlink(&target, to).await;
+    #[cfg(target_family = "windows")]
+    let res = if from.is_dir() {
+        fs::symlink_dir(&target, to).await
+    } else {
+        fs::symlink_file(&target, to).await
+    };
+    res.context(format!("Could not create symlink {to:?} -> {target:?}"))
+}
+
 pub async fn read_dir<P: AsRef<Path>>(path: P) -> Result<ReadDir> {
     fs::read_dir(&path)
         .await
@@ -3,15 +3,17 @@ mod tests;
 
 pub mod anyhow;
 mod cargo;
+mod download;
 pub mod exe;
+pub mod exit;
 pub mod fs;
 mod path;
 pub mod sync;
 mod util;
 
-pub use cargo::{MetadataExt, PackageExt};
+pub use cargo::{MetadataExt, PackageExt, ResolveExt};
 pub use exe::{Exe, ExeMeta};
 pub use path::{
     append_str_to_filename, determine_pdb_filename, remove_nested, PathBufExt, PathExt,
 };
-pub use util::{os_arch, StrAdditions};
+pub use util::{format_bytes, os_arch, split_args, strip_ansi, Platform, StrAdditions};
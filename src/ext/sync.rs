@@ -1,14 +1,26 @@
-use crate::ext::anyhow::{bail, Context, Result};
+use crate::{
+    ext::{
+        anyhow::{bail, Context, Result},
+        strip_ansi,
+    },
+    logger::{self, GRAY},
+};
 use std::{
-    net::SocketAddr,
-    process::{Output, Stdio},
-    time::Duration,
+    collections::HashSet,
+    fmt::Display,
+    future::Future,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    process::{ExitStatus, Output, Stdio},
+    sync::Mutex,
+    time::{Duration, SystemTime, UNIX_EPOCH},
 };
 use tokio::{
+    io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, BufReader},
     net::TcpStream,
     process::{Child, Command},
     sync::broadcast,
-    time::sleep,
+    task::JoinHandle,
+    time::{sleep, Instant},
 };
 
 pub trait OutputExt {
@@ -36,17 +48,34 @@ impl OutputExt for Output {
         self.stdout.len() > 1
     }
 }
+lazy_static::lazy_static! {
+  /// the pids of the running child processes, killed on a forced quit
+  static ref CHILDREN: Mutex<HashSet<u32>> = Mutex::new(HashSet::new());
+}
+
 pub enum CommandResult<T> {
     Success(T),
     Failure(T),
     Interrupted,
 }
 
+/// The number of output lines logged when a command times out
+const TIMED_OUT_LINES: usize = 20;
+
+/// How long the output of a killed command is read for, in case a process that left its
+/// process group keeps the pipes open
+const KILLED_OUTPUT_WAIT: Duration = Duration::from_secs(1);
+
+/// Waits for the process, killing it on interrupt or when it runs longer than the timeout,
+/// which is a failure. The processes it started are killed with it when it was started with
+/// [`new_process_group`].
 pub async fn wait_interruptible(
     name: &str,
     mut process: Child,
     mut interrupt_rx: broadcast::Receiver<()>,
+    timeout: Option<Duration>,
 ) -> Result<CommandResult<()>> {
+    let group = ProcessGroup::of(&process);
     tokio::select! {
         res = process.wait() => match res {
             Ok(exit) => {
@@ -61,27 +90,49 @@ pub async fn wait_interruptible(
             Err(e) => bail!("Command failed due to: {e}"),
         },
         _ = interrupt_rx.recv() => {
+            group.kill();
             process.kill().await.context("Could not kill process")?;
             log::trace!("{name} process interrupted");
             Ok(CommandResult::Interrupted)
         }
+        _ = expired(timeout) => {
+            log::warn!(
+                "{name} timed out after {}s, killing it",
+                timeout.unwrap_or_default().as_secs()
+            );
+            kill_timed_out(&mut process, &group).await?;
+            Ok(CommandResult::Failure(()))
+        }
     }
 }
 
+/// Runs the command with its stdout and stderr captured, in its own process group so that
+/// the processes it starts are killed with it on interrupt or timeout. A timeout is a failure
+/// with the output so far, whose last lines are logged.
 pub async fn wait_piped_interruptible(
     name: &str,
     mut cmd: Command,
     mut interrupt_rx: broadcast::Receiver<()>,
+    timeout: Option<Duration>,
 ) -> Result<CommandResult<Output>> {
     // see: https://docs.rs/tokio/latest/tokio/process/index.html
 
     cmd.kill_on_drop(true);
     cmd.stdout(Stdio::piped());
     cmd.stderr(Stdio::piped());
-    let process = cmd.spawn()?;
+    new_process_group(&mut cmd);
+    let mut process = cmd.spawn()?;
+    let group = ProcessGroup::of(&process);
+    let stdout = read_output(process.stdout.take());
+    let stderr = read_output(process.stderr.take());
     tokio::select! {
-        res = process.wait_with_output() => match res {
-            Ok(output) => {
+        res = process.wait() => match res {
+            Ok(status) => {
+                let output = Output {
+                    status,
+                    stdout: stdout.await.unwrap_or_default(),
+                    stderr: stderr.await.unwrap_or_default(),
+                };
                 if output.status.success() {
                     log::trace!("{name} process finished with success");
                     Ok(CommandResult::Success(output))
@@ -93,21 +144,698 @@ pub async fn wait_piped_interruptible(
             Err(e) => bail!("Command failed due to: {e}"),
         },
         _ = interrupt_rx.recv() => {
+            // the process itself is killed on drop
+            group.kill();
             log::trace!("{name} process interrupted");
             Ok(CommandResult::Interrupted)
         }
+        _ = expired(timeout) => {
+            log::warn!(
+                "{name} timed out after {}s, killing it {}",
+                timeout.unwrap_or_default().as_secs(),
+                GRAY.paint(format!("{:?}", cmd.as_std()))
+            );
+            let status = kill_timed_out(&mut process, &group).await?;
+            let output = Output {
+                status,
+                stdout: killed_output(stdout).await,
+                stderr: killed_output(stderr).await,
+            };
+            let lines = last_lines(&[&output.stdout, &output.stderr], TIMED_OUT_LINES);
+            if !lines.is_empty() {
+                log::warn!("{name} last output lines:\n{lines}");
+            }
+            Ok(CommandResult::Failure(output))
+        }
+    }
+}
+
+/// The first delay between the attempts of a retried tool, doubled at each attempt
+const TOOL_RETRY_DELAY: Duration = Duration::from_millis(500);
+
+/// Why a command failed, for the log of its retry. Its output is printed already when it
+/// isn't captured.
+pub trait FailureReason {
+    fn reason(&self) -> Option<String>;
+}
+
+impl FailureReason for () {
+    fn reason(&self) -> Option<String> {
+        None
+    }
+}
+
+impl FailureReason for Output {
+    fn reason(&self) -> Option<String> {
+        let stderr = last_lines(&[&self.stderr], 1);
+        if stderr.is_empty() {
+            Some(self.status.to_string())
+        } else {
+            Some(format!("{}, {stderr}", self.status))
+        }
+    }
+}
+
+/// Runs the command again, up to `retries` times, when it fails, for the tools whose spurious
+/// failures are worth a retry, like tailwind on a cold start. The delay before a retry grows
+/// with each attempt, and an interrupt during it stops at once.
+pub async fn retry_failures<T, F, Fut>(
+    name: &str,
+    retries: u32,
+    mut interrupt_rx: broadcast::Receiver<()>,
+    mut run: F,
+) -> Result<CommandResult<T>>
+where
+    T: FailureReason,
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<CommandResult<T>>>,
+{
+    let mut attempt = 1;
+    loop {
+        match run().await? {
+            CommandResult::Failure(output) if attempt <= retries => {
+                let delay = backoff(TOOL_RETRY_DELAY, attempt);
+                let reason = output
+                    .reason()
+                    .map(|reason| format!(" with {reason}"))
+                    .unwrap_or_default();
+                log::warn!("{name} failed{reason}. Retrying in {delay:.1?} ({attempt}/{retries})");
+                if !sleep_interruptible(delay, &mut interrupt_rx).await {
+                    log::trace!("{name} retry interrupted");
+                    return Ok(CommandResult::Interrupted);
+                }
+                attempt += 1;
+            }
+            res => return Ok(res),
+        }
+    }
+}
+
+/// The delay before the retry after the attempt: the base delay doubled at each attempt, with
+/// up to 50% more so that concurrent retries don't all happen at once
+pub fn backoff(base: Duration, attempt: u32) -> Duration {
+    let delay = base * 2u32.saturating_pow(attempt.saturating_sub(1));
+    // the sub-second part of the clock is random enough for spreading the retries
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .subsec_nanos();
+    delay + delay.mul_f64(f64::from(nanos) / 2e9)
+}
+
+/// Sleeps for the delay, false when interrupted meanwhile
+pub async fn sleep_interruptible(
+    delay: Duration,
+    interrupt_rx: &mut broadcast::Receiver<()>,
+) -> bool {
+    tokio::select! {
+        _ = sleep(delay) => true,
+        _ = interrupt_rx.recv() => false,
+    }
+}
+
+/// Never ends without a timeout
+async fn expired(timeout: Option<Duration>) {
+    match timeout {
+        Some(timeout) => sleep(timeout).await,
+        None => std::future::pending().await,
+    }
+}
+
+async fn kill_timed_out(process: &mut Child, group: &ProcessGroup) -> Result<ExitStatus> {
+    group.kill();
+    process.kill().await.context("Could not kill process")?;
+    process
+        .wait()
+        .await
+        .context("Could not wait for the killed process")
+}
+
+fn read_output(stream: Option<impl AsyncRead + Unpin + Send + 'static>) -> JoinHandle<Vec<u8>> {
+    tokio::spawn(async move {
+        let mut data = Vec::new();
+        if let Some(mut stream) = stream {
+            let _ = stream.read_to_end(&mut data).await;
+        }
+        data
+    })
+}
+
+async fn killed_output(handle: JoinHandle<Vec<u8>>) -> Vec<u8> {
+    tokio::time::timeout(KILLED_OUTPUT_WAIT, handle)
+        .await
+        .ok()
+        .and_then(Result::ok)
+        .unwrap_or_default()
+}
+
+/// The last non-empty lines of the outputs, without their colors
+pub(crate) fn last_lines(outputs: &[&[u8]], count: usize) -> String {
+    let lines: Vec<String> = outputs
+        .iter()
+        .flat_map(|output| {
+            String::from_utf8_lossy(output)
+                .lines()
+                .filter(|line| !line.trim().is_empty())
+                .map(strip_ansi)
+                .collect::<Vec<_>>()
+        })
+        .collect();
+    lines[lines.len().saturating_sub(count)..].join("\n")
+}
+
+/// The processes of a child started with [`new_process_group`]: its process group on unix,
+/// or on windows the job object that it is put in, which the processes it starts join too.
+/// The child is registered for [`kill_children`] until the group is dropped, which on windows
+/// also kills the processes left in the job.
+pub struct ProcessGroup {
+    pid: Option<u32>,
+    #[cfg(windows)]
+    job: Option<job::Job>,
+}
+
+impl ProcessGroup {
+    pub fn of(process: &Child) -> Self {
+        let pid = process.id();
+        if let Some(pid) = pid {
+            CHILDREN.lock().unwrap().insert(pid);
+        }
+        Self {
+            pid,
+            #[cfg(windows)]
+            job: job::Job::assign(process),
+        }
+    }
+
+    /// Kills all the processes of the group, which [`Child::kill`] doesn't
+    pub fn kill(&self) {
+        #[cfg(windows)]
+        if let Some(job) = &self.job {
+            job.terminate();
+        }
+        if let Some(pid) = self.pid {
+            kill_group(pid);
+        }
     }
 }
-pub async fn wait_for_socket(name: &str, addr: SocketAddr) -> bool {
-    let duration = Duration::from_millis(500);
 
-    for _ in 0..20 {
-        if TcpStream::connect(&addr).await.is_ok() {
-            log::debug!("{name} server port {addr} open");
-            return true;
+impl Drop for ProcessGroup {
+    fn drop(&mut self) {
+        if let Some(pid) = self.pid {
+            CHILDREN.lock().unwrap().remove(&pid);
         }
-        sleep(duration).await;
     }
-    log::warn!("{name} timed out waiting for port {addr}");
-    false
+}
+
+/// Kills the running child processes outright, with the processes they started, for the
+/// forced quit of a second Ctrl+C
+pub fn kill_children() {
+    let pids: Vec<u32> = CHILDREN.lock().unwrap().drain().collect();
+    for pid in pids {
+        log::debug!("Leptos killing process {pid}");
+        force_kill(pid);
+    }
+}
+
+/// Kills the process and its process group, or its process tree on windows. The killed
+/// processes are reaped by the init process once cargo-leptos has exited.
+pub(crate) fn force_kill(pid: u32) {
+    #[cfg(unix)]
+    unsafe {
+        // the group only exists when the process was started with new_process_group
+        libc::kill(-(pid as i32), libc::SIGKILL);
+        libc::kill(pid as i32, libc::SIGKILL);
+    }
+    #[cfg(windows)]
+    {
+        let _ = std::process::Command::new("taskkill")
+            .args(["/F", "/T", "/PID", &pid.to_string()])
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status();
+    }
+    #[cfg(not(any(unix, windows)))]
+    let _ = pid;
+}
+
+/// Starts the process in its own process group, so that it can be stopped with its children
+/// and doesn't get the terminal's Ctrl+C, which leaves the stopping to [`stop_gracefully`]
+/// or to the [`ProcessGroup`] of the process.
+pub fn new_process_group(cmd: &mut Command) {
+    #[cfg(unix)]
+    cmd.process_group(0);
+    #[cfg(windows)]
+    {
+        const CREATE_NEW_PROCESS_GROUP: u32 = 0x0000_0200;
+        cmd.creation_flags(CREATE_NEW_PROCESS_GROUP);
+    }
+}
+
+/// The fd of the first socket of the systemd socket activation protocol, which listenfd reads
+#[cfg(unix)]
+const LISTEN_FDS_START: i32 = 3;
+
+/// Passes the listener to the process as its fd 3, with LISTEN_FDS=1, as systemd and
+/// systemfd do, for the listenfd crate to pick it up. The socket is shared, so it stays open
+/// when the process exits and the connections made meanwhile wait for the next one.
+#[cfg(unix)]
+pub fn pass_listener(cmd: &mut Command, listener: &std::net::TcpListener) {
+    use std::os::unix::io::AsRawFd;
+
+    let fd = listener.as_raw_fd();
+    cmd.env("LISTEN_FDS", "1")
+        .env_remove("LISTEN_PID")
+        .env_remove("LISTEN_FDNAMES");
+    // SAFETY: only async-signal-safe calls are made between the fork and the exec
+    unsafe {
+        cmd.pre_exec(move || {
+            if fd == LISTEN_FDS_START {
+                // dup2 would keep the close-on-exec flag of the same fd
+                let flags = libc::fcntl(fd, libc::F_GETFD);
+                if flags < 0 || libc::fcntl(fd, libc::F_SETFD, flags & !libc::FD_CLOEXEC) < 0 {
+                    return Err(std::io::Error::last_os_error());
+                }
+            } else if libc::dup2(fd, LISTEN_FDS_START) < 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+            Ok(())
+        });
+    }
+}
+
+/// Asks the process started with [`new_process_group`] to stop, with a SIGTERM to its group
+/// or a CTRL_BREAK event on windows, and kills its group when it hasn't stopped after the
+/// grace period.
+pub async fn stop_gracefully(
+    name: &str,
+    process: &mut Child,
+    group: ProcessGroup,
+    grace: Duration,
+) -> Result<()> {
+    let Some(pid) = process.id() else {
+        // already exited
+        return Ok(());
+    };
+    if !grace.is_zero() && terminate(pid) {
+        match tokio::time::timeout(grace, process.wait()).await {
+            Ok(Ok(status)) => {
+                log::trace!("{name} process stopped with {status}");
+                return Ok(());
+            }
+            Ok(Err(e)) => bail!("Could not wait for the {name} process: {e}"),
+            Err(_) => log::warn!(
+                "{name} process didn't stop within the shutdown-grace-period of {grace:?}, killing it"
+            ),
+        }
+    }
+    group.kill();
+    process.kill().await.context("Could not kill process")?;
+    Ok(())
+}
+
+/// Sends a SIGINT to the process, like the Ctrl+C of the terminal. On windows the processes
+/// of the console all get the Ctrl+C, so nothing is sent.
+pub fn interrupt(process: &Child) {
+    #[cfg(unix)]
+    if let Some(pid) = process.id() {
+        unsafe {
+            libc::kill(pid as i32, libc::SIGINT);
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = process;
+}
+
+/// Sends the stop request, false when it couldn't be sent
+fn terminate(pid: u32) -> bool {
+    #[cfg(unix)]
+    {
+        // a negative pid is the process group
+        unsafe { libc::kill(-(pid as i32), libc::SIGTERM) == 0 }
+    }
+    #[cfg(windows)]
+    {
+        use windows_sys::Win32::System::Console::{GenerateConsoleCtrlEvent, CTRL_BREAK_EVENT};
+        unsafe { GenerateConsoleCtrlEvent(CTRL_BREAK_EVENT, pid) != 0 }
+    }
+    #[cfg(not(any(unix, windows)))]
+    {
+        false
+    }
+}
+
+/// Kills the process group of the process
+fn kill_group(pid: u32) {
+    #[cfg(unix)]
+    unsafe {
+        libc::kill(-(pid as i32), libc::SIGKILL);
+    }
+    #[cfg(not(unix))]
+    let _ = pid;
+}
+
+#[cfg(windows)]
+mod job {
+    use std::{mem, ptr};
+    use tokio::process::Child;
+    use windows_sys::Win32::{
+        Foundation::{CloseHandle, HANDLE},
+        System::JobObjects::{
+            AssignProcessToJobObject, CreateJobObjectW, JobObjectExtendedLimitInformation,
+            SetInformationJobObject, TerminateJobObject, JOBOBJECT_EXTENDED_LIMIT_INFORMATION,
+            JOB_OBJECT_LIMIT_KILL_ON_JOB_CLOSE,
+        },
+    };
+
+    /// A job object that kills its processes when closed, as the windows processes have no
+    /// process group that could be killed
+    pub struct Job(HANDLE);
+
+    impl Job {
+        /// None when the process has exited or the job couldn't be set up
+        pub fn assign(process: &Child) -> Option<Self> {
+            let handle = process.raw_handle()? as HANDLE;
+            // SAFETY: the job handle is owned by the Job, and the process handle by the Child
+            unsafe {
+                let job = CreateJobObjectW(ptr::null(), ptr::null());
+                if job == 0 {
+                    return None;
+                }
+                let job = Self(job);
+                let mut info: JOBOBJECT_EXTENDED_LIMIT_INFORMATION = mem::zeroed();
+                info.BasicLimitInformation.LimitFlags = JOB_OBJECT_LIMIT_KILL_ON_JOB_CLOSE;
+                let set = SetInformationJobObject(
+                    job.0,
+                    JobObjectExtendedLimitInformation,
+                    &info as *const _ as *const _,
+                    mem::size_of::<JOBOBJECT_EXTENDED_LIMIT_INFORMATION>() as u32,
+                );
+                if set == 0 || AssignProcessToJobObject(job.0, handle) == 0 {
+                    log::trace!("Could not put the process in a job object");
+                    return None;
+                }
+                Some(job)
+            }
+        }
+
+        pub fn terminate(&self) {
+            unsafe {
+                TerminateJobObject(self.0, 1);
+            }
+        }
+    }
+
+    impl Drop for Job {
+        fn drop(&mut self) {
+            unsafe {
+                CloseHandle(self.0);
+            }
+        }
+    }
+}
+
+/// Prints the lines of the piped stdout and stderr of the process with the prefix, so that
+/// the output of concurrent processes stays readable. The handles finish at end of output.
+pub fn print_prefixed(process: &mut Child, prefix: &str) -> Vec<JoinHandle<()>> {
+    let mut handles = Vec::new();
+    if let Some(stdout) = process.stdout.take() {
+        handles.push(print_lines(stdout, Some(prefix.to_string()), false));
+    }
+    if let Some(stderr) = process.stderr.take() {
+        handles.push(print_lines(stderr, Some(prefix.to_string()), true));
+    }
+    handles
+}
+
+/// Prints the lines of the piped stdout and stderr of the process as they are, like
+/// [`print_prefixed`] without the prefix, e.g. for the JSON lines of another cargo-leptos
+pub fn print_unchanged(process: &mut Child) -> Vec<JoinHandle<()>> {
+    let mut handles = Vec::new();
+    if let Some(stdout) = process.stdout.take() {
+        handles.push(print_lines(stdout, None, false));
+    }
+    if let Some(stderr) = process.stderr.take() {
+        handles.push(print_lines(stderr, None, true));
+    }
+    handles
+}
+
+/// Prints the lines of the piped stdout and stderr of the process as the output of the
+/// command, and tells whether one of them contains one of the patterns, like the
+/// characteristic error of a missing dependency. The handle finishes at end of output.
+pub fn print_finding(
+    process: &mut Child,
+    command: &'static str,
+    patterns: &'static [&'static str],
+) -> JoinHandle<bool> {
+    let find = |stream: Option<Box<dyn AsyncRead + Unpin + Send>>, is_stderr: bool| {
+        tokio::spawn(async move {
+            let Some(stream) = stream else {
+                return false;
+            };
+            let mut found = false;
+            let mut lines = BufReader::new(stream).lines();
+            while let Ok(Some(line)) = lines.next_line().await {
+                logger::print_output(command, &line, &line, is_stderr);
+                let plain = strip_ansi(&line);
+                found |= patterns.iter().any(|pattern| plain.contains(pattern));
+            }
+            found
+        })
+    };
+    let stdout = find(process.stdout.take().map(|s| Box::new(s) as Box<_>), false);
+    let stderr = find(process.stderr.take().map(|s| Box::new(s) as Box<_>), true);
+    tokio::spawn(async move {
+        let stdout = stdout.await.unwrap_or_default();
+        stderr.await.unwrap_or_default() || stdout
+    })
+}
+
+/// Prints the piped stdout and stderr of the process like [`print_prefixed`], and keeps their
+/// lines without the colors, stdout first. The handle finishes at end of output.
+pub fn print_prefixed_lines(process: &mut Child, prefix: &str) -> JoinHandle<Vec<String>> {
+    let keep = |stream: Option<Box<dyn AsyncRead + Unpin + Send>>, is_stderr: bool| {
+        let prefix = prefix.to_string();
+        tokio::spawn(async move {
+            let mut kept = Vec::new();
+            let Some(stream) = stream else {
+                return kept;
+            };
+            let command = prefix.trim_start_matches('[').trim_end_matches(']').trim();
+            let mut lines = BufReader::new(stream).lines();
+            while let Ok(Some(line)) = lines.next_line().await {
+                logger::print_output(command, &line, &format!("{prefix} {line}"), is_stderr);
+                kept.push(strip_ansi(&line));
+            }
+            kept
+        })
+    };
+    let stdout = keep(process.stdout.take().map(|s| Box::new(s) as Box<_>), false);
+    let stderr = keep(process.stderr.take().map(|s| Box::new(s) as Box<_>), true);
+    tokio::spawn(async move {
+        let mut lines = stdout.await.unwrap_or_default();
+        lines.extend(stderr.await.unwrap_or_default());
+        lines
+    })
+}
+
+/// Pipes the stderr of the cargo command for [`capture_errors`], keeping its colors when
+/// the logs are colored, with the human log format
+pub fn pipe_cargo_stderr(cmd: &mut Command) {
+    if logger::is_colored() && !logger::is_json() {
+        cmd.env("CARGO_TERM_COLOR", "always");
+    }
+    cmd.stderr(Stdio::piped());
+}
+
+/// Prints the piped stderr of the process and keeps its error messages, without the colors:
+/// the lines from one that starts with `error` up to the next blank line or warning.
+/// The handle finishes at end of output, with nothing when the stderr wasn't piped.
+pub fn capture_errors(process: &mut Child) -> JoinHandle<Vec<String>> {
+    let stderr = process.stderr.take();
+    tokio::spawn(async move {
+        let mut errors = Vec::new();
+        let Some(stderr) = stderr else {
+            return errors;
+        };
+        let mut lines = BufReader::new(stderr).lines();
+        let mut in_error = false;
+        while let Ok(Some(line)) = lines.next_line().await {
+            logger::print_output("cargo", &line, &line, true);
+            let plain = strip_ansi(&line);
+            if plain.starts_with("error") {
+                in_error = true;
+            } else if plain.trim().is_empty() || plain.starts_with("warning") {
+                in_error = false;
+            }
+            if in_error {
+                errors.push(plain);
+            }
+        }
+        errors
+    })
+}
+
+fn print_lines(
+    stream: impl AsyncRead + Unpin + Send + 'static,
+    prefix: Option<String>,
+    is_stderr: bool,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut lines = BufReader::new(stream).lines();
+        while let Ok(Some(line)) = lines.next_line().await {
+            match &prefix {
+                Some(prefix) => {
+                    let command = prefix.trim_start_matches('[').trim_end_matches(']').trim();
+                    logger::print_output(command, &line, &format!("{prefix} {line}"), is_stderr)
+                }
+                None => logger::print_line(&line, is_stderr),
+            }
+        }
+    })
+}
+
+/// true when another process listens on the addr
+pub fn addr_in_use(addr: SocketAddr) -> bool {
+    match std::net::TcpListener::bind(addr) {
+        Ok(_) => false,
+        Err(e) => e.kind() == std::io::ErrorKind::AddrInUse,
+    }
+}
+
+/// What to do when the site-addr is taken, naming the process that listens on it when lsof
+/// can tell
+pub fn addr_in_use_help(addr: SocketAddr) -> String {
+    let owner = port_owner(addr.port())
+        .map(|owner| format!(" by {owner}"))
+        .unwrap_or_default();
+    format!(
+        "The site-addr {addr} is already in use{owner}. Another server is probably running, \
+        like the one of a previous cargo-leptos run. Stop it, or choose another port with \
+        site-addr in the Cargo.toml or with the LEPTOS_SITE_ADDR env var"
+    )
+}
+
+/// The name and pid of the process that listens on the port, like `myapp (pid 1234)`
+fn port_owner(port: u16) -> Option<String> {
+    let lsof = which::which("lsof").ok()?;
+    let output = std::process::Command::new(lsof)
+        .args(["-nP", &format!("-iTCP:{port}"), "-sTCP:LISTEN", "-Fpc"])
+        .output()
+        .ok()?;
+    // a line per field, prefixed by its letter
+    let text = String::from_utf8_lossy(&output.stdout);
+    let pid = text.lines().find_map(|l| l.strip_prefix('p'))?;
+    match text.lines().find_map(|l| l.strip_prefix('c')) {
+        Some(name) => Some(format!("{name} (pid {pid})")),
+        None => Some(format!("pid {pid}")),
+    }
+}
+
+pub enum Readiness {
+    Ready,
+    TimedOut,
+    Interrupted,
+}
+
+/// How to check that a server is ready
+pub enum Probe {
+    /// the server accepts TCP connections
+    Tcp(SocketAddr),
+    /// a GET of the url answers with a success status
+    Get(String),
+    /// a GET of the url gets an answer, whatever its status. For a socket bound by
+    /// cargo-leptos, which accepts the TCP connections before the server does
+    Answer(String),
+}
+
+impl Probe {
+    /// A GET of the `health_path` on the server when given, a TCP connect otherwise
+    pub fn new(addr: SocketAddr, health_path: Option<&str>) -> Self {
+        let addr = connectable(addr);
+        match health_path {
+            Some(path) => Self::Get(format!("http://{addr}/{}", path.trim_start_matches('/'))),
+            None => Self::Tcp(addr),
+        }
+    }
+
+    /// A GET of the root of the server, ready on any answer
+    pub fn answer(addr: SocketAddr) -> Self {
+        Self::Answer(format!("http://{}/", connectable(addr)))
+    }
+
+    async fn is_ready(&self) -> bool {
+        match self {
+            Self::Tcp(addr) => TcpStream::connect(addr).await.is_ok(),
+            Self::Get(url) => match reqwest::get(url).await {
+                Ok(resp) => resp.status().is_success(),
+                Err(_) => false,
+            },
+            Self::Answer(url) => reqwest::get(url).await.is_ok(),
+        }
+    }
+}
+
+impl Display for Probe {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Tcp(addr) => write!(f, "{addr}"),
+            Self::Get(url) | Self::Answer(url) => write!(f, "{url}"),
+        }
+    }
+}
+
+/// Polls the server until the probe succeeds. The delay between attempts
+/// doubles from 50ms up to 1s. Gives up after `timeout` or on interrupt.
+pub async fn wait_for_ready(
+    name: &str,
+    probe: &Probe,
+    timeout: Duration,
+    mut interrupt_rx: broadcast::Receiver<()>,
+) -> Readiness {
+    let deadline = Instant::now() + timeout;
+    let mut delay = Duration::from_millis(50);
+
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        tokio::select! {
+            ready = tokio::time::timeout(remaining, probe.is_ready()) => {
+                if let Ok(true) = ready {
+                    log::debug!("{name} server {probe} ready");
+                    return Readiness::Ready;
+                }
+            }
+            _ = interrupt_rx.recv() => {
+                log::trace!("{name} interrupted waiting for {probe}");
+                return Readiness::Interrupted;
+            }
+        }
+
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            log::warn!("{name} timed out waiting for {probe}");
+            return Readiness::TimedOut;
+        }
+        tokio::select! {
+            _ = sleep(delay.min(remaining)) => {}
+            _ = interrupt_rx.recv() => {
+                log::trace!("{name} interrupted waiting for {probe}");
+                return Readiness::Interrupted;
+            }
+        }
+        delay = (delay * 2).min(Duration::from_secs(1));
+    }
+}
+
+/// a server listening on all interfaces is reached through localhost
+pub fn connectable(addr: SocketAddr) -> SocketAddr {
+    if addr.ip().is_unspecified() {
+        let ip: IpAddr = match addr {
+            SocketAddr::V4(_) => Ipv4Addr::LOCALHOST.into(),
+            SocketAddr::V6(_) => Ipv6Addr::LOCALHOST.into(),
+        };
+        SocketAddr::new(ip, addr.port())
+    } else {
+        addr
+    }
 }
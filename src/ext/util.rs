@@ -1,6 +1,6 @@
 use crate::ext::anyhow::{bail, Context, Result};
 use camino::Utf8PathBuf;
-use std::borrow::Cow;
+use std::{borrow::Cow, fmt, path::Path};
 
 pub fn os_arch() -> Result<(&'static str, &'static str)> {
     let target_os = if cfg!(target_os = "windows") {
@@ -23,6 +23,136 @@ pub fn os_arch() -> Result<(&'static str, &'static str)> {
     Ok((target_os, target_arch))
 }
 
+/// The platform that the tools are downloaded for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Platform {
+    /// as returned by [os_arch]
+    pub os: &'static str,
+    pub arch: &'static str,
+    /// a linux with the musl libc, like Alpine, which can't run the glibc binaries
+    pub musl: bool,
+}
+
+impl Platform {
+    pub fn current() -> Result<Self> {
+        let (os, arch) = os_arch()?;
+        Ok(Self {
+            os,
+            arch,
+            musl: os == "linux" && is_musl(),
+        })
+    }
+}
+
+/// as the rust target triple, like x86_64-unknown-linux-musl
+impl fmt::Display for Platform {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let arch = self.arch;
+        match (self.os, self.musl) {
+            ("linux", true) => write!(f, "{arch}-unknown-linux-musl"),
+            ("linux", false) => write!(f, "{arch}-unknown-linux-gnu"),
+            ("macos", _) => write!(f, "{arch}-apple-darwin"),
+            ("windows", _) => write!(f, "{arch}-pc-windows-msvc"),
+            (os, _) => write!(f, "{arch}-{os}"),
+        }
+    }
+}
+
+/// A static musl build of cargo-leptos also runs on a glibc linux, so the glibc loader is
+/// looked for
+fn is_musl() -> bool {
+    if !cfg!(target_env = "musl") {
+        return false;
+    }
+    let loaders = ["/lib64/ld-linux-x86-64.so.2", "/lib/ld-linux-aarch64.so.1"];
+    !loaders.iter().any(|loader| Path::new(loader).exists())
+}
+
+/// removes the terminal color and style escape sequences
+pub fn strip_ansi(text: &str) -> String {
+    let mut plain = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\x1b' {
+            plain.push(c);
+        } else if chars.next() == Some('[') {
+            // the parameters up to the final byte, like `1;31m`
+            for c in chars.by_ref() {
+                if ('@'..='~').contains(&c) {
+                    break;
+                }
+            }
+        }
+    }
+    plain
+}
+
+/// formats a byte count for humans, e.g. `1.5 MiB`
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+    if bytes < 1024 {
+        return format!("{bytes} B");
+    }
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{size:.1} {}", UNITS[unit])
+}
+
+/// splits a command line into its args like a posix shell, without expanding anything:
+/// the args are separated by whitespace, which '…' and "…" quote. A backslash escapes a
+/// whitespace, a quote or a backslash outside of the single quotes, and is kept before the
+/// other chars, like in the windows paths
+pub fn split_args(line: &str) -> Result<Vec<String>> {
+    let mut args = Vec::new();
+    let mut arg: Option<String> = None;
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => args.extend(arg.take()),
+            '\'' => {
+                let arg = arg.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => arg.push(c),
+                        None => bail!("Unclosed ' in {line:?}"),
+                    }
+                }
+            }
+            '"' => {
+                let arg = arg.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(c @ ('"' | '\\')) => arg.push(c),
+                            Some(c) => arg.extend(['\\', c]),
+                            None => bail!("Unclosed \" in {line:?}"),
+                        },
+                        Some(c) => arg.push(c),
+                        None => bail!("Unclosed \" in {line:?}"),
+                    }
+                }
+            }
+            '\\' => {
+                let arg = arg.get_or_insert_with(String::new);
+                match chars.next() {
+                    Some(c) if c.is_whitespace() || matches!(c, '\'' | '"' | '\\') => arg.push(c),
+                    Some(c) => arg.extend(['\\', c]),
+                    None => arg.push('\\'),
+                }
+            }
+            c => arg.get_or_insert_with(String::new).push(c),
+        }
+    }
+    args.extend(arg);
+    Ok(args)
+}
+
 pub trait StrAdditions {
     fn with(&self, append: &str) -> String;
     fn pad_left_to(&self, len: usize) -> Cow<str>;
@@ -1,7 +1,35 @@
+//! The build tool for Leptos, as the `cargo leptos` binary and as a library.
+//!
+//! The library builds, serves and tests the projects of a [`config::Config`], loaded from the
+//! same [`config::Opts`] as the command line options:
+//!
+//! ```no_run
+//! # async fn build() -> cargo_leptos::ext::anyhow::Result<()> {
+//! use camino::Utf8Path;
+//! use cargo_leptos::config::{BuildOpts, Config, Opts};
+//!
+//! let opts = Opts {
+//!     release: true,
+//!     ..Default::default()
+//! };
+//! let cwd = Utf8Path::new("/path/to/app");
+//! let config = Config::load(opts, cwd, &cwd.join("Cargo.toml"), false)?;
+//! cargo_leptos::setup(&config)?;
+//! let report = cargo_leptos::command::build_all(&config, &BuildOpts::default()).await?;
+//! for file in &report.projects[0].site_files {
+//!     println!("{} {}", file.path, file.size);
+//! }
+//! # Ok(()) }
+//! ```
+//!
+//! The build events are received with [`signal::StatusSignal::subscribe_events`], and the
+//! commands stop on ctrl-c once [`signal::Interrupt::run_ctrl_c_monitor`] runs. The command
+//! line parsing is behind the `cli` feature, a default one that the binary requires.
+
 #[cfg(all(test, feature = "full_tests"))]
 mod tests;
 
-mod command;
+pub mod command;
 pub mod compile;
 pub mod config;
 pub mod ext;
@@ -9,21 +37,68 @@ mod logger;
 pub mod service;
 pub mod signal;
 
-use crate::config::Commands;
+#[cfg(feature = "cli")]
+use crate::config::{Cli, Commands, MessageFormat, Opts};
 use crate::ext::anyhow::{Context, Result};
+#[cfg(feature = "cli")]
 use crate::ext::PathBufExt;
 use crate::logger::GRAY;
+#[cfg(feature = "cli")]
 use camino::Utf8PathBuf;
-use config::{Cli, Config};
-use ext::fs;
-use signal::Interrupt;
+use config::Config;
+#[cfg(feature = "cli")]
+use ext::exit::Exit;
+use ext::{exe, fs};
+#[cfg(feature = "cli")]
+use signal::{Interrupt, StatusSignal};
 use std::env;
 
+/// Prepares the process for the commands of the config, as the binary does once it is
+/// loaded: the tools settings, cargo offline, and the working dir set to the workspace,
+/// which the paths of the config are relative to
+pub fn setup(config: &Config) -> Result<()> {
+    if config.cli.offline {
+        // for the cargo commands
+        env::set_var("CARGO_NET_OFFLINE", "true");
+    }
+    exe::set_tool_settings(config.tool_settings());
+    if let Ok(dir) = exe::get_cache_dir() {
+        log::debug!("Install tool cache {}", GRAY.paint(dir.to_string_lossy()));
+    }
+    env::set_current_dir(&config.working_dir).dot()?;
+    log::debug!(
+        "Path working dir {}",
+        GRAY.paint(config.working_dir.as_str())
+    );
+    Ok(())
+}
+
+#[cfg(feature = "cli")]
 pub async fn run(args: Cli) -> Result<()> {
-    let verbose = args.opts().map(|o| o.verbose).unwrap_or(0);
-    logger::setup(verbose, &args.log);
+    let res = run_command(args).await;
+    if let Err(e) = &res {
+        // as printed by main
+        logger::write_log_file(&format!("Error: {e:?}"));
+    }
+    logger::flush_log_file();
+    res
+}
 
-    if let Commands::New(new) = &args.command {
+#[cfg(feature = "cli")]
+async fn run_command(args: Cli) -> Result<()> {
+    let verbose = args.opts().map_or(0, |o| o.verbose).max(args.verbose);
+    logger::setup(
+        verbose,
+        &args.log,
+        args.log_format,
+        args.log_timestamps,
+        args.color,
+    );
+    if let Some(log_file) = &args.log_file {
+        logger::log_to_file(log_file, args.log_file_append).map_err(|e| e.context(Exit::Config))?;
+    }
+
+    if let Some(Commands::New(new)) = &args.command {
         return new.run().await;
     }
 
@@ -36,24 +111,106 @@ pub async fn run(args: Cli) -> Result<()> {
     let mut cwd = Utf8PathBuf::from_path_buf(std::env::current_dir().unwrap()).unwrap();
     cwd.clean_windows_path();
 
+    if args.version {
+        // the tools of the project are looked up with its config, when there is one
+        let config = if manifest_path.exists() {
+            match Config::load(Opts::default(), &cwd, &manifest_path, false) {
+                Ok(config) => Some(config),
+                Err(e) => {
+                    log::warn!("Version not using the project config: {e:#}");
+                    None
+                }
+            }
+        } else {
+            None
+        };
+        if let Some(config) = &config {
+            exe::set_tool_settings(config.tool_settings());
+        }
+        return command::version(config.as_ref(), args.verbose > 0, args.json).await;
+    }
+
+    let Some(command) = &args.command else {
+        return Err(Exit::Config).context("A command is needed, like build or watch, see --help");
+    };
+
+    if let Commands::Init(init) = command {
+        return init.run(&cwd, &manifest_path).await;
+    }
+
+    if let Commands::Tools(tools) = command {
+        // list and clean also work outside of a project
+        let config = if manifest_path.exists() || tools.needs_project() {
+            match Config::load(Opts::default(), &cwd, &manifest_path, false) {
+                Ok(config) => Some(config),
+                Err(e) if !tools.needs_project() => {
+                    log::warn!("Tools not using the project config: {e:#}");
+                    None
+                }
+                Err(e) => return Err(e.context(Exit::Config)),
+            }
+        } else {
+            None
+        };
+        if let Some(config) = &config {
+            exe::set_tool_settings(config.tool_settings());
+        }
+        return tools.run(config.as_ref()).await;
+    }
+
+    if let Commands::Clean(clean) = command {
+        let opts = Opts {
+            project: clean.project.clone(),
+            ..Default::default()
+        };
+        let config = Config::load(opts, &cwd, &manifest_path, false)
+            .dot()
+            .map_err(|e| e.context(Exit::Config))?;
+        exe::set_tool_settings(config.tool_settings());
+        return clean.run(&config);
+    }
+
     let opts = args.opts().unwrap();
+    if opts.message_format == MessageFormat::Json {
+        StatusSignal::print_json();
+    }
 
-    let watch = matches!(args.command, Commands::Watch(_));
-    let config = Config::load(opts, &cwd, &manifest_path, watch).dot()?;
-    env::set_current_dir(&config.working_dir).dot()?;
-    log::debug!(
-        "Path working dir {}",
-        GRAY.paint(config.working_dir.as_str())
-    );
+    let watch = matches!(command, Commands::Watch(_));
+    let config = Config::load(opts, &cwd, &manifest_path, watch)
+        .dot()
+        .map_err(|e| e.context(Exit::Config))?;
+    if let [proj] = &config.projects[..] {
+        logger::set_project(&proj.name);
+    }
+    setup(&config)?;
 
     let _monitor = Interrupt::run_ctrl_c_monitor();
-    use Commands::{Build, EndToEnd, New, Serve, Test, Watch};
-    match args.command {
-        New(_) => panic!(),
-        Build(_) => command::build_all(&config).await,
-        Serve(_) => command::serve(&config.current_project()?).await,
-        Test(_) => command::test_all(&config).await,
-        EndToEnd(_) => command::end2end_all(&config).await,
-        Watch(_) => command::watch(&config.current_project()?).await,
+    use Commands::{
+        Build, Bundle, Clean, Doc, EndToEnd, Fmt, Init, New, Run, Serve, Test, Tools, Watch,
+    };
+    let result = match args.command.unwrap() {
+        New(_) | Init(_) | Tools(_) | Clean(_) => panic!(),
+        Build(mut build) => {
+            // relative to the dir cargo-leptos was started in
+            build.out_dir = build.out_dir.map(|dir| cwd.join(dir));
+            command::build_all(&config, &build).await?.check().map(drop)
+        }
+        Bundle(mut bundle) => {
+            bundle.output = bundle.output.map(|output| cwd.join(output));
+            command::bundle(&config.current_project()?, &bundle).await
+        }
+        Serve(serve) | Watch(serve) if serve.all_projects => command::all_projects(&config).await,
+        Serve(serve) => command::serve(&config.current_project()?, &serve).await,
+        Run(_) => command::run(&config.current_project()?).await,
+        Test(test) => command::test_all(&config, &test).await?.check().map(drop),
+        Doc(doc) => command::doc_all(&config, &doc).await,
+        Fmt(fmt) => command::fmt_all(&config, &fmt).await,
+        EndToEnd(e2e) => command::end2end_all(&config, &e2e).await,
+        Watch(serve) => command::watch(&config.current_project()?, &serve).await,
+    };
+    // also when the command ended normally after the shutdown, like watch
+    if result.is_ok() && Interrupt::is_ctrl_c_received() {
+        return Err(Exit::Interrupted.into());
     }
+    result
 }
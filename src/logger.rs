@@ -1,27 +1,321 @@
-use ansi_term::{Colour::Fixed, Style};
+use ansi_term::{ANSIGenericString, Colour::Fixed, Style};
+use camino::Utf8Path;
 use flexi_logger::{
     filter::{LogLineFilter, LogLineWriter},
     DeferredNow, Level, Record,
 };
 use once_cell::sync::OnceCell;
-use std::io::Write;
+use serde::Serialize;
+use std::{
+    borrow::Cow,
+    cell::RefCell,
+    env, fmt,
+    fs::{File, OpenOptions},
+    io::{BufWriter, IsTerminal, Write},
+    panic,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+    time::Duration,
+};
 
-use crate::{config::Log, ext::StrAdditions};
+use crate::{
+    config::{ColorMode, Log, LogFormat, Project},
+    ext::{
+        anyhow::{Context, Result},
+        strip_ansi, StrAdditions,
+    },
+};
 
 // https://gist.github.com/fnky/458719343aabd01cfb17a3a4f7296797
 lazy_static::lazy_static! {
-   static ref ERR_RED: ansi_term::Color = Fixed(196);
-   static ref WARN_YELLOW: ansi_term::Color = Fixed(214);
-   pub static ref INFO_GREEN: ansi_term::Color = Fixed(77);
-   static ref DBG_BLUE: ansi_term::Color = Fixed(26);
-   static ref TRACE_VIOLET: ansi_term::Color = Fixed(98);
-
-   pub static ref GRAY: ansi_term::Color = Fixed(241);
-   pub static ref BOLD: ansi_term::Style = Style::new().bold();
+   static ref ERR_RED: Paint = Paint(Fixed(196).normal());
+   static ref WARN_YELLOW: Paint = Paint(Fixed(214).normal());
+   pub static ref INFO_GREEN: Paint = Paint(Fixed(77).normal());
+   static ref DBG_BLUE: Paint = Paint(Fixed(26).normal());
+   static ref TRACE_VIOLET: Paint = Paint(Fixed(98).normal());
+
+   pub static ref GRAY: Paint = Paint(Fixed(241).normal());
+   pub static ref BOLD: Paint = Paint(Style::new().bold());
+   /// the colors are on, until the --color is resolved
+   static ref COLORED: AtomicBool = AtomicBool::new(true);
+   /// the --color of the cargo commands, when the colors are forced on or off
+   static ref CARGO_COLOR: OnceCell<&'static str> = OnceCell::new();
    static ref LOG_SELECT: OnceCell<LogFlag> = OnceCell::new();
+   /// a progress line is drawn at the bottom of the terminal
+   static ref PROGRESS_SHOWN: AtomicBool = AtomicBool::new(false);
+   /// the logs and the output of the commands are printed as JSON lines
+   static ref JSON: AtomicBool = AtomicBool::new(false);
+   /// the human log lines start with the time
+   static ref TIMESTAMPS: AtomicBool = AtomicBool::new(false);
+   /// the project of the JSON log lines that don't name another one
+   static ref PROJECT: OnceCell<String> = OnceCell::new();
+   static ref LOG_FILE: Mutex<Option<BufWriter<File>>> = Mutex::new(None);
+}
+
+thread_local! {
+    /// the fields of the logs made in [with_fields]
+    static FIELDS: RefCell<Option<LogFields>> = RefCell::new(None);
+}
+
+const CLEAR_LINE: &str = "\r\x1b[2K";
+
+/// A color or a style of the logs, which paints nothing when the colors are off
+#[derive(Debug, Clone, Copy)]
+pub struct Paint(Style);
+
+impl Paint {
+    pub fn paint<'a, I, S: 'a + ToOwned + ?Sized>(&self, input: I) -> ANSIGenericString<'a, S>
+    where
+        I: Into<Cow<'a, S>>,
+        <S as ToOwned>::Owned: fmt::Debug,
+    {
+        if is_colored() {
+            self.0.paint(input)
+        } else {
+            Style::new().paint(input)
+        }
+    }
+}
+
+pub fn is_colored() -> bool {
+    COLORED.load(Ordering::Relaxed)
+}
+
+/// The `--color` for the cargo commands: `never` when the colors are off and `always` when
+/// they are forced, so that a piped cargo keeps its colors. None with the auto colors.
+pub fn cargo_color() -> Option<&'static str> {
+    CARGO_COLOR.get().copied()
+}
+
+/// Adds the [cargo_color] to the args of a cargo command
+pub fn add_cargo_color(args: &mut Vec<String>) {
+    if let Some(color) = cargo_color() {
+        args.push(format!("--color={color}"));
+    }
+}
+
+/// The --color, with NO_COLOR and then CLICOLOR_FORCE deciding the auto colors, as
+/// https://no-color.org and https://bixense.com/clicolors have it
+fn resolve_color(color: ColorMode) -> ColorMode {
+    let is_set = |var| env::var_os(var).map_or(false, |val| !val.is_empty() && val != "0");
+    match color {
+        ColorMode::Auto if is_set("NO_COLOR") => ColorMode::Never,
+        ColorMode::Auto if is_set("CLICOLOR_FORCE") => ColorMode::Always,
+        color => color,
+    }
+}
+
+const TIMESTAMP: &str = "%Y-%m-%dT%H:%M:%S%.3f%:z";
+
+/// The fields that the JSON log lines have besides the message. The human format leaves them
+/// out, as the messages tell the same.
+#[derive(Debug, Default, Clone, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct LogFields {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    project: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stage: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    outcome: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    duration_ms: Option<u128>,
+    /// the times of the steps of a build
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    steps: Vec<StepDuration>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct StepDuration {
+    step: &'static str,
+    duration_ms: u128,
+}
+
+impl LogFields {
+    pub fn project(proj: &Project) -> Self {
+        Self {
+            project: Some(proj.name.clone()),
+            ..Self::default()
+        }
+    }
+
+    /// like front, server or tailwind
+    pub fn stage(mut self, stage: &'static str) -> Self {
+        self.stage = Some(stage);
+        self
+    }
+
+    /// like failed or interrupted
+    pub fn outcome(mut self, outcome: &'static str) -> Self {
+        self.outcome = Some(outcome);
+        self
+    }
+
+    pub fn duration(mut self, duration: Duration) -> Self {
+        self.duration_ms = Some(duration.as_millis());
+        self
+    }
+
+    pub fn step(mut self, step: &'static str, duration: Duration) -> Self {
+        self.steps.push(StepDuration {
+            step,
+            duration_ms: duration.as_millis(),
+        });
+        self
+    }
+}
+
+/// Logs with the fields: the logs made by `log` get them in the JSON log format
+pub fn with_fields<T>(fields: LogFields, log: impl FnOnce() -> T) -> T {
+    FIELDS.with(|f| *f.borrow_mut() = Some(fields));
+    let res = log();
+    FIELDS.with(|f| *f.borrow_mut() = None);
+    res
+}
+
+pub fn is_json() -> bool {
+    JSON.load(Ordering::Relaxed)
+}
+
+/// The project of all the logs, when cargo-leptos runs for a single project
+pub fn set_project(name: &str) {
+    _ = PROJECT.set(name.to_string());
+}
+
+/// The fields of the current log line, with the project of [set_project] by default
+fn fields() -> LogFields {
+    let mut fields = FIELDS.with(|f| f.borrow().clone()).unwrap_or_default();
+    if fields.project.is_none() {
+        fields.project = PROJECT.get().cloned();
+    }
+    fields
+}
+
+/// A JSON log line
+#[derive(Serialize)]
+struct JsonLine<'a> {
+    timestamp: String,
+    level: &'a str,
+    target: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    module: Option<&'a str>,
+    /// the stream of the output of a command
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stream: Option<&'a str>,
+    message: String,
+    #[serde(flatten)]
+    fields: LogFields,
 }
 
-pub fn setup(verbose: u8, logs: &[Log]) {
+/// Prints a line of the output of a command on the stream it was read from, as the `text` of
+/// the caller, or in the JSON log format as a JSON line without its colors and with the
+/// command as target
+pub fn print_output(command: &str, line: &str, text: &str, is_stderr: bool) {
+    if !is_json() {
+        return print_line(text, is_stderr);
+    }
+    let line = JsonLine {
+        timestamp: DeferredNow::new().format(TIMESTAMP).to_string(),
+        level: "info",
+        target: command,
+        module: None,
+        stream: Some(if is_stderr { "stderr" } else { "stdout" }),
+        message: strip_ansi(line),
+        fields: fields(),
+    };
+    if let Ok(line) = serde_json::to_string(&line) {
+        print_line(&line, is_stderr);
+    }
+}
+
+/// Prints the captured output of a command line by line, as [print_output]
+pub fn print_captured(command: &str, output: &str, is_stderr: bool) {
+    for line in output.lines() {
+        print_output(command, line, line, is_stderr);
+    }
+}
+
+/// Prints the line as it is, without its colors when they are off, and writes it to the log
+/// file
+pub fn print_line(line: &str, is_stderr: bool) {
+    let line = if is_colored() {
+        Cow::Borrowed(line)
+    } else {
+        Cow::Owned(strip_ansi(line))
+    };
+    if is_stderr {
+        eprintln!("{line}");
+    } else {
+        println!("{line}");
+    }
+    write_log_file(&line);
+}
+
+/// Writes the logs and the output of the commands to the file as well, without the colors.
+/// The file is truncated, unless `append`. It is flushed by [flush_log_file] and on panic.
+pub fn log_to_file(path: &Utf8Path, append: bool) -> Result<()> {
+    let file = OpenOptions::new()
+        .create(true)
+        .write(true)
+        .append(append)
+        .truncate(!append)
+        .open(path)
+        .context(format!("Could not open the log file {path}"))?;
+    *LOG_FILE.lock().unwrap_or_else(|e| e.into_inner()) = Some(BufWriter::new(file));
+
+    let hook = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        hook(info);
+        // not waiting for the lock, which the panicking thread might hold
+        if let Ok(mut file) = LOG_FILE.try_lock() {
+            if let Some(file) = file.as_mut() {
+                _ = writeln!(file, "{info}");
+                _ = file.flush();
+            }
+        }
+    }));
+    log::info!("Leptos logging to {}", GRAY.paint(path.as_str()));
+    Ok(())
+}
+
+pub fn has_log_file() -> bool {
+    LOG_FILE.lock().unwrap_or_else(|e| e.into_inner()).is_some()
+}
+
+pub fn flush_log_file() {
+    if let Some(file) = LOG_FILE.lock().unwrap_or_else(|e| e.into_inner()).as_mut() {
+        _ = file.flush();
+    }
+}
+
+/// Writes the text without the colors and the line clearing of the progress line
+pub fn write_log_file(text: &str) {
+    if let Some(file) = LOG_FILE.lock().unwrap_or_else(|e| e.into_inner()).as_mut() {
+        _ = writeln!(file, "{}", strip_ansi(text).replace('\r', ""));
+    }
+}
+
+/// Draws the progress line at the bottom of stderr. The next log line replaces it, and it is
+/// drawn again below at its next update.
+pub fn show_progress(line: &str) {
+    let mut stderr = std::io::stderr().lock();
+    _ = write!(stderr, "{CLEAR_LINE}{line}");
+    _ = stderr.flush();
+    PROGRESS_SHOWN.store(true, Ordering::Relaxed);
+}
+
+pub fn clear_progress() {
+    if PROGRESS_SHOWN.swap(false, Ordering::Relaxed) {
+        let mut stderr = std::io::stderr().lock();
+        _ = write!(stderr, "{CLEAR_LINE}");
+        _ = stderr.flush();
+    }
+}
+
+pub fn setup(verbose: u8, logs: &[Log], log_format: LogFormat, timestamps: bool, color: ColorMode) {
     let log_level = match verbose {
         0 => "info",
         1 => "debug",
@@ -29,10 +323,25 @@ pub fn setup(verbose: u8, logs: &[Log]) {
     };
 
     if LOG_SELECT.get().is_none() {
+        let json = log_format == LogFormat::Json;
+        JSON.store(json, Ordering::Relaxed);
+        TIMESTAMPS.store(timestamps, Ordering::Relaxed);
+        let colored = match resolve_color(color) {
+            ColorMode::Always => {
+                _ = CARGO_COLOR.set("always");
+                true
+            }
+            ColorMode::Never => {
+                _ = CARGO_COLOR.set("never");
+                false
+            }
+            ColorMode::Auto => std::io::stderr().is_terminal(),
+        };
+        COLORED.store(colored, Ordering::Relaxed);
         flexi_logger::Logger::try_with_str(log_level)
             .unwrap()
             .filter(Box::new(Filter))
-            .format(format)
+            .format(tee_format)
             .start()
             .unwrap();
 
@@ -77,17 +386,29 @@ impl Log {
 // https://docs.rs/flexi_logger/0.24.1/flexi_logger/type.FormatFunction.html
 fn format(
     write: &mut dyn Write,
-    _now: &mut DeferredNow,
+    now: &mut DeferredNow,
     record: &Record<'_>,
 ) -> Result<(), std::io::Error> {
-    let args = record.args().to_string();
+    let mut args = record.args().to_string();
+    if !is_colored() {
+        // like the output of a command in the message
+        args = strip_ansi(&args);
+    }
+
+    if PROGRESS_SHOWN.swap(false, Ordering::Relaxed) {
+        write!(write, "{CLEAR_LINE}")?;
+    }
+    if TIMESTAMPS.load(Ordering::Relaxed) {
+        let time = now.format("%H:%M:%S%.3f").to_string();
+        write!(write, "{} ", GRAY.paint(time))?;
+    }
 
     let lvl_color = record.level().color();
 
     if let Some(dep) = dependency(record) {
         let dep = format!("[{}]", dep);
         let dep = dep.pad_left_to(12);
-        write!(write, "{} {}", lvl_color.paint(dep), record.args())
+        write!(write, "{} {}", lvl_color.paint(dep), args)
     } else {
         let (word, rest) = split(&args);
         let word = word.pad_left_to(12);
@@ -95,6 +416,44 @@ fn format(
     }
 }
 
+/// Formats the log line in the log format, and writes it to the log file as well
+fn tee_format(
+    write: &mut dyn Write,
+    now: &mut DeferredNow,
+    record: &Record<'_>,
+) -> Result<(), std::io::Error> {
+    let mut line = Vec::new();
+    if is_json() {
+        json_format(&mut line, now, record)?;
+    } else {
+        format(&mut line, now, record)?;
+    }
+    write_log_file(&String::from_utf8_lossy(&line));
+    // the file has the warnings and errors at once, as the reason to look at it
+    if record.level() <= Level::Warn {
+        flush_log_file();
+    }
+    write.write_all(&line)
+}
+
+fn json_format(
+    write: &mut dyn Write,
+    now: &mut DeferredNow,
+    record: &Record<'_>,
+) -> Result<(), std::io::Error> {
+    let level = record.level().as_str().to_lowercase();
+    let line = JsonLine {
+        timestamp: now.format(TIMESTAMP).to_string(),
+        level: &level,
+        target: record.target(),
+        module: record.module_path(),
+        stream: None,
+        message: strip_ansi(&record.args().to_string()),
+        fields: fields(),
+    };
+    serde_json::to_writer(write, &line).map_err(std::io::Error::from)
+}
+
 fn split(args: &String) -> (&str, &str) {
     match args.find(' ') {
         Some(i) => (&args[..i], &args[i + 1..]),
@@ -132,11 +491,11 @@ impl LogLineFilter for Filter {
 }
 
 trait LevelExt {
-    fn color(&self) -> ansi_term::Color;
+    fn color(&self) -> Paint;
 }
 
 impl LevelExt for Level {
-    fn color(&self) -> ansi_term::Color {
+    fn color(&self) -> Paint {
         match self {
             Level::Error => *ERR_RED,
             Level::Warn => *WARN_YELLOW,
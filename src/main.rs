@@ -1,9 +1,9 @@
-use cargo_leptos::{config::Cli, ext::anyhow::Result, run};
+use cargo_leptos::{config::Cli, ext::exit::exit_code, run};
 use clap::Parser;
-use std::env;
+use std::{env, process};
 
 #[tokio::main]
-async fn main() -> Result<()> {
+async fn main() {
     let mut args: Vec<String> = env::args().collect();
     // when running as cargo leptos, the second argument is "leptos" which
     // clap doesn't expect
@@ -12,5 +12,9 @@ async fn main() -> Result<()> {
     }
 
     let args = Cli::parse_from(&args);
-    crate::run(args).await
+    if let Err(e) = crate::run(args).await {
+        // as printed when main returns the error
+        eprintln!("Error: {e:?}");
+        process::exit(exit_code(&e));
+    }
 }
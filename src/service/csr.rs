@@ -0,0 +1,240 @@
+use std::{net::SocketAddr, sync::Arc};
+
+use axum::{
+    body::Body,
+    extract::ConnectInfo,
+    http::{header, Method, Request, Response, StatusCode, Uri},
+    routing::any,
+    Router,
+};
+use camino::{Utf8Path, Utf8PathBuf};
+use percent_encoding::percent_decode_str;
+use tokio::task::JoinHandle;
+
+use crate::config::Project;
+use crate::ext::anyhow::{bail, Context, Result};
+use crate::ext::fs;
+use crate::ext::sync::{addr_in_use, addr_in_use_help};
+use crate::logger::GRAY;
+use crate::signal::Interrupt;
+
+use super::{
+    proxy, reload,
+    tls::{self, Incoming},
+};
+
+/// Serves the site-root of a client-side-rendered project, which has no server bin.
+/// The paths that are not files, nor look like files, get the index.html so that the
+/// routing is done in the browser. The requests matching a proxy route are forwarded.
+pub async fn spawn(proj: &Arc<Project>) -> JoinHandle<Result<()>> {
+    let proj = proj.clone();
+    let mut int = Interrupt::subscribe_shutdown();
+
+    tokio::spawn(async move {
+        let addr = proj.site.addr;
+        // the bind failures are only fatal when none of the addresses is free
+        if proj.site.addrs.iter().all(|addr| addr_in_use(*addr)) {
+            Interrupt::request_shutdown().await;
+            bail!(addr_in_use_help(addr));
+        }
+        let help = format!(
+            "Serve could not listen on {}. You can set it with site-addr",
+            proj.site.addrs_string()
+        );
+        let tls = match tls::server_config(&proj).await {
+            Ok(tls) => tls,
+            Err(e) => {
+                Interrupt::request_shutdown().await;
+                return Err(e);
+            }
+        };
+        let scheme = if tls.is_some() { "https" } else { "http" };
+        let (incoming, bound) = Incoming::bind_all(&proj.site.addrs, tls)
+            .await
+            .context(help)?;
+
+        log::info!(
+            "Serve static site on {scheme}://{}{}/ {}",
+            proj.site.browse_addr(),
+            proj.site.base_path(),
+            GRAY.paint(proj.site.root_dir.as_str())
+        );
+        if bound.len() > 1 {
+            let bound: Vec<String> = bound.iter().map(|addr| addr.to_string()).collect();
+            log::info!("Serve listening on {}", bound.join(", "));
+        }
+
+        if let Some(proxy) = &proj.proxy {
+            log::info!("Serve proxy routes {}", GRAY.paint(proxy.routes_string()));
+        }
+        let client = proxy::client();
+        let app = Router::new().fallback(any(
+            move |ConnectInfo(remote): ConnectInfo<SocketAddr>, req: Request<Body>| {
+                handle(proj.clone(), client.clone(), remote, req)
+            },
+        ));
+        axum::Server::builder(incoming)
+            .serve(app.into_make_service_with_connect_info::<SocketAddr>())
+            .with_graceful_shutdown(async move {
+                _ = int.recv().await;
+            })
+            .await
+            .context("Serve static site")?;
+        log::trace!("Serve stopped");
+        Ok(())
+    })
+}
+
+async fn handle(
+    proj: Arc<Project>,
+    client: proxy::HttpClient,
+    remote: SocketAddr,
+    req: Request<Body>,
+) -> Response<Body> {
+    let routed = proj
+        .proxy
+        .as_ref()
+        .map_or(false, |conf| conf.route(req.uri().path()).is_some());
+    if routed {
+        return match proxy::forward(req, remote, proj, client).await {
+            Ok(resp) => resp,
+            Err(never) => match never {},
+        };
+    }
+    if !matches!(*req.method(), Method::GET | Method::HEAD) {
+        return status(StatusCode::METHOD_NOT_ALLOWED);
+    }
+    serve_file(proj, req.uri().clone()).await
+}
+
+async fn serve_file(proj: Arc<Project>, uri: Uri) -> Response<Body> {
+    let base = proj.site.base_path();
+    let path = match under_base_path(uri.path(), &base) {
+        // the relative links of the index.html need the trailing slash
+        Some("") => return redirect(&format!("{base}/")),
+        Some(path) => path,
+        None if uri.path() == "/" => return redirect(&format!("{base}/")),
+        None => {
+            log::debug!(
+                "Serve {} is outside of the site-base-path {base}",
+                GRAY.paint(uri.path())
+            );
+            return status(StatusCode::NOT_FOUND);
+        }
+    };
+    let Some(path) = site_path(path) else {
+        return status(StatusCode::BAD_REQUEST);
+    };
+    let root = &proj.site.root_dir;
+    let mut file = root.join(&path);
+    if file.is_dir() {
+        file = file.join("index.html");
+    }
+    if !file.is_file() {
+        if !is_route(&path, &proj.site.pkg_dir) {
+            log::debug!("Serve {} not found", GRAY.paint(uri.path()));
+            return status(StatusCode::NOT_FOUND);
+        }
+        file = root.join("index.html");
+    }
+    log::trace!("Serve {} -> {file}", uri.path());
+
+    let mut data = match fs::read(&file).await {
+        Ok(data) => data,
+        Err(e) => {
+            log::warn!("Serve {e:#}");
+            return status(StatusCode::NOT_FOUND);
+        }
+    };
+    if proj.watch && file.extension() == Some("html") {
+        data = with_reload_script(&proj, data);
+    }
+
+    Response::builder()
+        .header(header::CONTENT_TYPE, content_type(&file))
+        // the files change with every build
+        .header(header::CACHE_CONTROL, "no-cache")
+        .body(Body::from(data))
+        .unwrap()
+}
+
+/// The request path without the site-base-path, None when it is outside of it
+pub(crate) fn under_base_path<'a>(path: &'a str, base: &str) -> Option<&'a str> {
+    let rest = path.strip_prefix(base)?;
+    (rest.is_empty() || rest.starts_with('/')).then_some(rest)
+}
+
+/// The request path relative to the site-root, None when it points outside of it
+pub(crate) fn site_path(path: &str) -> Option<Utf8PathBuf> {
+    let path = percent_decode_str(path).decode_utf8().ok()?;
+    let mut rel = Utf8PathBuf::new();
+    for segment in path.split('/') {
+        match segment {
+            "" | "." => {}
+            ".." => return None,
+            s if s.contains('\\') || s.contains(':') => return None,
+            s => rel.push(s),
+        }
+    }
+    Some(rel)
+}
+
+/// A missing path is a route handled in the browser, unless it looks like a file
+pub(crate) fn is_route(path: &Utf8Path, pkg_dir: &Utf8Path) -> bool {
+    path.extension().is_none() && !path.starts_with(pkg_dir)
+}
+
+fn with_reload_script(proj: &Project, data: Vec<u8>) -> Vec<u8> {
+    let html = match String::from_utf8(data) {
+        Ok(html) => html,
+        Err(e) => return e.into_bytes(),
+    };
+    let script = format!("<script>{}</script>", reload::client_script(proj));
+    let html = match html.rfind("</body>") {
+        Some(pos) => format!("{}{script}{}", &html[..pos], &html[pos..]),
+        None => format!("{html}{script}"),
+    };
+    html.into_bytes()
+}
+
+fn content_type(file: &Utf8Path) -> &'static str {
+    match file.extension().unwrap_or_default() {
+        "html" | "htm" => "text/html; charset=utf-8",
+        "js" | "mjs" => "text/javascript; charset=utf-8",
+        "wasm" => "application/wasm",
+        "css" => "text/css; charset=utf-8",
+        "json" | "map" => "application/json",
+        "txt" => "text/plain; charset=utf-8",
+        "xml" => "application/xml",
+        "svg" => "image/svg+xml",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "avif" => "image/avif",
+        "ico" => "image/x-icon",
+        "woff" => "font/woff",
+        "woff2" => "font/woff2",
+        "ttf" => "font/ttf",
+        "otf" => "font/otf",
+        "mp4" => "video/mp4",
+        "webm" => "video/webm",
+        "mp3" => "audio/mpeg",
+        "pdf" => "application/pdf",
+        _ => "application/octet-stream",
+    }
+}
+
+fn redirect(location: &str) -> Response<Body> {
+    Response::builder()
+        .status(StatusCode::TEMPORARY_REDIRECT)
+        .header(header::LOCATION, location)
+        .body(Body::empty())
+        .unwrap()
+}
+
+fn status(status: StatusCode) -> Response<Body> {
+    let mut resp = Response::new(Body::from(status.to_string()));
+    *resp.status_mut() = status;
+    resp
+}
@@ -0,0 +1,120 @@
+use std::{
+    io::{stdin, stdout, IsTerminal, Write},
+    sync::Arc,
+    time::Duration,
+};
+
+use crossterm::{
+    cursor::MoveTo,
+    event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
+    execute,
+    terminal::{self, Clear, ClearType},
+};
+use tokio::{runtime::Handle, sync::broadcast::error::TryRecvError, task::JoinHandle};
+
+use crate::compile::{Change, WatchStats};
+use crate::config::Project;
+use crate::signal::Interrupt;
+
+const HELP: &str =
+    "Keys: r rebuild, o open the browser, c clear the screen, s stats, q quit, h help";
+
+/// how often the shutdown is checked while waiting for a key
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Reads the keybindings of the watch mode from the terminal, which is put in raw mode.
+/// Not started when stdin or stdout isn't a terminal, or with `--no-tui-keys`.
+/// The handle finishes after the shutdown, once the terminal is restored.
+pub fn spawn(proj: &Arc<Project>, open_path: Option<String>) -> Option<JoinHandle<()>> {
+    if !proj.watch_keys || !stdin().is_terminal() || !stdout().is_terminal() {
+        log::debug!("Keys not read as the terminal isn't interactive or --no-tui-keys is set");
+        return None;
+    }
+    if let Err(e) = terminal::enable_raw_mode() {
+        log::warn!("Keys could not set the terminal in raw mode: {e}");
+        return None;
+    }
+    keep_output_and_signals();
+    println!("{HELP}");
+
+    let proj = proj.clone();
+    let runtime = Handle::current();
+    let mut shutdown = Interrupt::subscribe_shutdown();
+    Some(tokio::task::spawn_blocking(move || {
+        let open_path = open_path.unwrap_or_else(|| "/".to_string());
+        while let Err(TryRecvError::Empty) = shutdown.try_recv() {
+            match event::poll(POLL_INTERVAL) {
+                Ok(false) => {}
+                Ok(true) => match event::read() {
+                    Ok(Event::Key(key)) => handle(key, &proj, &open_path, &runtime),
+                    Ok(_) => {}
+                    Err(e) => {
+                        log::warn!("Keys could not read the terminal: {e}");
+                        break;
+                    }
+                },
+                Err(e) => {
+                    log::warn!("Keys could not read the terminal: {e}");
+                    break;
+                }
+            }
+        }
+        if let Err(e) = terminal::disable_raw_mode() {
+            log::warn!("Keys could not restore the terminal: {e}");
+        }
+        log::trace!("Keys stopped");
+    }))
+}
+
+fn handle(key: KeyEvent, proj: &Arc<Project>, open_path: &str, runtime: &Handle) {
+    // windows also reports the releases
+    if key.kind != KeyEventKind::Press {
+        return;
+    }
+    match key.code {
+        // the raw mode of windows gives Ctrl+C as a key instead of the signal
+        KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+            runtime.block_on(Interrupt::request_ctrl_c_shutdown());
+        }
+        KeyCode::Char('r') => {
+            log::info!("Keys rebuilding");
+            // through the interrupt, like a file change
+            Interrupt::send(&[Change::LibSource, Change::BinSource]);
+        }
+        KeyCode::Char('c') => {
+            if let Err(e) = execute!(stdout(), Clear(ClearType::All), MoveTo(0, 0)) {
+                log::warn!("Keys could not clear the screen: {e}");
+            }
+        }
+        KeyCode::Char('o') => {
+            let _guard = runtime.enter();
+            super::open::spawn(proj, open_path);
+        }
+        KeyCode::Char('s') => WatchStats::log_summary(),
+        KeyCode::Char('q') => {
+            log::info!("Keys quitting");
+            runtime.block_on(Interrupt::request_shutdown());
+        }
+        KeyCode::Char('h') => println!("{HELP}"),
+        _ => {}
+    }
+    _ = stdout().flush();
+}
+
+/// The raw mode also stops translating the newlines of the output, which breaks the logs,
+/// and turns Ctrl+C into a key. Both are restored, so that Ctrl+C still sends the SIGINT.
+#[cfg(unix)]
+fn keep_output_and_signals() {
+    // SAFETY: the termios is initialized by tcgetattr before being used
+    unsafe {
+        let mut attrs: libc::termios = std::mem::zeroed();
+        if libc::tcgetattr(libc::STDIN_FILENO, &mut attrs) == 0 {
+            attrs.c_oflag |= libc::OPOST | libc::ONLCR;
+            attrs.c_lflag |= libc::ISIG;
+            libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &attrs);
+        }
+    }
+}
+
+#[cfg(not(unix))]
+fn keep_output_and_signals() {}
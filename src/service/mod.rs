@@ -1,5 +1,16 @@
+#[cfg(test)]
+mod tests;
+
+pub mod csr;
+pub mod keys;
+pub mod notification;
 pub mod notify;
+pub mod open;
 pub mod patch;
+pub mod proxy;
 pub mod reload;
 pub mod serve;
 pub mod site;
+pub mod status;
+pub mod tls;
+mod watch_ignore;
@@ -0,0 +1,57 @@
+use notify_rust::Notification;
+
+use crate::config::Project;
+use crate::signal::ReloadSignal;
+
+/// Shows a desktop notification when a watch mode build fails, and for the first success
+/// after a failure, with `--notify` or `notifications = true`. Where the notifications
+/// aren't available a warning is logged instead.
+pub struct BuildNotifier {
+    enabled: bool,
+    project: String,
+    failing: bool,
+}
+
+impl BuildNotifier {
+    pub fn new(proj: &Project) -> Self {
+        Self {
+            enabled: proj.notifications,
+            project: proj.name.clone(),
+            failing: false,
+        }
+    }
+
+    pub fn build_done(&mut self, ok: bool) {
+        if !self.enabled || (ok && !self.failing) {
+            return;
+        }
+        self.failing = !ok;
+        let (title, body) = if ok {
+            (
+                format!("{} build fixed", self.project),
+                "The build succeeded".to_string(),
+            )
+        } else {
+            (format!("{} build failed", self.project), error_summary())
+        };
+        tokio::task::spawn_blocking(move || {
+            let shown = Notification::new()
+                .appname("cargo-leptos")
+                .summary(&title)
+                .body(&body)
+                .show();
+            if let Err(e) = shown {
+                log::warn!("Notification could not be shown: {e}");
+            }
+        });
+    }
+}
+
+/// The first line of the compiler errors
+fn error_summary() -> String {
+    ReloadSignal::build_status()
+        .errors
+        .into_iter()
+        .next()
+        .unwrap_or_else(|| "See the cargo-leptos output".to_string())
+}
@@ -1,33 +1,103 @@
-use crate::compile::Change;
-use crate::config::Project;
+use crate::compile::{Change, ChangedFile, WatchStats, MAX_SYMLINK_DEPTH};
+use crate::config::{Project, WatchAction};
 use crate::ext::anyhow::{anyhow, Result};
 use crate::signal::Interrupt;
+
+use super::watch_ignore::WatchIgnore;
 use crate::{
     ext::{remove_nested, PathBufExt, PathExt},
     logger::GRAY,
 };
-use camino::Utf8PathBuf;
+use camino::{Utf8Path, Utf8PathBuf};
 use itertools::Itertools;
-use notify::{DebouncedEvent, RecursiveMode, Watcher};
+use notify::{
+    op::Op, DebouncedEvent, PollWatcher, RawEvent, RecommendedWatcher, RecursiveMode, Watcher,
+};
 use std::collections::HashSet;
 use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError, Sender};
 use std::sync::Arc;
-use std::{fmt::Display, time::Duration};
+use std::{
+    fmt::Display,
+    time::{Duration, Instant},
+};
 use tokio::task::JoinHandle;
 
+/// a continuous stream of file events delays the rebuild by at most this many debounce periods
+const MAX_DEBOUNCE_PERIODS: u32 = 10;
+
+/// how often the files are compared for detecting a watcher that gets no events
+const STALL_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+/// the number of files that are compared
+const STALL_CHECK_FILES: usize = 32;
+
 pub async fn spawn(proj: &Arc<Project>) -> Result<JoinHandle<()>> {
     let mut set: HashSet<Utf8PathBuf> = HashSet::from_iter(vec![]);
 
-    set.extend(proj.lib.src_paths.clone());
-    set.extend(proj.bin.src_paths.clone());
+    // the own dirs of the packages are always watched, like the lib's src dir which is also a
+    // dependency dir of the bin
+    let mut own_dirs = HashSet::new();
+    let mut dependencies = HashSet::new();
+    let packages = proj
+        .bin
+        .iter()
+        .map(|bin| (&bin.src_paths, &bin.dependency_dirs))
+        .chain([(&proj.lib.src_paths, &proj.lib.dependency_dirs)]);
+    for (src_paths, dependency_dirs) in packages {
+        for path in src_paths {
+            if dependency_dirs.contains(path) {
+                dependencies.insert(path.clone());
+            } else {
+                own_dirs.insert(path.clone());
+            }
+        }
+    }
+    dependencies.retain(|dir| !own_dirs.contains(dir));
+    set.extend(own_dirs);
+    if !proj.watch_dependencies {
+        dependencies.clear();
+    }
+    if !dependencies.is_empty() {
+        log::info!(
+            "Notify watching {} path dependency dirs {}",
+            dependencies.len(),
+            GRAY.paint(dependencies.iter().sorted().join(", "))
+        );
+    }
+    set.extend(dependencies);
     set.insert(proj.js_dir.clone());
 
     if let Some(file) = &proj.style.file {
         set.insert(file.source.clone().without_last());
     }
 
+    let mut links = Vec::new();
     if let Some(assets) = &proj.assets {
         set.insert(assets.dir.clone());
+
+        if !assets.preserve_symlinks {
+            links = symlink_targets(&assets.dir, &proj.working_dir);
+            // the dirs of the file targets are watched without their subdirs
+            set.extend(
+                links
+                    .iter()
+                    .filter(|(_, target)| target.is_dir())
+                    .map(|(_, target)| target.clone()),
+            );
+        }
+    }
+
+    for file in &proj.watch_additional_files {
+        if file.watched.exists() {
+            set.insert(file.watched.clone());
+        } else {
+            log::warn!(
+                "Notify watch-additional-files entry {:?} not found: {}",
+                file.pattern,
+                file.watched
+            );
+        }
     }
 
     let paths = remove_nested(set.into_iter().filter(|path| Path::new(path).exists()));
@@ -38,55 +108,347 @@ pub async fn spawn(proj: &Arc<Project>) -> Result<JoinHandle<()>> {
     );
     let proj = proj.clone();
 
-    Ok(tokio::spawn(async move { run(&paths, proj).await }))
+    Ok(tokio::spawn(async move { run(&paths, proj, links).await }))
 }
 
-async fn run(paths: &[Utf8PathBuf], proj: Arc<Project>) {
-    let (sync_tx, sync_rx) = std::sync::mpsc::channel::<DebouncedEvent>();
+/// Finds the symlinked dirs and files in the assets dir, returned as (link, resolved target).
+/// The target is relative to the working dir when inside it, like the watched paths.
+pub(crate) fn symlink_targets(
+    dir: &Utf8Path,
+    working_dir: &Utf8Path,
+) -> Vec<(Utf8PathBuf, Utf8PathBuf)> {
+    let mut found = Vec::new();
+    let mut dirs = vec![(dir.to_path_buf(), 0)];
 
-    let proj = proj.clone();
+    while let Some((dir, link_depth)) = dirs.pop() {
+        let Ok(entries) = dir.read_dir_utf8() else {
+            continue
+        };
+        for entry in entries.flatten() {
+            let path = entry.path().to_path_buf();
+            let Ok(file_type) = entry.file_type() else {
+                continue
+            };
+            if !file_type.is_symlink() {
+                if file_type.is_dir() {
+                    dirs.push((path, link_depth));
+                }
+                continue;
+            }
+            let Ok(target) = path.canonicalize_utf8() else {
+                continue
+            };
+            if target.is_file() {
+                let target = target.unbase(working_dir).unwrap_or(target);
+                log::debug!(
+                    "Notify watching symlink target {}",
+                    GRAY.paint(format!("{path} -> {target}"))
+                );
+                found.push((path, target));
+                continue;
+            }
+            if !target.is_dir() || link_depth >= MAX_SYMLINK_DEPTH {
+                continue;
+            }
+            let is_cycle = dir
+                .canonicalize_utf8()
+                .map(|d| d.starts_with(&target))
+                .unwrap_or(true);
+            if is_cycle {
+                continue;
+            }
+            let target = target.unbase(working_dir).unwrap_or(target);
+            log::debug!(
+                "Notify watching symlink target {}",
+                GRAY.paint(format!("{path} -> {target}"))
+            );
+            found.push((path.clone(), target));
+            dirs.push((path, link_depth + 1));
+        }
+    }
+    found
+}
+
+async fn run(paths: &[Utf8PathBuf], proj: Arc<Project>, links: Vec<(Utf8PathBuf, Utf8PathBuf)>) {
+    let (sync_tx, sync_rx) = channel::<DebouncedEvent>();
+
+    let debounce = proj.watch_debounce;
+    let received = Arc::new(AtomicBool::new(false));
+    let mut watcher = FsWatcher::new(&proj, sync_tx).expect("failed to build file system watcher");
+
+    let event_proj = proj.clone();
+    let event_received = received.clone();
     std::thread::spawn(move || {
+        let proj = event_proj;
+        let mut ignore = proj.watch_gitignore.then(WatchIgnore::default);
         while let Ok(event) = sync_rx.recv() {
-            match Watched::try_new(&event, &proj) {
-                Ok(Some(watched)) => handle(watched, proj.clone()),
-                Err(e) => log::error!("Notify error {e}"),
-                _ => log::trace!("Notify not handled {}", GRAY.paint(format!("{:?}", event))),
-            }
+            event_received.store(true, Ordering::Relaxed);
+            let events = collect_quiet_period(event, &sync_rx, debounce);
+            handle(&events, &proj, &links, ignore.as_mut());
         }
         log::debug!("Notify stopped");
     });
 
-    let mut watcher = notify::watcher(sync_tx, Duration::from_millis(200))
-        .expect("failed to build file system watcher");
-
     for path in paths {
         if let Err(e) = watcher.watch(path, RecursiveMode::Recursive) {
             log::error!("Notify could not watch {path:?} due to {e:?}");
         }
     }
+    // the dir of the .env file, which might not exist yet, is usually the workspace root
+    let dotenv_dir = match proj.dotenv_file.parent() {
+        Some(dir) if !dir.as_str().is_empty() => dir,
+        _ => Utf8Path::new("."),
+    };
+    let watch_dotenv_dir =
+        proj.bin.is_some() && !paths.iter().any(|p| proj.dotenv_file.starts_with(p));
+    if watch_dotenv_dir {
+        if let Err(e) = watcher.watch(dotenv_dir, RecursiveMode::NonRecursive) {
+            log::error!("Notify could not watch {dotenv_dir:?} due to {e:?}");
+        }
+    }
+    // the editors replace the files, which loses the watch of a symlinked file
+    let file_link_dirs = links
+        .iter()
+        .filter(|(_, target)| !target.is_dir())
+        .filter_map(|(_, target)| target.parent())
+        .map(|dir| match dir.as_str() {
+            "" => Utf8Path::new("."),
+            _ => dir,
+        })
+        .filter(|dir| !paths.iter().any(|p| dir.starts_with(p)))
+        .unique();
+    for dir in file_link_dirs {
+        if let Err(e) = watcher.watch(dir, RecursiveMode::NonRecursive) {
+            log::error!("Notify could not watch {dir:?} due to {e:?}");
+        }
+    }
+    // the Cargo.toml is replaced by the editors, so its dir is watched for the proxy routes
+    if let Some(proxy) = &proj.proxy {
+        let dir = match proxy.manifest.parent() {
+            Some(dir) if !dir.as_str().is_empty() => dir,
+            _ => Utf8Path::new("."),
+        };
+        let watched = paths.iter().any(|p| proxy.manifest.starts_with(p))
+            || (watch_dotenv_dir && dir == dotenv_dir);
+        if !watched {
+            if let Err(e) = watcher.watch(dir, RecursiveMode::NonRecursive) {
+                log::error!("Notify could not watch {dir:?} due to {e:?}");
+            }
+        }
+    }
+    if let FsWatcher::Native(_) = watcher {
+        spawn_stall_check(paths, received);
+    }
 
     if let Err(e) = Interrupt::subscribe_shutdown().recv().await {
         log::trace!("Notify stopped due to: {e:?}");
     }
 }
 
-fn handle(watched: Watched, proj: Arc<Project>) {
+/// The native watcher, or the polling one with `--poll`
+enum FsWatcher {
+    Native(RecommendedWatcher),
+    Poll(PollWatcher),
+}
+
+impl FsWatcher {
+    fn new(proj: &Project, tx: Sender<DebouncedEvent>) -> notify::Result<Self> {
+        let Some(interval) = proj.watch_poll else {
+            // the watcher merges the events of each path, the quiet period merges them all
+            let delay = proj.watch_debounce.min(Duration::from_millis(200));
+            return Ok(Self::Native(notify::watcher(tx, delay)?));
+        };
+        log::info!("Notify polling for changes every {interval:?}");
+        // notify's debounced poll watcher has a fixed interval of 30s, so the raw events
+        // are forwarded instead and merged by the quiet period
+        let (raw_tx, raw_rx) = channel::<RawEvent>();
+        let interval = u32::try_from(interval.as_millis()).unwrap_or(u32::MAX);
+        let watcher = PollWatcher::with_delay_ms(raw_tx, interval)?;
+        std::thread::spawn(move || {
+            while let Ok(raw) = raw_rx.recv() {
+                if let Some(event) = debounced_event(raw) {
+                    if tx.send(event).is_err() {
+                        return;
+                    }
+                }
+            }
+        });
+        Ok(Self::Poll(watcher))
+    }
+
+    fn watch(&mut self, path: &Utf8Path, mode: RecursiveMode) -> notify::Result<()> {
+        match self {
+            Self::Native(watcher) => watcher.watch(path, mode),
+            Self::Poll(watcher) => watcher.watch(path, mode),
+        }
+    }
+}
+
+/// The poll watcher only reports creates, writes and removes
+fn debounced_event(raw: RawEvent) -> Option<DebouncedEvent> {
+    let op = match raw.op {
+        Ok(op) => op,
+        Err(e) => return Some(DebouncedEvent::Error(e, raw.path)),
+    };
+    if op.contains(Op::RESCAN) {
+        return Some(DebouncedEvent::Rescan);
+    }
+    let path = raw.path?;
+    Some(if op.contains(Op::REMOVE) {
+        DebouncedEvent::Remove(path)
+    } else if op.contains(Op::CREATE) {
+        DebouncedEvent::Create(path)
+    } else if op.contains(Op::WRITE) {
+        DebouncedEvent::Write(path)
+    } else {
+        DebouncedEvent::Chmod(path)
+    })
+}
+
+/// Warns once when some files changed but the native watcher had no event at all, which
+/// happens with Docker bind mounts from macOS and with network file systems. Compares
+/// the modification times of a few files at the top of the watched dirs.
+fn spawn_stall_check(paths: &[Utf8PathBuf], received: Arc<AtomicBool>) {
+    let files = paths
+        .iter()
+        .flat_map(|path| match path.read_dir_utf8() {
+            Ok(entries) => entries
+                .flatten()
+                .map(|entry| entry.path().to_path_buf())
+                .filter(|path| path.is_file())
+                .collect(),
+            Err(_) => vec![path.clone()],
+        })
+        .take(STALL_CHECK_FILES)
+        .collect::<Vec<_>>();
+    let mtimes = move || {
+        files
+            .iter()
+            .map(|file| file.metadata().and_then(|m| m.modified()).ok())
+            .collect::<Vec<_>>()
+    };
+
+    std::thread::spawn(move || {
+        let initial = mtimes();
+        loop {
+            std::thread::sleep(STALL_CHECK_INTERVAL);
+            if received.load(Ordering::Relaxed) {
+                return;
+            }
+            if mtimes() != initial {
+                log::warn!(
+                    "Notify received no file events although files changed. The file system \
+                    doesn't seem to support change notifications, which is common with Docker \
+                    volumes and network file systems. Use --poll to watch by polling instead"
+                );
+                return;
+            }
+        }
+    });
+}
+
+/// Receives events until there were none for the debounce period. A continuous stream of
+/// events is cut after [`MAX_DEBOUNCE_PERIODS`] so that it doesn't hold back the rebuild.
+fn collect_quiet_period(
+    first: DebouncedEvent,
+    rx: &Receiver<DebouncedEvent>,
+    debounce: Duration,
+) -> Vec<DebouncedEvent> {
+    let mut events = vec![first];
+    let started = Instant::now();
+    let max = debounce * MAX_DEBOUNCE_PERIODS;
+    loop {
+        let remaining = max.saturating_sub(started.elapsed());
+        if remaining.is_zero() {
+            log::debug!("Notify still receiving events after {max:?}, rebuilding");
+            return events;
+        }
+        match rx.recv_timeout(debounce.min(remaining)) {
+            Ok(event) => events.push(event),
+            Err(RecvTimeoutError::Timeout | RecvTimeoutError::Disconnected) => return events,
+        }
+    }
+}
+
+fn handle(
+    events: &[DebouncedEvent],
+    proj: &Project,
+    links: &[(Utf8PathBuf, Utf8PathBuf)],
+    mut ignore: Option<&mut WatchIgnore>,
+) {
+    let mut changes = Vec::new();
+    let mut files = Vec::new();
+    let mut all_changed = false;
+    let mut proxy_changed = false;
+    for event in events {
+        match Watched::try_new(event, proj) {
+            Ok(Some(watched)) => {
+                let watched = watched.resolve_links(links);
+                if let Some(proxy) = &proj.proxy {
+                    proxy_changed |= watched.path_starts_with(&proxy.manifest);
+                }
+                // the .env file is usually git-ignored, but its changes restart the server
+                let ignored = ignore
+                    .as_mut()
+                    .filter(|_| !watched.path_starts_with(&proj.dotenv_file))
+                    .and_then(|ignore| ignore.ignored(&watched));
+                if let Some(why) = ignored {
+                    log::trace!("Notify ignored {watched} by {why}");
+                    continue;
+                }
+                match changes_of(&watched, proj) {
+                    Some(found) => {
+                        if !found.is_empty() {
+                            WatchStats::add_paths(watched.path().cloned());
+                            files.extend(ChangedFile::new(&watched, &found));
+                        }
+                        changes.extend(found)
+                    }
+                    None => all_changed = true,
+                }
+            }
+            Err(e) => log::error!("Notify error {e}"),
+            _ => log::trace!("Notify not handled {}", GRAY.paint(format!("{:?}", event))),
+        }
+    }
+
+    if proxy_changed {
+        super::proxy::reload(proj);
+    }
+    if all_changed {
+        log::info!("Notify {} file events, rebuilding everything", events.len());
+        Interrupt::send_all_changed();
+    } else if !changes.is_empty() {
+        log::info!(
+            "Notify {} file events coalesced into a rebuild",
+            events.len()
+        );
+        Interrupt::send_with_files(&changes, files);
+    }
+}
+
+/// The changes of the watched path, None when everything needs to be rebuilt
+fn changes_of(watched: &Watched, proj: &Project) -> Option<Vec<Change>> {
     log::trace!(
         "Notify handle {}",
         GRAY.paint(format!("{:?}", watched.path()))
     );
 
-    let Some(path) = watched.path() else {
-        Interrupt::send_all_changed();
-        return
-    };
+    let path = watched.path()?;
 
     let mut changes = Vec::new();
 
     if let Some(assets) = &proj.assets {
         if path.starts_with(&assets.dir) {
-            log::debug!("Notify asset change {}", GRAY.paint(watched.to_string()));
-            changes.push(Change::Asset(watched.clone()));
+            if let Some(pattern) = assets.ignored_by(path, path.is_dir()) {
+                log::debug!(
+                    "Notify asset ignored (assets-ignore {pattern:?}) {}",
+                    GRAY.paint(watched.to_string())
+                );
+            } else {
+                log::debug!("Notify asset change {}", GRAY.paint(watched.to_string()));
+                changes.push(Change::Asset(watched.clone()));
+            }
         }
     }
 
@@ -101,7 +463,11 @@ fn handle(watched: Watched, proj: Arc<Project>) {
         changes.push(Change::LibSource);
     }
 
-    if path.starts_with_any(&proj.bin.src_paths) && path.is_ext_any(&["rs"]) {
+    let bin_rs = proj
+        .bin
+        .as_ref()
+        .map_or(false, |bin| path.starts_with_any(&bin.src_paths));
+    if bin_rs && path.is_ext_any(&["rs"]) {
         log::debug!(
             "Notify bin source change {}",
             GRAY.paint(watched.to_string())
@@ -117,14 +483,34 @@ fn handle(watched: Watched, proj: Arc<Project>) {
         }
     }
 
-    if !changes.is_empty() {
-        Interrupt::send(&changes);
-    } else {
+    for file in &proj.watch_additional_files {
+        if file.matches(path) {
+            log::debug!(
+                "Notify watch-additional-files {:?} ({}) matched {}",
+                file.pattern,
+                file.action,
+                GRAY.paint(watched.to_string())
+            );
+            changes.push(match file.action {
+                WatchAction::RebuildFront => Change::LibSource,
+                WatchAction::RestartServer => Change::ServerRestart,
+                WatchAction::ReloadBrowserOnly => Change::BrowserReload,
+            });
+        }
+    }
+
+    if proj.bin.is_some() && watched.path_starts_with(&proj.dotenv_file) {
+        log::debug!("Notify .env change {}", GRAY.paint(watched.to_string()));
+        changes.push(Change::DotEnv);
+    }
+
+    if changes.is_empty() {
         log::trace!(
             "Notify changed but not watched: {}",
             GRAY.paint(watched.to_string())
         );
     }
+    Some(changes)
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -166,6 +552,29 @@ impl Watched {
         })
     }
 
+    /// maps paths inside a symlink target back to the path through the link, so that
+    /// changes behind a link are handled like changes to the linked location
+    pub fn resolve_links(self, links: &[(Utf8PathBuf, Utf8PathBuf)]) -> Self {
+        let resolve = |path: Utf8PathBuf| {
+            for (link, target) in links {
+                match path.unbase(target) {
+                    // a symlinked file
+                    Ok(rel) if rel == "." => return link.clone(),
+                    Ok(rel) => return link.join(rel),
+                    Err(_) => {}
+                }
+            }
+            path
+        };
+        match self {
+            Self::Remove(p) => Self::Remove(resolve(p)),
+            Self::Rename(fr, to) => Self::Rename(resolve(fr), resolve(to)),
+            Self::Write(p) => Self::Write(resolve(p)),
+            Self::Create(p) => Self::Create(resolve(p)),
+            Self::Rescan => Self::Rescan,
+        }
+    }
+
     pub fn path_ext(&self) -> Option<&str> {
         self.path().and_then(|p| p.extension())
     }
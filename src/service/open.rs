@@ -0,0 +1,53 @@
+use std::{sync::Arc, time::Duration};
+
+use tokio::task::JoinHandle;
+
+use crate::config::Project;
+use crate::ext::sync::{connectable, wait_for_ready, Probe, Readiness};
+use crate::signal::Interrupt;
+
+/// the time the server has to start before the browser is not opened
+const READY_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Opens the system browser at the path of the site once the server accepts connections.
+/// The site is opened through the dev proxy when there is one, over https with `--https`.
+/// Disabled by setting `LEPTOS_NO_OPEN`.
+pub fn spawn(proj: &Arc<Project>, path: &str) -> Option<JoinHandle<()>> {
+    if std::env::var_os("LEPTOS_NO_OPEN").is_some() {
+        log::debug!("Open skipped as LEPTOS_NO_OPEN is set");
+        return None;
+    }
+    let (addr, scheme) = match proj.proxy.as_ref().and_then(|proxy| proxy.addr) {
+        Some(addr) if proj.tls.is_some() => (addr, "https"),
+        Some(addr) => (addr, "http"),
+        // the static server of a csr project is the only site server with tls
+        None if proj.tls.is_some() && proj.bin.is_none() => (proj.site.browse_addr(), "https"),
+        None => (proj.site.browse_addr(), "http"),
+    };
+    let addr = connectable(addr);
+    let url = format!(
+        "{scheme}://{addr}{}/{}",
+        proj.site.base_path(),
+        path.trim_start_matches('/')
+    );
+
+    Some(tokio::spawn(async move {
+        let ready = wait_for_ready(
+            "Open",
+            &Probe::Tcp(addr),
+            READY_TIMEOUT,
+            Interrupt::subscribe_shutdown(),
+        )
+        .await;
+        if !matches!(ready, Readiness::Ready) {
+            return;
+        }
+        log::info!("Open {url} in the browser");
+        let res = tokio::task::spawn_blocking(move || webbrowser::open(&url)).await;
+        match res {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) => log::warn!("Open could not launch the browser: {e}"),
+            Err(e) => log::warn!("Open {e}"),
+        }
+    }))
+}
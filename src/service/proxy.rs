@@ -0,0 +1,209 @@
+use std::{convert::Infallible, net::SocketAddr, sync::Arc, time::Duration};
+
+use hyper::{
+    client::HttpConnector,
+    header::{self, HeaderMap, HeaderValue},
+    service::{make_service_fn, service_fn},
+    Body, Client, Request, Response, StatusCode, Uri,
+};
+use tokio::task::JoinHandle;
+
+use crate::config::Project;
+use crate::ext::anyhow::{bail, Context, Result};
+use crate::logger::GRAY;
+use crate::signal::Interrupt;
+
+use super::tls::{self, Conn, Incoming};
+
+/// so that an unreachable upstream answers with a 502 instead of hanging
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// headers that apply to a single connection and are not forwarded
+const HOP_BY_HOP: &[&str] = &[
+    "connection",
+    "keep-alive",
+    "proxy-authenticate",
+    "proxy-authorization",
+    "te",
+    "trailer",
+    "transfer-encoding",
+    "upgrade",
+];
+
+pub(super) type HttpClient = Client<HttpConnector, Body>;
+
+/// Starts the dev proxy when there is a proxy-addr. It forwards the requests matching a
+/// proxy route to its target and the others to the site server.
+pub async fn spawn(proj: &Arc<Project>) -> Option<JoinHandle<()>> {
+    let conf = proj.proxy.as_ref()?;
+    let addr = conf.addr?;
+    let routes = conf.routes_string();
+    let proj = proj.clone();
+
+    Some(tokio::spawn(async move {
+        let tls = match tls::server_config(&proj).await {
+            Ok(tls) => tls,
+            Err(e) => {
+                log::error!("Proxy {e:#}");
+                Interrupt::request_shutdown().await;
+                return;
+            }
+        };
+        let scheme = if tls.is_some() { "https" } else { "http" };
+        let incoming = match Incoming::bind(addr, tls).await {
+            Ok(incoming) => incoming,
+            Err(e) => {
+                log::error!(
+                    "Proxy could not listen on {addr}: {e}. You can set it with proxy-addr"
+                );
+                Interrupt::request_shutdown().await;
+                return;
+            }
+        };
+
+        let client = client();
+        let make_service = make_service_fn(move |conn: &Conn| {
+            let remote = conn.remote_addr();
+            let (proj, client) = (proj.clone(), client.clone());
+            async move {
+                Ok::<_, Infallible>(service_fn(move |req| {
+                    forward(req, remote, proj.clone(), client.clone())
+                }))
+            }
+        });
+
+        log::info!(
+            "Proxy listening on {scheme}://{addr} {}",
+            GRAY.paint(routes)
+        );
+
+        match hyper::Server::builder(incoming).serve(make_service).await {
+            Ok(_) => log::debug!("Proxy stopped"),
+            Err(e) => log::error!("Proxy {e}"),
+        }
+    }))
+}
+
+/// Reads the routes again after a change of the Cargo.toml with them, in watch mode
+pub fn reload(proj: &Project) {
+    match proj.reload_proxy() {
+        Ok(true) => {
+            let routes = proj.proxy.as_ref().map(|conf| conf.routes_string());
+            log::info!(
+                "Proxy routes reloaded {}",
+                GRAY.paint(routes.unwrap_or_default())
+            );
+        }
+        Ok(false) => log::debug!("Proxy routes unchanged"),
+        Err(e) => log::warn!("Proxy kept its routes, as the config could not be read: {e:#}"),
+    }
+}
+
+pub(super) fn client() -> HttpClient {
+    let mut connector = HttpConnector::new();
+    connector.set_connect_timeout(Some(CONNECT_TIMEOUT));
+    Client::builder().build(connector)
+}
+
+/// Forwards the request to its route, or to the site server when it matches none
+pub(super) async fn forward(
+    req: Request<Body>,
+    remote: SocketAddr,
+    proj: Arc<Project>,
+    client: HttpClient,
+) -> Result<Response<Body>, Infallible> {
+    let Some(conf) = &proj.proxy else {
+        return Ok(bad_gateway("no proxy configuration".to_string()));
+    };
+    let path_and_query = req.uri().path_and_query().map_or("/", |p| p.as_str());
+    let (url, ws) = conf.upstream(path_and_query);
+    let method = req.method().clone();
+    log::trace!("Proxy {method} {path_and_query} -> {url}");
+
+    match try_forward(req, remote, &url, ws, &client).await {
+        Ok(resp) => Ok(resp),
+        Err(e) => {
+            log::warn!("Proxy {method} {url} failed: {e:#}");
+            Ok(bad_gateway(format!("{e:#}")))
+        }
+    }
+}
+
+async fn try_forward(
+    mut req: Request<Body>,
+    remote: SocketAddr,
+    url: &str,
+    ws: bool,
+    client: &HttpClient,
+) -> Result<Response<Body>> {
+    let upgrade = is_upgrade(req.headers());
+    if upgrade && !ws {
+        bail!("websockets are not passed through, set ws = true on the proxy route");
+    }
+    let uri: Uri = url.parse().context("Invalid upstream url")?;
+    let client_upgrade = upgrade.then(|| hyper::upgrade::on(&mut req));
+
+    let headers = req.headers_mut();
+    remove_hop_by_hop(headers, upgrade);
+    if let Some(host) = headers.remove(header::HOST) {
+        headers.insert("x-forwarded-host", host);
+    }
+    if let Some(authority) = uri.authority() {
+        headers.insert(header::HOST, HeaderValue::from_str(authority.as_str())?);
+    }
+    let forwarded_for = match headers.get("x-forwarded-for").and_then(|v| v.to_str().ok()) {
+        Some(previous) => format!("{previous}, {}", remote.ip()),
+        None => remote.ip().to_string(),
+    };
+    headers.insert("x-forwarded-for", HeaderValue::from_str(&forwarded_for)?);
+    headers.insert("x-forwarded-proto", HeaderValue::from_static("http"));
+    *req.uri_mut() = uri;
+
+    let mut resp = client.request(req).await?;
+
+    match client_upgrade {
+        Some(client_upgrade) if resp.status() == StatusCode::SWITCHING_PROTOCOLS => {
+            let upstream_upgrade = hyper::upgrade::on(&mut resp);
+            tokio::spawn(async move {
+                match tokio::try_join!(client_upgrade, upstream_upgrade) {
+                    Ok((mut client_io, mut upstream_io)) => {
+                        if let Err(e) =
+                            tokio::io::copy_bidirectional(&mut client_io, &mut upstream_io).await
+                        {
+                            log::debug!("Proxy websocket closed: {e}");
+                        }
+                    }
+                    Err(e) => log::warn!("Proxy websocket upgrade failed: {e}"),
+                }
+            });
+        }
+        _ => remove_hop_by_hop(resp.headers_mut(), false),
+    }
+    Ok(resp)
+}
+
+fn is_upgrade(headers: &HeaderMap) -> bool {
+    let connection_upgrade = headers
+        .get_all(header::CONNECTION)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .any(|v| v.trim().eq_ignore_ascii_case("upgrade"));
+    connection_upgrade && headers.contains_key(header::UPGRADE)
+}
+
+/// Removes the hop-by-hop headers. When upgrading the connection and upgrade headers are kept
+fn remove_hop_by_hop(headers: &mut HeaderMap, upgrade: bool) {
+    for name in HOP_BY_HOP {
+        if upgrade && (*name == "connection" || *name == "upgrade") {
+            continue;
+        }
+        headers.remove(*name);
+    }
+}
+
+fn bad_gateway(msg: String) -> Response<Body> {
+    let mut resp = Response::new(Body::from(format!("cargo-leptos proxy: {msg}")));
+    *resp.status_mut() = StatusCode::BAD_GATEWAY;
+    resp
+}
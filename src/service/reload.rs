@@ -1,19 +1,120 @@
 use crate::config::Project;
-use crate::ext::sync::wait_for_socket;
+use crate::ext::sync::{wait_for_ready, Probe, Readiness};
 use crate::logger::GRAY;
 use crate::signal::Interrupt;
-use crate::signal::{ReloadSignal, ReloadType};
+use crate::signal::{BuildStatus, ReloadSignal, ReloadType};
+
+use super::status;
+use super::tls::{self, Incoming};
 use axum::{
     extract::ws::{Message, WebSocket, WebSocketUpgrade},
+    http::{header, StatusCode, Uri},
     response::IntoResponse,
     routing::get,
     Router,
 };
 use serde::Serialize;
 use std::sync::Arc;
-use std::{fmt::Display, net::SocketAddr};
+use std::{fmt::Display, net::SocketAddr, time::Duration};
 use tokio::{net::TcpStream, select, sync::RwLock, task::JoinHandle};
 
+/// Connects to the reload websocket and reloads the page or the style when told to.
+/// Reconnects with a backoff when the connection is lost, reloading the page if a build
+/// was done meanwhile, and shows the errors of a failed build in a dismissible overlay.
+const CLIENT_SCRIPT: &str = r#"(function () {
+  const url = `RELOAD_PROTOCOL://${window.location.hostname}:RELOAD_PORTBASE_PATH/live_reload`;
+  const overlayEnabled = ERROR_OVERLAY;
+  const autoReload = AUTO_RELOAD;
+  let seen = null;
+  let delay = 100;
+  let overlay = null;
+  let connected = false;
+
+  function reload() {
+    if (autoReload) window.location.reload();
+    else console.info("cargo-leptos: the site was rebuilt, auto-reload is disabled");
+  }
+  function hideOverlay() {
+    if (overlay) overlay.remove();
+    overlay = null;
+  }
+  function showOverlay(errors) {
+    hideOverlay();
+    if (!overlayEnabled || !document.body) return;
+    overlay = document.createElement("div");
+    overlay.style.cssText = "position:fixed;left:1em;right:1em;bottom:1em;z-index:2147483647;max-height:50vh;overflow:auto;padding:1em;border-left:4px solid #e5484d;border-radius:4px;background:#1e1e1e;color:#eee;font:13px/1.4 monospace;box-shadow:0 2px 12px rgba(0,0,0,.5)";
+    const close = document.createElement("button");
+    close.textContent = "×";
+    close.title = "Dismiss";
+    close.style.cssText = "float:right;border:none;background:none;color:inherit;font-size:18px;cursor:pointer";
+    close.onclick = hideOverlay;
+    const title = document.createElement("div");
+    title.textContent = "cargo-leptos: build failed";
+    title.style.cssText = "margin-bottom:.5em;color:#e5484d;font-weight:bold";
+    const pre = document.createElement("pre");
+    pre.style.cssText = "margin:0;white-space:pre-wrap";
+    pre.textContent = errors.length ? errors.join("\n") : "See the cargo-leptos output.";
+    overlay.append(close, title, pre);
+    document.body.appendChild(overlay);
+  }
+  function onStatus(status, reconnected) {
+    // a build happened while disconnected, or cargo-leptos was restarted
+    if (reconnected && seen !== null && status.build !== seen && status.ok) return reload();
+    seen = status.build;
+    if (status.ok) hideOverlay();
+    else showOverlay(status.errors);
+  }
+  function connect(reconnected) {
+    const ws = new WebSocket(url);
+    let first = true;
+    ws.onopen = () => {
+      connected = true;
+      delay = 100;
+    };
+    ws.onmessage = (ev) => {
+      const msg = JSON.parse(ev.data);
+      if (msg.status) {
+        onStatus(msg.status, reconnected && first);
+        first = false;
+      }
+      if (msg.all) {
+        hideOverlay();
+        reload();
+      }
+      if (msg.css) {
+        hideOverlay();
+        let found = false;
+        document.querySelectorAll("link").forEach((link) => {
+          if (link.getAttribute("href").includes(msg.css)) {
+            let newHref = "BASE_PATH/" + msg.css + "?version=" + new Date().getMilliseconds();
+            link.setAttribute("href", newHref);
+            found = true;
+          }
+        });
+        if (!found) console.warn(`CSS hot-reload: Could not find a <link href=BASE_PATH/${msg.css}> element`);
+      }
+    };
+    ws.onclose = () => {
+      if (connected) console.info("cargo-leptos: live-reload disconnected, reconnecting");
+      connected = false;
+      setTimeout(() => connect(true), delay);
+      delay = Math.min(delay * 2, 5000);
+    };
+  }
+  connect(false);
+})()"#;
+
+/// The live-reload client, for the pages that aren't served by a leptos server integration
+pub fn client_script(proj: &Project) -> String {
+    let protocol = if proj.tls.is_some() { "wss" } else { "ws" };
+    CLIENT_SCRIPT
+        .replace("RELOAD_PROTOCOL", protocol)
+        .replace("RELOAD_PORT", &proj.site.reload.port().to_string())
+        .replace("BASE_PATH", &proj.site.base_path())
+        .replace("ERROR_OVERLAY", &proj.watch_error_overlay.to_string())
+        .replace("AUTO_RELOAD", &proj.watch_auto_reload.to_string())
+}
+
 lazy_static::lazy_static! {
   static ref SITE_ADDR: RwLock<SocketAddr> = RwLock::new(SocketAddr::new([127,0,0,1].into(), 3000));
   static ref CSS_LINK: RwLock<String> = RwLock::new(String::default());
@@ -39,8 +140,15 @@ pub async fn spawn(proj: &Arc<Project>) -> JoinHandle<()> {
         let _change = ReloadSignal::subscribe();
 
         let reload_addr = proj.site.reload;
+        let reload_addrs = proj.site.reload_addrs();
 
-        if TcpStream::connect(&reload_addr).await.is_ok() {
+        let mut taken = 0;
+        for addr in &reload_addrs {
+            if TcpStream::connect(addr).await.is_ok() {
+                taken += 1;
+            }
+        }
+        if taken == reload_addrs.len() {
             log::error!(
                     "Reload TCP port {reload_addr} already in use. You can set the port in the server integration's RenderOptions reload_port"
                 );
@@ -48,14 +156,57 @@ pub async fn spawn(proj: &Arc<Project>) -> JoinHandle<()> {
 
             return;
         }
-        let route = Router::new().route("/live_reload", get(websocket_handler));
+        let tls = match tls::server_config(&proj).await {
+            Ok(tls) => tls,
+            Err(e) => {
+                log::error!("Reload {e:#}");
+                Interrupt::request_shutdown().await;
+                return;
+            }
+        };
+        let (incoming, bound) = match Incoming::bind_all(&reload_addrs, tls).await {
+            Ok(bound) => bound,
+            Err(e) => {
+                log::error!("Reload could not listen on {reload_addr}: {e}");
+                Interrupt::request_shutdown().await;
+                return;
+            }
+        };
+        let script_proj = proj.clone();
+        let base_proj = proj.clone();
+        let mut route = Router::new()
+            .route("/live_reload", get(websocket_handler))
+            .route(
+                "/live_reload.js",
+                get(move || {
+                    let script = client_script(&script_proj);
+                    async move {
+                        (
+                            [(header::CONTENT_TYPE, "text/javascript; charset=utf-8")],
+                            script,
+                        )
+                    }
+                }),
+            );
+        if proj.status_addr.is_none() {
+            route = route.merge(status::router());
+        }
+        // the client of a site with a site-base-path connects under it
+        let route = route.fallback(get(move |uri: Uri, ws: WebSocketUpgrade| {
+            let path = format!("{}/live_reload", base_proj.site.base_path());
+            async move {
+                if uri.path() == path {
+                    ws.on_upgrade(websocket).into_response()
+                } else {
+                    StatusCode::NOT_FOUND.into_response()
+                }
+            }
+        }));
 
-        log::debug!(
-            "Reload server started {}",
-            GRAY.paint(reload_addr.to_string())
-        );
+        let bound: Vec<String> = bound.iter().map(|addr| addr.to_string()).collect();
+        log::debug!("Reload server started {}", GRAY.paint(bound.join(", ")));
 
-        match axum::Server::bind(&reload_addr)
+        match axum::Server::builder(incoming)
             .serve(route.into_make_service())
             .await
         {
@@ -75,6 +226,9 @@ async fn websocket(mut stream: WebSocket) {
 
     log::trace!("Reload websocket connected");
     tokio::spawn(async move {
+        // the status of the latest build, which might have been done before the connection
+        let status = BrowserMessage::status(ReloadSignal::build_status());
+        send_now(&mut stream, status).await;
         loop {
             select! {
                 res = rx.recv() =>{
@@ -89,6 +243,9 @@ async fn websocket(mut stream: WebSocket) {
                         Ok(ReloadType::ViewPatches(data)) => {
                             send(&mut stream, BrowserMessage::view(data)).await;
                         }
+                        Ok(ReloadType::Status(status)) => {
+                            send_now(&mut stream, BrowserMessage::status(status)).await;
+                        }
                         Err(e) => log::debug!("Reload recive error {e}")
                     }
                 }
@@ -103,10 +260,21 @@ async fn websocket(mut stream: WebSocket) {
 
 async fn send(stream: &mut WebSocket, msg: BrowserMessage) {
     let site_addr = *SITE_ADDR.read().await;
-    if !wait_for_socket("Reload", site_addr).await {
+    let ready = wait_for_ready(
+        "Reload",
+        &Probe::new(site_addr, None),
+        Duration::from_secs(10),
+        Interrupt::subscribe_shutdown(),
+    )
+    .await;
+    if !matches!(ready, Readiness::Ready) {
         log::warn!(r#"Reload could not send "{msg}" to websocket"#);
     }
+    send_now(stream, msg).await;
+}
 
+/// Sends without waiting for the server, which is only needed for the reloads
+async fn send_now(stream: &mut WebSocket, msg: BrowserMessage) {
     let text = serde_json::to_string(&msg).unwrap();
     match stream.send(Message::Text(text)).await {
         Err(e) => {
@@ -129,6 +297,9 @@ struct BrowserMessage {
     css: Option<String>,
     view: Option<String>,
     all: bool,
+    /// not sent with the reloads, as the leptos integrations' clients don't know it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    status: Option<BuildStatus>,
 }
 
 impl BrowserMessage {
@@ -141,6 +312,7 @@ impl BrowserMessage {
             css: Some(link),
             view: None,
             all: false,
+            status: None,
         }
     }
 
@@ -149,6 +321,16 @@ impl BrowserMessage {
             css: None,
             view: Some(data),
             all: false,
+            status: None,
+        }
+    }
+
+    fn status(status: BuildStatus) -> Self {
+        Self {
+            css: None,
+            view: None,
+            all: false,
+            status: Some(status),
         }
     }
 
@@ -157,6 +339,7 @@ impl BrowserMessage {
             css: None,
             view: None,
             all: true,
+            status: None,
         }
     }
 }
@@ -165,6 +348,9 @@ impl Display for BrowserMessage {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         if let Some(css) = &self.css {
             write!(f, "reload {}", css)
+        } else if let Some(status) = &self.status {
+            let outcome = if status.ok { "ok" } else { "failed" };
+            write!(f, "build {} {outcome}", status.build)
         } else {
             write!(f, "reload all")
         }
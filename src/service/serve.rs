@@ -1,34 +1,113 @@
-use std::sync::Arc;
+use std::collections::VecDeque;
+use std::net::SocketAddr;
+use std::process::{ExitStatus, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
+#[cfg(unix)]
+use crate::ext::sync::pass_listener;
 use crate::{
+    compile::Step,
     config::Project,
-    ext::{anyhow::Result, append_str_to_filename, determine_pdb_filename, fs},
-    logger::GRAY,
-    signal::{Interrupt, ReloadSignal, ServerRestart},
+    ext::{
+        anyhow::{bail, Result},
+        append_str_to_filename, determine_pdb_filename, fs,
+        sync::{
+            addr_in_use, addr_in_use_help, new_process_group, stop_gracefully, wait_for_ready,
+            Probe, ProcessGroup, Readiness,
+        },
+    },
+    logger::{self, GRAY},
+    signal::{Interrupt, ReloadSignal, ServerRestart, StatusEvent, StatusSignal},
 };
 use camino::Utf8PathBuf;
+use flexi_logger::DeferredNow;
+use itertools::Itertools;
 use tokio::{
+    io::{AsyncBufReadExt, AsyncRead, BufReader},
     process::{Child, Command},
     select,
     task::JoinHandle,
+    time::sleep,
 };
 
 pub async fn spawn(proj: &Arc<Project>) -> JoinHandle<Result<()>> {
+    spawn_server(proj, None).await
+}
+
+/// Like [`spawn`] but also keeps the last lines printed by the server,
+/// for reporting why it didn't start.
+pub async fn spawn_captured(proj: &Arc<Project>) -> (JoinHandle<Result<()>>, ServerOutput) {
+    let output = ServerOutput::default();
+    (spawn_server(proj, Some(output.clone())).await, output)
+}
+
+/// Runs the server once for `run`, without the reload vars, until it exits or until the
+/// shutdown, which stops it gracefully. None when it was stopped.
+pub async fn run(proj: &Arc<Project>) -> Result<Option<ExitStatus>> {
+    let mut server = ServerProcess::new(proj, None);
+    server.reload = false;
+    server.open().await?;
+    let Some(process) = &mut server.process else {
+        bail!("Serve no server exe {}", server.binary);
+    };
+    let mut int = Interrupt::subscribe_shutdown();
+    let status = select! {
+        status = process.wait() => Some(status?),
+        _ = int.recv() => None,
+    };
+    if status.is_none() {
+        server.stop().await;
+    }
+    Ok(status)
+}
+
+async fn spawn_server(proj: &Arc<Project>, output: Option<ServerOutput>) -> JoinHandle<Result<()>> {
+    if proj.bin.is_none() {
+        return super::csr::spawn(proj).await;
+    }
     let mut int = Interrupt::subscribe_shutdown();
     let proj = proj.clone();
     let mut change = ServerRestart::subscribe();
     tokio::spawn(async move {
-        let mut server = ServerProcess::start_new(&proj).await?;
+        let mut server = match ServerProcess::start_new(&proj, output).await {
+            Ok(server) => server,
+            Err(e) => {
+                Interrupt::request_shutdown().await;
+                return Err(e);
+            }
+        };
+        // reports a server that exits right away
+        if let Some(Readiness::Ready) = server.wait_until_up().await {
+            StatusSignal::send(StatusEvent::ServerReady);
+        }
         loop {
             select! {
               res = change.recv() => {
                 if let Ok(()) = res {
+                      StatusSignal::send(StatusEvent::ServerRestarting);
+                      let step = Step::start(&proj, "server-restart");
                       server.restart().await?;
-                      ReloadSignal::send_full();
+                      // the browser reloads from the new server, not the one stopping
+                      match server.wait_until_up().await {
+                          Some(Readiness::Ready) => {
+                              step.finished(|elapsed| {
+                                  log::info!("Serve restarted in {elapsed:.1?}")
+                              });
+                              StatusSignal::send(StatusEvent::ServerReady);
+                              ReloadSignal::send_full();
+                          }
+                          Some(Readiness::TimedOut) => {
+                              log::warn!("Serve reloading the browser anyway");
+                              ReloadSignal::send_full();
+                          }
+                          Some(Readiness::Interrupted) | None => {}
+                      }
                 }
               },
               _ = int.recv() => {
-                    server.kill().await;
+                    server.stop().await;
                     return Ok(())
               },
             }
@@ -36,45 +115,276 @@ pub async fn spawn(proj: &Arc<Project>) -> JoinHandle<Result<()>> {
     })
 }
 
+/// The last lines written by the server to stdout and stderr
+#[derive(Debug, Clone, Default)]
+pub struct ServerOutput {
+    lines: Arc<Mutex<VecDeque<String>>>,
+    /// set when the server closes its stdout, which it does when it exits
+    closed: Arc<AtomicBool>,
+}
+
+impl ServerOutput {
+    const MAX_LINES: usize = 200;
+
+    pub fn is_closed(&self) -> bool {
+        self.closed.load(Ordering::Relaxed)
+    }
+
+    fn push(&self, line: String) {
+        let mut lines = self.lines.lock().unwrap();
+        if lines.len() == Self::MAX_LINES {
+            lines.pop_front();
+        }
+        lines.push_back(line);
+    }
+
+    pub fn text(&self) -> String {
+        self.lines.lock().unwrap().iter().join("\n")
+    }
+}
+
+/// How the lines of the server are printed, to tell them from the ones of cargo-leptos
+#[derive(Debug, Clone)]
+struct ServerLines {
+    /// painted
+    prefix: Option<String>,
+    timestamps: bool,
+}
+
+impl ServerLines {
+    fn new(proj: &Project) -> Self {
+        Self {
+            prefix: proj
+                .server_output_prefix
+                .as_ref()
+                .map(|prefix| GRAY.paint(prefix).to_string()),
+            timestamps: proj.server_output_timestamps,
+        }
+    }
+
+    /// false when the output is passed through as is
+    fn is_formatted(&self) -> bool {
+        self.prefix.is_some() || self.timestamps || logger::is_json() || logger::has_log_file()
+    }
+
+    fn print(&self, line: &str, is_stderr: bool) {
+        let mut text = String::new();
+        if self.timestamps {
+            let now = DeferredNow::new().format("%H:%M:%S%.3f").to_string();
+            text.push_str(&format!("{} ", GRAY.paint(now)));
+        }
+        if let Some(prefix) = &self.prefix {
+            text.push_str(&format!("{prefix} "));
+        }
+        text.push_str(line);
+        logger::print_output("server", line, &text, is_stderr);
+    }
+
+    /// prints the lines of the stream, and keeps them when captured
+    fn forward(
+        &self,
+        stream: impl AsyncRead + Unpin + Send + 'static,
+        is_stderr: bool,
+        output: Option<ServerOutput>,
+    ) {
+        let printer = self.clone();
+        tokio::spawn(async move {
+            let mut lines = BufReader::new(stream).lines();
+            while let Ok(Some(line)) = lines.next_line().await {
+                printer.print(&line, is_stderr);
+                if let Some(output) = &output {
+                    output.push(line);
+                }
+            }
+            if let Some(output) = output.filter(|_| !is_stderr) {
+                output.closed.store(true, Ordering::Relaxed);
+            }
+        });
+    }
+}
+
+/// the vars of [Project::to_envs] for the reload websocket and the watch mode
+const RELOAD_ENVS: &[&str] = &[
+    "LEPTOS_RELOAD_PORT",
+    "LEPTOS_RELOAD_WS_PROTOCOL",
+    "LEPTOS_WATCH",
+];
+
+/// the checks of a taken site-addr in watch mode, 250ms apart and doubling
+const BIND_ATTEMPTS: u32 = 5;
+
 struct ServerProcess {
     process: Option<Child>,
-    envs: Vec<(&'static str, String)>,
+    /// the vars of the .env file, kept when it can't be read anymore
+    dotenvs: Vec<(String, String)>,
+    proj: Arc<Project>,
     binary: Utf8PathBuf,
+    output: Option<ServerOutput>,
+    lines: ServerLines,
+    addr: SocketAddr,
+    shutdown_grace_period: Duration,
+    /// the site-addr socket passed to the server with reuse-listener. It is closed after the
+    /// server has stopped, and given up when a server doesn't use it
+    listener: Option<std::net::TcpListener>,
+    /// the processes of the server, like the ones started by a wrapper script
+    group: Option<ProcessGroup>,
+    /// the reload vars are given to the server, so that the pages connect to the reload
+    /// websocket. Not for `run`, which has none.
+    reload: bool,
 }
 
 impl ServerProcess {
-    fn new(proj: &Project) -> Self {
+    fn new(proj: &Arc<Project>, output: Option<ServerOutput>) -> Self {
         Self {
             process: None,
-            envs: proj.to_envs(),
-            binary: proj.bin.exe_file.clone(),
+            dotenvs: Vec::new(),
+            proj: proj.clone(),
+            binary: proj
+                .bin
+                .as_ref()
+                .map(|bin| bin.exe_file.clone())
+                .unwrap_or_default(),
+            output,
+            lines: ServerLines::new(proj),
+            addr: proj.site.addr,
+            shutdown_grace_period: proj.shutdown_grace_period,
+            listener: None,
+            group: None,
+            reload: true,
         }
     }
 
-    async fn start_new(proj: &Project) -> Result<Self> {
-        let mut me = Self::new(proj);
-        me.start().await?;
+    async fn start_new(proj: &Arc<Project>, output: Option<ServerOutput>) -> Result<Self> {
+        let mut me = Self::new(proj, output);
+        me.open().await?;
         Ok(me)
     }
 
-    async fn kill(&mut self) {
-        if let Some(proc) = self.process.as_mut() {
-            if let Err(e) = proc.kill().await {
-                log::error!("Serve error killing server process: {e}");
+    /// The first start, once the site-addr is free
+    async fn open(&mut self) -> Result<()> {
+        if self.binary.exists() && !self.wait_for_free_addr().await {
+            bail!(addr_in_use_help(self.addr));
+        }
+        self.bind_listener();
+        self.start().await
+    }
+
+    /// Binds the site-addr with reuse-listener. The server binds it itself when it can't be
+    fn bind_listener(&mut self) {
+        if !self.proj.reuse_listener {
+            return;
+        }
+        if cfg!(not(unix)) {
+            log::warn!("Serve reuse-listener is only supported on unix, the server binds the site-addr itself");
+            return;
+        }
+        match std::net::TcpListener::bind(self.addr) {
+            Ok(listener) => {
+                log::debug!("Serve listening on {} for the server", self.addr);
+                self.listener = Some(listener);
+            }
+            Err(e) => log::warn!(
+                "Serve could not listen on {} for the server, which binds it itself: {e}",
+                self.addr
+            ),
+        }
+    }
+
+    /// false when the site-addr is still taken by another process after the retries, which
+    /// in watch mode give a server of a previous run the time to stop
+    async fn wait_for_free_addr(&self) -> bool {
+        let attempts = if self.proj.watch { BIND_ATTEMPTS } else { 1 };
+        let mut delay = Duration::from_millis(250);
+        for attempt in 1..=attempts {
+            if !addr_in_use(self.addr) {
+                return true;
+            }
+            if attempt < attempts {
+                log::debug!("Serve {} in use, retrying in {delay:?}", self.addr);
+                sleep(delay).await;
+                delay *= 2;
+            }
+        }
+        false
+    }
+
+    /// Stops the server gracefully, killing it after the shutdown-grace-period
+    async fn stop(&mut self) {
+        if let (Some(mut proc), Some(group)) = (self.process.take(), self.group.take()) {
+            let grace = self.shutdown_grace_period;
+            if let Err(e) = stop_gracefully("Serve", &mut proc, group, grace).await {
+                log::error!("Serve error stopping server process: {e}");
             } else {
                 log::trace!("Serve stopped");
             }
-            self.process = None;
         }
     }
 
     async fn restart(&mut self) -> Result<()> {
-        self.kill().await;
+        // with reuse-listener, the connections made meanwhile wait for the new server
+        self.stop().await;
+        if self.listener.is_none() && !self.wait_for_free_addr().await {
+            log::error!("Serve {}", addr_in_use_help(self.addr));
+            return Ok(());
+        }
         self.start().await?;
         log::trace!("Serve restarted");
         Ok(())
     }
 
+    /// Waits for the reload-health-path to answer, or for the server to accept connections,
+    /// for up to the reload-health-timeout. None when the server didn't start or exited
+    /// before being up
+    async fn wait_until_up(&mut self) -> Option<Readiness> {
+        let proj = self.proj.clone();
+        let health_path = proj.reload_health_path.as_deref();
+        loop {
+            let probe = match (&self.listener, health_path) {
+                // the passed socket accepts the TCP connections before the server does
+                (Some(_), None) => Probe::answer(self.addr),
+                _ => Probe::new(self.addr, health_path),
+            };
+            let Some(process) = &mut self.process else {
+                return None;
+            };
+            let up = wait_for_ready(
+                "Serve",
+                &probe,
+                proj.reload_health_timeout,
+                Interrupt::subscribe_shutdown(),
+            );
+            let exited = select! {
+                ready = up => return Some(ready),
+                status = process.wait() => status,
+            };
+            self.process = None;
+            self.group = None;
+            // most likely binding the site-addr itself, which the passed socket holds
+            if self.listener.take().is_some() {
+                log::warn!(
+                    "Serve the server exited without using the socket passed with reuse-listener. \
+                    See the README for the listenfd integration. Starting it without the socket"
+                );
+                if let Err(e) = self.start().await {
+                    log::error!("Serve {e:#}");
+                    return None;
+                }
+                continue;
+            }
+            match exited {
+                // the server panics when it can't bind, as another process took the addr
+                Ok(_) if addr_in_use(self.addr) => {
+                    log::error!("Serve {}", addr_in_use_help(self.addr))
+                }
+                Ok(status) => {
+                    log::warn!("Serve the server exited with {status} before accepting connections")
+                }
+                Err(e) => log::error!("Serve could not wait for the server: {e}"),
+            }
+            return None;
+        }
+    }
+
     async fn start(&mut self) -> Result<()> {
         let bin = &self.binary;
         let child = if bin.exists() {
@@ -88,7 +398,7 @@ impl ServerProcess {
                     GRAY.paint(bin.as_str()),
                     GRAY.paint(new_bin_path.as_str())
                 );
-                fs::copy(bin, &new_bin_path).await?;
+                fs::copy_atomic(bin, &new_bin_path).await?;
                 // also copy the .pdb file if it exists to allow debugging to attach
                 if let Some(pdb) = determine_pdb_filename(bin) {
                     let new_pdb_path = append_str_to_filename(&pdb, "_leptos")?;
@@ -97,19 +407,59 @@ impl ServerProcess {
                         GRAY.paint(pdb.as_str()),
                         GRAY.paint(new_pdb_path.as_str())
                     );
-                    fs::copy(&pdb, &new_pdb_path).await?;
+                    fs::copy_atomic(&pdb, &new_pdb_path).await?;
                 }
                 new_bin_path
             } else {
                 bin.clone()
             };
 
+            match self.proj.read_dotenvs() {
+                Ok(dotenvs) => self.dotenvs = dotenvs,
+                Err(e) => log::error!("Serve {e:#}. Keeping the previous env"),
+            }
+
             log::debug!("Serve running {}", GRAY.paint(bin_path.as_str()));
-            Some(Command::new(bin_path).envs(self.envs.clone()).spawn()?)
+            let mut cmd = Command::new(bin_path);
+            cmd.args(&self.proj.bin_args);
+            // the leptos vars take precedence over the .env file
+            cmd.envs(self.dotenvs.clone());
+            // read at each start, as the site-base-path can change with the .env file
+            let reload = self.reload;
+            cmd.envs(
+                self.proj
+                    .to_envs()
+                    .into_iter()
+                    .filter(|(name, _)| reload || !RELOAD_ENVS.contains(name)),
+            );
+            new_process_group(&mut cmd);
+            #[cfg(unix)]
+            if let Some(listener) = &self.listener {
+                pass_listener(&mut cmd, listener);
+            }
+            let piped = self.output.is_some() || self.lines.is_formatted();
+            if piped {
+                cmd.stdout(Stdio::piped()).stderr(Stdio::piped());
+                // the usual hints for coloring a piped output
+                if logger::is_colored() && !logger::is_json() {
+                    cmd.env("CLICOLOR_FORCE", "1").env("FORCE_COLOR", "1");
+                } else {
+                    cmd.env("NO_COLOR", "1");
+                }
+            }
+            let mut child = cmd.spawn()?;
+            if let Some(stdout) = child.stdout.take() {
+                self.lines.forward(stdout, false, self.output.clone());
+            }
+            if let Some(stderr) = child.stderr.take() {
+                self.lines.forward(stderr, true, self.output.clone());
+            }
+            Some(child)
         } else {
             log::debug!("Serve no exe found {}", GRAY.paint(bin.as_str()));
             None
         };
+        self.group = child.as_ref().map(ProcessGroup::of);
         self.process = child;
         Ok(())
     }
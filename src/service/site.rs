@@ -1,20 +1,33 @@
 use std::{
     collections::HashMap,
+    env,
     fmt::{self, Display},
+    fs::Metadata,
+    hash::Hasher,
     net::SocketAddr,
+    sync::RwLock as SyncRwLock,
+    time::{Instant, SystemTime},
 };
 
 use camino::{Utf8Path, Utf8PathBuf};
-use tokio::sync::RwLock;
+use seahash::SeaHasher;
+use tokio::{io::AsyncReadExt, sync::RwLock};
 
 use crate::{
-    config::ProjectConfig,
+    config::{ProjectConfig, SiteFileMode},
     ext::{
         anyhow::{Context, Result},
-        fs, PathBufExt,
+        format_bytes, fs,
+        sync::connectable,
+        PathBufExt,
     },
 };
 
+/// the buffer of the file hashing, as the files can be big videos or debug wasm
+const HASH_BUFFER: usize = 64 * 1024;
+/// the files from which size the hashing time is logged
+const LOGGED_HASH_SIZE: u64 = 1024 * 1024;
+
 #[derive(Clone)]
 pub struct SourcedSiteFile {
     /// source file's relative path from the root (workspace or project) directory
@@ -74,22 +87,40 @@ impl std::fmt::Debug for SiteFile {
 }
 
 pub struct Site {
+    /// the first of the addresses, that the server is given
     pub addr: SocketAddr,
+    /// all the site-addr addresses, that the static server and the reload listen on
+    pub addrs: Vec<SocketAddr>,
     pub reload: SocketAddr,
     pub root_dir: Utf8PathBuf,
     pub pkg_dir: Utf8PathBuf,
+    /// the site-base-path, like "/shop", or "" when served at the root. Changed with the .env file
+    base_path: SyncRwLock<String>,
+    /// the site-base-path of the Cargo.toml, used when the .env file doesn't set one anymore
+    base_path_conf: String,
     file_reg: RwLock<HashMap<String, u64>>,
     ext_file_reg: RwLock<HashMap<String, u64>>,
+    /// the hashes of the files read, with the size and the modification time they had, so
+    /// that the unchanged files aren't read again
+    hashes: RwLock<HashMap<Utf8PathBuf, (u64, Option<SystemTime>, u64)>>,
+    file_modes: Vec<SiteFileMode>,
 }
 
 impl fmt::Debug for Site {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        f.debug_struct("Site")
-            .field("addr", &self.addr)
-            .field("reload", &self.reload)
+        let mut f = f.debug_struct("Site");
+        f.field("addr", &self.addr);
+        if self.addrs.len() > 1 {
+            f.field("addrs", &self.addrs);
+        }
+        f.field("reload", &self.reload)
             .field("root_dir", &self.root_dir)
-            .field("pkg_dir", &self.pkg_dir)
-            .field("file_reg", &self.file_reg.blocking_read())
+            .field("pkg_dir", &self.pkg_dir);
+        let base_path = self.base_path();
+        if !base_path.is_empty() {
+            f.field("base_path", &base_path);
+        }
+        f.field("file_reg", &self.file_reg.blocking_read())
             .field("ext_file_reg", &self.ext_file_reg.blocking_read())
             .finish()
     }
@@ -101,20 +132,87 @@ impl Site {
         reload.set_port(config.reload_port);
         Self {
             addr: config.site_addr.clone(),
+            addrs: config.site_addrs.clone(),
             reload,
             root_dir: config.site_root.clone(),
             pkg_dir: config.site_pkg_dir.clone(),
+            base_path: SyncRwLock::new(normalize_base_path(&config.site_base_path)),
+            base_path_conf: config.site_base_path_conf.clone(),
             file_reg: Default::default(),
             ext_file_reg: Default::default(),
+            hashes: Default::default(),
+            file_modes: Vec::new(),
         }
     }
 
+    pub fn with_file_modes(mut self, file_modes: Vec<SiteFileMode>) -> Self {
+        self.file_modes = file_modes;
+        self
+    }
+
+    /// The reload addresses: the site ones with the reload port
+    pub fn reload_addrs(&self) -> Vec<SocketAddr> {
+        self.addrs
+            .iter()
+            .map(|addr| SocketAddr::new(addr.ip(), self.reload.port()))
+            .collect()
+    }
+
+    /// The address to show and to open in the browser: a loopback one when there is one,
+    /// as the others can be for the other machines of the network
+    pub fn browse_addr(&self) -> SocketAddr {
+        self.addrs
+            .iter()
+            .find(|addr| addr.ip().is_loopback() || addr.ip().is_unspecified())
+            .map_or(self.addr, |addr| connectable(*addr))
+    }
+
+    /// The addresses separated by commas, as in the LEPTOS_SITE_ADDRS env var
+    pub fn addrs_string(&self) -> String {
+        self.addrs
+            .iter()
+            .map(|addr| addr.to_string())
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+
+    pub fn base_path(&self) -> String {
+        self.base_path.read().unwrap().clone()
+    }
+
+    /// The absolute link to a path of the site, under the base path
+    pub fn link(&self, site: &Utf8Path) -> String {
+        // Always use `/` as separator in links
+        let link = site.components().map(|c| c.as_str()).collect::<Vec<_>>();
+        format!("{}/{}", self.base_path(), link.join("/"))
+    }
+
+    /// Sets the base path from the LEPTOS_SITE_BASE_PATH var of the changed .env file, or
+    /// from the Cargo.toml when the file has none. The var of the environment still takes
+    /// precedence. true when the base path changed
+    pub fn update_base_path(&self, dotenvs: &[(String, String)]) -> bool {
+        if env::var_os(BASE_PATH_VAR).is_some() {
+            return false;
+        }
+        let path = dotenvs
+            .iter()
+            .find(|(key, _)| key == BASE_PATH_VAR)
+            .map_or(self.base_path_conf.as_str(), |(_, val)| val.as_str());
+        let path = normalize_base_path(path);
+        let mut current = self.base_path.write().unwrap();
+        if *current == path {
+            return false;
+        }
+        *current = path;
+        true
+    }
+
     pub fn root_relative_pkg_dir(&self) -> Utf8PathBuf {
         self.root_dir.join(&self.pkg_dir)
     }
     /// check if the file changed
     pub async fn did_external_file_change(&self, to: &Utf8Path) -> Result<bool> {
-        let new_hash = file_hash(to).await.dot()?;
+        let new_hash = self.file_hash(to).await.dot()?;
         let cur_hash = { self.ext_file_reg.read().await.get(to.as_str()).copied() };
         if Some(new_hash) == cur_hash {
             return Ok(false);
@@ -128,22 +226,24 @@ impl Site {
     pub async fn updated(&self, file: &SourcedSiteFile) -> Result<bool> {
         fs::create_dir_all(file.dest.clone().without_last()).await?;
 
-        let new_hash = file_hash(&file.source).await?;
-        let cur_hash = self.current_hash(&file.site, &file.dest).await?;
-
-        if Some(new_hash) == cur_hash {
+        let size = fs::metadata(&file.source).await?.len();
+        let new_hash = self.file_hash(&file.source).await?;
+        if self
+            .is_unchanged(&file.site, &file.dest, size, new_hash)
+            .await?
+        {
+            self.update_mode(&file.dest, Some(&file.source)).await?;
             return Ok(false);
         }
-        fs::copy(&file.source, &file.dest).await?;
-
-        let mut reg = self.file_reg.write().await;
-        reg.insert(file.site.to_string(), new_hash);
+        fs::copy_atomic(&file.source, &file.dest).await?;
+        self.update_mode(&file.dest, Some(&file.source)).await?;
+        self.written(&file.as_site_file(), new_hash).await;
         Ok(true)
     }
 
     /// check after writing the file if it changed
     pub async fn did_file_change(&self, file: &SiteFile) -> Result<bool> {
-        let new_hash = file_hash(&file.dest).await.dot()?;
+        let new_hash = self.file_hash(&file.dest).await.dot()?;
         let cur_hash = { self.file_reg.read().await.get(file.site.as_str()).copied() };
         if Some(new_hash) == cur_hash {
             return Ok(false);
@@ -156,32 +256,139 @@ impl Site {
     pub async fn updated_with(&self, file: &SiteFile, data: &[u8]) -> Result<bool> {
         fs::create_dir_all(file.dest.clone().without_last()).await?;
 
-        let new_hash = seahash::hash(data);
-        let cur_hash = self.current_hash(&file.site, &file.dest).await?;
+        let new_hash = data_hash(data);
+        if self
+            .is_unchanged(&file.site, &file.dest, data.len() as u64, new_hash)
+            .await?
+        {
+            self.update_mode(&file.dest, None).await?;
+            return Ok(false);
+        }
 
-        if Some(new_hash) == cur_hash {
+        fs::write_atomic(&file.dest, &data).await?;
+        self.update_mode(&file.dest, None).await?;
+        self.written(file, new_hash).await;
+        Ok(true)
+    }
+
+    /// Gives the site file the site-file-mode matching it, else the permissions of its source
+    /// (of the target of a symlinked source). Without a source, the written file kept the
+    /// permissions of the file it replaced.
+    pub async fn update_mode(&self, dest: &Utf8Path, source: Option<&Utf8Path>) -> Result<()> {
+        let forced = dest
+            .strip_prefix(&self.root_dir)
+            .ok()
+            .and_then(|site| self.file_modes.iter().find(|m| m.matches(site)));
+        match (forced, source) {
+            (Some(forced), _) => fs::set_mode(dest, forced.mode).await,
+            (None, Some(source)) => fs::copy_permissions(source, dest).await,
+            (None, None) => Ok(()),
+        }
+    }
+
+    /// Whether the dest has the size and the hash. The dest is only read when it has the size
+    /// and its hash isn't registered
+    async fn is_unchanged(
+        &self,
+        site: &Utf8Path,
+        dest: &Utf8Path,
+        size: u64,
+        hash: u64,
+    ) -> Result<bool> {
+        let Ok(metadata) = fs::metadata(dest).await else {
+            return Ok(false);
+        };
+        if metadata.len() != size {
             return Ok(false);
         }
+        let registered = self.file_reg.read().await.get(site.as_str()).copied();
+        let cur_hash = match registered {
+            Some(hash) => hash,
+            None => self.hash_with(dest, &metadata).await?,
+        };
+        Ok(cur_hash == hash)
+    }
 
-        fs::write(&file.dest, &data).await?;
+    async fn written(&self, file: &SiteFile, hash: u64) {
+        self.file_reg
+            .write()
+            .await
+            .insert(file.site.to_string(), hash);
+        if let Ok(metadata) = fs::metadata(&file.dest).await {
+            self.hashes.write().await.insert(
+                file.dest.clone(),
+                (metadata.len(), metadata.modified().ok(), hash),
+            );
+        }
+    }
 
-        let mut reg = self.file_reg.write().await;
-        reg.insert(file.site.to_string(), new_hash);
-        Ok(true)
+    /// The hash of the file, which is only read when its size or modification time changed
+    async fn file_hash(&self, file: &Utf8Path) -> Result<u64> {
+        let metadata = fs::metadata(file).await?;
+        self.hash_with(file, &metadata).await
     }
 
-    async fn current_hash(&self, site: &Utf8Path, dest: &Utf8Path) -> Result<Option<u64>> {
-        if let Some(hash) = self.file_reg.read().await.get(site.as_str()).copied() {
-            Ok(Some(hash))
-        } else if dest.exists() {
-            Ok(Some(file_hash(&dest).await?))
-        } else {
-            Ok(None)
+    async fn hash_with(&self, file: &Utf8Path, metadata: &Metadata) -> Result<u64> {
+        let key = (metadata.len(), metadata.modified().ok());
+        if let Some((size, modified, hash)) = self.hashes.read().await.get(file) {
+            if (*size, *modified) == key {
+                return Ok(*hash);
+            }
         }
+        let hash = file_hash(file).await?;
+        self.hashes
+            .write()
+            .await
+            .insert(file.to_path_buf(), (key.0, key.1, hash));
+        Ok(hash)
+    }
+}
+
+const BASE_PATH_VAR: &str = "LEPTOS_SITE_BASE_PATH";
+
+/// "shop/", "/shop" and "/shop/" are all "/shop", and "/" is ""
+fn normalize_base_path(path: &str) -> String {
+    let path = path.trim().trim_matches('/');
+    if path.is_empty() {
+        String::new()
+    } else {
+        format!("/{path}")
     }
 }
 
+/// Hashes the file as it is read, with a small buffer
 async fn file_hash(file: &Utf8Path) -> Result<u64> {
-    let data = fs::read(&file).await?;
-    Ok(seahash::hash(&data))
+    let started = Instant::now();
+    let mut reader = tokio::fs::File::open(file)
+        .await
+        .context(format!("Could not open {file:?}"))?;
+    let mut hasher = SeaHasher::new();
+    let mut buf = vec![0; HASH_BUFFER];
+    let mut size = 0;
+    loop {
+        let read = reader
+            .read(&mut buf)
+            .await
+            .context(format!("Could not read {file:?}"))?;
+        if read == 0 {
+            break;
+        }
+        hasher.write(&buf[..read]);
+        size += read as u64;
+    }
+    if size >= LOGGED_HASH_SIZE {
+        log::debug!(
+            "Site hashed {file} ({}) in {:.2?}",
+            format_bytes(size),
+            started.elapsed()
+        );
+    }
+    Ok(hasher.finish())
+}
+
+/// the same hash as [file_hash] for the content, as the writes of the hasher can be split
+fn data_hash(data: &[u8]) -> u64 {
+    let mut hasher = SeaHasher::new();
+    hasher.write(data);
+    hasher.finish()
 }
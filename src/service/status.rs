@@ -0,0 +1,84 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::ws::{Message, WebSocket, WebSocketUpgrade},
+    http::header,
+    response::IntoResponse,
+    routing::get,
+    Router,
+};
+use tokio::{sync::broadcast::error::RecvError, task::JoinHandle};
+
+use crate::config::{Project, StatusAddr};
+use crate::logger::GRAY;
+use crate::signal::{ReloadSignal, StatusEvent, StatusSignal};
+
+use super::tls::Incoming;
+
+/// The routes of the build events for editors: the /status websocket, which sends the last
+/// status and then each event, and the last status at /status.json
+pub fn router() -> Router {
+    Router::new()
+        .route("/status", get(status_socket))
+        .route("/status.json", get(status_json))
+}
+
+/// Serves the status routes on the `--status-addr`, when it isn't the reload port or off
+pub fn spawn(proj: &Arc<Project>) -> Option<JoinHandle<()>> {
+    let Some(StatusAddr::Addr(addr)) = proj.status_addr else {
+        return None;
+    };
+    Some(tokio::spawn(async move {
+        let incoming = match Incoming::bind(addr, None).await {
+            Ok(incoming) => incoming,
+            Err(e) => {
+                log::error!(
+                    "Status could not listen on {addr}: {e}. You can set it with --status-addr"
+                );
+                return;
+            }
+        };
+        log::debug!("Status server started {}", GRAY.paint(addr.to_string()));
+        match axum::Server::builder(incoming)
+            .serve(router().into_make_service())
+            .await
+        {
+            Ok(_) => log::debug!("Status server stopped"),
+            Err(e) => log::error!("Status {e}"),
+        }
+    }))
+}
+
+fn last_status() -> String {
+    StatusSignal::message(&StatusEvent::Status(ReloadSignal::build_status()))
+}
+
+async fn status_json() -> impl IntoResponse {
+    ([(header::CONTENT_TYPE, "application/json")], last_status())
+}
+
+async fn status_socket(ws: WebSocketUpgrade) -> impl IntoResponse {
+    ws.on_upgrade(status_events)
+}
+
+async fn status_events(mut stream: WebSocket) {
+    let mut rx = StatusSignal::subscribe();
+    log::trace!("Status websocket connected");
+    if stream.send(Message::Text(last_status())).await.is_err() {
+        return;
+    }
+    loop {
+        match rx.recv().await {
+            Ok(message) => {
+                if stream.send(Message::Text(message)).await.is_err() {
+                    break;
+                }
+            }
+            Err(RecvError::Lagged(missed)) => {
+                log::debug!("Status websocket missed {missed} events")
+            }
+            Err(RecvError::Closed) => break,
+        }
+    }
+    log::trace!("Status websocket closed");
+}
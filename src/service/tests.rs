@@ -0,0 +1,149 @@
+use camino::{Utf8Path, Utf8PathBuf};
+
+use crate::config::Config;
+
+use super::{
+    csr::{is_route, site_path, under_base_path},
+    reload::client_script,
+};
+
+/// The quoted string literals of the script that contain a line break, which is a syntax
+/// error in JS. The template literals may span lines.
+fn multiline_strings(script: &str) -> Vec<String> {
+    let mut found = Vec::new();
+    let mut chars = script.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '/' if chars.peek() == Some(&'/') => {
+                chars.by_ref().find(|c| *c == '\n');
+            }
+            '"' | '\'' | '`' => {
+                let mut literal = String::new();
+                while let Some(next) = chars.next() {
+                    match next {
+                        '\\' => literal.extend(chars.next()),
+                        _ if next == c => break,
+                        _ => literal.push(next),
+                    }
+                }
+                if c != '`' && literal.contains('\n') {
+                    found.push(literal);
+                }
+            }
+            _ => {}
+        }
+    }
+    found
+}
+
+#[test]
+fn test_client_script_strings() {
+    let conf = Config::test_load(
+        Default::default(),
+        "examples",
+        "examples/project/Cargo.toml",
+        true,
+    );
+    let script = client_script(&conf.projects[0]);
+    assert_eq!(multiline_strings(&script), Vec::<String>::new());
+    assert!(script.contains(r#"errors.join("\n")"#));
+
+    assert_eq!(multiline_strings("a = \"x\ny\";"), ["x\ny"]);
+    assert!(multiline_strings("a = `x\ny`; // \"\n").is_empty());
+}
+
+#[test]
+fn test_csr_site_path() {
+    assert_eq!(site_path("/"), Some(Utf8PathBuf::new()));
+    assert_eq!(
+        site_path("/pkg/./app.js"),
+        Some(Utf8PathBuf::from("pkg/app.js"))
+    );
+    assert_eq!(
+        site_path("/my%20file.txt"),
+        Some(Utf8PathBuf::from("my file.txt"))
+    );
+    // outside of the site-root
+    assert_eq!(site_path("/../Cargo.toml"), None);
+    assert_eq!(site_path("/pkg/../../Cargo.toml"), None);
+    assert_eq!(site_path("/%2e%2e/Cargo.toml"), None);
+    assert_eq!(site_path("/%2E%2E%2FCargo.toml"), None);
+    assert_eq!(site_path("/..%5CCargo.toml"), None);
+    assert_eq!(site_path("/c:/Windows"), None);
+    assert_eq!(site_path("/pkg%5C..%5C..%5Csecret"), None);
+    assert_eq!(site_path("/%ff"), None);
+}
+
+#[test]
+fn test_csr_is_route() {
+    let pkg = Utf8Path::new("pkg");
+    // the missing files get a 404
+    assert!(!is_route(Utf8Path::new("pkg/app.js"), pkg));
+    assert!(!is_route(Utf8Path::new("pkg/app_bg.wasm"), pkg));
+    assert!(!is_route(Utf8Path::new("favicon.ico"), pkg));
+    assert!(!is_route(Utf8Path::new("pkg/snippets"), pkg));
+    // and the routes the index.html
+    assert!(is_route(Utf8Path::new(""), pkg));
+    assert!(is_route(Utf8Path::new("about"), pkg));
+    assert!(is_route(Utf8Path::new("users/42/edit"), pkg));
+    assert!(is_route(Utf8Path::new("package"), pkg));
+}
+
+#[test]
+fn test_csr_under_base_path() {
+    assert_eq!(under_base_path("/about", ""), Some("/about"));
+    assert_eq!(under_base_path("/shop", "/shop"), Some(""));
+    assert_eq!(under_base_path("/shop/", "/shop"), Some("/"));
+    assert_eq!(under_base_path("/shop/cart", "/shop"), Some("/cart"));
+    assert_eq!(under_base_path("/shopping", "/shop"), None);
+    assert_eq!(under_base_path("/about", "/shop"), None);
+    assert_eq!(under_base_path("/", "/shop"), None);
+}
+
+#[cfg(target_family = "unix")]
+#[test]
+fn test_symlinked_file_target() {
+    use super::notify::{symlink_targets, Watched};
+
+    let dir = temp_dir::TempDir::new().unwrap();
+    let root = Utf8PathBuf::from_path_buf(dir.path().to_path_buf())
+        .unwrap()
+        .canonicalize_utf8()
+        .unwrap();
+    std::fs::create_dir_all(root.join("assets")).unwrap();
+    std::fs::create_dir_all(root.join("vendor/icons")).unwrap();
+    std::fs::write(root.join("vendor/logo.svg"), "<svg/>").unwrap();
+    std::os::unix::fs::symlink(root.join("vendor/logo.svg"), root.join("assets/logo.svg")).unwrap();
+    std::os::unix::fs::symlink(root.join("vendor/icons"), root.join("assets/icons")).unwrap();
+
+    let links = symlink_targets(&root.join("assets"), &root);
+    let mut found: Vec<(String, String)> = links
+        .iter()
+        .map(|(link, target)| {
+            let link = link.strip_prefix(&root).unwrap();
+            (link.to_string(), target.to_string())
+        })
+        .collect();
+    found.sort();
+    assert_eq!(
+        found,
+        [
+            ("assets/icons".to_string(), "vendor/icons".to_string()),
+            ("assets/logo.svg".to_string(), "vendor/logo.svg".to_string()),
+        ]
+    );
+
+    // the events of the targets are handled as the ones of the links
+    let links: Vec<_> = links
+        .into_iter()
+        .map(|(link, target)| (link.strip_prefix(&root).unwrap().to_path_buf(), target))
+        .collect();
+    assert_eq!(
+        Watched::Write("vendor/logo.svg".into()).resolve_links(&links),
+        Watched::Write("assets/logo.svg".into())
+    );
+    assert_eq!(
+        Watched::Create("vendor/icons/new.svg".into()).resolve_links(&links),
+        Watched::Create("assets/icons/new.svg".into())
+    );
+}
@@ -0,0 +1,266 @@
+use std::{
+    io,
+    net::SocketAddr,
+    pin::Pin,
+    sync::Arc,
+    task::{self, Poll},
+};
+
+use axum::extract::connect_info::Connected;
+use camino::Utf8PathBuf;
+use hyper::server::accept::Accept;
+use rcgen::{CertificateParams, DnType, SanType};
+use rustls_pemfile::Item;
+use tokio::{
+    io::{AsyncRead, AsyncWrite, ReadBuf},
+    net::{TcpListener, TcpStream},
+    sync::{mpsc, OnceCell},
+};
+use tokio_rustls::{
+    rustls::{Certificate, PrivateKey, ServerConfig},
+    server::TlsStream,
+    TlsAcceptor,
+};
+
+use crate::{
+    config::{Project, TlsConfig},
+    ext::{
+        anyhow::{anyhow, bail, Context, Result},
+        exe::get_cache_dir,
+        fs,
+    },
+};
+
+lazy_static::lazy_static! {
+  static ref SERVER_CONFIG: OnceCell<Arc<ServerConfig>> = OnceCell::new();
+}
+
+/// The TLS configuration for the proxy and reload servers, None when not using https
+pub async fn server_config(proj: &Project) -> Result<Option<Arc<ServerConfig>>> {
+    let Some(tls) = &proj.tls else {
+        return Ok(None);
+    };
+    let config = SERVER_CONFIG.get_or_try_init(|| load(tls)).await?;
+    Ok(Some(config.clone()))
+}
+
+async fn load(tls: &TlsConfig) -> Result<Arc<ServerConfig>> {
+    let (cert_file, key_file) = match &tls.cert_key {
+        Some((cert, key)) => (cert.clone(), key.clone()),
+        None => generate(tls).await?,
+    };
+    let pem = fs::read(&cert_file).await?;
+    let certs = rustls_pemfile::certs(&mut pem.as_slice())
+        .context(format!("Invalid TLS certificate {cert_file}"))?;
+    let pem = fs::read(&key_file).await?;
+    let key = private_key(&pem).context(format!("Invalid TLS key {key_file}"))?;
+
+    let config = ServerConfig::builder()
+        .with_safe_defaults()
+        .with_no_client_auth()
+        .with_single_cert(
+            certs.into_iter().map(Certificate).collect(),
+            PrivateKey(key),
+        )
+        .context(format!("Could not use the TLS certificate {cert_file}"))?;
+    log::debug!("TLS using certificate {cert_file}");
+    Ok(Arc::new(config))
+}
+
+fn private_key(pem: &[u8]) -> Result<Vec<u8>> {
+    for item in rustls_pemfile::read_all(&mut &*pem)? {
+        match item {
+            Item::PKCS8Key(key) | Item::RSAKey(key) | Item::ECKey(key) => return Ok(key),
+            _ => {}
+        }
+    }
+    bail!("No private key found")
+}
+
+/// Generates a self-signed certificate for the hosts, cached in the cargo-leptos cache dir
+async fn generate(tls: &TlsConfig) -> Result<(Utf8PathBuf, Utf8PathBuf)> {
+    let dir = Utf8PathBuf::from_path_buf(get_cache_dir()?.join("tls"))
+        .map_err(|dir| anyhow!("The cache dir {dir:?} is not utf-8"))?;
+    let hash = seahash::hash(tls.hosts.join(",").as_bytes());
+    let cert_file = dir.join(format!("cert-{hash:x}.pem"));
+    let key_file = dir.join(format!("key-{hash:x}.pem"));
+    if cert_file.exists() && key_file.exists() {
+        return Ok((cert_file, key_file));
+    }
+
+    let (ips, names) = tls.ips_and_names();
+    let mut params = CertificateParams::new(names);
+    params
+        .subject_alt_names
+        .extend(ips.into_iter().map(SanType::IpAddress));
+    params
+        .distinguished_name
+        .push(DnType::CommonName, "cargo-leptos dev certificate");
+    let cert = rcgen::Certificate::from_params(params)
+        .context("Could not generate the TLS certificate")?;
+
+    fs::create_dir_all(&dir).await?;
+    fs::write(&cert_file, cert.serialize_pem()?).await?;
+    fs::write(&key_file, cert.serialize_private_key_pem()).await?;
+
+    log::warn!(
+        "TLS generated a self-signed certificate for {}: {cert_file}. \
+        The browser warns about it once per host and port: accept the warning for the \
+        proxy-addr and open https://<host>:<reload-port> once for the reload websocket. \
+        Or add the certificate to the trusted ones of the system.",
+        tls.hosts.join(", ")
+    );
+    Ok((cert_file, key_file))
+}
+
+/// The connections accepted by a server, over TLS when there is a TLS configuration
+pub struct Incoming {
+    rx: mpsc::Receiver<Conn>,
+}
+
+impl Incoming {
+    pub async fn bind(addr: SocketAddr, tls: Option<Arc<ServerConfig>>) -> io::Result<Self> {
+        let listener = TcpListener::bind(addr).await?;
+        let (tx, rx) = mpsc::channel(32);
+        accept(listener, addr, tls.map(TlsAcceptor::from), tx);
+        Ok(Self { rx })
+    }
+
+    /// Listens on all the addresses that can be bound, each failure being logged, and
+    /// gives the bound ones. Fails with the last error when none can be bound.
+    pub async fn bind_all(
+        addrs: &[SocketAddr],
+        tls: Option<Arc<ServerConfig>>,
+    ) -> io::Result<(Self, Vec<SocketAddr>)> {
+        let acceptor = tls.map(TlsAcceptor::from);
+        let (tx, rx) = mpsc::channel(32);
+        let mut bound = Vec::new();
+        let mut error = None;
+        for addr in addrs {
+            match TcpListener::bind(addr).await {
+                Ok(listener) => {
+                    accept(listener, *addr, acceptor.clone(), tx.clone());
+                    bound.push(*addr);
+                }
+                Err(e) => {
+                    log::warn!("Could not listen on {addr}: {e}");
+                    error = Some(e);
+                }
+            }
+        }
+        match error {
+            Some(e) if bound.is_empty() => Err(e),
+            _ => Ok((Self { rx }, bound)),
+        }
+    }
+}
+
+/// Sends the connections of the listener until the receiver of the Incoming is dropped
+fn accept(
+    listener: TcpListener,
+    addr: SocketAddr,
+    acceptor: Option<TlsAcceptor>,
+    tx: mpsc::Sender<Conn>,
+) {
+    tokio::spawn(async move {
+        while !tx.is_closed() {
+            let (stream, remote) = match listener.accept().await {
+                Ok(accepted) => accepted,
+                Err(e) => {
+                    log::debug!("Could not accept a connection on {addr}: {e}");
+                    continue;
+                }
+            };
+            let (acceptor, tx) = (acceptor.clone(), tx.clone());
+            // the handshake is made in its own task so that a slow client doesn't block others
+            tokio::spawn(async move {
+                let stream = match acceptor {
+                    None => Stream::Plain(stream),
+                    Some(acceptor) => match acceptor.accept(stream).await {
+                        Ok(stream) => Stream::Tls(Box::new(stream)),
+                        Err(e) => {
+                            log::debug!("TLS handshake with {remote} failed: {e}");
+                            return;
+                        }
+                    },
+                };
+                _ = tx.send(Conn { stream, remote }).await;
+            });
+        }
+    });
+}
+
+impl Accept for Incoming {
+    type Conn = Conn;
+    type Error = io::Error;
+
+    fn poll_accept(
+        mut self: Pin<&mut Self>,
+        cx: &mut task::Context<'_>,
+    ) -> Poll<Option<Result<Self::Conn, Self::Error>>> {
+        self.rx.poll_recv(cx).map(|conn| conn.map(Ok))
+    }
+}
+
+pub struct Conn {
+    stream: Stream,
+    remote: SocketAddr,
+}
+
+enum Stream {
+    Plain(TcpStream),
+    Tls(Box<TlsStream<TcpStream>>),
+}
+
+impl Conn {
+    pub fn remote_addr(&self) -> SocketAddr {
+        self.remote
+    }
+}
+
+/// For the `ConnectInfo<SocketAddr>` of the axum routers
+impl Connected<&Conn> for SocketAddr {
+    fn connect_info(conn: &Conn) -> Self {
+        conn.remote
+    }
+}
+
+impl AsyncRead for Conn {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut task::Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        match &mut self.get_mut().stream {
+            Stream::Plain(stream) => Pin::new(stream).poll_read(cx, buf),
+            Stream::Tls(stream) => Pin::new(stream.as_mut()).poll_read(cx, buf),
+        }
+    }
+}
+
+impl AsyncWrite for Conn {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut task::Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        match &mut self.get_mut().stream {
+            Stream::Plain(stream) => Pin::new(stream).poll_write(cx, buf),
+            Stream::Tls(stream) => Pin::new(stream.as_mut()).poll_write(cx, buf),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> Poll<io::Result<()>> {
+        match &mut self.get_mut().stream {
+            Stream::Plain(stream) => Pin::new(stream).poll_flush(cx),
+            Stream::Tls(stream) => Pin::new(stream.as_mut()).poll_flush(cx),
+        }
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> Poll<io::Result<()>> {
+        match &mut self.get_mut().stream {
+            Stream::Plain(stream) => Pin::new(stream).poll_shutdown(cx),
+            Stream::Tls(stream) => Pin::new(stream.as_mut()).poll_shutdown(cx),
+        }
+    }
+}
@@ -0,0 +1,99 @@
+use std::collections::HashMap;
+
+use camino::{Utf8Path, Utf8PathBuf};
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+
+use super::notify::Watched;
+
+/// The ignore files of each dir, by increasing precedence
+const IGNORE_FILES: &[&str] = &[".gitignore", ".ignore", ".leptosignore"];
+
+/// Matches the watched paths against the .gitignore, .ignore and .leptosignore files of
+/// their dir and of the parent dirs up to the working dir. As with git, the file of the
+/// deepest dir that has a matching pattern decides, so that a nested file can re-include
+/// what a parent excludes.
+#[derive(Default)]
+pub struct WatchIgnore {
+    /// the ignore files by dir, relative to the working dir. None when the dir has none
+    dirs: HashMap<Utf8PathBuf, Option<Gitignore>>,
+}
+
+impl WatchIgnore {
+    /// The reason when all the paths of the event are ignored
+    pub fn ignored(&mut self, watched: &Watched) -> Option<String> {
+        match watched {
+            Watched::Rescan => None,
+            Watched::Rename(from, to) => {
+                let why = self.ignored_path(from)?;
+                self.ignored_path(to).map(|_| why)
+            }
+            Watched::Remove(path) | Watched::Write(path) | Watched::Create(path) => {
+                if is_ignore_file(path) {
+                    // re-read on the next use
+                    self.dirs.remove(path.parent().unwrap_or(Utf8Path::new("")));
+                }
+                self.ignored_path(path)
+            }
+        }
+    }
+
+    fn ignored_path(&mut self, path: &Utf8Path) -> Option<String> {
+        // outside of the working dir, like a symlink target
+        if path.is_absolute() {
+            return None;
+        }
+        let is_dir = path.is_dir();
+        let mut dir = path.parent();
+        while let Some(current) = dir {
+            if let Some(gitignore) = self.load(current) {
+                let found = gitignore.matched_path_or_any_parents(path, is_dir);
+                if found.is_whitelist() {
+                    return None;
+                }
+                if let Some(glob) = found.inner().filter(|_| found.is_ignore()) {
+                    let file = glob
+                        .from()
+                        .map(|f| f.display().to_string())
+                        .unwrap_or_default();
+                    return Some(format!("{:?} in {file}", glob.original()));
+                }
+            }
+            dir = current.parent();
+        }
+        None
+    }
+
+    fn load(&mut self, dir: &Utf8Path) -> Option<&Gitignore> {
+        self.dirs
+            .entry(dir.to_path_buf())
+            .or_insert_with(|| {
+                let mut builder = GitignoreBuilder::new(dir);
+                let mut found = false;
+                for name in IGNORE_FILES {
+                    let file = dir.join(name);
+                    if file.exists() {
+                        if let Some(e) = builder.add(&file) {
+                            log::warn!("Notify could not read {file}: {e}");
+                        }
+                        found = true;
+                    }
+                }
+                if !found {
+                    return None;
+                }
+                match builder.build() {
+                    Ok(gitignore) => Some(gitignore),
+                    Err(e) => {
+                        log::warn!("Notify invalid ignore file in {dir}: {e}");
+                        None
+                    }
+                }
+            })
+            .as_ref()
+    }
+}
+
+fn is_ignore_file(path: &Utf8Path) -> bool {
+    path.file_name()
+        .map_or(false, |name| IGNORE_FILES.contains(&name))
+}
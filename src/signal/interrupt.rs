@@ -1,10 +1,18 @@
+use std::{
+    process,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
 use tokio::{
     signal,
     sync::{broadcast, RwLock},
     task::JoinHandle,
 };
 
-use crate::compile::{Change, ChangeSet};
+use crate::compile::{Change, ChangeSet, ChangedFile};
+use crate::config::BuildStrategy;
+use crate::ext::{exit::Exit, sync::kill_children};
+use crate::logger;
 
 lazy_static::lazy_static! {
   static ref ANY_INTERRUPT: broadcast::Sender<()> = broadcast::channel(10).0;
@@ -12,6 +20,10 @@ lazy_static::lazy_static! {
 
   static ref SHUTDOWN_REQUESTED: RwLock<bool> = RwLock::new(false);
   static ref SOURCE_CHANGES: RwLock<ChangeSet> = RwLock::new(ChangeSet::default());
+  /// the builds are only interrupted by the shutdown, with the queue build-strategy
+  static ref QUEUE_CHANGES: AtomicBool = AtomicBool::new(false);
+  /// the shutdown was requested by ctrl-c, rather than by the q key of the watch mode
+  static ref CTRL_C_RECEIVED: AtomicBool = AtomicBool::new(false);
 }
 
 pub struct Interrupt {}
@@ -21,6 +33,18 @@ impl Interrupt {
         *SHUTDOWN_REQUESTED.read().await
     }
 
+    pub fn is_ctrl_c_received() -> bool {
+        CTRL_C_RECEIVED.load(Ordering::Relaxed)
+    }
+
+    /// Ends the shutdown that only stopped the server of a project, like the one of its
+    /// end2end tests, for the commands that go on with the next project. Not after a ctrl-c.
+    pub async fn end_server_shutdown() {
+        if !Self::is_ctrl_c_received() {
+            *SHUTDOWN_REQUESTED.write().await = false;
+        }
+    }
+
     pub fn subscribe_any() -> broadcast::Receiver<()> {
         ANY_INTERRUPT.subscribe()
     }
@@ -29,10 +53,33 @@ impl Interrupt {
         SHUTDOWN.subscribe()
     }
 
+    /// What stops a build step: any change, or only the shutdown when the changes are queued
+    pub fn subscribe_build() -> broadcast::Receiver<()> {
+        if Self::is_queuing_changes() {
+            SHUTDOWN.subscribe()
+        } else {
+            ANY_INTERRUPT.subscribe()
+        }
+    }
+
+    pub fn set_build_strategy(strategy: BuildStrategy) {
+        QUEUE_CHANGES.store(strategy == BuildStrategy::Queue, Ordering::Relaxed);
+    }
+
+    pub fn is_queuing_changes() -> bool {
+        QUEUE_CHANGES.load(Ordering::Relaxed)
+    }
+
     pub async fn get_source_changes() -> ChangeSet {
         SOURCE_CHANGES.read().await.clone()
     }
 
+    /// The changes to build, the ones made meanwhile are kept for the next build
+    pub async fn take_source_changes() -> ChangeSet {
+        let mut ch = SOURCE_CHANGES.write().await;
+        std::mem::take(&mut *ch)
+    }
+
     pub async fn clear_source_changes() {
         let mut ch = SOURCE_CHANGES.write().await;
         ch.clear();
@@ -47,11 +94,20 @@ impl Interrupt {
     }
 
     pub fn send(changes: &[Change]) {
+        Self::send_with_files(changes, Vec::new())
+    }
+
+    /// Like send, with the files of the changes for the log of the rebuild. The files alone
+    /// don't interrupt the build when their changes are already pending.
+    pub fn send_with_files(changes: &[Change], files: Vec<ChangedFile>) {
         let mut ch = SOURCE_CHANGES.blocking_write();
         let mut did_change = false;
         for change in changes {
             did_change |= ch.add(change.clone());
         }
+        for file in files {
+            ch.add_file(file);
+        }
         drop(ch);
 
         if did_change {
@@ -61,6 +117,20 @@ impl Interrupt {
         }
     }
 
+    /// Like send, from an async task
+    pub async fn send_async(changes: &[Change]) {
+        let mut ch = SOURCE_CHANGES.write().await;
+        let mut did_change = false;
+        for change in changes {
+            did_change |= ch.add(change.clone());
+        }
+        drop(ch);
+
+        if did_change {
+            Self::send_any();
+        }
+    }
+
     fn send_any() {
         if let Err(e) = ANY_INTERRUPT.send(()) {
             log::error!("Interrupt error could not send due to: {e}");
@@ -77,11 +147,31 @@ impl Interrupt {
         _ = ANY_INTERRUPT.send(());
     }
 
+    /// The shutdown by ctrl-c, after which cargo-leptos exits with 130. A second ctrl-c
+    /// during the shutdown quits at once with 137, killing the child processes.
+    pub async fn request_ctrl_c_shutdown() {
+        if CTRL_C_RECEIVED.swap(true, Ordering::Relaxed) {
+            Self::force_quit();
+        }
+        log::info!("Leptos ctrl-c received, press Ctrl+C again to force quit");
+        Self::request_shutdown().await;
+    }
+
+    fn force_quit() -> ! {
+        log::warn!("Leptos force quit, killing the child processes");
+        kill_children();
+        // left on by the keys of the watch mode
+        _ = crossterm::terminal::disable_raw_mode();
+        logger::flush_log_file();
+        process::exit(Exit::ForceQuit as i32)
+    }
+
     pub fn run_ctrl_c_monitor() -> JoinHandle<()> {
         tokio::spawn(async move {
-            signal::ctrl_c().await.expect("failed to listen for event");
-            log::info!("Leptos ctrl-c received");
-            Interrupt::request_shutdown().await;
+            loop {
+                signal::ctrl_c().await.expect("failed to listen for event");
+                Interrupt::request_ctrl_c_shutdown().await;
+            }
         })
     }
 }
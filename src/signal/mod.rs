@@ -1,10 +1,15 @@
+#[cfg(test)]
+mod tests;
+
 mod interrupt;
 mod product;
 mod reload;
+mod status;
 
 pub use interrupt::Interrupt;
 pub use product::{Outcome, Product, ProductSet, ServerRestart};
-pub use reload::{ReloadSignal, ReloadType};
+pub use reload::{BuildStatus, ReloadSignal, ReloadType};
+pub use status::{Stage, StageOutcome, StatusChange, StatusEvent, StatusSignal};
 
 #[macro_export]
 macro_rules! location {
@@ -1,8 +1,26 @@
+use std::{
+    sync::Mutex,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
 use leptos_hot_reload::diff::Patches;
+use serde::Serialize;
 use tokio::sync::broadcast;
 
+use super::{StatusEvent, StatusSignal};
+
+/// the compiler error lines kept for the browser
+const MAX_ERROR_LINES: usize = 60;
+
 lazy_static::lazy_static! {
   static ref RELOAD_CHANNEL: broadcast::Sender::<ReloadType> = broadcast::channel::<ReloadType>(1).0;
+  static ref BUILD_STATUS: Mutex<BuildStatus> = Mutex::new(BuildStatus::default());
+  /// the errors of the running build
+  static ref BUILD_ERRORS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+  /// distinguishes the builds of this run from the ones of a previous run
+  static ref RUN_ID: u128 = SystemTime::now()
+      .duration_since(UNIX_EPOCH)
+      .map_or(0, |d| d.as_millis());
 }
 
 #[derive(Debug, Clone)]
@@ -10,6 +28,34 @@ pub enum ReloadType {
     Full,
     Style,
     ViewPatches(String),
+    Status(BuildStatus),
+}
+
+/// The outcome of the latest build, kept for the browsers that connect after it
+#[derive(Debug, Clone, Serialize)]
+pub struct BuildStatus {
+    #[serde(skip)]
+    count: u64,
+    /// the build of this cargo-leptos run, a browser that saw another one missed a build
+    pub build: String,
+    pub ok: bool,
+    /// the first lines of the compiler errors, without colors
+    pub errors: Vec<String>,
+}
+
+impl Default for BuildStatus {
+    fn default() -> Self {
+        Self {
+            count: 0,
+            build: build_id(0),
+            ok: true,
+            errors: Vec::new(),
+        }
+    }
+}
+
+fn build_id(count: u64) -> String {
+    format!("{}-{count}", *RUN_ID)
 }
 
 pub struct ReloadSignal {}
@@ -37,6 +83,39 @@ impl ReloadSignal {
         }
     }
 
+    /// Keeps error lines of the running build, sent with its status
+    pub fn add_build_errors(lines: Vec<String>) {
+        let mut errors = BUILD_ERRORS.lock().unwrap();
+        let room = MAX_ERROR_LINES.saturating_sub(errors.len());
+        errors.extend(lines.into_iter().take(room));
+    }
+
+    /// Updates the status with the outcome of a build and sends it to the browsers and to
+    /// the status clients
+    pub fn send_build_status(ok: bool) {
+        let errors = std::mem::take(&mut *BUILD_ERRORS.lock().unwrap());
+        let status = {
+            let mut status = BUILD_STATUS.lock().unwrap();
+            let count = status.count + 1;
+            *status = BuildStatus {
+                count,
+                build: build_id(count),
+                ok,
+                errors: if ok { Vec::new() } else { errors },
+            };
+            status.clone()
+        };
+        StatusSignal::send(StatusEvent::BuildFinished(status.clone()));
+        // the browsers that aren't connected get it when they connect
+        if RELOAD_CHANNEL.send(ReloadType::Status(status)).is_err() {
+            log::trace!("Reload no browser connected for the build status");
+        }
+    }
+
+    pub fn build_status() -> BuildStatus {
+        BUILD_STATUS.lock().unwrap().clone()
+    }
+
     pub fn subscribe() -> broadcast::Receiver<ReloadType> {
         RELOAD_CHANNEL.subscribe()
     }
@@ -0,0 +1,163 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+    time::Instant,
+};
+
+use serde::Serialize;
+use tokio::sync::broadcast;
+
+use super::{BuildStatus, Outcome, Product};
+use crate::compile::{Change, ChangeSet, ChangedFile, SiteSummary};
+
+/// The version of the status messages, increased on incompatible changes of their schema
+pub const STATUS_VERSION: u32 = 1;
+
+lazy_static::lazy_static! {
+  static ref STATUS_CHANNEL: broadcast::Sender<String> = broadcast::channel(64).0;
+  /// the same events unserialized, for the library users
+  static ref EVENT_CHANNEL: broadcast::Sender<StatusEvent> = broadcast::channel(64).0;
+  /// with `--message-format json`
+  static ref PRINT_JSON: AtomicBool = AtomicBool::new(false);
+  /// for the elapsed time of the stages
+  static ref BUILD_STARTED: Mutex<Instant> = Mutex::new(Instant::now());
+}
+
+/// A build event, as sent over the status endpoint and printed by `--message-format json`
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "event", rename_all = "kebab-case")]
+pub enum StatusEvent {
+    /// the outcome of the last build, sent first to the clients that connect
+    Status(BuildStatus),
+    BuildStarted {
+        changes: Vec<StatusChange>,
+        /// the files that the watcher found changed, with the pipelines of their changes
+        #[serde(skip_serializing_if = "Vec::is_empty")]
+        files: Vec<ChangedFile>,
+    },
+    StageFinished {
+        stage: Stage,
+        outcome: StageOutcome,
+        /// since the build started, the stages of watch running side by side
+        #[serde(rename = "elapsed-ms")]
+        elapsed_ms: u128,
+    },
+    /// with the first lines of the compiler errors when it failed
+    BuildFinished(BuildStatus),
+    /// a change was made during the build, which is started again
+    BuildInterrupted,
+    ServerRestarting,
+    /// the server answers after a start or a restart
+    ServerReady,
+    /// the sizes of the site files at the end of a release build or with `--summary`
+    SiteSummary(SiteSummary),
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct StatusChange {
+    pub kind: &'static str,
+    /// the changed file of an asset change
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Stage {
+    Front,
+    Assets,
+    Style,
+    Server,
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum StageOutcome {
+    Built,
+    Unchanged,
+    Failed,
+    Stopped,
+}
+
+impl From<&Outcome<Product>> for StageOutcome {
+    fn from(outcome: &Outcome<Product>) -> Self {
+        match outcome {
+            Outcome::Success(Product::None) => Self::Unchanged,
+            Outcome::Success(_) => Self::Built,
+            Outcome::Failed => Self::Failed,
+            Outcome::Stopped => Self::Stopped,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct StatusMessage<'a> {
+    version: u32,
+    #[serde(flatten)]
+    event: &'a StatusEvent,
+}
+
+pub struct StatusSignal {}
+
+impl StatusSignal {
+    pub fn print_json() {
+        PRINT_JSON.store(true, Ordering::Relaxed);
+    }
+
+    /// The event as a versioned JSON message
+    pub fn message(event: &StatusEvent) -> String {
+        let message = StatusMessage {
+            version: STATUS_VERSION,
+            event,
+        };
+        serde_json::to_string(&message).unwrap_or_default()
+    }
+
+    pub fn send(event: StatusEvent) {
+        let message = Self::message(&event);
+        if PRINT_JSON.load(Ordering::Relaxed) {
+            println!("{message}");
+        }
+        if STATUS_CHANNEL.send(message).is_err() {
+            log::trace!("Status no client connected for {event:?}");
+        }
+        // no subscriber is the usual case of the binary
+        let _ = EVENT_CHANNEL.send(event);
+    }
+
+    pub fn build_started(changes: &ChangeSet) {
+        let files = changes.files().to_vec();
+        let changes = changes
+            .iter()
+            .map(|change| StatusChange {
+                kind: change.kind(),
+                path: match change {
+                    Change::Asset(watched) => watched.path().map(|path| path.to_string()),
+                    _ => None,
+                },
+            })
+            .collect();
+        *BUILD_STARTED.lock().unwrap() = Instant::now();
+        Self::send(StatusEvent::BuildStarted { changes, files });
+    }
+
+    pub fn stage_finished(stage: Stage, outcome: &Outcome<Product>) {
+        Self::send(StatusEvent::StageFinished {
+            stage,
+            outcome: outcome.into(),
+            elapsed_ms: BUILD_STARTED.lock().unwrap().elapsed().as_millis(),
+        });
+    }
+
+    pub fn subscribe() -> broadcast::Receiver<String> {
+        STATUS_CHANNEL.subscribe()
+    }
+
+    /// The build events sent from now on, as the values that the JSON messages serialize. A
+    /// receiver that lags behind by more than 64 events misses the oldest ones.
+    pub fn subscribe_events() -> broadcast::Receiver<StatusEvent> {
+        EVENT_CHANNEL.subscribe()
+    }
+}
@@ -0,0 +1,49 @@
+use itertools::Itertools;
+
+use super::{Outcome, Product, Stage, StatusEvent, StatusSignal};
+use crate::{
+    compile::{Change, ChangeSet, ChangedFile},
+    service::notify::Watched,
+};
+
+#[test]
+fn test_status_message() {
+    let event = StatusEvent::StageFinished {
+        stage: Stage::Front,
+        outcome: (&Outcome::Success(Product::Front)).into(),
+        elapsed_ms: 8400,
+    };
+    assert_eq!(
+        StatusSignal::message(&event),
+        r#"{"version":1,"event":"stage-finished","stage":"front","outcome":"built","elapsed-ms":8400}"#
+    );
+    assert_eq!(
+        StatusSignal::message(&StatusEvent::ServerRestarting),
+        r#"{"version":1,"event":"server-restarting"}"#
+    );
+}
+
+#[test]
+fn test_build_started_files() {
+    let write = Watched::Write("src/app.rs".into());
+    let remove = Watched::Remove("style/old.scss".into());
+    let mut changes = ChangeSet::default();
+    changes.add(Change::LibSource);
+    changes.add_file(ChangedFile::new(&write, &[Change::LibSource]).unwrap());
+    changes.add_file(ChangedFile::new(&remove, &[Change::Style]).unwrap());
+    changes.add_file(ChangedFile::new(&write, &[Change::BinSource, Change::LibSource]).unwrap());
+    assert!(ChangedFile::new(&Watched::Rescan, &[Change::Style]).is_none());
+    assert_eq!(
+        changes.files().iter().join(", "),
+        "src/app.rs (modified: front, server), style/old.scss (removed: style)"
+    );
+
+    let event = StatusEvent::BuildStarted {
+        changes: Vec::new(),
+        files: changes.files()[1..].to_vec(),
+    };
+    assert_eq!(
+        StatusSignal::message(&event),
+        r#"{"version":1,"event":"build-started","changes":[],"files":[{"path":"style/old.scss","kind":"removed","pipelines":["style"]}]}"#
+    );
+}
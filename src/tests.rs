@@ -1,19 +1,27 @@
 use camino::Utf8PathBuf;
 
 use crate::{
-    config::{Cli, Commands, Opts},
+    config::{BuildOpts, Cli, ColorMode, Commands, LogFormat},
     ext::PathBufExt,
     run,
 };
 
 #[tokio::test]
 async fn workspace_build() {
-    let command = Commands::Build(Opts::default());
+    let command = Commands::Build(BuildOpts::default());
 
     let cli = Cli {
+        version: false,
+        json: false,
+        verbose: 0,
         manifest_path: Some(Utf8PathBuf::from("examples/workspace/Cargo.toml")),
         log: Vec::new(),
-        command,
+        log_format: LogFormat::Human,
+        log_timestamps: false,
+        color: ColorMode::Auto,
+        log_file: None,
+        log_file_append: false,
+        command: Some(command),
     };
 
     run(cli).await.unwrap();
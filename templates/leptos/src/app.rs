@@ -0,0 +1,49 @@
+use leptos::*;
+{% if flavor != "csr" %}use leptos_meta::*;
+use leptos_router::*;
+
+#[component]
+pub fn App() -> impl IntoView {
+    provide_meta_context();
+
+    view! {
+        <Stylesheet id="leptos" href="/pkg/{{crate_name}}.css"/>
+        <Title text="Welcome to Leptos"/>
+        <Router>
+            <main>
+                <Routes>
+                    <Route path="" view=HomePage/>
+                </Routes>
+            </main>
+        </Router>
+    }
+}
+
+#[component]
+fn HomePage() -> impl IntoView {
+    view! {
+        <h1>"Welcome to Leptos!"</h1>
+        <Counter/>
+    }
+}
+{% else %}
+#[component]
+pub fn App() -> impl IntoView {
+    view! {
+        <main>
+            <h1>"Welcome to Leptos!"</h1>
+            <Counter/>
+        </main>
+    }
+}
+{% endif %}
+{% if flavor == "islands" %}/// hydrated in the browser, unlike the rest of the page
+#[island]{% else %}#[component]{% endif %}
+fn Counter() -> impl IntoView {
+    let (count, set_count) = create_signal(0);
+    let on_click = move |_| set_count.update(|count| *count += 1);
+
+    view! {
+        <button on:click=on_click>"Click Me: " {count}</button>
+    }
+}
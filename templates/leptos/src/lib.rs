@@ -0,0 +1,19 @@
+pub mod app;
+{% if flavor == "csr" %}
+#[wasm_bindgen::prelude::wasm_bindgen(start)]
+pub fn main() {
+    console_error_panic_hook::set_once();
+    leptos::mount_to_body(app::App);
+}
+{% else %}#[cfg(feature = "ssr")]
+pub mod fileserv;
+
+#[cfg(feature = "hydrate")]
+#[wasm_bindgen::prelude::wasm_bindgen]
+pub fn hydrate() {
+    console_error_panic_hook::set_once();
+{% if flavor == "islands" %}    // only the islands are hydrated
+    leptos::leptos_dom::HydrationCtx::stop_hydrating();
+{% else %}    leptos::mount_to_body(app::App);
+{% endif %}}
+{% endif %}
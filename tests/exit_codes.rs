@@ -0,0 +1,22 @@
+use std::process::Command;
+
+fn cargo_leptos(args: &[&str]) -> Option<i32> {
+    Command::new(env!("CARGO_BIN_EXE_cargo-leptos"))
+        .args(args)
+        .status()
+        .expect("cargo-leptos should start")
+        .code()
+}
+
+#[test]
+fn config_error_exit_code() {
+    let code = cargo_leptos(&["build", "--manifest-path", "examples/missing/Cargo.toml"]);
+    assert_eq!(code, Some(2));
+}
+
+#[cfg(feature = "full_tests")]
+#[test]
+fn failed_build_exit_code() {
+    let code = cargo_leptos(&["build", "--manifest-path", "examples/failing/Cargo.toml"]);
+    assert_eq!(code, Some(1));
+}
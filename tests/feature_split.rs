@@ -0,0 +1,36 @@
+#![cfg(feature = "full_tests")]
+
+use camino::Utf8PathBuf;
+use cargo_leptos::{
+    compile::{build_cargo_front_cmd, build_cargo_server_cmd},
+    config::{Config, Opts},
+};
+use tokio::process::Command;
+
+const DIR: &str = "examples/split_features";
+
+/// Checks one side with the cargo command of cargo-leptos, run in the project dir
+async fn check(front: bool) {
+    let dir = Utf8PathBuf::from(DIR).canonicalize_utf8().unwrap();
+    let config = Config::load(Opts::default(), &dir, &dir.join("Cargo.toml"), false).unwrap();
+    let proj = &config.projects[0];
+    let mut command = Command::new("cargo");
+    let (_, line) = if front {
+        build_cargo_front_cmd("check", &["--lib"], true, proj, &mut command)
+    } else {
+        build_cargo_server_cmd("check", &[], proj, &mut command)
+    };
+    let status = command.current_dir(&dir).status().await.unwrap();
+    assert!(status.success(), "{line} failed");
+}
+
+/// The hydrate deps only compile for the front and the ssr ones for the server, while ssr is
+/// a default feature of the package: each side gets its own features whatever the order
+#[tokio::test]
+async fn split_features_in_either_order() {
+    for front_first in [true, false] {
+        _ = std::fs::remove_dir_all(format!("{DIR}/target"));
+        check(front_first).await;
+        check(!front_first).await;
+    }
+}
@@ -0,0 +1,25 @@
+#![cfg(feature = "slow_tests")]
+
+use std::{path::Path, process::Command};
+
+fn cargo_leptos(dir: &Path, args: &[&str]) {
+    let status = Command::new(env!("CARGO_BIN_EXE_cargo-leptos"))
+        .args(args)
+        .current_dir(dir)
+        .status()
+        .expect("cargo-leptos should start");
+    assert!(status.success(), "cargo leptos {} failed", args.join(" "));
+}
+
+/// Each flavor of the built-in template builds as generated
+#[test]
+fn new_flavors_build() {
+    let flavors: [&[&str]; 3] = [&["--csr"], &["--ssr"], &["--ssr", "--islands"]];
+    for flavor in flavors {
+        let dir = temp_dir::TempDir::new().unwrap();
+        let mut args = vec!["new", "--name", "flavored"];
+        args.extend(flavor);
+        cargo_leptos(dir.path(), &args);
+        cargo_leptos(&dir.path().join("flavored"), &["build"]);
+    }
+}